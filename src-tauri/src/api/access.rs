@@ -0,0 +1,45 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+use warp::http::Method;
+use warp::reply::Response;
+
+/// Lo que una ruta con token sabe de la solicitud y el registro de acceso no;
+/// viaja en las extensiones de la respuesta
+#[derive(Clone)]
+pub struct RequestTrace {
+    pub request_id: String,
+    /// Token adicional con que se autenticó
+    pub caller: Option<String>,
+}
+
+/// Un evento de tracing por solicitud, que las capas del módulo de logging
+/// escriben en consola y en el archivo JSON
+pub fn record(method: &Method, path: &str, client: Option<SocketAddr>, started: Instant, response: &Response) {
+    let status = response.status().as_u16();
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let client_ip = client.map(|addr| addr.ip().to_string());
+    let request_id = response
+        .headers()
+        .get(super::REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let token_id = response
+        .extensions()
+        .get::<RequestTrace>()
+        .and_then(|trace| trace.caller.as_deref());
+
+    tracing::info!(
+        target: "print_my_bridge::access",
+        method = %method,
+        path,
+        status,
+        latency_ms,
+        client_ip = client_ip.as_deref(),
+        token_id,
+        request_id,
+        "{} {} {} ({} ms)",
+        method,
+        path,
+        status,
+        latency_ms
+    );
+}
@@ -0,0 +1,105 @@
+use crate::error::BridgeError;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use warp::reject::Reject;
+
+/// Versión del contrato HTTP que expone el bridge.
+pub const API_VERSION: &str = "1";
+
+/// Campos de `PrintRequest` renombrados o eliminados entre versiones.
+/// `None` como reemplazo significa que el campo ya no tiene equivalente.
+/// Vacía mientras no se haya renombrado ninguno.
+const DEPRECATED_PRINT_FIELDS: &[(&str, Option<&str>)] = &[];
+
+/// Versiones de cliente ya registradas; con el tope, un cliente que invente
+/// versiones no hace crecer la lista sin límite
+static SEEN_CLIENT_VERSIONS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+const MAX_SEEN_CLIENT_VERSIONS: usize = 256;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DeprecationWarning {
+    pub field: String,
+    pub replacement: Option<String>,
+    pub message: String,
+}
+
+/// Error de una solicitud que usaba campos obsoletos: la respuesta de error
+/// lleva también las advertencias
+#[derive(Debug)]
+pub struct DeprecatedFailure {
+    pub error: BridgeError,
+    pub deprecation: Vec<DeprecationWarning>,
+}
+
+impl Reject for DeprecatedFailure {}
+
+/// Rechazo con `error`, acompañado de `deprecation` si hay advertencias
+pub fn reject(error: BridgeError, deprecation: Vec<DeprecationWarning>) -> warp::Rejection {
+    if deprecation.is_empty() {
+        warp::reject::custom(error)
+    } else {
+        warp::reject::custom(DeprecatedFailure { error, deprecation })
+    }
+}
+
+/// Reescribe los campos obsoletos de una solicitud de impresión a sus nombres
+/// actuales y devuelve las advertencias correspondientes.
+pub fn migrate_print_fields(body: &mut Value) -> Vec<DeprecationWarning> {
+    let mut warnings = Vec::new();
+
+    let Some(object) = body.as_object_mut() else {
+        return warnings;
+    };
+
+    for (old_name, replacement) in DEPRECATED_PRINT_FIELDS {
+        let Some(value) = object.remove(*old_name) else {
+            continue;
+        };
+
+        let message = match replacement {
+            Some(new_name) => {
+                // El nombre nuevo tiene prioridad si el cliente envía ambos
+                if !object.contains_key(*new_name) {
+                    object.insert(new_name.to_string(), value);
+                }
+                format!("El campo '{}' está obsoleto, use '{}'", old_name, new_name)
+            }
+            None => format!("El campo '{}' fue eliminado y se ignora", old_name),
+        };
+
+        warnings.push(DeprecationWarning {
+            field: old_name.to_string(),
+            replacement: replacement.map(|s| s.to_string()),
+            message,
+        });
+    }
+
+    warnings
+}
+
+/// Registra la versión de SDK que declara el cliente, si la envía: la primera
+/// vez que aparece cada versión en `info`, y después en `debug`.
+pub fn log_client_version(client_version: Option<&str>, warnings: &[DeprecationWarning]) {
+    match client_version {
+        Some(version) if !warnings.is_empty() => {
+            log::warn!("⚠️ Cliente v{} usa {} campo(s) obsoleto(s)", version, warnings.len());
+        }
+        Some(version) => {
+            let first_seen = {
+                let mut seen = SEEN_CLIENT_VERSIONS.lock().unwrap();
+                !seen.contains(version) && seen.len() < MAX_SEEN_CLIENT_VERSIONS && seen.insert(version.to_string())
+            };
+            if first_seen {
+                log::info!("🔖 Cliente v{}", version);
+            } else {
+                log::debug!("🔖 Cliente v{}", version);
+            }
+        }
+        None if !warnings.is_empty() => {
+            log::warn!("⚠️ Cliente sin X-Client-Version usa {} campo(s) obsoleto(s)", warnings.len());
+        }
+        None => {}
+    }
+}
@@ -0,0 +1,140 @@
+use super::Grant;
+use crate::config::{JwtSettings, TokenScope};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Espera mínima entre descargas del JWKS al no encontrar un `kid`, para que
+/// tokens inventados no conviertan el bridge en un cliente del proveedor
+const JWKS_RETRY: Duration = Duration::from_secs(60);
+
+/// Si `token` tiene la forma de un JWT (tres partes separadas por puntos);
+/// los tokens estáticos del bridge nunca llevan puntos
+pub fn looks_like_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+/// Claves del JWKS descargadas y cuándo se descargaron
+#[derive(Clone, Default)]
+pub struct JwksCache {
+    keys: Arc<Mutex<Option<(Instant, JwkSet)>>>,
+}
+
+impl JwksCache {
+    /// Comprueba firma, caducidad, `iss` y `aud` de `token` y traduce sus
+    /// claims a permisos e impresoras permitidas
+    pub async fn verify(&self, token: &str, settings: &JwtSettings) -> Result<Grant, String> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| e.to_string())?;
+        let hmac = matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512);
+
+        // El algoritmo lo fija el tipo de clave, no el token: un HS256 firmado
+        // con la clave pública del JWKS no debe pasar
+        let key = if hmac {
+            let secret = settings.secret.as_deref().ok_or("Tokens HMAC no admitidos: falta jwt.secret")?;
+            DecodingKey::from_secret(secret.as_bytes())
+        } else {
+            let url = settings.jwks_url.as_deref().ok_or("Tokens firmados con clave pública no admitidos: falta jwt.jwks_url")?;
+            let kid = header.kid.as_deref().ok_or("El token no indica kid")?;
+            self.key(url, kid, Duration::from_secs(settings.jwks_cache_secs)).await?
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = settings.leeway_secs;
+        if let Some(issuer) = &settings.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &settings.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &key, &validation)
+            .map_err(|e| e.to_string())?
+            .claims;
+
+        let scopes = scopes(&claims, settings);
+        if scopes.is_empty() {
+            return Err(format!("El claim '{}' no concede ningún permiso", settings.scope_claim));
+        }
+        let printers = claims.get(&settings.printers_claim).map(strings);
+        let subject = claims.get("sub").and_then(|sub| sub.as_str()).unwrap_or("?");
+
+        Ok(Grant {
+            scopes,
+            caller: Some(format!("jwt:{}", subject)),
+            printers,
+        })
+    }
+
+    /// Clave `kid` del JWKS; se vuelve a descargar si caducó o no la tiene
+    /// (el proveedor rotó las claves)
+    async fn key(&self, url: &str, kid: &str, max_age: Duration) -> Result<DecodingKey, String> {
+        let cached = {
+            let keys = self.keys.lock().unwrap();
+            match keys.as_ref() {
+                Some((fetched, set)) if fetched.elapsed() < max_age => match set.find(kid) {
+                    Some(jwk) => return DecodingKey::from_jwk(jwk).map_err(|e| e.to_string()),
+                    None if fetched.elapsed() < JWKS_RETRY => return Err(format!("Clave {} desconocida", kid)),
+                    None => true,
+                },
+                _ => false,
+            }
+        };
+
+        log::debug!("🔑 Descargando JWKS de {} ({})", url, if cached { "kid nuevo" } else { "caducado" });
+        let set: JwkSet = reqwest::Client::new()
+            .get(url)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("No se pudo descargar el JWKS: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("JWKS inválido: {}", e))?;
+
+        let key = set
+            .find(kid)
+            .map(|jwk| DecodingKey::from_jwk(jwk).map_err(|e| e.to_string()))
+            .unwrap_or_else(|| Err(format!("Clave {} desconocida", kid)));
+        *self.keys.lock().unwrap() = Some((Instant::now(), set));
+        key
+    }
+}
+
+/// Permisos del bridge que concede el claim de permisos
+fn scopes(claims: &serde_json::Value, settings: &JwtSettings) -> Vec<TokenScope> {
+    let values = match claims.get(&settings.scope_claim) {
+        Some(serde_json::Value::String(text)) => text.split_whitespace().map(str::to_string).collect(),
+        Some(value) => strings(value),
+        None => Vec::new(),
+    };
+
+    let mut scopes = Vec::new();
+    for value in values {
+        let scope = if settings.scope_map.is_empty() {
+            match value.as_str() {
+                "print" => Some(TokenScope::Print),
+                "admin" => Some(TokenScope::Admin),
+                _ => None,
+            }
+        } else {
+            settings.scope_map.get(&value).copied()
+        };
+        if let Some(scope) = scope.filter(|scope| !scopes.contains(scope)) {
+            scopes.push(scope);
+        }
+    }
+    scopes
+}
+
+/// Las cadenas de una lista JSON; cualquier otro valor, ninguna
+fn strings(value: &serde_json::Value) -> Vec<String> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item.as_str().map(str::to_string))
+        .collect()
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Fallos de autenticación seguidos de una IP
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Tokens inválidos por IP. Tras `auth_max_failures` seguidos la IP queda
+/// bloqueada `auth_lockout_secs`; un acierto o un periodo igual sin fallos
+/// pone la cuenta a cero.
+#[derive(Clone, Default)]
+pub struct AuthFailures {
+    entries: Arc<Mutex<HashMap<String, Failures>>>,
+}
+
+impl AuthFailures {
+    /// Segundos de bloqueo que le quedan a `ip`
+    pub fn locked(&self, ip: &str) -> Option<u64> {
+        let entries = self.entries.lock().unwrap();
+        let remaining = entries.get(ip)?.locked_until?.checked_duration_since(Instant::now())?;
+        Some(remaining.as_secs().max(1))
+    }
+
+    /// Anota un fallo de `ip`. Devuelve los fallos seguidos y, si con este
+    /// queda bloqueada, los segundos de bloqueo.
+    pub fn record(&self, ip: &str, max_failures: u32, lockout: Duration) -> (u32, Option<u64>) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        // Sin esto un barrido de IPs haría crecer el mapa sin límite
+        entries.retain(|_, failures| now.duration_since(failures.last) < lockout);
+
+        let failures = entries.entry(ip.to_string()).or_insert(Failures {
+            count: 0,
+            last: now,
+            locked_until: None,
+        });
+        failures.count += 1;
+        failures.last = now;
+
+        if max_failures > 0 && failures.count >= max_failures {
+            failures.count = 0;
+            failures.locked_until = Some(now + lockout);
+            return (max_failures, Some(lockout.as_secs()));
+        }
+        (failures.count, None)
+    }
+
+    pub fn clear(&self, ip: &str) {
+        self.entries.lock().unwrap().remove(ip);
+    }
+}
@@ -1,142 +1,1650 @@
+mod access;
+mod compat;
+mod jwt;
+mod lockout;
+mod signing;
+
+use warp::filters::BoxedFilter;
 use warp::{Filter, Reply};
+use access::RequestTrace;
+use jwt::JwksCache;
+use lockout::AuthFailures;
+use signing::{SeenSignatures, SignatureHeaders};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use crate::printer::PrinterManager;
-use crate::error::BridgeError;
-use crate::config::Config;
+use crate::printer::{check_display_message, check_header_footer, PageRange, PrinterCache, PrinterManager};
+use crate::error::{BridgeError, FieldProblem};
+use crate::config::{self, ApiToken, Config, DedupeAction, ListenerAuth, PrintTemplate, RecoveryPolicy, TokenScope};
+use crate::control::BridgeControl;
+use crate::queue::dedupe::{Claim, ContentHash, RecentJobs};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use crate::history::{InterruptedJob, JobAttempt, JobFilter, JobHistory, JobRecord, JobState, Usage, UsageGroup};
+use crate::discovery;
+use crate::source::{self, SpooledFile};
+use futures_util::TryStreamExt;
+use warp::hyper::body::Buf;
+use crate::notifications::{self, JobOutcome, NotifyOptions};
+use crate::tools::{self, Tool, ToolReport, ToolStatus};
+use crate::renderer;
+use crate::templates;
+use crate::audit::{AuditEntry, AuditLog, AuthFailureEntry};
+use crate::metrics::MetricsSnapshot;
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Deserialize)]
+/// Cabecera con que se correlaciona una solicitud en respuestas, historial y registros
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Cabeceras que un navegador puede enviar desde otro origen
+const CORS_HEADERS: [&str; 7] = [
+    "content-type",
+    "authorization",
+    "x-api-token",
+    "x-client-version",
+    REQUEST_ID_HEADER,
+    signing::SIGNATURE_HEADER,
+    signing::TIMESTAMP_HEADER,
+];
+
+/// Tamaño máximo de `metadata` serializado
+const MAX_METADATA_BYTES: usize = 4096;
+
+/// Valores de `content_type` que el bridge sabe imprimir
+const CONTENT_TYPES: [&str; 12] = ["pdf", "html", "markdown", "text", "image", "svg", "docx", "odt", "xlsx", "template", "label", "raw"];
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct PrintRequest {
     pub printer_name: Option<String>,
+    /// Documento en línea (base64, o texto plano para html/markdown/text)
+    #[serde(default)]
     pub content: String,
-    pub content_type: String, // "pdf", "html", "text", "image"
+    /// Alternativa a `content`: el bridge descarga el documento
+    pub url: Option<String>,
+    /// Documento ya recibido en disco (subida multipart o cuerpo binario)
+    #[serde(skip)]
+    pub spooled: Option<SpooledFile>,
+    pub content_type: String, // "pdf", "html", "markdown", "text", "image", "svg", "docx", "odt", "xlsx", "template", "label", "raw"
+    /// Tipo MIME opcional del contenido (p. ej. "image/jpeg")
+    pub mime: Option<String>,
+    /// Plantilla de `templates` que produce el documento, con `content_type = "template"`,
+    /// o diseño de `labels.layouts` con `content_type = "label"`
+    pub template_name: Option<String>,
+    /// Datos con que se rellena la plantilla o la etiqueta
+    pub data: Option<serde_json::Value>,
     pub copies: Option<u32>,
     pub options: Option<PrintOptions>,
+    /// Preajuste de la configuración; lo indicado en la solicitud tiene prioridad
+    pub preset: Option<String>,
+    /// Orden en la cola cuando hay trabajos esperando
+    #[serde(default)]
+    pub priority: JobPriority,
+    /// Referencia del sistema que envía (p. ej. el número de pedido del POS)
+    pub external_id: Option<String>,
+    /// Datos libres que se guardan con el trabajo y se pueden buscar
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+    pub notify: Option<NotifyOptions>,
+    /// Validar, renderizar y comprobar la impresora sin enviar nada a CUPS
+    #[serde(default)]
+    pub dry_run: bool,
+    /// `X-Request-Id` de la solicitud; llega a CUPS como título del trabajo
+    #[serde(skip)]
+    pub request_id: Option<String>,
 }
 
-#[derive(Deserialize)]
+impl PrintRequest {
+    /// Deserializa el cuerpo de una solicitud. Si no encaja con el esquema,
+    /// prueba cada campo por separado para indicar cuáles fallan.
+    fn from_json(body: serde_json::Value) -> Result<Self, BridgeError> {
+        let error = match PrintRequest::deserialize(&body) {
+            Ok(request) => return Ok(request),
+            Err(e) => e,
+        };
+        
+        let serde_json::Value::Object(fields) = body else {
+            return Err(BridgeError::ValidationFailed(vec![FieldProblem::new("body", "se esperaba un objeto JSON")]));
+        };
+        
+        let mut problems = Vec::new();
+        if !fields.contains_key("content_type") {
+            problems.push(FieldProblem::new("content_type", "es obligatorio"));
+        }
+        for (name, value) in &fields {
+            // `content_type` es el único campo obligatorio; el resto puede ir solo
+            let mut probe = serde_json::Map::new();
+            probe.insert("content_type".to_string(), serde_json::Value::from("pdf"));
+            probe.insert(name.clone(), value.clone());
+            if let Err(e) = PrintRequest::deserialize(&serde_json::Value::Object(probe)) {
+                problems.push(FieldProblem::new(name, e.to_string()));
+            }
+        }
+        if problems.is_empty() {
+            problems.push(FieldProblem::new("body", error.to_string()));
+        }
+        
+        Err(BridgeError::ValidationFailed(problems))
+    }
+    
+    /// Comprueba lo que el esquema no expresa (valores permitidos, rangos y
+    /// campos incompatibles) y devuelve todos los problemas a la vez
+    fn validate(&self) -> Result<(), BridgeError> {
+        let mut problems = Vec::new();
+        
+        if !CONTENT_TYPES.contains(&self.content_type.as_str()) {
+            problems.push(FieldProblem::new(
+                "content_type",
+                format!("'{}' no es válido; use uno de: {}", self.content_type, CONTENT_TYPES.join(", ")),
+            ));
+        }
+        if self.copies == Some(0) {
+            problems.push(FieldProblem::new("copies", "debe ser al menos 1"));
+        }
+        if self.printer_name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            problems.push(FieldProblem::new("printer_name", "no puede estar vacío; omítalo para usar la predeterminada"));
+        }
+        
+        // Con `content_type = "template"` o `"label"` ya se habrían aplicado y quitado
+        if self.template_name.is_some() {
+            problems.push(FieldProblem::new("template_name", "solo se aplica con content_type = template o label"));
+        }
+        if self.data.is_some() {
+            problems.push(FieldProblem::new("data", "solo se aplica con content_type = template o label"));
+        }
+        
+        let has_content = !self.content.is_empty() || self.spooled.is_some();
+        match (&self.url, has_content) {
+            (Some(_), true) => problems.push(FieldProblem::new("url", "use 'content' o 'url', no ambos")),
+            (None, false) => problems.push(FieldProblem::new("content", "falta 'content' o 'url'")),
+            _ => {}
+        }
+        
+        if let Some(options) = &self.options {
+            problems.extend(options.problems());
+            let rendered = matches!(self.content_type.as_str(), "pdf" | "html" | "markdown");
+            if options.page_range.as_deref().is_some_and(|range| PageRange::parse(range).is_ok()) && !rendered {
+                problems.push(FieldProblem::new("options.page_range", "solo se aplica a PDF, HTML y Markdown"));
+            }
+            if options.watermark.is_some() && !rendered {
+                problems.push(FieldProblem::new("options.watermark", "solo se aplica a PDF, HTML y Markdown"));
+            }
+            for (field, template) in [("options.header", &options.header), ("options.footer", &options.footer)] {
+                let valid = template.as_deref().is_some_and(|template| check_header_footer(template).is_ok());
+                if valid && !matches!(self.content_type.as_str(), "text" | "html" | "markdown") {
+                    problems.push(FieldProblem::new(field, "solo se aplica a texto, HTML y Markdown"));
+                }
+            }
+            if options.text.is_some() && self.content_type != "text" {
+                problems.push(FieldProblem::new("options.text", "solo se aplica a texto"));
+            }
+            if options.spreadsheet.is_some() && self.content_type != "xlsx" {
+                problems.push(FieldProblem::new("options.spreadsheet", "solo se aplica a hojas de cálculo XLSX"));
+            }
+            if self.content_type != "image" {
+                if options.fit.is_some() {
+                    problems.push(FieldProblem::new("options.fit", "solo se aplica a imágenes"));
+                }
+                if options.auto_rotate.is_some() {
+                    problems.push(FieldProblem::new("options.auto_rotate", "solo se aplica a imágenes"));
+                }
+            }
+        }
+        
+        if let Some(webhook) = self.notify.as_ref().and_then(|notify| notify.webhook.as_deref()) {
+            let valid = reqwest::Url::parse(webhook).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+            if !valid {
+                problems.push(FieldProblem::new("notify.webhook", "debe ser una URL http o https"));
+            }
+        }
+        
+        // Los metadatos se copian en cada fila del historial y en los avisos
+        let metadata_size = serde_json::to_string(&self.metadata).map_or(0, |m| m.len());
+        if metadata_size > MAX_METADATA_BYTES {
+            problems.push(FieldProblem::new(
+                "metadata",
+                format!("ocupa {} bytes; el máximo es {}", metadata_size, MAX_METADATA_BYTES),
+            ));
+        }
+        
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(BridgeError::ValidationFailed(problems))
+        }
+    }
+    
+    /// Completa impresora, copias y opciones con las del preajuste pedido. Se
+    /// aplica una sola vez: al preparar el documento se consumen opciones
+    /// (`watermark`, `page_range`) que el preajuste no debe volver a poner.
+    fn apply_preset(&mut self, config: &Config) -> Result<(), BridgeError> {
+        let Some(name) = self.preset.take() else {
+            return Ok(());
+        };
+        let preset = config.presets.get(&name)
+            .ok_or_else(|| BridgeError::InvalidRequest(format!("Preajuste desconocido: {}", name)))?;
+        
+        self.printer_name = self.printer_name.take().or_else(|| preset.printer.clone());
+        self.copies = self.copies.or(preset.copies);
+        self.options = Some(match self.options.take() {
+            Some(options) => options.or(&preset.options),
+            None => preset.options.clone(),
+        });
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct PrintOptions {
     pub paper_size: Option<String>,
     pub orientation: Option<String>,
     pub color: Option<bool>,
     pub duplex: Option<bool>,
+    /// Bandeja de entrada, uno de los valores de `trays` de la impresora
+    pub tray: Option<String>,
+    /// Cómo ajustar una imagen a la página
+    pub fit: Option<ImageFit>,
+    /// Girar la imagen para que coincida con la orientación de la página
+    pub auto_rotate: Option<bool>,
+    /// Páginas a imprimir, como `1-3,5,8-`. El bridge recorta el PDF antes de
+    /// enviarlo, así que funciona igual en colas raw que no entienden `page-ranges`.
+    pub page_range: Option<String>,
+    /// Contraseña de un PDF protegido. Se descifra antes de encolarlo y no se
+    /// guarda en el historial.
+    pub pdf_password: Option<String>,
+    /// Texto estampado en cada página, p. ej. "COPIA" o el número de pedido
+    pub watermark: Option<Watermark>,
+    /// Cabecera de cada página, con marcadores como `{business}` o `{page}`.
+    /// Vacía quita la de `[header_footer]`.
+    pub header: Option<String>,
+    /// Pie de cada página, con los mismos marcadores que `header`
+    pub footer: Option<String>,
+    /// Convertir el texto en el bridge (PDF o ESC/POS) en lugar de dejárselo
+    /// al filtro de CUPS
+    pub text: Option<TextOptions>,
+    /// Hoja, área de impresión y escala de un XLSX
+    pub spreadsheet: Option<SpreadsheetOptions>,
+    /// Cortar el papel al terminar (impresoras de tickets ESC/POS)
+    pub cut: Option<bool>,
+    /// Abrir el cajón portamonedas al terminar
+    pub open_drawer: Option<bool>,
+    /// Pitar al terminar, p. ej. para avisar en cocina de una comanda
+    pub beep: Option<BeepOptions>,
+    /// Mensaje para el visor de cliente conectado a la impresora; `\n` separa
+    /// las dos líneas
+    pub display_message: Option<String>,
+}
+
+impl PrintOptions {
+    /// Valores fuera de rango o mal formados, sea cual sea el tipo de
+    /// contenido. Sirve también para las opciones de los preajustes.
+    pub fn problems(&self) -> Vec<FieldProblem> {
+        let mut problems = Vec::new();
+        if let Some(orientation) = self.orientation.as_deref() {
+            if !matches!(orientation, "portrait" | "landscape") {
+                problems.push(FieldProblem::new(
+                    "options.orientation",
+                    format!("'{}' no es válida; use portrait o landscape", orientation),
+                ));
+            }
+        }
+        if let Some(Err(message)) = self.page_range.as_deref().map(PageRange::parse) {
+            problems.push(FieldProblem::new("options.page_range", message));
+        }
+        if let Some(watermark) = &self.watermark {
+            if watermark.text.trim().is_empty() {
+                problems.push(FieldProblem::new("options.watermark.text", "no puede estar vacío"));
+            }
+            if watermark.opacity.is_some_and(|opacity| !(opacity > 0.0 && opacity <= 1.0)) {
+                problems.push(FieldProblem::new("options.watermark.opacity", "debe estar entre 0 (sin incluir) y 1"));
+            }
+        }
+        for (field, template) in [("options.header", &self.header), ("options.footer", &self.footer)] {
+            if let Some(Err(message)) = template.as_deref().map(check_header_footer) {
+                problems.push(FieldProblem::new(field, message));
+            }
+        }
+        if let Some(text) = &self.text {
+            problems.extend(text.problems());
+        }
+        if let Some(spreadsheet) = &self.spreadsheet {
+            if spreadsheet.sheet.as_deref().is_some_and(|sheet| sheet.trim().is_empty()) {
+                problems.push(FieldProblem::new("options.spreadsheet.sheet", "no puede estar vacío"));
+            }
+            if let Some(Err(message)) = spreadsheet.range.as_deref().map(renderer::check_print_range) {
+                problems.push(FieldProblem::new("options.spreadsheet.range", message));
+            }
+        }
+        if let Some(beep) = &self.beep {
+            if beep.times.is_some_and(|times| !(1..=9).contains(&times)) {
+                problems.push(FieldProblem::new("options.beep.times", "debe estar entre 1 y 9"));
+            }
+            if beep.duration_ms.is_some_and(|ms| !(50..=450).contains(&ms)) {
+                problems.push(FieldProblem::new("options.beep.duration_ms", "debe estar entre 50 y 450"));
+            }
+        }
+        if let Some(Err(message)) = self.display_message.as_deref().map(check_display_message) {
+            problems.push(FieldProblem::new("options.display_message", message));
+        }
+        problems
+    }
+    
+    /// Cada opción sin valor toma la de `fallback`
+    fn or(self, fallback: &PrintOptions) -> PrintOptions {
+        PrintOptions {
+            paper_size: self.paper_size.or_else(|| fallback.paper_size.clone()),
+            orientation: self.orientation.or_else(|| fallback.orientation.clone()),
+            color: self.color.or(fallback.color),
+            duplex: self.duplex.or(fallback.duplex),
+            tray: self.tray.or_else(|| fallback.tray.clone()),
+            fit: self.fit.or(fallback.fit),
+            auto_rotate: self.auto_rotate.or(fallback.auto_rotate),
+            page_range: self.page_range.or_else(|| fallback.page_range.clone()),
+            pdf_password: self.pdf_password.or_else(|| fallback.pdf_password.clone()),
+            watermark: self.watermark.or_else(|| fallback.watermark.clone()),
+            header: self.header.or_else(|| fallback.header.clone()),
+            footer: self.footer.or_else(|| fallback.footer.clone()),
+            text: self.text.or_else(|| fallback.text.clone()),
+            spreadsheet: self.spreadsheet.or_else(|| fallback.spreadsheet.clone()),
+            cut: self.cut.or(fallback.cut),
+            open_drawer: self.open_drawer.or(fallback.open_drawer),
+            beep: self.beep.or_else(|| fallback.beep.clone()),
+            display_message: self.display_message.or_else(|| fallback.display_message.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPriority {
+    /// Informes y lotes grandes
+    Low,
+    #[default]
+    Normal,
+    /// Tickets para el cliente: pasan por delante del resto
+    High,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFit {
+    /// Toda la imagen visible, lo más grande posible
+    Contain,
+    /// Cubrir la página entera, recortando lo que sobre
+    Cover,
+    /// Tamaño real (96 ppp), centrada
+    Actual,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Watermark {
+    pub text: String,
+    /// De 0 (invisible) a 1 (opaca); 0.3 si no se indica
+    pub opacity: Option<f32>,
+    pub position: Option<WatermarkPosition>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatermarkPosition {
+    /// En diagonal, cruzando la página
+    Center,
+    /// Sello pequeño en el margen superior
+    Top,
+    /// Sello pequeño en el margen inferior
+    Bottom,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SpreadsheetOptions {
+    /// Nombre de la hoja a imprimir; sin valor, todas las visibles
+    pub sheet: Option<String>,
+    /// Área de impresión como `A1:F40`, de `sheet` o de la primera hoja visible
+    pub range: Option<String>,
+    /// Escalar la hoja al ancho de la página, con las páginas de alto que haga falta
+    pub fit_to_width: Option<bool>,
+}
+
+/// Zumbador de las impresoras ESC/POS
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BeepOptions {
+    /// Pitidos seguidos, de 1 a 9 (sin valor, 1)
+    pub times: Option<u8>,
+    /// Duración de cada pitido en ms, de 50 a 450 en pasos de 50 (sin valor, 200)
+    pub duration_ms: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TextOptions {
+    #[serde(default)]
+    pub output: TextOutput,
+    /// Solo PDF
+    pub font: Option<TextFont>,
+    /// Cuerpo en puntos. Solo PDF; sin valor se ajusta a `chars_per_line`
+    pub font_size: Option<f32>,
+    /// Las líneas más largas se parten, por un espacio si lo hay
+    pub chars_per_line: Option<u32>,
+    /// Margen en milímetros; en ESC/POS solo el izquierdo
+    pub margin_mm: Option<f32>,
+    /// Solo ESC/POS
+    pub encoding: Option<TextEncoding>,
+}
+
+impl TextOptions {
+    /// Valores fuera de rango y opciones que no aplican a `output`
+    fn problems(&self) -> Vec<FieldProblem> {
+        let mut problems = Vec::new();
+        if self.font_size.is_some_and(|size| !(size > 0.0 && size <= 72.0)) {
+            problems.push(FieldProblem::new("options.text.font_size", "debe estar entre 0 (sin incluir) y 72"));
+        }
+        if self.chars_per_line == Some(0) {
+            problems.push(FieldProblem::new("options.text.chars_per_line", "debe ser al menos 1"));
+        }
+        if self.margin_mm.is_some_and(|margin| !(0.0..=50.0).contains(&margin)) {
+            problems.push(FieldProblem::new("options.text.margin_mm", "debe estar entre 0 y 50"));
+        }
+        match self.output {
+            TextOutput::Pdf if self.encoding.is_some() => {
+                problems.push(FieldProblem::new("options.text.encoding", "solo se aplica con output = escpos; el PDF usa Latin-1"));
+            }
+            TextOutput::Escpos => {
+                for (field, set) in [("options.text.font", self.font.is_some()), ("options.text.font_size", self.font_size.is_some())] {
+                    if set {
+                        problems.push(FieldProblem::new(field, "solo se aplica con output = pdf; en ESC/POS manda la fuente de la impresora"));
+                    }
+                }
+            }
+            TextOutput::Pdf => {}
+        }
+        problems
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextOutput {
+    /// Páginas con fuente, cuerpo y márgenes fijos
+    #[default]
+    Pdf,
+    /// Comandos para impresoras de tickets, enviados a la cola en crudo
+    Escpos,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextFont {
+    /// Monoespaciada: las columnas del texto quedan alineadas
+    Courier,
+    Helvetica,
+    Times,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextEncoding {
+    /// La tabla de fábrica de casi todas las impresoras de tickets
+    Cp437,
+    /// Latin-1 de DOS, con las vocales acentuadas en mayúscula
+    Cp850,
+}
+
+/// Parámetros de `/api/print/raw`, planos porque viajan en la query
+#[derive(Deserialize)]
+pub struct RawPrintParams {
+    pub printer_name: Option<String>,
+    pub external_id: Option<String>,
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub priority: JobPriority,
+    pub content_type: Option<String>,
+    pub copies: Option<u32>,
+    pub paper_size: Option<String>,
+    pub orientation: Option<String>,
+    pub color: Option<bool>,
+    pub duplex: Option<bool>,
+    pub tray: Option<String>,
+    pub fit: Option<ImageFit>,
+    pub auto_rotate: Option<bool>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Varios documentos que se imprimen en orden como un solo lote
+#[derive(Deserialize)]
+pub struct BatchPrintRequest {
+    /// Cada elemento tiene la misma forma que el cuerpo de `/api/print`
+    pub items: Vec<serde_json::Value>,
+    /// Omitir los elementos restantes tras el primer fallo
+    #[serde(default)]
+    pub abort_on_failure: bool,
+    /// Unir los PDF en un solo trabajo, para que salgan juntos aunque otros
+    /// usuarios impriman a la vez. El primer elemento decide impresora y opciones.
+    #[serde(default)]
+    pub merge: bool,
+}
+
+/// Cuerpo de `POST /api/peripherals/drawer/open`
+#[derive(Deserialize)]
+pub struct DrawerRequest {
+    /// Impresora de tickets con el cajón; sin valor, la predeterminada
+    pub printer_name: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchItemStatus {
+    Printed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Serialize)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub status: BatchItemStatus,
+    pub job_id: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+}
+
+impl BatchItemResult {
+    fn printed(index: usize, response: PrintResponse) -> Self {
+        Self {
+            index,
+            status: BatchItemStatus::Printed,
+            job_id: response.job_id,
+            message: response.message,
+            error: None,
+        }
+    }
+    
+    fn failed(index: usize, error: &BridgeError) -> Self {
+        Self {
+            index,
+            status: BatchItemStatus::Failed,
+            job_id: None,
+            message: error.to_string(),
+            error: Some(error.code()),
+        }
+    }
+    
+    fn skipped(index: usize) -> Self {
+        Self {
+            index,
+            status: BatchItemStatus::Skipped,
+            job_id: None,
+            message: "Omitido por un fallo anterior en el lote".to_string(),
+            error: None,
+        }
+    }
 }
 
 #[derive(Serialize)]
+pub struct BatchPrintResponse {
+    pub success: bool,
+    pub batch_id: String,
+    pub results: Vec<BatchItemResult>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deprecation: Vec<compat::DeprecationWarning>,
+}
+
+#[derive(Serialize, Clone)]
 pub struct PrintResponse {
     pub success: bool,
     pub message: String,
     pub job_id: Option<String>,
+    /// Cola que imprimió de verdad; difiere de la pedida si hubo respaldo
+    pub printer: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deprecation: Vec<compat::DeprecationWarning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<DryRunReport>,
+    /// Páginas impresas contando las copias, si se pudieron contar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<u64>,
 }
 
-#[derive(Serialize)]
+/// Lo que habría hecho un trabajo con `dry_run`
+#[derive(Serialize, Clone, Debug)]
+pub struct DryRunReport {
+    /// Cola elegida tras alias, grupos y respaldos
+    pub printer: String,
+    /// URI del dispositivo (o la cola, si CUPS no expone ninguna)
+    pub device: String,
+    /// Argumentos que se pasarían a `lp`
+    pub lp_args: Vec<String>,
+    /// Tamaño del PDF generado, para los tipos que el bridge convierte
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rendered_bytes: Option<u64>,
+}
+
+#[derive(Serialize, Clone)]
 pub struct PrinterInfo {
     pub name: String,
     pub status: String,
     pub is_default: bool,
     pub supports_color: bool,
     pub paper_sizes: Vec<String>,
+    /// Bandejas de entrada (InputSlot/MediaSource) que acepta `options.tray`
+    pub trays: Vec<String>,
+    /// Nombres amigables de `printer_aliases` que apuntan a esta cola
+    pub aliases: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PrinterDetail {
+    pub name: String,
+    pub status: String,
+    pub is_default: bool,
+    pub aliases: Vec<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    /// Trabajos pendientes en la cola
+    pub queue_length: usize,
+    /// Todas las opciones del driver (bandejas, soportes, resolución, dúplex...)
+    pub options: Vec<PrinterOption>,
+    /// Consumibles que informa CUPS; vacío si la impresora no los reporta
+    pub supplies: Vec<SupplyLevel>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SupplyLevel {
+    pub name: String,
+    /// `toner`, `ink-cartridge`, `paper`... según `marker-types`
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    /// Color en hexadecimal (`#00FFFF`)
+    pub color: Option<String>,
+    /// Porcentaje restante; `None` si la impresora no lo sabe
+    pub level: Option<u8>,
+    /// Por debajo del umbral de aviso de la impresora
+    pub low: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PrinterOption {
+    pub key: String,
+    pub label: String,
+    pub default: Option<String>,
+    pub choices: Vec<String>,
+}
+
+/// Formato de la vista previa: el PDF completo o la primera página
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewFormat {
+    #[default]
+    Pdf,
+    /// Primera página rasterizada
+    Png,
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    #[serde(default)]
+    pub format: PreviewFormat,
+}
+
+/// Filtros de `/api/jobs`. `from`/`to` admiten RFC 3339 o una fecha
+/// `AAAA-MM-DD` en hora local (`to` incluye ese día completo).
+#[derive(Deserialize)]
+pub struct JobsQuery {
+    pub status: Option<JobStatusFilter>,
+    pub printer: Option<String>,
+    pub external_id: Option<String>,
+    pub request_id: Option<String>,
+    /// `clave:valor` sobre `metadata` (p. ej. `order_id:A-1042`)
+    pub metadata: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct HealthQuery {
+    /// Añadir la autocomprobación; sin ella la respuesta es inmediata
+    #[serde(default)]
+    pub detail: bool,
+}
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Consumo de un token en el periodo pedido y en el día de hoy
+#[derive(Serialize)]
+pub struct TokenUsage {
+    /// `None` agrupa el token principal y los trabajos sin autenticar
+    pub token_id: Option<String>,
+    pub name: Option<String>,
+    pub jobs: u64,
+    pub pages: u64,
+    pub pages_today: u64,
+    pub daily_page_quota: Option<u64>,
+    pub remaining_today: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct LogLevelRequest {
+    /// Nivel (`debug`) o directivas con la sintaxis de `RUST_LOG`
+    pub level: String,
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PrinterUsage {
+    pub printer: Option<String>,
+    pub jobs: u64,
+    pub pages: u64,
+}
+
+#[derive(Serialize)]
+pub struct UsageStats {
+    pub tokens: Vec<TokenUsage>,
+    pub printers: Vec<PrinterUsage>,
+    /// Desde el arranque, sin importar el periodo pedido
+    pub since_start: MetricsSnapshot,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatusFilter {
+    Printed,
+    Failed,
+}
+
+#[derive(Serialize)]
+pub struct JobsPage {
+    pub jobs: Vec<JobRecord>,
+    pub total: u64,
+    pub page: u32,
+    pub per_page: u32,
+    pub pages: u64,
+}
+
+#[derive(Deserialize)]
+pub struct PrintersQuery {
+    /// Ignorar la caché y volver a consultar CUPS
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Option<Vec<TokenScope>>,
+    pub daily_page_quota: Option<u64>,
+    pub printers: Option<Vec<String>>,
+}
+
+/// Vista pública de un token: nunca incluye el secreto completo
+#[derive(Serialize)]
+pub struct TokenSummary {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: u64,
+    pub token_hint: String,
+    pub daily_page_quota: Option<u64>,
+    pub printers: Option<Vec<String>>,
+}
+
+impl From<&ApiToken> for TokenSummary {
+    fn from(token: &ApiToken) -> Self {
+        Self {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            scopes: token.scopes.clone(),
+            created_at: token.created_at,
+            token_hint: format!("{}…", token.token.chars().take(4).collect::<String>()),
+            daily_page_quota: token.daily_page_quota,
+            printers: token.printers.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ContentTypeCapability {
+    pub content_type: String,
+    pub available: bool,
+    pub missing: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct Capabilities {
+    pub content_types: Vec<ContentTypeCapability>,
+    pub dependencies: Vec<ToolStatus>,
 }
 
 #[derive(Clone)]
 pub struct SecurityContext {
     pub config: Arc<Config>,
     pub rate_limiter: Arc<Mutex<HashMap<String, Vec<u64>>>>,
+    pub tokens: Arc<Mutex<Vec<ApiToken>>>,
+    /// Un solo cambio del archivo de configuración a la vez desde la API: dos
+    /// escrituras simultáneas podrían guardarse en orden inverso y perder una
+    pub config_writes: Arc<tokio::sync::Mutex<()>>,
+    /// Se vuelve a detectar cuando una recarga cambia `tool_paths`
+    pub tools: Arc<Mutex<Arc<ToolReport>>>,
+    pub control: BridgeControl,
+    pub history: JobHistory,
+    pub printers: PrinterCache,
+    pub recent: RecentJobs,
+    pub audit: AuditLog,
+    pub auth_failures: AuthFailures,
+    pub jwks: JwksCache,
+    pub signatures: SeenSignatures,
+    /// Autenticación que exige el listener por el que llegó la solicitud
+    pub listener: ListenerAuth,
+    /// Token adicional con que se autenticó la solicitud; `None` con el token
+    /// principal o con el bridge abierto
+    pub caller: Option<String>,
+    /// Impresoras (alias o colas) a las que puede enviar quien llama; `None` = todas
+    pub allowed_printers: Option<Vec<String>>,
+    /// `X-Request-Id` de la solicitud en curso, el del cliente o uno generado
+    pub request_id: String,
+    /// Dirección y `Origin` del cliente, para el registro de auditoría
+    pub client_ip: Option<String>,
+    pub origin: Option<String>,
 }
 
-pub fn routes(config: Config) -> impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone {
-    let security_context = SecurityContext {
-        config: Arc::new(config),
-        rate_limiter: Arc::new(Mutex::new(HashMap::new())),
-    };
+impl SecurityContext {
+    fn new(config: Config, control: BridgeControl, history: JobHistory) -> Self {
+        // Detectar herramientas externas al arrancar; después solo si cambia `tool_paths`
+        let tool_report = detect_tools(&config);
+        
+        SecurityContext {
+            tokens: Arc::new(Mutex::new(config.tokens.clone())),
+            config_writes: Arc::new(tokio::sync::Mutex::new(())),
+            tools: Arc::new(Mutex::new(Arc::new(tool_report))),
+            control,
+            history,
+            printers: PrinterCache::default(),
+            recent: RecentJobs::default(),
+            audit: AuditLog::open(),
+            auth_failures: AuthFailures::default(),
+            jwks: JwksCache::default(),
+            signatures: SeenSignatures::default(),
+            listener: ListenerAuth::Token,
+            caller: None,
+            allowed_printers: None,
+            request_id: String::new(),
+            client_ip: None,
+            origin: None,
+            config: Arc::new(config),
+            rate_limiter: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
     
-    // Configurar CORS correctamente
-    let cors = if security_context.config.allowed_origins.contains(&"*".to_string()) {
-        // Si contiene "*", permitir cualquier origen
-        warp::cors()
-            .allow_any_origin()
-            .allow_headers(vec!["content-type", "authorization", "x-api-token"])
-            .allow_methods(vec!["GET", "POST", "OPTIONS"])
-    } else {
-        // Si no, usar los orígenes específicos (deben tener esquema completo)
-        warp::cors()
-            .allow_origins(security_context.config.allowed_origins.iter().map(|s| s.as_str()).collect::<Vec<_>>())
-            .allow_headers(vec!["content-type", "authorization", "x-api-token"])
-            .allow_methods(vec!["GET", "POST", "OPTIONS"])
+    /// Estado compartido por las rutas de todos los listeners: tokens,
+    /// límites, deduplicación y auditoría valen para cualquiera de ellos
+    pub fn start(config: Config, control: BridgeControl, history: JobHistory) -> Self {
+        let security_context = Self::new(config, control, history);
+        
+        // Solo en el primer arranque: tras un reinicio del servidor los trabajos
+        // "en curso" siguen vivos en este proceso
+        static RECOVERY: std::sync::Once = std::sync::Once::new();
+        RECOVERY.call_once(|| recover_interrupted(&security_context));
+        
+        sync_tokens(&security_context);
+        sync_tools(&security_context);
+        security_context
+    }
+    
+    /// Herramientas externas detectadas con los `tool_paths` vigentes
+    pub fn tools(&self) -> Arc<ToolReport> {
+        self.tools.lock().unwrap().clone()
+    }
+    
+    fn trace(&self) -> RequestTrace {
+        RequestTrace {
+            request_id: self.request_id.clone(),
+            caller: self.caller.clone(),
+        }
+    }
+    
+    fn for_request(mut self, request: RequestInfo) -> Self {
+        self.request_id = request.request_id;
+        self.client_ip = request.client_ip;
+        self.origin = request.origin;
+        self
+    }
+}
+
+/// Identificador de la solicitud y datos del cliente, leídos antes de autenticar
+struct RequestInfo {
+    request_id: String,
+    client_ip: Option<String>,
+    origin: Option<String>,
+}
+
+/// Rutas de la API para un listener; todos comparten `context`
+pub fn routes(context: &SecurityContext, listener: ListenerAuth) -> impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone {
+    let security_context = SecurityContext { listener, ..context.clone() };
+    
+    let cors = cors(&security_context.config, &security_context.config.cors.methods);
+    let preflights = route_preflights(&security_context.config);
+    
+    // Huella del certificado para que el usuario pueda confiar en él
+    let tls_fingerprint = match crate::tls::resolve(&security_context.config) {
+        Ok(files) => files.map(|f| f.fingerprint),
+        Err(e) => {
+            log::error!("❌ {}", e);
+            None
+        }
     };
     
+    let queue = security_context.control.jobs().clone();
     let health = warp::path("health")
         .and(warp::get())
-        .map(|| warp::reply::json(&serde_json::json!({
-            "status": "ok",
-            "service": "print-my-bridge",
-            "version": env!("CARGO_PKG_VERSION")
-        })));
+        .and(warp::query::<HealthQuery>())
+        .and(with_credentials())
+        .and(with_security_context(security_context.clone()))
+        .and(with_request_info())
+        .and_then(move |query: HealthQuery, token: Option<String>, ctx: SecurityContext, request: RequestInfo| {
+            let mut body = serde_json::json!({
+                "status": "ok",
+                "service": "print-my-bridge",
+                "version": env!("CARGO_PKG_VERSION"),
+                "tls_fingerprint": tls_fingerprint,
+                "queue": {
+                    "paused": queue.is_held(),
+                    "pending": queue.pending(),
+                    "waiting": queue.waiting(),
+                    "max_pending": ctx.config.max_pending_jobs,
+                }
+            });
+            async move {
+                // El detalle consulta CUPS y el disco y enseña rutas del
+                // equipo: pide token y cuenta para el límite de solicitudes
+                if query.detail {
+                    let ctx = authorize(token, ctx.for_request(request), TokenScope::Print).await?;
+                    let detail = health_detail(&ctx.config, &ctx.control, ctx.history.clone(), ctx.config.content_hash()).await;
+                    if let (Some(body), serde_json::Value::Object(detail)) = (body.as_object_mut(), detail) {
+                        body.extend(detail);
+                    }
+                }
+                Ok::<_, warp::Rejection>(warp::reply::json(&body))
+            }
+        });
+    
+    // Página sencilla para probar la impresión desde el navegador
+    let test_page = warp::path("test-page")
+        .and(warp::get())
+        .map(|| warp::reply::html(include_str!("test_page.html")));
     
-    let auth_filter = warp::header::optional::<String>("x-api-token")
+    // Descripción OpenAPI para que los clientes generen sus SDK; es pública
+    // como `/health` porque no expone nada de esta instalación
+    let spec = openapi_spec(&security_context.config);
+    let openapi_context = security_context.clone();
+    let openapi = warp::path!("api" / "openapi.json")
+        .and(warp::get())
+        .map(move || {
+            // El puerto real, si el configurado estaba ocupado
+            let mut spec = spec.clone();
+            if let Some(port) = openapi_context.control.server_port() {
+                spec["servers"][0]["url"] = openapi_context.config.base_url_on(port).into();
+            }
+            warp::reply::json(&spec)
+        });
+    
+    let auth_filter = with_credentials()
         .and(with_security_context(security_context.clone()))
+        .and(with_request_info())
         .and_then(validate_auth);
     
     let printers = warp::path!("api" / "printers")
         .and(warp::get())
+        .and(warp::query::<PrintersQuery>())
+        .and(auth_filter.clone())
+        .and_then(|query: PrintersQuery, ctx: SecurityContext| traced(ctx.trace(), get_printers(query, ctx)));
+    
+    let discovered_printers = warp::path!("api" / "printers" / "discovered")
+        .and(warp::get())
+        .and(auth_filter.clone())
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), get_discovered_printers(ctx)));
+    
+    let printer_detail = warp::path!("api" / "printers" / String)
+        .and(warp::get())
+        .and(auth_filter.clone())
+        .and_then(|name: String, ctx: SecurityContext| traced(ctx.trace(), get_printer_detail(name, ctx)));
+    
+    let jobs = warp::path!("api" / "jobs")
+        .and(warp::get())
+        .and(warp::query::<JobsQuery>())
         .and(auth_filter.clone())
-        .and_then(get_printers);
+        .and_then(|query: JobsQuery, ctx: SecurityContext| traced(ctx.trace(), get_jobs(query, ctx)));
+    
+    let capabilities = warp::path!("api" / "capabilities")
+        .and(warp::get())
+        .and(auth_filter.clone())
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), get_capabilities(ctx)));
+    
+    let selftest = warp::path!("api" / "selftest")
+        .and(warp::get())
+        .and(auth_filter.clone())
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), get_selftest(ctx)));
+    
+    // El JSON lleva los binarios en base64 (4 bytes por cada 3) más los demás
+    // campos; el límite de cada tipo se comprueba al decodificar
+    let json_limit = security_context.config.largest_file_size() / 3 * 4 + 1024 * 1024;
     
     let print = warp::path!("api" / "print")
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 1024 * 50)) // 50MB limit
-        .and(warp::body::json())
-        .and(auth_filter)
-        .and_then(handle_print);
+        .and(warp::body::content_length_limit(json_limit))
+        .and(warp::header::optional::<String>("x-client-version"))
+        .and(signed_json::<serde_json::Value>(&security_context))
+        .and(auth_filter.clone())
+        .and_then(|version: Option<String>, body: serde_json::Value, ctx: SecurityContext| {
+            traced(ctx.trace(), handle_print(version, body, ctx))
+        });
     
-    health.or(printers).or(print).with(cors)
-}
-
-fn with_security_context(ctx: SecurityContext) -> impl Filter<Extract = (SecurityContext,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || ctx.clone())
-}
-
-async fn validate_auth(token: Option<String>, ctx: SecurityContext) -> Result<SecurityContext, warp::Rejection> {
-    // Rate limiting
-    let client_ip = "127.0.0.1".to_string(); // TODO: Get real IP
-    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let preview = warp::path!("api" / "preview")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(json_limit))
+        .and(warp::query::<PreviewQuery>())
+        .and(signed_json::<serde_json::Value>(&security_context))
+        .and(auth_filter.clone())
+        .and_then(|query: PreviewQuery, body: serde_json::Value, ctx: SecurityContext| {
+            traced(ctx.trace(), handle_preview(query, body, ctx))
+        });
+    
+    let print_batch = warp::path!("api" / "print" / "batch")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(json_limit))
+        .and(warp::header::optional::<String>("x-client-version"))
+        .and(signed_json::<BatchPrintRequest>(&security_context))
+        .and(auth_filter.clone())
+        .and_then(|version: Option<String>, batch: BatchPrintRequest, ctx: SecurityContext| {
+            traced(ctx.trace(), handle_print_batch(version, batch, ctx))
+        });
+    
+    // Límite del formulario: el documento más margen para los campos
+    let upload_limit = security_context.config.largest_file_size() + 1024 * 1024;
+    let print_upload = warp::path!("api" / "print" / "upload")
+        .and(warp::post())
+        .and(warp::multipart::form().max_length(upload_limit))
+        .and(auth_filter.clone())
+        .and_then(|form: warp::multipart::FormData, ctx: SecurityContext| traced(ctx.trace(), handle_print_upload(form, ctx)));
+    
+    let open_drawer = warp::path!("api" / "peripherals" / "drawer" / "open")
+        .and(warp::post())
+        .and(signed_json::<DrawerRequest>(&security_context))
+        .and(auth_filter.clone())
+        .and_then(|body: DrawerRequest, ctx: SecurityContext| traced(ctx.trace(), handle_open_drawer(body, ctx)));
+    
+    // Sin content_length_limit: exige Content-Length y rechazaría envíos por
+    // chunks; el límite se aplica mientras se vuelca a disco
+    let print_raw = warp::path!("api" / "print" / "raw")
+        .and(warp::post())
+        .and(warp::query::<RawPrintParams>())
+        .and(warp::header::optional::<String>("content-type"))
+        .and(signing::headers())
+        .and(warp::body::stream())
+        .and(auth_filter)
+        .and_then(|params: RawPrintParams, mime: Option<String>, signature: SignatureHeaders, body, ctx: SecurityContext| {
+            traced(ctx.trace(), handle_print_raw(params, mime, signature, body, ctx))
+        });
+    
+    let admin_filter = with_credentials()
+        .and(with_security_context(security_context.clone()))
+        .and(with_request_info())
+        .and_then(validate_admin);
+    
+    let create_token = warp::path!("api" / "tokens")
+        .and(warp::post())
+        .and(signed_json::<CreateTokenRequest>(&security_context))
+        .and(admin_filter.clone())
+        .and_then(|request: CreateTokenRequest, ctx: SecurityContext| traced(ctx.trace(), handle_create_token(request, ctx)));
+    
+    let list_tokens = warp::path!("api" / "tokens")
+        .and(warp::get())
+        .and(admin_filter.clone())
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), handle_list_tokens(ctx)));
+    
+    let delete_token = warp::path!("api" / "tokens" / String)
+        .and(warp::delete())
+        .and(signed(&security_context))
+        .and(admin_filter.clone())
+        .and_then(|id: String, ctx: SecurityContext| traced(ctx.trace(), handle_delete_token(id, ctx)));
+    
+    let pause_queue = warp::path!("api" / "queue" / "pause")
+        .and(warp::post())
+        .and(signed(&security_context))
+        .and(admin_filter.clone())
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), handle_queue_hold(true, ctx)));
+    
+    let resume_queue = warp::path!("api" / "queue" / "resume")
+        .and(warp::post())
+        .and(signed(&security_context))
+        .and(admin_filter.clone())
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), handle_queue_hold(false, ctx)));
+    
+    let stats = warp::path!("api" / "stats")
+        .and(warp::get())
+        .and(warp::query::<StatsQuery>())
+        .and(admin_filter.clone())
+        .and_then(|query: StatsQuery, ctx: SecurityContext| traced(ctx.trace(), get_stats(query, ctx)));
+    
+    let audit = warp::path!("api" / "audit")
+        .and(warp::get())
+        .and(warp::query::<AuditQuery>())
+        .and(admin_filter.clone())
+        .and_then(|query: AuditQuery, ctx: SecurityContext| traced(ctx.trace(), get_audit(query, ctx)));
+    
+    let log_level = warp::path!("api" / "admin" / "log-level")
+        .and(warp::put())
+        .and(signed_json::<LogLevelRequest>(&security_context))
+        .and(admin_filter.clone())
+        .and_then(|request: LogLevelRequest, ctx: SecurityContext| traced(ctx.trace(), handle_log_level(request, ctx)));
+    
+    let validate_config = warp::path!("api" / "admin" / "config" / "validate")
+        .and(warp::get())
+        .and(admin_filter.clone())
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), handle_validate_config(ctx)));
+    
+    let list_templates = warp::path!("api" / "admin" / "templates")
+        .and(warp::get())
+        .and(admin_filter.clone())
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), handle_list_templates(ctx)));
+    
+    let put_template = warp::path!("api" / "admin" / "templates" / String)
+        .and(warp::put())
+        .and(signed_json::<PrintTemplate>(&security_context))
+        .and(admin_filter.clone())
+        .and_then(|name: String, template: PrintTemplate, ctx: SecurityContext| traced(ctx.trace(), handle_put_template(name, template, ctx)));
+    
+    let delete_template = warp::path!("api" / "admin" / "templates" / String)
+        .and(warp::delete())
+        .and(signed(&security_context))
+        .and(admin_filter.clone())
+        .and_then(|name: String, ctx: SecurityContext| traced(ctx.trace(), handle_delete_template(name, ctx)));
+    
+    let diagnostics = warp::path!("api" / "admin" / "diagnostics")
+        .and(warp::get())
+        .and(admin_filter)
+        .and_then(|ctx: SecurityContext| traced(ctx.trace(), get_diagnostics(ctx)));
+    
+    let api = health
+        .or(test_page)
+        .or(openapi)
+        .or(printers)
+        .or(discovered_printers)
+        .or(printer_detail)
+        .or(jobs)
+        .or(capabilities)
+        .or(selftest)
+        .or(print)
+        .or(preview)
+        .or(print_batch)
+        .or(print_upload)
+        .or(print_raw)
+        .or(open_drawer)
+        .or(create_token)
+        .or(list_tokens)
+        .or(delete_token)
+        .or(pause_queue)
+        .or(resume_queue)
+        .or(stats)
+        .or(audit)
+        .or(log_level)
+        .or(validate_config)
+        .or(list_templates)
+        .or(put_template)
+        .or(delete_template)
+        .or(diagnostics);
+    
+    // Toda respuesta lleva `X-Request-Id`. Las rutas con token ya lo traen de
+    // `traced`; al resto (y a los rechazos previos al manejador) se les pone
+    // el que envió el cliente o uno nuevo. Después se anota en el registro de acceso.
+    let metrics = security_context.control.metrics().clone();
+    let routes = warp::any()
+        .map(Instant::now)
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(warp::addr::remote())
+        .and(warp::header::optional::<String>(REQUEST_ID_HEADER))
+        .and(
+            api.map(|reply| Ok(Reply::into_response(reply)))
+                .or_else(|rejection| async move { Ok::<_, std::convert::Infallible>((Err(rejection),)) }),
+        )
+        .map(move |started: Instant,
+              method: warp::http::Method,
+              path: warp::path::FullPath,
+              client: Option<SocketAddr>,
+              request_id: Option<String>,
+              result: Result<warp::reply::Response, warp::Rejection>| {
+            let response = match result {
+                Ok(response) if response.headers().contains_key(REQUEST_ID_HEADER) => response,
+                result => {
+                    let request_id = request_id_or_new(request_id);
+                    let mut response = result.unwrap_or_else(|rejection| rejection_response(&rejection, &request_id));
+                    set_request_id(&mut response, &request_id);
+                    response
+                }
+            };
+            access::record(&method, path.as_str(), client, started, &response);
+            metrics.record_request();
+            response
+        })
+        .with(cors)
+        .map(Reply::into_response)
+        .boxed();
+    
+    match preflights {
+        Some(preflights) => preflights.or(routes).unify().boxed(),
+        None => routes,
+    }
+}
+
+/// CORS de la API para `allowed_origins` y `[cors]`, con `methods` como
+/// métodos permitidos
+fn cors(config: &Config, methods: &[String]) -> warp::filters::cors::Builder {
+    // warp entra en pánico con un método inválido; validate.rs ya los señala
+    let methods: Vec<warp::http::Method> = methods
+        .iter()
+        .filter_map(|method| warp::http::Method::from_bytes(method.to_ascii_uppercase().as_bytes()).ok())
+        .collect();
+    let builder = warp::cors()
+        .allow_headers(CORS_HEADERS)
+        .allow_methods(methods)
+        .expose_headers(vec![REQUEST_ID_HEADER])
+        .allow_credentials(config.cors.allow_credentials);
+    let builder = if config.allowed_origins.iter().any(|origin| origin == "*") {
+        builder.allow_any_origin()
+    } else {
+        // Deben tener esquema completo
+        builder.allow_origins(config.allowed_origins.iter().map(String::as_str))
+    };
+    match config.cors.max_age_secs {
+        0 => builder,
+        seconds => builder.max_age(seconds),
+    }
+}
+
+/// Preflights de las rutas con métodos propios en `cors.route_methods`. El
+/// CORS de warp responde cualquier preflight sin mirar la ruta, así que cada
+/// prefijo va delante del general con su propio filtro.
+fn route_preflights(config: &Config) -> Option<BoxedFilter<(warp::reply::Response,)>> {
+    let mut prefixes: Vec<(&String, &Vec<String>)> = config.cors.route_methods.iter().collect();
+    prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+    
+    prefixes
+        .into_iter()
+        .map(|(prefix, methods)| {
+            let prefix = prefix.clone();
+            let preflight = warp::any()
+                .map(warp::reply)
+                .with(cors(config, methods))
+                .map(Reply::into_response)
+                .or_else(|_: warp::Rejection| async {
+                    let request_id = request_id_or_new(None);
+                    let rejection = warp::reject::custom(BridgeError::Forbidden);
+                    Ok::<_, warp::Rejection>((rejection_response(&rejection, &request_id),))
+                });
+            warp::options()
+                .and(warp::header::<String>("access-control-request-method"))
+                .and(warp::path::full())
+                .and_then(move |_: String, path: warp::path::FullPath| {
+                    let matches = path.as_str().starts_with(&prefix);
+                    async move { if matches { Ok(()) } else { Err(warp::reject::not_found()) } }
+                })
+                .untuple_one()
+                .and(preflight)
+                .boxed()
+        })
+        .reduce(|first, second| first.or(second).unify().boxed())
+}
+
+/// `openapi.json` con la versión y la dirección de esta instancia. Hay que
+/// actualizarlo al cambiar rutas o estructuras de la API.
+fn openapi_spec(config: &Config) -> serde_json::Value {
+    let mut spec: serde_json::Value = serde_json::from_str(include_str!("openapi.json")).expect("openapi.json válido");
+    spec["info"]["version"] = env!("CARGO_PKG_VERSION").into();
+    spec["servers"][0]["url"] = config.base_url().into();
+    spec
+}
+
+/// Respuesta de una ruta con token. Lleva en la cabecera, y si falla también en
+/// el cuerpo del error, el mismo id con que se registró el trabajo.
+async fn traced(
+    trace: RequestTrace,
+    handler: impl std::future::Future<Output = Result<impl Reply, warp::Rejection>>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let mut response = match handler.await {
+        Ok(reply) => reply.into_response(),
+        Err(rejection) => rejection_response(&rejection, &trace.request_id),
+    };
+    set_request_id(&mut response, &trace.request_id);
+    // Para el registro de acceso, que no ve el contexto
+    response.extensions_mut().insert(trace);
+    Ok(response)
+}
+
+/// El `X-Request-Id` del cliente si es razonable (hasta 128 caracteres ASCII
+/// visibles); si no, uno nuevo
+fn request_id_or_new(request_id: Option<String>) -> String {
+    request_id
+        .filter(|id| !id.is_empty() && id.len() <= 128 && id.bytes().all(|byte| byte.is_ascii_graphic()))
+        .unwrap_or_else(|| config::generate_id("req"))
+}
+
+fn set_request_id(response: &mut warp::reply::Response, request_id: &str) {
+    if let Ok(value) = warp::http::HeaderValue::from_str(request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+}
+
+/// Convierte cualquier rechazo en una respuesta JSON con código de error estable
+fn rejection_response(rejection: &warp::Rejection, request_id: &str) -> warp::reply::Response {
+    let deprecated = rejection.find::<compat::DeprecatedFailure>();
+    let bridge_error = rejection.find::<BridgeError>().or(deprecated.map(|failure| &failure.error));
+    let retry_after = bridge_error.and_then(BridgeError::retry_after);
+    let (status, code, message, details) = if let Some(error) = bridge_error {
+        (error.status(), error.code(), error.to_string(), error.details())
+    } else if rejection.is_not_found() {
+        (warp::http::StatusCode::NOT_FOUND, "NOT_FOUND", "Ruta no encontrada".to_string(), None)
+    } else if let Some(error) = rejection.find::<warp::filters::body::BodyDeserializeError>() {
+        (warp::http::StatusCode::BAD_REQUEST, "INVALID_REQUEST", error.to_string(), None)
+    } else if rejection.find::<warp::reject::PayloadTooLarge>().is_some() {
+        (warp::http::StatusCode::PAYLOAD_TOO_LARGE, "FILE_TOO_LARGE", "Cuerpo demasiado grande".to_string(), None)
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (warp::http::StatusCode::METHOD_NOT_ALLOWED, "METHOD_NOT_ALLOWED", "Método no permitido".to_string(), None)
+    } else {
+        log::error!("[{}] Rechazo no manejado: {:?}", request_id, rejection);
+        (warp::http::StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", "Error interno".to_string(), None)
+    };
+    
+    let mut body = serde_json::json!({
+        "success": false,
+        "error": code,
+        "message": message,
+        "request_id": request_id,
+    });
+    if let Some(details) = details {
+        body["details"] = details;
+    }
+    if let Some(failure) = deprecated {
+        body["deprecation"] = serde_json::json!(failure.deprecation);
+    }
+    
+    let mut response = warp::reply::with_status(warp::reply::json(&body), status).into_response();
+    if let Some(seconds) = retry_after {
+        response.headers_mut().insert(warp::http::header::RETRY_AFTER, seconds.into());
+    }
+    response
+}
+
+/// Contexto de cada solicitud, con la configuración vigente en ese momento:
+/// una recarga en caliente se aplica desde la siguiente solicitud
+fn with_security_context(ctx: SecurityContext) -> impl Filter<Extract = (SecurityContext,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || {
+        let mut ctx = ctx.clone();
+        ctx.config = ctx.control.config();
+        ctx
+    })
+}
+
+/// Lleva a la lista de tokens en memoria los cambios de `tokens` que traiga
+/// una recarga de la configuración. Termina con las rutas que la crearon.
+fn sync_tokens(ctx: &SecurityContext) {
+    let tokens = Arc::downgrade(&ctx.tokens);
+    let mut config = ctx.control.watch_config();
+    tokio::spawn(async move {
+        while config.changed().await.is_ok() {
+            let Some(tokens) = tokens.upgrade() else {
+                break;
+            };
+            let updated = config.borrow_and_update().tokens.clone();
+            *tokens.lock().unwrap() = updated;
+        }
+    });
+}
+
+/// Vuelve a buscar las herramientas externas cuando una recarga de la
+/// configuración cambia `tool_paths`, p. ej. al elegir una ruta desde la GUI.
+/// Termina con las rutas que la crearon.
+fn sync_tools(ctx: &SecurityContext) {
+    let tools = Arc::downgrade(&ctx.tools);
+    let mut config = ctx.control.watch_config();
+    let mut tool_paths = ctx.config.tool_paths.clone();
+    tokio::spawn(async move {
+        while config.changed().await.is_ok() {
+            let updated = config.borrow_and_update().clone();
+            if updated.tool_paths == tool_paths {
+                continue;
+            }
+            tool_paths = updated.tool_paths.clone();
+            // Buscar en disco puede tardar; no ocupa un hilo del runtime
+            let Ok(report) = tokio::task::spawn_blocking(move || detect_tools(&updated)).await else {
+                continue;
+            };
+            let Some(tools) = tools.upgrade() else {
+                break;
+            };
+            *tools.lock().unwrap() = Arc::new(report);
+            log::info!("🔧 Herramientas externas detectadas de nuevo tras cambiar tool_paths");
+        }
+    });
+}
+
+fn detect_tools(config: &Config) -> ToolReport {
+    let report = tools::detect_all(config);
+    for status in report.statuses().iter().filter(|s| !s.available) {
+        log::warn!("⚠️ {} no encontrado, los tipos que lo requieren serán rechazados", status.name);
+    }
+    report
+}
+
+/// Cuerpo JSON de la solicitud. Con `request_signing_secret` configurado,
+/// antes se comprueba que `X-Signature` corresponda a la solicitud con este cuerpo.
+fn signed_json<T: DeserializeOwned + Send>(ctx: &SecurityContext) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone {
+    let control = ctx.control.clone();
+    let seen = ctx.signatures.clone();
+    signing::headers()
+        .and(warp::body::bytes())
+        .and_then(move |headers: SignatureHeaders, body: warp::hyper::body::Bytes| {
+            let config = control.config();
+            let seen = seen.clone();
+            async move {
+                signing::verify(&config, &headers, &body, &seen).map_err(warp::reject::custom)?;
+                serde_json::from_slice::<T>(&body)
+                    .map_err(|e| warp::reject::custom(BridgeError::InvalidRequest(format!("JSON inválido: {}", e))))
+            }
+        })
+}
+
+/// Para las rutas sin cuerpo que cambian algo. Con `request_signing_secret`
+/// se exige la firma de método, ruta y marca de tiempo, con el cuerpo vacío.
+fn signed(ctx: &SecurityContext) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    let control = ctx.control.clone();
+    let seen = ctx.signatures.clone();
+    signing::headers()
+        .and_then(move |headers: SignatureHeaders| {
+            let config = control.config();
+            let seen = seen.clone();
+            async move { signing::verify(&config, &headers, &[], &seen).map_err(warp::reject::custom) }
+        })
+        .untuple_one()
+}
+
+/// Token de `X-API-Token` o, si no viene, de `Authorization: Bearer`
+fn with_credentials() -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("x-api-token")
+        .and(warp::header::optional::<String>("authorization"))
+        .map(|token: Option<String>, authorization: Option<String>| {
+            token.or_else(|| {
+                authorization
+                    .as_deref()
+                    .and_then(|value| value.strip_prefix("Bearer "))
+                    .map(|bearer| bearer.trim().to_string())
+            })
+        })
+}
+
+fn with_request_info() -> impl Filter<Extract = (RequestInfo,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>(REQUEST_ID_HEADER)
+        .and(warp::addr::remote())
+        .and(warp::header::optional::<String>("origin"))
+        .map(|request_id, addr: Option<SocketAddr>, origin| RequestInfo {
+            request_id: request_id_or_new(request_id),
+            client_ip: addr.map(|addr| addr.ip().to_string()),
+            origin,
+        })
+}
+
+async fn validate_auth(token: Option<String>, ctx: SecurityContext, request: RequestInfo) -> Result<SecurityContext, warp::Rejection> {
+    authorize(token, ctx.for_request(request), TokenScope::Print).await
+}
+
+async fn validate_admin(token: Option<String>, ctx: SecurityContext, request: RequestInfo) -> Result<SecurityContext, warp::Rejection> {
+    authorize(token, ctx.for_request(request), TokenScope::Admin).await
+}
+
+/// Espera añadida por cada fallo seguido antes de responder a un token inválido
+const AUTH_FAILURE_DELAY: Duration = Duration::from_millis(250);
+
+async fn authorize(token: Option<String>, mut ctx: SecurityContext, required: TokenScope) -> Result<SecurityContext, warp::Rejection> {
+    // Rate limiting
+    let client = ctx.client_ip.clone().unwrap_or_default();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
     
     {
         let mut limiter = ctx.rate_limiter.lock().unwrap();
-        let requests = limiter.entry(client_ip).or_insert_with(Vec::new);
+        let requests = limiter.entry(client.clone()).or_insert_with(Vec::new);
         
         // Remove old requests (older than 1 minute)
         requests.retain(|&time| now - time < 60);
         
         if requests.len() >= ctx.config.rate_limit_per_minute as usize {
-            log::warn!("🚫 Rate limit exceeded for IP");
+            log::warn!("🚫 Rate limit exceeded for IP {}", client);
             return Err(warp::reject::custom(BridgeError::RateLimitExceeded));
         }
         
         requests.push(now);
     }
     
-    // Token validation
-    if let Some(required_token) = &ctx.config.api_token {
-        match token {
-            Some(provided_token) if provided_token == *required_token => {
-                log::debug!("✅ Token válido");
-                Ok(ctx)
-            }
-            _ => {
-                log::warn!("🚫 Token inválido o faltante");
-                Err(warp::reject::custom(BridgeError::Unauthorized))
+    if let Some(retry_after_secs) = ctx.auth_failures.locked(&client) {
+        return Err(warp::reject::custom(BridgeError::AuthLocked { retry_after_secs }));
+    }
+    
+    // Listener de loopback sin autenticación, p. ej. para la GUI
+    if ctx.listener == ListenerAuth::None {
+        return Ok(ctx);
+    }
+    
+    // Sin credenciales configuradas el bridge queda abierto solo para
+    // imprimir desde el propio equipo; administrarlo exige siempre un token
+    if !ctx.config.has_credentials() && ctx.tokens.lock().unwrap().is_empty() {
+        // Sin dirección llega por `ipc_path`, que también es local
+        let local = ctx.client_ip.as_deref().is_none_or(|ip| ip.parse::<IpAddr>().is_ok_and(|ip| ip.to_canonical().is_loopback()));
+        if required == TokenScope::Print && local {
+            return Ok(ctx);
+        }
+        log::warn!("🚫 Solicitud sin credenciales configuradas desde {}", client);
+        return Err(warp::reject::custom(BridgeError::Unauthorized));
+    }
+    
+    let Some(provided) = token else {
+        log::warn!("🚫 Token faltante");
+        return Err(warp::reject::custom(BridgeError::Unauthorized));
+    };
+    
+    let matched = match &ctx.config.jwt {
+        Some(settings) if jwt::looks_like_jwt(&provided) => match ctx.jwks.verify(&provided, settings).await {
+            Ok(grant) => Some(grant),
+            Err(e) => {
+                log::warn!("🚫 JWT rechazado: {}", e);
+                None
             }
+        },
+        _ => static_grant(&provided, &ctx),
+    };
+    
+    let Some(grant) = matched else {
+        log::warn!("🚫 Token inválido desde {}", client);
+        let lockout = Duration::from_secs(ctx.config.auth_lockout_secs);
+        let (failures, locked_secs) = ctx.auth_failures.record(&client, ctx.config.auth_max_failures, lockout);
+        if let Some(seconds) = locked_secs {
+            log::warn!("🔒 {} bloqueada {}s tras {} tokens inválidos seguidos", client, seconds, failures);
         }
-    } else {
+        ctx.audit.record_auth_failure(&AuthFailureEntry {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            event: "auth_failure",
+            request_id: ctx.request_id.clone(),
+            client_ip: ctx.client_ip.clone(),
+            origin: ctx.origin.clone(),
+            failures,
+            locked_secs,
+        });
+        // Frena los intentos de adivinar el token sin afectar a quien acierta
+        tokio::time::sleep(AUTH_FAILURE_DELAY * failures.min(10)).await;
+        return Err(warp::reject::custom(BridgeError::Unauthorized));
+    };
+    ctx.auth_failures.clear(&client);
+    
+    if grant.scopes.contains(&required) {
+        log::debug!("✅ Token válido");
+        ctx.caller = grant.caller;
+        ctx.allowed_printers = grant.printers;
         Ok(ctx)
+    } else {
+        log::warn!("🚫 Token sin permiso {:?}", required);
+        Err(warp::reject::custom(BridgeError::Forbidden))
     }
 }
 
-async fn get_printers(_ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
-    match PrinterManager::get_available_printers().await {
+/// Lo que concede una credencial válida
+pub struct Grant {
+    pub scopes: Vec<TokenScope>,
+    /// Id del token adicional o `jwt:<sub>`; `None` con los tokens principales
+    pub caller: Option<String>,
+    /// Impresoras permitidas; `None` = todas
+    pub printers: Option<Vec<String>>,
+}
+
+/// Busca `provided` entre `admin_token`, `api_token` y los tokens adicionales
+fn static_grant(provided: &str, ctx: &SecurityContext) -> Option<Grant> {
+    let full = || vec![TokenScope::Print, TokenScope::Admin];
+    if token_matches(ctx.config.admin_token.as_deref(), provided) {
+        return Some(Grant { scopes: full(), caller: None, printers: None });
+    }
+    // El token principal conserva acceso completo mientras no haya
+    // uno de administración; con él queda solo para imprimir
+    if token_matches(ctx.config.api_token.as_deref(), provided) {
+        let scopes = if ctx.config.admin_token.is_some() { vec![TokenScope::Print] } else { full() };
+        return Some(Grant { scopes, caller: None, printers: None });
+    }
+    ctx.tokens
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|t| token_matches(Some(&t.token), provided))
+        .map(|found| Grant {
+            scopes: found.scopes.clone(),
+            caller: Some(found.id.clone()),
+            printers: found.printers.clone(),
+        })
+}
+
+/// Compara sin salir antes en el primer byte distinto, para no revelar por el
+/// tiempo de respuesta cuánto del token se acertó
+fn token_matches(expected: Option<&str>, provided: &str) -> bool {
+    expected.is_some_and(|expected| bool::from(expected.as_bytes().ct_eq(provided.as_bytes())))
+}
+
+async fn get_printers(query: PrintersQuery, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    match ctx.printers.printers(&ctx.config, query.refresh).await {
         Ok(printers) => Ok(warp::reply::json(&printers)),
         Err(e) => {
             log::error!("Error obteniendo impresoras: {}", e);
@@ -145,28 +1653,1280 @@ async fn get_printers(_ctx: SecurityContext) -> Result<impl Reply, warp::Rejecti
     }
 }
 
-async fn handle_print(request: PrintRequest, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
-    // Validar tipo de archivo
-    if !ctx.config.allowed_file_types.contains(&request.content_type) {
-        return Err(warp::reject::custom(BridgeError::UnsupportedFormat(request.content_type)));
+async fn get_discovered_printers(ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    match discovery::browse(ctx.config.discovery_timeout()).await {
+        Ok(printers) => Ok(warp::reply::json(&printers)),
+        Err(e) => {
+            log::error!("Error buscando impresoras en la red: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+async fn get_printer_detail(name: String, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let queue = ctx.config.resolve_printer(&name).map_err(warp::reject::custom)?;
+    match PrinterManager::get_printer_detail(queue, &ctx.config).await {
+        Ok(detail) => Ok(warp::reply::json(&detail)),
+        Err(e) => {
+            log::error!("Error obteniendo la impresora {}: {}", name, e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+async fn get_jobs(query: JobsQuery, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(50).clamp(1, 500);
+    
+    // Los fallos se guardan con el nombre pedido y los éxitos con la cola que
+    // imprimió: se buscan el alias, sus respaldos y los miembros de su grupo
+    let printers = match query.printer.as_deref() {
+        Some(printer) => {
+            let mut names = vec![printer.to_string()];
+            names.extend(ctx.config.printer_chain(printer).unwrap_or_default());
+            if let Some(pool) = ctx.config.printer_pools.get(printer) {
+                names.extend(pool.printers.iter().filter_map(|member| {
+                    ctx.config.resolve_printer(member).ok().map(str::to_string)
+                }));
+            }
+            names.sort();
+            names.dedup();
+            names
+        }
+        None => Vec::new(),
+    };
+    
+    let filter = JobFilter {
+        success: query.status.map(|status| status == JobStatusFilter::Printed),
+        printers,
+        external_id: query.external_id,
+        request_id: query.request_id,
+        metadata: query.metadata
+            .map(|pair| {
+                pair.split_once(':')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .ok_or_else(|| BridgeError::InvalidRequest("'metadata' debe tener la forma clave:valor".to_string()))
+            })
+            .transpose()
+            .map_err(warp::reject::custom)?,
+        from: query.from.as_deref().map(|from| parse_time_bound(from, false)).transpose().map_err(warp::reject::custom)?,
+        to: query.to.as_deref().map(|to| parse_time_bound(to, true)).transpose().map_err(warp::reject::custom)?,
+        limit: per_page,
+        offset: (page - 1).saturating_mul(per_page),
+    };
+    
+    let history = ctx.history.clone();
+    let (jobs, total) = tokio::task::spawn_blocking(move || history.search(&filter))
+        .await
+        .map_err(|e| warp::reject::custom(BridgeError::IoError(std::io::Error::other(e))))?
+        .map_err(warp::reject::custom)?;
+    
+    Ok(warp::reply::json(&JobsPage {
+        jobs,
+        total,
+        page,
+        per_page,
+        pages: total.div_ceil(u64::from(per_page)),
+    }))
+}
+
+/// RFC 3339, o `AAAA-MM-DD` en hora local: el inicio del día, o el del
+/// siguiente si es el límite superior, para que `to` incluya el día entero
+fn parse_time_bound(value: &str, end_of_day: bool) -> Result<i64, BridgeError> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.timestamp());
+    }
+    
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| BridgeError::InvalidRequest(format!("Fecha inválida: {}", value)))?;
+    let date = if end_of_day { date.succ_opt().unwrap_or(date) } else { date };
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(chrono::Local).earliest())
+        .map(|time| time.timestamp())
+        .ok_or_else(|| BridgeError::InvalidRequest(format!("Fecha inválida: {}", value)))
+}
+
+/// Trabajos y páginas impresos por token e impresora, con el consumo de hoy
+/// frente a la cuota de cada token
+async fn get_stats(query: StatsQuery, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let from = query.from.as_deref().map(|from| parse_time_bound(from, false)).transpose().map_err(warp::reject::custom)?;
+    let to = query.to.as_deref().map(|to| parse_time_bound(to, true)).transpose().map_err(warp::reject::custom)?;
+    let today = start_of_today();
+    
+    let history = ctx.history.clone();
+    let (by_token, by_printer, today_by_token) = tokio::task::spawn_blocking(move || {
+        Ok::<_, BridgeError>((
+            history.usage(UsageGroup::Token, from, to)?,
+            history.usage(UsageGroup::Printer, from, to)?,
+            history.usage(UsageGroup::Token, Some(today), None)?,
+        ))
+    })
+    .await
+    .map_err(|e| warp::reject::custom(BridgeError::IoError(std::io::Error::other(e))))?
+    .map_err(warp::reject::custom)?;
+    
+    // Todos los tokens actuales, tengan o no trabajos, y los ya revocados que imprimieron
+    let tokens = ctx.tokens.lock().unwrap().clone();
+    let mut ids: Vec<Option<String>> = tokens.iter().map(|token| Some(token.id.clone())).collect();
+    for usage in &by_token {
+        if !ids.contains(&usage.key) {
+            ids.push(usage.key.clone());
+        }
+    }
+    
+    let find = |usage: &[Usage], id: &Option<String>| usage.iter().find(|u| u.key == *id).map(|u| (u.jobs, u.pages));
+    let tokens = ids
+        .into_iter()
+        .map(|id| {
+            let token = tokens.iter().find(|token| Some(&token.id) == id.as_ref());
+            let (jobs, pages) = find(&by_token, &id).unwrap_or_default();
+            let pages_today = find(&today_by_token, &id).map_or(0, |(_, pages)| pages);
+            let daily_page_quota = token.and_then(|token| token.daily_page_quota);
+            TokenUsage {
+                token_id: id,
+                name: token.map(|token| token.name.clone()),
+                jobs,
+                pages,
+                pages_today,
+                daily_page_quota,
+                remaining_today: daily_page_quota.map(|quota| quota.saturating_sub(pages_today)),
+            }
+        })
+        .collect();
+    
+    let printers = by_printer
+        .into_iter()
+        .map(|usage| PrinterUsage {
+            printer: usage.key,
+            jobs: usage.jobs,
+            pages: usage.pages,
+        })
+        .collect();
+    
+    Ok(warp::reply::json(&UsageStats {
+        tokens,
+        printers,
+        since_start: ctx.control.metrics().snapshot(),
+    }))
+}
+
+/// Exporta el registro de auditoría del rango como JSON Lines
+async fn get_audit(query: AuditQuery, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let from = query.from.as_deref().map(|from| parse_time_bound(from, false)).transpose().map_err(warp::reject::custom)?;
+    let to = query.to.as_deref().map(|to| parse_time_bound(to, true)).transpose().map_err(warp::reject::custom)?;
+    
+    let audit = ctx.audit.clone();
+    let lines = tokio::task::spawn_blocking(move || audit.export(from, to))
+        .await
+        .map_err(|e| warp::reject::custom(BridgeError::IoError(std::io::Error::other(e))))?
+        .map_err(warp::reject::custom)?;
+    
+    Ok(warp::reply::with_header(
+        warp::reply::with_header(lines, "content-type", "application/x-ndjson"),
+        "content-disposition",
+        "attachment; filename=\"audit.jsonl\"",
+    ))
+}
+
+/// Lo que añade `/health?detail=true` para que una sonda detecte un bridge
+/// que escucha pero no puede imprimir (`status: degraded`)
+async fn health_detail(config: &Config, control: &BridgeControl, history: JobHistory, config_hash: String) -> serde_json::Value {
+    let cups = match PrinterManager::scheduler_running(config).await {
+        Ok(running) => serde_json::json!({ "reachable": running }),
+        Err(e) => serde_json::json!({ "reachable": false, "error": e.to_string() }),
+    };
+    let selftest = crate::selftest::run(config).await;
+    
+    let (last_job_at, spool) = match tokio::task::spawn_blocking(move || (history.last_job_at(), source::spool_space())).await {
+        Ok((last_job_at, spool)) => (last_job_at.ok().flatten(), spool),
+        Err(e) => (None, Err(std::io::Error::other(e))),
+    };
+    let spool = match spool {
+        Ok(space) => serde_json::json!(space),
+        Err(e) => serde_json::json!({ "error": e.to_string() }),
+    };
+    
+    let degraded = !selftest.passed || cups["reachable"] != true;
+    serde_json::json!({
+        "status": if degraded { "degraded" } else { "ok" },
+        "started_at": chrono::DateTime::<chrono::Local>::from(control.started_at()).to_rfc3339(),
+        "uptime_seconds": control.uptime().as_secs(),
+        "last_job_at": last_job_at.and_then(|at| chrono::DateTime::from_timestamp(at, 0)).map(|at| at.to_rfc3339()),
+        "cups": cups,
+        "spool": spool,
+        "config_hash": config_hash,
+        "selftest": selftest,
+    })
+}
+
+async fn get_selftest(ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    Ok(warp::reply::json(&crate::selftest::run(&ctx.config).await))
+}
+
+async fn get_diagnostics(ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let bundle = crate::diagnostics::export(&ctx.config).await.map_err(warp::reject::custom)?;
+    log::info!("[{}] 🩺 Paquete de diagnóstico exportado ({} bytes)", ctx.request_id, bundle.len());
+    
+    Ok(warp::reply::with_header(
+        warp::reply::with_header(bundle, "content-type", "application/zip"),
+        "content-disposition",
+        format!("attachment; filename=\"{}\"", crate::diagnostics::file_name()),
+    ))
+}
+
+async fn get_capabilities(ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let tools = ctx.tools();
+    let content_types = ctx.config.allowed_file_types
+        .iter()
+        .map(|content_type| {
+            let missing: Vec<&'static str> = tools
+                .missing_for(content_type, &ctx.config)
+                .iter()
+                .map(|tool| tool.display_name())
+                .collect();
+            ContentTypeCapability {
+                content_type: content_type.clone(),
+                available: missing.is_empty(),
+                missing,
+            }
+        })
+        .collect();
+    
+    Ok(warp::reply::json(&Capabilities {
+        content_types,
+        dependencies: tools.statuses().to_vec(),
+    }))
+}
+
+async fn handle_print(client_version: Option<String>, mut body: serde_json::Value, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    if ctx.control.is_paused() {
+        log::warn!("⏸️ Solicitud rechazada: bridge en pausa");
+        return Err(warp::reject::custom(BridgeError::Paused));
     }
     
-    // Validar tamaño (aproximado por base64)
-    let estimated_size = (request.content.len() * 3) / 4; // base64 to bytes
-    let max_size = (ctx.config.max_file_size_mb as usize) * 1024 * 1024;
+    // Traducir campos obsoletos antes de deserializar
+    let deprecation = compat::migrate_print_fields(&mut body);
+    compat::log_client_version(client_version.as_deref(), &deprecation);
     
-    if estimated_size > max_size {
-        log::warn!("🚫 Archivo demasiado grande: {} bytes", estimated_size);
-        return Err(warp::reject::custom(BridgeError::FileTooLarge));
+    let request = match PrintRequest::from_json(body) {
+        Ok(request) => request,
+        Err(e) => return Err(compat::reject(e, deprecation)),
+    };
+    
+    match process_print(request, None, &ctx).await {
+        Ok(mut response) => {
+            response.deprecation = deprecation;
+            Ok(warp::reply::with_header(
+                warp::reply::json(&response),
+                "x-bridge-api-version",
+                compat::API_VERSION,
+            ))
+        }
+        Err(e) => {
+            log::error!("[{}] Error en impresión: {}", ctx.request_id, e);
+            Err(compat::reject(e, deprecation))
+        }
     }
+}
+
+/// Renderiza como `/api/print` pero devuelve el PDF (o la primera página en
+/// PNG) en lugar de imprimir, con el mismo motor que usaría la impresión
+async fn handle_preview(query: PreviewQuery, body: serde_json::Value, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let mut request = PrintRequest::from_json(body).map_err(warp::reject::custom)?;
+    let render = async {
+        if query.format == PreviewFormat::Png && !ctx.tools().is_available(Tool::Ghostscript) {
+            return Err(BridgeError::DependencyMissing(format!(
+                "La vista previa en PNG requiere {} instalado",
+                Tool::Ghostscript.display_name()
+            )));
+        }
+        
+        prepare_document(&mut request, &ctx).await?;
+        let pdf = PrinterManager::render_pdf(&request, &ctx.config).await?;
+        match query.format {
+            PreviewFormat::Pdf => Ok((pdf, "application/pdf")),
+            PreviewFormat::Png => Ok((renderer::rasterize_first_page(&pdf, &ctx.config).await?, "image/png")),
+        }
+    };
     
-    log::info!("📄 Nueva solicitud de impresión: {} ({} bytes)", request.content_type, estimated_size);
+    let (body, content_type) = render.await.map_err(|e| {
+        log::error!("[{}] Error en vista previa: {}", ctx.request_id, e);
+        warp::reject::custom(e)
+    })?;
+    log::info!("👁️ Vista previa de {} generada ({} bytes)", request.content_type, body.len());
+    
+    Ok(warp::reply::with_header(body, "content-type", content_type))
+}
+
+/// Abre el cajón portamonedas. Queda en la auditoría como un trabajo `drawer`.
+async fn handle_open_drawer(body: DrawerRequest, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    check_printer_allowed(body.printer_name.as_deref(), &ctx).map_err(warp::reject::custom)?;
+    let printer = body.printer_name.as_deref()
+        .or(ctx.config.default_printer.as_deref())
+        .unwrap_or("default");
+    let result = PrinterManager::open_drawer(printer, &ctx.control, &ctx.config).await;
     
-    match PrinterManager::print(request, &ctx.config).await {
+    write_audit(AuditEntry {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        request_id: ctx.request_id.clone(),
+        token_id: ctx.caller.clone(),
+        client_ip: ctx.client_ip.clone(),
+        origin: ctx.origin.clone(),
+        content_type: "drawer".to_string(),
+        sha256: None,
+        size: 0,
+        pages: None,
+        copies: 1,
+        printer: Some(result.as_ref().ok().and_then(|response| response.printer.clone()).unwrap_or_else(|| printer.to_string())),
+        job_id: result.as_ref().ok().and_then(|response| response.job_id.clone()),
+        batch_id: None,
+        success: result.is_ok(),
+        message: match &result {
+            Ok(response) => response.message.clone(),
+            Err(e) => e.to_string(),
+        },
+    }, &ctx).await;
+    
+    match result {
         Ok(response) => Ok(warp::reply::json(&response)),
         Err(e) => {
-            log::error!("Error en impresión: {}", e);
-            Err(warp::reject::custom(BridgeError::PrintError(e.to_string())))
+            log::error!("[{}] No se pudo abrir el cajón en {}: {}", ctx.request_id, printer, e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+async fn handle_print_batch(client_version: Option<String>, batch: BatchPrintRequest, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    if ctx.control.is_paused() {
+        log::warn!("⏸️ Lote rechazado: bridge en pausa");
+        return Err(warp::reject::custom(BridgeError::Paused));
+    }
+    
+    if batch.items.is_empty() {
+        return Err(warp::reject::custom(BridgeError::InvalidRequest("El lote no contiene elementos".to_string())));
+    }
+    
+    let batch_id = config::generate_id("batch");
+    log::info!("📦 Lote {} con {} elementos", batch_id, batch.items.len());
+    
+    let mut deprecation: Vec<compat::DeprecationWarning> = Vec::new();
+    let mut results = Vec::with_capacity(batch.items.len());
+    let mut aborted = false;
+    
+    // Un solo trabajo: todos los elementos comparten el resultado
+    if batch.merge {
+        let count = batch.items.len();
+        let result = match merge_batch(batch.items, &mut deprecation, &ctx).await {
+            Ok(request) => print_request(request, Some(&batch_id), true, &ctx).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &result {
+            log::error!("[{}] Error en el lote unido {}: {}", ctx.request_id, batch_id, e);
+        }
+        for index in 0..count {
+            results.push(match &result {
+                Ok(response) => BatchItemResult::printed(index, response.clone()),
+                Err(e) => BatchItemResult::failed(index, e),
+            });
+        }
+    } else {
+        // En orden: la comanda de cocina sale antes que el ticket del cliente
+        for (index, mut item) in batch.items.into_iter().enumerate() {
+            if aborted {
+                results.push(BatchItemResult::skipped(index));
+                continue;
+            }
+        
+            for warning in compat::migrate_print_fields(&mut item) {
+                if !deprecation.iter().any(|w| w.field == warning.field) {
+                    deprecation.push(warning);
+                }
+            }
+        
+            let result = match PrintRequest::from_json(item) {
+                Ok(request) => process_print(request, Some(&batch_id), &ctx).await,
+                Err(e) => Err(e),
+            };
+        
+            match result {
+                Ok(response) => results.push(BatchItemResult::printed(index, response)),
+                Err(e) => {
+                    log::error!("[{}] Error en elemento {} del lote {}: {}", ctx.request_id, index, batch_id, e);
+                    results.push(BatchItemResult::failed(index, &e));
+                    aborted = batch.abort_on_failure;
+                }
+            }
+        }
+    }
+    compat::log_client_version(client_version.as_deref(), &deprecation);
+    
+    let response = BatchPrintResponse {
+        success: results.iter().all(|r| r.status == BatchItemStatus::Printed),
+        batch_id,
+        results,
+        deprecation,
+    };
+    
+    Ok(warp::reply::with_header(
+        warp::reply::json(&response),
+        "x-bridge-api-version",
+        compat::API_VERSION,
+    ))
+}
+
+/// Junta los PDF de un lote `merge` en una sola solicitud ya preparada. Cada
+/// elemento se prepara por separado (descifrado, `page_range`, marca de agua);
+/// impresora, copias, preajuste y el resto de opciones deben coincidir.
+async fn merge_batch(
+    items: Vec<serde_json::Value>,
+    deprecation: &mut Vec<compat::DeprecationWarning>,
+    ctx: &SecurityContext,
+) -> Result<PrintRequest, BridgeError> {
+    let mut parts: Vec<PrintRequest> = Vec::with_capacity(items.len());
+    let mut settings = None;
+    for (index, mut item) in items.into_iter().enumerate() {
+        for warning in compat::migrate_print_fields(&mut item) {
+            if !deprecation.iter().any(|w| w.field == warning.field) {
+                deprecation.push(warning);
+            }
+        }
+        
+        let mut request = PrintRequest::from_json(item)?;
+        if request.content_type != "pdf" {
+            return Err(BridgeError::InvalidRequest(format!(
+                "Elemento {}: un lote con merge solo admite PDF, no {}",
+                index, request.content_type
+            )));
+        }
+        // Se compara lo que pidió el cliente, antes de que la preparación lo cambie
+        let job_settings = merge_settings(&request);
+        match &settings {
+            None => settings = Some(job_settings),
+            Some(first) if *first != job_settings => {
+                return Err(BridgeError::InvalidRequest(format!(
+                    "Elemento {}: en un lote con merge todos los elementos llevan la misma impresora, copias, \
+                     preajuste y opciones; solo page_range, pdf_password y watermark pueden cambiar",
+                    index
+                )));
+            }
+            Some(_) => {}
+        }
+        prepare_document(&mut request, ctx).await?;
+        parts.push(request);
+    }
+    
+    let files: Vec<SpooledFile> = parts.iter().filter_map(|part| part.spooled.clone()).collect();
+    let merged = tokio::task::spawn_blocking(move || PrinterManager::merge_pdfs(&files))
+        .await
+        .map_err(|e| BridgeError::IoError(std::io::Error::other(e)))??;
+    
+    let mut request = parts.swap_remove(0);
+    request.spooled = Some(source::spool_bytes(&merged, ctx.config.max_file_size("pdf"), &ctx.config)?);
+    log::info!("📎 [{}] {} PDF unidos en un solo trabajo ({} bytes)", ctx.request_id, parts.len() + 1, merged.len());
+    Ok(request)
+}
+
+/// Lo que un lote `merge` aplica al trabajo entero; lo propio de cada documento
+/// queda fuera
+fn merge_settings(request: &PrintRequest) -> serde_json::Value {
+    let options = PrintOptions {
+        page_range: None,
+        pdf_password: None,
+        watermark: None,
+        ..request.options.clone().unwrap_or_default()
+    };
+    serde_json::json!({
+        "printer_name": request.printer_name,
+        "copies": request.copies,
+        "preset": request.preset,
+        "options": options,
+    })
+}
+
+/// `multipart/form-data` con el documento en la parte `file` y el resto de
+/// campos de `PrintRequest` como texto (`options` y `notify` en JSON).
+async fn handle_print_upload(mut form: warp::multipart::FormData, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    if ctx.control.is_paused() {
+        log::warn!("⏸️ Subida rechazada: bridge en pausa");
+        return Err(warp::reject::custom(BridgeError::Paused));
+    }
+    // warp entrega el formulario ya separado en partes, sin los bytes sobre los que se firmó
+    if ctx.config.request_signing_secret.is_some() {
+        return Err(warp::reject::custom(BridgeError::InvalidSignature(
+            "Con firma de solicitudes no se admiten subidas multipart; usa /api/print/raw o /api/print".to_string(),
+        )));
+    }
+    
+    let invalid = |message: String| warp::reject::custom(BridgeError::InvalidRequest(message));
+    // La parte `file` puede llegar antes que `content_type`; el límite del
+    // tipo se aplica después, en `prepare_document`
+    let max_size = ctx.config.largest_file_size();
+    
+    let mut fields: HashMap<String, String> = HashMap::new();
+    let mut upload: Option<(SpooledFile, Option<String>)> = None;
+    
+    while let Some(part) = form.try_next().await.map_err(|e| invalid(e.to_string()))? {
+        let name = part.name().to_string();
+        if name == "file" {
+            let mime = part.content_type().map(str::to_string);
+            let file = source::spool_stream(part.stream(), max_size, &ctx.config).await.map_err(warp::reject::custom)?;
+            upload = Some((file, mime));
+        } else {
+            let value = part
+                .stream()
+                .try_fold(Vec::new(), |mut acc, chunk| {
+                    acc.extend_from_slice(chunk.chunk());
+                    async move { Ok(acc) }
+                })
+                .await
+                .map_err(|e| invalid(e.to_string()))?;
+            let value = String::from_utf8(value).map_err(|_| invalid(format!("El campo '{}' no es UTF-8", name)))?;
+            fields.insert(name, value);
+        }
+    }
+    
+    let (file, part_mime) = upload.ok_or_else(|| invalid("Falta la parte 'file'".to_string()))?;
+    let mime = fields.remove("mime").or(part_mime);
+    
+    let content_type = match fields.remove("content_type") {
+        Some(content_type) => content_type,
+        None => mime
+            .as_deref()
+            .and_then(source::content_type_for_mime)
+            .map(str::to_string)
+            .ok_or_else(|| invalid("Falta 'content_type' y no se pudo deducir del archivo".to_string()))?,
+    };
+    
+    let copies = fields
+        .remove("copies")
+        .map(|c| c.parse::<u32>().map_err(|_| invalid(format!("'copies' inválido: {}", c))))
+        .transpose()?;
+    let options = fields
+        .remove("options")
+        .map(|o| serde_json::from_str(&o).map_err(|e| invalid(format!("'options' inválido: {}", e))))
+        .transpose()?;
+    let priority = fields
+        .remove("priority")
+        .map(|p| serde_json::from_value(serde_json::Value::String(p.clone())).map_err(|_| invalid(format!("'priority' inválido: {}", p))))
+        .transpose()?
+        .unwrap_or_default();
+    let metadata = fields
+        .remove("metadata")
+        .map(|m| serde_json::from_str(&m).map_err(|e| invalid(format!("'metadata' inválido: {}", e))))
+        .transpose()?
+        .unwrap_or_default();
+    let notify = fields
+        .remove("notify")
+        .map(|n| serde_json::from_str(&n).map_err(|e| invalid(format!("'notify' inválido: {}", e))))
+        .transpose()?;
+    
+    let request = PrintRequest {
+        printer_name: fields.remove("printer_name"),
+        content: String::new(),
+        url: None,
+        spooled: Some(file),
+        content_type,
+        mime,
+        template_name: None,
+        data: None,
+        copies,
+        options,
+        preset: fields.remove("preset"),
+        priority,
+        external_id: fields.remove("external_id"),
+        metadata,
+        notify,
+        dry_run: fields.remove("dry_run").is_some_and(|d| d == "true"),
+        request_id: None,
+    };
+    
+    print_spooled(request, ctx).await
+}
+
+/// El cuerpo es el documento tal cual; el resto de campos llega por query
+/// (`?printer_name=...&copies=2`) y el tipo se deduce de `Content-Type`.
+async fn handle_print_raw(
+    params: RawPrintParams,
+    mime: Option<String>,
+    signature: SignatureHeaders,
+    body: impl futures_util::Stream<Item = Result<impl Buf, warp::Error>>,
+    ctx: SecurityContext,
+) -> Result<impl Reply, warp::Rejection> {
+    if ctx.control.is_paused() {
+        log::warn!("⏸️ Solicitud rechazada: bridge en pausa");
+        return Err(warp::reject::custom(BridgeError::Paused));
+    }
+    
+    let content_type = match params.content_type {
+        Some(content_type) => content_type,
+        None => mime
+            .as_deref()
+            .and_then(source::content_type_for_mime)
+            .map(str::to_string)
+            .ok_or_else(|| warp::reject::custom(BridgeError::InvalidRequest(
+                "Falta 'content_type' y no se pudo deducir de Content-Type".to_string(),
+            )))?,
+    };
+    
+    // La firma se calcula mientras el cuerpo se vuelca a disco
+    let mut verifier = signing::Verifier::start(&ctx.config, &signature).map_err(warp::reject::custom)?;
+    let body = body.map_ok(|mut chunk| {
+        let bytes = chunk.copy_to_bytes(chunk.remaining());
+        if let Some(verifier) = verifier.as_mut() {
+            verifier.update(&bytes);
+        }
+        bytes
+    });
+    let max_size = ctx.config.max_file_size(&content_type);
+    let file = source::spool_stream(body, max_size, &ctx.config).await.map_err(warp::reject::custom)?;
+    if let Some(verifier) = verifier {
+        verifier.finish(&ctx.signatures).map_err(warp::reject::custom)?;
+    }
+    
+    let request = PrintRequest {
+        printer_name: params.printer_name,
+        content: String::new(),
+        url: None,
+        spooled: Some(file),
+        content_type,
+        mime,
+        template_name: None,
+        data: None,
+        copies: params.copies,
+        options: Some(PrintOptions {
+            paper_size: params.paper_size,
+            orientation: params.orientation,
+            color: params.color,
+            duplex: params.duplex,
+            tray: params.tray,
+            fit: params.fit,
+            auto_rotate: params.auto_rotate,
+            ..Default::default()
+        }),
+        preset: params.preset,
+        priority: params.priority,
+        external_id: params.external_id,
+        metadata: serde_json::Map::new(),
+        notify: None,
+        dry_run: params.dry_run,
+        request_id: None,
+    };
+    
+    print_spooled(request, ctx).await
+}
+
+/// Respuesta común de los endpoints que reciben el documento ya en disco
+async fn print_spooled(request: PrintRequest, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    match process_print(request, None, &ctx).await {
+        Ok(response) => Ok(warp::reply::with_header(
+            warp::reply::json(&response),
+            "x-bridge-api-version",
+            compat::API_VERSION,
+        )),
+        Err(e) => {
+            log::error!("[{}] Error en impresión: {}", ctx.request_id, e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+/// `prepare_document` salvo que ya se hiciera; entonces solo el tamaño
+async fn prepare_once(request: &mut PrintRequest, prepared: bool, ctx: &SecurityContext) -> Result<u64, BridgeError> {
+    match &request.spooled {
+        Some(file) if prepared => Ok(file.len()?),
+        _ => prepare_document(request, ctx).await,
+    }
+}
+
+/// Valida la solicitud y deja el documento listo para renderizar: descarga
+/// `url`, vuelca los binarios al spool y comprueba el tamaño. Devuelve los bytes.
+async fn prepare_document(request: &mut PrintRequest, ctx: &SecurityContext) -> Result<u64, BridgeError> {
+    // La plantilla se aplica antes que nada y el resto se comprueba sobre el
+    // documento que produce
+    let templated = request.content_type == "template";
+    if templated || request.content_type == "label" {
+        if !ctx.config.allowed_file_types.contains(&request.content_type) {
+            return Err(BridgeError::UnsupportedFormat(request.content_type.clone()));
+        }
+        if templated {
+            templates::render_request(request, &ctx.config)?;
+        } else {
+            templates::render_label(request, &ctx.config)?;
         }
     }
+    
+    // Se valida lo que envió el cliente, antes de sumar el preajuste; de lo
+    // que aporta el preajuste solo se comprueban los valores
+    request.validate()?;
+    request.apply_preset(&ctx.config)?;
+    let mut problems = request.options.as_ref().map(PrintOptions::problems).unwrap_or_default();
+    if request.copies == Some(0) {
+        problems.push(FieldProblem::new("copies", "debe ser al menos 1"));
+    }
+    if !problems.is_empty() {
+        return Err(BridgeError::ValidationFailed(problems));
+    }
+    check_printer_allowed(request.printer_name.as_deref(), ctx)?;
+    
+    // Validar tipo de archivo. Lo que produce una plantilla no necesita estar
+    // permitido aparte: las plantillas las define el administrador.
+    if !templated && !ctx.config.allowed_file_types.contains(&request.content_type) {
+        return Err(BridgeError::UnsupportedFormat(request.content_type.clone()));
+    }
+    
+    // Validar que las herramientas externas necesarias estén instaladas
+    ctx.tools().ensure_for(&request.content_type, &ctx.config)?;
+    
+    let max_size = ctx.config.max_file_size(&request.content_type);
+    
+    // Con `url` se descarga el documento; lo que se recuerda para reimprimir ya
+    // lleva el contenido, así que reimprimir no vuelve a descargarlo
+    if let Some(url) = request.url.take() {
+        request.spooled = Some(source::fetch(&url, max_size, &ctx.config).await?);
+    }
+    
+    // Los binarios en base64 se decodifican por bloques directamente a disco y
+    // se libera el texto, en lugar de tener base64, bytes y archivo a la vez.
+    // Se cuentan los bytes decodificados y se corta al pasar el límite.
+    if request.spooled.is_none() && !source::is_text_type(&request.content_type) {
+        let content = std::mem::take(&mut request.content);
+        request.spooled = Some(source::spool_base64(&content, max_size, &ctx.config)?);
+    }
+    
+    let size = match &request.spooled {
+        Some(file) => file.len()?,
+        None => request.content.len() as u64,
+    };
+    if size > max_size {
+        log::warn!("🚫 Archivo demasiado grande: {} bytes (máximo {} para '{}')", size, max_size, request.content_type);
+        return Err(BridgeError::FileTooLarge);
+    }
+    
+    // Un PDF dañado, vacío o con contraseña se rechaza aquí y no en el filtro de CUPS.
+    // La contraseña, el rango y la marca de agua no pasan de aquí: lo que se
+    // encola y se guarda en el historial es el PDF ya descifrado, recortado y
+    // marcado, y un reintento no lo vuelve a marcar.
+    if request.content_type == "pdf" {
+        let options = request.options.as_mut();
+        let (password, page_range, watermark) = match options {
+            Some(options) => (options.pdf_password.take(), options.page_range.take(), options.watermark.take()),
+            None => (None, None, None),
+        };
+        let page_range = page_range.map(|range| PageRange::parse(&range)).transpose().map_err(BridgeError::InvalidRequest)?;
+        
+        let pdf = request.clone();
+        let (pages, rewritten) = tokio::task::spawn_blocking(move || {
+            PrinterManager::prepare_pdf(&pdf, password.as_deref(), page_range.as_ref(), watermark.as_ref())
+        })
+        .await
+        .map_err(|e| BridgeError::IoError(std::io::Error::other(e)))??;
+        if let Some(data) = rewritten {
+            request.spooled = Some(source::spool_bytes(&data, max_size, &ctx.config)?);
+        }
+        log::debug!("📄 [{}] PDF válido, {} páginas", ctx.request_id, pages);
+    }
+    
+    Ok(size)
+}
+
+/// Hace todo lo de `process_print` salvo enviar a CUPS; no pasa por la cola ni
+/// queda en el historial
+async fn dry_run(mut request: PrintRequest, prepared: bool, ctx: &SecurityContext) -> Result<PrintResponse, BridgeError> {
+    let size = prepare_once(&mut request, prepared, ctx).await?;
+    let report = PrinterManager::dry_run(&request, &ctx.control, &ctx.config).await?;
+    log::info!("🧪 [{}] Simulación de {} ({} bytes) en {}", ctx.request_id, request.content_type, size, report.printer);
+    
+    Ok(PrintResponse {
+        success: true,
+        message: "Simulación correcta: el trabajo se puede imprimir (no se envió)".to_string(),
+        job_id: None,
+        printer: Some(report.printer.clone()),
+        deprecation: Vec::new(),
+        dry_run: Some(report),
+        pages: None,
+    })
+}
+
+/// Valida, imprime y registra una solicitud ya deserializada.
+/// Compartido por `/api/print` y `/api/print/batch`.
+async fn process_print(request: PrintRequest, batch_id: Option<&str>, ctx: &SecurityContext) -> Result<PrintResponse, BridgeError> {
+    print_request(request, batch_id, false, ctx).await
+}
+
+/// Como `process_print`; con `prepared` el documento ya pasó por
+/// `prepare_document` (el PDF unido de un lote `merge`) y no se repite
+async fn print_request(mut request: PrintRequest, batch_id: Option<&str>, prepared: bool, ctx: &SecurityContext) -> Result<PrintResponse, BridgeError> {
+    if request.dry_run {
+        return dry_run(request, prepared, ctx).await;
+    }
+    
+    if ctx.control.shutdown_requested() {
+        return Err(BridgeError::ShuttingDown);
+    }
+    
+    let queue = ctx.control.jobs();
+    let _pending = queue.admit(ctx.config.max_pending_jobs).ok_or_else(|| {
+        log::warn!("🚦 Cola llena ({} trabajos pendientes), solicitud rechazada", queue.pending());
+        BridgeError::QueueFull {
+            pending: queue.pending(),
+            retry_after_secs: ctx.config.queue_retry_after_secs,
+        }
+    })?;
+    
+    let size = prepare_once(&mut request, prepared, ctx).await?;
+    log::info!("📄 [{}] Nueva solicitud de impresión: {} ({} bytes)", ctx.request_id, request.content_type, size);
+    
+    check_page_quota(&request, ctx)?;
+    
+    let dedupe_hash = match ctx.config.dedupe_window_secs {
+        0 => None,
+        window => {
+            let hash = content_hash(&request).await?;
+            match ctx.recent.claim(hash, Duration::from_secs(window)) {
+                Claim::New => Some(hash),
+                Claim::Duplicate { seconds_ago, response } => {
+                    log::warn!("🔂 [{}] Documento repetido (enviado hace {}s), no se imprime", ctx.request_id, seconds_ago);
+                    let job_id = response.as_ref().and_then(|r| r.job_id.clone());
+                    return match ctx.config.dedupe_action {
+                        DedupeAction::Reject => Err(BridgeError::DuplicateJob { seconds_ago, job_id }),
+                        DedupeAction::Coalesce => Ok(PrintResponse {
+                            success: true,
+                            message: format!("Documento idéntico enviado hace {}s; no se imprimió de nuevo", seconds_ago),
+                            job_id,
+                            printer: response.and_then(|r| r.printer),
+                            deprecation: Vec::new(),
+                            dry_run: None,
+                            pages: None,
+                        }),
+                    };
+                }
+            }
+        }
+    };
+    
+    request.request_id = Some(ctx.request_id.clone());
+    let active = ctx.history.enqueue(&request, batch_id, ctx.caller.as_deref(), Some(&ctx.request_id));
+    active.set_state(JobState::Rendering);
+    
+    let policy = &ctx.config.retry;
+    let mut attempts = Vec::new();
+    let result = loop {
+        let result = PrinterManager::print(&request, &ctx.control, &ctx.config).await;
+        attempts.push(match &result {
+            Ok(response) => JobAttempt::new(response.printer.clone(), true, response.message.clone()),
+            Err(e) => JobAttempt::new(request.printer_name.clone(), false, e.to_string()),
+        });
+        
+        let attempt = attempts.len() as u32;
+        match &result {
+            Err(e) if attempt < policy.max_attempts && policy.should_retry(e) => {
+                let delay = policy.delay(attempt);
+                log::warn!("🔁 [{}] Intento {} fallido ({}); reintento en {}ms", ctx.request_id, attempt, e, delay.as_millis());
+                tokio::time::sleep(delay).await;
+            }
+            _ => break result,
+        }
+    };
+    
+    let (success, message, job_id, printer, pages) = match &result {
+        Ok(response) => (true, response.message.clone(), response.job_id.clone(), response.printer.clone(), response.pages),
+        Err(e) => (false, e.to_string(), None, request.printer_name.clone(), None),
+    };
+    let outcome = JobOutcome {
+        success,
+        message,
+        job_id,
+        printer,
+        batch_id: batch_id.map(str::to_string),
+        external_id: request.external_id.clone(),
+        metadata: request.metadata.clone(),
+        pages,
+    };
+    active.finish(&request.content_type, &outcome, &attempts);
+    ctx.control.metrics().record_job(&request.content_type, outcome.printer.as_deref(), outcome.success);
+    if !outcome.success {
+        ctx.control.record_error(Some(&ctx.request_id), &outcome.message);
+    }
+    audit(&request, size, &outcome, ctx).await;
+    
+    if let Some(hash) = &dedupe_hash {
+        match &result {
+            Ok(response) => ctx.recent.complete(hash, response),
+            Err(_) => ctx.recent.forget(hash),
+        }
+    }
+    
+    if let Some(options) = request.notify.clone() {
+        notifications::dispatch(options, outcome, ctx.control.clone());
+    }
+    
+    if result.is_ok() {
+        ctx.control.remember_job(&request);
+    }
+    
+    result
+}
+
+/// Imprime un trabajo que no llega por HTTP (la línea de comandos, reimprimir
+/// desde el tray) por el mismo camino que `/api/print`: validación,
+/// reintentos, historial y auditoría
+pub async fn print_local(request: PrintRequest, config: Config, control: BridgeControl, history: JobHistory) -> Result<PrintResponse, BridgeError> {
+    let mut ctx = SecurityContext::new(config, control, history);
+    ctx.request_id = config::generate_id("cli");
+    process_print(request, None, &ctx).await
+}
+
+/// Deja constancia en el registro de auditoría de un trabajo que llegó a la
+/// impresora, haya salido bien o no
+async fn audit(request: &PrintRequest, size: u64, outcome: &JobOutcome, ctx: &SecurityContext) {
+    let sha256 = match hash_document(Sha256::new(), request).await {
+        Ok(hash) => Some(format!("{:x}", hash)),
+        Err(e) => {
+            log::warn!("⚠️ No se pudo calcular el hash para la auditoría: {}", e);
+            None
+        }
+    };
+    
+    let entry = AuditEntry {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        request_id: ctx.request_id.clone(),
+        token_id: ctx.caller.clone(),
+        client_ip: ctx.client_ip.clone(),
+        origin: ctx.origin.clone(),
+        content_type: request.content_type.clone(),
+        sha256,
+        size,
+        pages: outcome.pages,
+        copies: request.copies.unwrap_or(1),
+        printer: outcome.printer.clone(),
+        job_id: outcome.job_id.clone(),
+        batch_id: outcome.batch_id.clone(),
+        success: outcome.success,
+        message: outcome.message.clone(),
+    };
+    write_audit(entry, ctx).await;
+}
+
+async fn write_audit(entry: AuditEntry, ctx: &SecurityContext) {
+    let audit = ctx.audit.clone();
+    if let Err(e) = tokio::task::spawn_blocking(move || audit.record(&entry)).await {
+        log::error!("❌ No se pudo escribir en el registro de auditoría: {}", e);
+    }
+}
+
+/// Con una lista de impresoras permitidas en la credencial, la pedida (o la
+/// predeterminada) tiene que estar en ella, por su nombre o por su cola
+fn check_printer_allowed(printer_name: Option<&str>, ctx: &SecurityContext) -> Result<(), BridgeError> {
+    let Some(allowed) = &ctx.allowed_printers else {
+        return Ok(());
+    };
+    let requested = printer_name
+        .or(ctx.config.default_printer.as_deref())
+        .unwrap_or("default");
+    let queue = ctx.config.resolve_printer(requested).unwrap_or(requested);
+    if allowed.iter().any(|printer| printer == requested || printer == queue) {
+        return Ok(());
+    }
+    log::warn!("🚫 [{}] {} no puede imprimir en {}", ctx.request_id, ctx.caller.as_deref().unwrap_or("?"), requested);
+    Err(BridgeError::PrinterForbidden(requested.to_string()))
+}
+
+/// Rechaza el trabajo si deja al token por encima de su cuota diaria de
+/// páginas. HTML y Markdown no se cuentan hasta renderizarlos: antes de
+/// imprimir valen una página y después se registra lo que ocuparon.
+fn check_page_quota(request: &PrintRequest, ctx: &SecurityContext) -> Result<(), BridgeError> {
+    let Some(token_id) = ctx.caller.as_deref() else {
+        return Ok(());
+    };
+    let quota = ctx.tokens
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|token| token.id == token_id)
+        .and_then(|token| token.daily_page_quota);
+    let Some(quota) = quota else {
+        return Ok(());
+    };
+    
+    let today = start_of_today();
+    let used = ctx.history.pages_since(token_id, today)?;
+    let requested = PrinterManager::count_pages(request).unwrap_or(1) * u64::from(request.copies.unwrap_or(1));
+    if used + requested > quota {
+        log::warn!("🧾 Cuota de páginas agotada para el token {}: {} de {} usadas, pide {}", token_id, used, quota, requested);
+        let now = chrono::Local::now().timestamp();
+        return Err(BridgeError::QuotaExceeded {
+            used,
+            quota,
+            requested,
+            retry_after_secs: (today + 86_400 - now).max(1) as u64,
+        });
+    }
+    
+    Ok(())
+}
+
+/// Medianoche de hoy en hora local, como marca de tiempo Unix
+fn start_of_today() -> i64 {
+    let now = chrono::Local::now();
+    now.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(chrono::Local).earliest())
+        .map_or(now.timestamp(), |midnight| midnight.timestamp())
+}
+
+/// Retoma o da por fallidos los trabajos que un cierre inesperado dejó a medias
+/// y avisa a la GUI para que nada desaparezca sin dejar rastro
+fn recover_interrupted(ctx: &SecurityContext) {
+    let jobs = match ctx.history.interrupted() {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            log::error!("❌ No se pudo leer la cola persistente: {}", e);
+            return;
+        }
+    };
+    
+    let documents: Vec<_> = jobs
+        .iter()
+        .filter_map(|job| job.request.spooled.as_ref().map(|file| file.path().to_path_buf()))
+        .collect();
+    source::remove_orphans(&documents, Duration::ZERO);
+    
+    if jobs.is_empty() {
+        return;
+    }
+    
+    let (mut resumed, mut failed) = (0, 0);
+    for interrupted in jobs {
+        let resume = match ctx.config.interrupted_jobs {
+            RecoveryPolicy::Resume => true,
+            RecoveryPolicy::ResumeQueued => interrupted.state == JobState::Queued,
+            RecoveryPolicy::Fail => false,
+        };
+        
+        if resume {
+            resumed += 1;
+            let mut ctx = ctx.clone();
+            tokio::spawn(async move {
+                let InterruptedJob { job, request, batch_id, token_id, request_id, .. } = interrupted;
+                // Cuenta para la cuota del token que lo envió y conserva su id
+                ctx.caller = token_id;
+                ctx.request_id = request_id.unwrap_or_else(|| config::generate_id("req"));
+                // `process_print` lo vuelve a registrar
+                drop(job);
+                if let Err(e) = process_print(request, batch_id.as_deref(), &ctx).await {
+                    log::error!("❌ Trabajo recuperado fallido: {}", e);
+                }
+            });
+        } else {
+            failed += 1;
+            let InterruptedJob { job, state, request, batch_id, .. } = interrupted;
+            let message = match state {
+                JobState::Queued => "Interrumpido por un cierre inesperado antes de imprimirse",
+                JobState::Rendering => "Interrumpido por un cierre inesperado; puede que se haya impreso",
+            };
+            let outcome = JobOutcome {
+                success: false,
+                message: message.to_string(),
+                job_id: None,
+                printer: request.printer_name.clone(),
+                batch_id,
+                external_id: request.external_id.clone(),
+                metadata: request.metadata.clone(),
+                pages: None,
+            };
+            job.finish(&request.content_type, &outcome, &[]);
+        }
+    }
+    
+    log::warn!("♻️ Trabajos interrumpidos: {} reanudados, {} marcados como fallidos", resumed, failed);
+    ctx.control.announce(
+        "Trabajos recuperados",
+        &format!("Tras un cierre inesperado: {} trabajos reanudados, {} marcados como fallidos", resumed, failed),
+    );
+}
+
+/// Huella del trabajo: impresora, tipo, copias, opciones y el documento
+async fn content_hash(request: &PrintRequest) -> Result<ContentHash, BridgeError> {
+    let mut hasher = Sha256::new();
+    hasher.update(request.printer_name.as_deref().unwrap_or_default());
+    hasher.update([0]);
+    hasher.update(&request.content_type);
+    hasher.update(request.copies.unwrap_or(1).to_be_bytes());
+    if let Some(options) = &request.options {
+        hasher.update(serde_json::to_vec(options).unwrap_or_default());
+    }
+    
+    Ok(hash_document(hasher, request).await?.into())
+}
+
+/// Añade el documento a `hasher`, leyendo del spool si está en disco
+async fn hash_document(mut hasher: Sha256, request: &PrintRequest) -> Result<sha2::digest::Output<Sha256>, BridgeError> {
+    match &request.spooled {
+        Some(file) => {
+            let path = file.path().to_path_buf();
+            tokio::task::spawn_blocking(move || {
+                let mut file = std::fs::File::open(path)?;
+                std::io::copy(&mut file, &mut hasher)?;
+                Ok(hasher.finalize())
+            })
+            .await
+            .map_err(|e| BridgeError::IoError(std::io::Error::other(e)))?
+        }
+        None => {
+            hasher.update(request.content.as_bytes());
+            Ok(hasher.finalize())
+        }
+    }
+}
+
+/// Detiene o reanuda la cola; los trabajos retenidos no se pierden
+async fn handle_queue_hold(held: bool, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let queue = ctx.control.jobs();
+    queue.set_held(held);
+    
+    Ok(warp::reply::json(&serde_json::json!({
+        "success": true,
+        "paused": queue.is_held(),
+        "pending": queue.pending(),
+        "waiting": queue.waiting(),
+    })))
+}
+
+/// Cambia el nivel de log en caliente para diagnosticar sin reiniciar; no
+/// se guarda en la configuración
+async fn handle_log_level(request: LogLevelRequest, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    log::info!("[{}] 📊 Cambio de nivel de log solicitado: {}", ctx.request_id, request.level);
+    let previous = crate::logging::set_level(request.level.trim()).map_err(warp::reject::custom)?;
+    
+    Ok(warp::reply::json(&serde_json::json!({
+        "success": true,
+        "level": crate::logging::current_level(),
+        "previous": previous,
+    })))
+}
+
+/// Revisa el archivo de configuración tal como está en disco, que puede no
+/// coincidir con el que usa el servidor
+async fn handle_validate_config(ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let report = config::validate::validate_file(&config::config_path()).await.map_err(warp::reject::custom)?;
+    log::info!(
+        "[{}] 🧾 Configuración validada: {} problemas{}",
+        ctx.request_id,
+        report.findings.len(),
+        if report.valid { "" } else { ", con errores" }
+    );
+    Ok(warp::reply::json(&report))
+}
+
+async fn handle_create_token(request: CreateTokenRequest, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let token = ApiToken {
+        id: config::generate_token_id(),
+        name: request.name,
+        token: config::generate_secure_token(),
+        scopes: request.scopes.unwrap_or_else(|| vec![TokenScope::Print]),
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        daily_page_quota: request.daily_page_quota,
+        printers: request.printers,
+    };
+    
+    let _writing = ctx.config_writes.lock().await;
+    let mut tokens = ctx.tokens.lock().unwrap().clone();
+    tokens.push(token.clone());
+    persist_tokens(tokens, &ctx).await?;
+    
+    log::info!("🔑 Token creado: {} ({})", token.id, token.name);
+    
+    // El secreto sólo se devuelve en la creación
+    Ok(warp::reply::with_status(
+        warp::reply::json(&token),
+        warp::http::StatusCode::CREATED,
+    ))
+}
+
+async fn handle_list_tokens(ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let tokens = ctx.tokens.lock().unwrap();
+    let summaries: Vec<TokenSummary> = tokens.iter().map(TokenSummary::from).collect();
+    Ok(warp::reply::json(&summaries))
+}
+
+async fn handle_delete_token(id: String, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let _writing = ctx.config_writes.lock().await;
+    let mut tokens = ctx.tokens.lock().unwrap().clone();
+    let before = tokens.len();
+    tokens.retain(|t| t.id != id);
+    if tokens.len() == before {
+        return Err(warp::reject::custom(BridgeError::NotFound(format!("token {}", id))));
+    }
+    persist_tokens(tokens, &ctx).await?;
+    
+    log::info!("🗑️ Token revocado: {}", id);
+    Ok(warp::reply::json(&serde_json::json!({ "success": true, "id": id })))
+}
+
+async fn handle_list_templates(ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    Ok(warp::reply::json(&ctx.config.templates))
+}
+
+async fn handle_put_template(name: String, template: PrintTemplate, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    templates::check(&template)
+        .map_err(|message| warp::reject::custom(BridgeError::InvalidRequest(format!("Plantilla '{}': {}", name, message))))?;
+    if let Some(preset) = template.preset.as_deref().filter(|preset| !ctx.config.presets.contains_key(*preset)) {
+        return Err(warp::reject::custom(BridgeError::InvalidRequest(format!("Preajuste desconocido: {}", preset))));
+    }
+    
+    let _writing = ctx.config_writes.lock().await;
+    let mut templates = ctx.control.config().templates.clone();
+    let created = templates.insert(name.clone(), template.clone()).is_none();
+    persist_templates(templates, &ctx).await?;
+    
+    log::info!("🧾 Plantilla {}: {}", if created { "creada" } else { "actualizada" }, name);
+    let status = if created { warp::http::StatusCode::CREATED } else { warp::http::StatusCode::OK };
+    Ok(warp::reply::with_status(warp::reply::json(&template), status))
+}
+
+async fn handle_delete_template(name: String, ctx: SecurityContext) -> Result<impl Reply, warp::Rejection> {
+    let _writing = ctx.config_writes.lock().await;
+    let mut templates = ctx.control.config().templates.clone();
+    if templates.remove(&name).is_none() {
+        return Err(warp::reject::custom(BridgeError::NotFound(format!("plantilla {}", name))));
+    }
+    persist_templates(templates, &ctx).await?;
+    
+    log::info!("🗑️ Plantilla eliminada: {}", name);
+    Ok(warp::reply::json(&serde_json::json!({ "success": true, "name": name })))
+}
+
+/// Guarda las plantillas en el archivo de configuración y las aplica ya en el
+/// servidor, sin esperar a que la recarga detecte el cambio
+async fn persist_templates(templates: HashMap<String, PrintTemplate>, ctx: &SecurityContext) -> Result<(), warp::Rejection> {
+    let saved = templates.clone();
+    update_config_file(move |current| current.templates = saved).await?;
+    
+    let mut live = (*ctx.control.config()).clone();
+    live.templates = templates;
+    ctx.control.set_config(live);
+    Ok(())
+}
+
+/// Guarda la lista de tokens en el archivo de configuración actual,
+/// preservando cualquier otro cambio hecho desde la GUI, y la aplica ya en
+/// memoria. Se llama con `config_writes` tomado.
+async fn persist_tokens(tokens: Vec<ApiToken>, ctx: &SecurityContext) -> Result<(), warp::Rejection> {
+    let saved = tokens.clone();
+    update_config_file(move |current| current.tokens = saved).await?;
+    *ctx.tokens.lock().unwrap() = tokens;
+    Ok(())
+}
+
+/// Lee el archivo de configuración, aplica `change` y lo guarda, fuera del
+/// runtime porque toca el disco y quizá el llavero
+async fn update_config_file(change: impl FnOnce(&mut Config) + Send + 'static) -> Result<(), warp::Rejection> {
+    tokio::task::spawn_blocking(move || {
+        let mut current = config::load_config()?;
+        change(&mut current);
+        config::save_config(&current)
+    })
+    .await
+    .map_err(|e| warp::reject::custom(BridgeError::IoError(std::io::Error::other(e))))?
+    .map_err(warp::reject::custom)
 }
\ No newline at end of file
@@ -0,0 +1,124 @@
+use crate::config::Config;
+use crate::error::BridgeError;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use warp::Filter;
+
+/// HMAC-SHA256 en hexadecimal de `<MÉTODO>\n<ruta>?<query>\n<X-Timestamp>\n<cuerpo>`;
+/// admite el prefijo `sha256=`
+pub const SIGNATURE_HEADER: &str = "x-signature";
+/// Segundos Unix en que el cliente firmó la solicitud
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+
+/// Las dos cabeceras de la firma, tal como llegaron, y la solicitud que cubren
+#[derive(Debug, Clone, Default)]
+pub struct SignatureHeaders {
+    signature: Option<String>,
+    timestamp: Option<String>,
+    method: String,
+    /// Ruta con la query, si la hay, tal como la envió el cliente
+    target: String,
+}
+
+pub fn headers() -> impl Filter<Extract = (SignatureHeaders,), Error = warp::Rejection> + Clone {
+    let query = warp::query::raw().or(warp::any().map(String::new)).unify();
+    warp::header::optional::<String>(SIGNATURE_HEADER)
+        .and(warp::header::optional::<String>(TIMESTAMP_HEADER))
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(query)
+        .map(|signature, timestamp, method: warp::http::Method, path: warp::path::FullPath, query: String| {
+            let target = if query.is_empty() {
+                path.as_str().to_string()
+            } else {
+                format!("{}?{}", path.as_str(), query)
+            };
+            SignatureHeaders { signature, timestamp, method: method.to_string(), target }
+        })
+}
+
+/// Firmas aceptadas dentro de la ventana, para rechazar una solicitud
+/// capturada y reenviada tal cual
+#[derive(Clone, Default)]
+pub struct SeenSignatures {
+    entries: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+/// Comprobación de la firma de una solicitud, alimentada con el cuerpo según llega
+pub struct Verifier {
+    mac: Hmac<Sha256>,
+    signature: String,
+    timestamp: u64,
+    window_secs: u64,
+}
+
+impl Verifier {
+    /// `None` si la configuración no exige firma. Antes de leer el cuerpo ya
+    /// se rechazan las solicitudes sin firma o fuera de la ventana.
+    pub fn start(config: &Config, headers: &SignatureHeaders) -> Result<Option<Self>, BridgeError> {
+        let Some(secret) = config.request_signing_secret.as_deref() else {
+            return Ok(None);
+        };
+        let (Some(signature), Some(timestamp)) = (&headers.signature, &headers.timestamp) else {
+            return Err(BridgeError::InvalidSignature(format!(
+                "Faltan las cabeceras {} y {}",
+                SIGNATURE_HEADER, TIMESTAMP_HEADER
+            )));
+        };
+
+        let timestamp: u64 = timestamp
+            .trim()
+            .parse()
+            .map_err(|_| BridgeError::InvalidSignature(format!("{} debe ser segundos Unix", TIMESTAMP_HEADER)))?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let window_secs = config.request_signing_window_secs;
+        if now.abs_diff(timestamp) > window_secs {
+            return Err(BridgeError::InvalidSignature(format!(
+                "Firma fuera de la ventana de {}s; revisa el reloj del cliente",
+                window_secs
+            )));
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC admite claves de cualquier longitud");
+        // Método y ruta van firmados: la misma firma no sirve en otra ruta
+        // ni con otros parámetros en la query
+        mac.update(format!("{}\n{}\n{}\n", headers.method, headers.target, timestamp).as_bytes());
+        let signature = signature.trim();
+        let signature = signature.strip_prefix("sha256=").unwrap_or(signature).to_ascii_lowercase();
+        Ok(Some(Self { mac, signature, timestamp, window_secs }))
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.mac.update(chunk);
+    }
+
+    /// Compara la firma con la del cuerpo completo y la anota como usada
+    pub fn finish(self, seen: &SeenSignatures) -> Result<(), BridgeError> {
+        let expected: String = self.mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        if !bool::from(expected.as_bytes().ct_eq(self.signature.as_bytes())) {
+            return Err(BridgeError::InvalidSignature("La firma no corresponde a la solicitud".to_string()));
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let mut entries = seen.entries.lock().unwrap();
+        // Pasada la ventana la marca de tiempo ya basta para rechazarla
+        entries.retain(|_, timestamp| now.abs_diff(*timestamp) <= self.window_secs);
+        if entries.insert(self.signature, self.timestamp).is_some() {
+            return Err(BridgeError::InvalidSignature("Solicitud repetida".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Comprueba la firma de un cuerpo que ya está entero en memoria
+pub fn verify(config: &Config, headers: &SignatureHeaders, body: &[u8], seen: &SeenSignatures) -> Result<(), BridgeError> {
+    let Some(mut verifier) = Verifier::start(config, headers)? else {
+        return Ok(());
+    };
+    verifier.update(body);
+    verifier.finish(seen)
+}
@@ -0,0 +1,164 @@
+use crate::config;
+use crate::error::BridgeResult;
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Un trabajo que llegó a la impresora: quién lo pidió, qué era, dónde se
+/// imprimió y cómo terminó
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// RFC 3339 en hora local
+    pub timestamp: String,
+    /// `X-Request-Id` de la solicitud, como en el historial
+    pub request_id: String,
+    /// Token adicional que lo envió; `None` con el token principal
+    pub token_id: Option<String>,
+    pub client_ip: Option<String>,
+    pub origin: Option<String>,
+    pub content_type: String,
+    /// SHA-256 del documento tal como llegó, en hexadecimal
+    pub sha256: Option<String>,
+    pub size: u64,
+    pub pages: Option<u64>,
+    pub copies: u32,
+    pub printer: Option<String>,
+    pub job_id: Option<String>,
+    pub batch_id: Option<String>,
+    pub success: bool,
+    pub message: String,
+}
+
+/// Un token inválido. Comparte archivo con los trabajos y se distingue por `event`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthFailureEntry {
+    /// RFC 3339 en hora local
+    pub timestamp: String,
+    pub event: &'static str,
+    pub request_id: String,
+    pub client_ip: Option<String>,
+    pub origin: Option<String>,
+    /// Fallos seguidos desde esa IP, contando este
+    pub failures: u32,
+    /// Segundos que queda bloqueada la IP, si este fallo la bloqueó
+    pub locked_secs: Option<u64>,
+}
+
+/// Registro de auditoría de solo añadir: una línea JSON por trabajo, en un
+/// archivo por mes (`audit/2024-05.jsonl`). Nada lo reescribe ni lo poda la
+/// retención del historial.
+#[derive(Clone)]
+pub struct AuditLog {
+    dir: PathBuf,
+    /// Serializa las escrituras para que dos trabajos no mezclen sus líneas
+    lock: Arc<Mutex<()>>,
+}
+
+impl AuditLog {
+    pub fn open() -> Self {
+        Self {
+            dir: config::app_data_dir().join("audit"),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Añade la entrada al archivo del mes. Si falla solo se registra: el
+    /// documento ya salió por la impresora.
+    pub fn record(&self, entry: &AuditEntry) {
+        if let Err(e) = self.append(entry) {
+            log::error!("❌ No se pudo escribir en el registro de auditoría: {}", e);
+        }
+    }
+
+    pub fn record_auth_failure(&self, entry: &AuthFailureEntry) {
+        if let Err(e) = self.append(entry) {
+            log::error!("❌ No se pudo escribir en el registro de auditoría: {}", e);
+        }
+    }
+
+    fn append(&self, entry: &impl Serialize) -> BridgeResult<()> {
+        let mut line = serde_json::to_vec(entry).map_err(std::io::Error::other)?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().unwrap();
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.jsonl", Local::now().format("%Y-%m")));
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Entradas con `from <= timestamp < to` (marcas Unix), en orden, como
+    /// JSON Lines. Solo se leen los archivos de los meses del rango.
+    pub fn export(&self, from: Option<i64>, to: Option<i64>) -> BridgeResult<String> {
+        let mut files: Vec<(NaiveDate, PathBuf)> = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .flatten()
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    let month = path.file_stem()?.to_str()?;
+                    let start = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").ok()?;
+                    Some((start, path))
+                })
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        files.sort();
+
+        let mut output = String::new();
+        for (start, path) in files {
+            if to.is_some_and(|to| local_timestamp(start) >= to) {
+                continue;
+            }
+            if from.is_some_and(|from| local_timestamp(next_month(start)) <= from) {
+                continue;
+            }
+
+            let _guard = self.lock.lock().unwrap();
+            let file = std::fs::File::open(&path)?;
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                let Some(timestamp) = entry_timestamp(&line) else {
+                    log::warn!("⚠️ Línea ilegible en {}, se omite", path.display());
+                    continue;
+                };
+                if from.is_some_and(|from| timestamp < from) || to.is_some_and(|to| timestamp >= to) {
+                    continue;
+                }
+                output.push_str(&line);
+                output.push('\n');
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+/// Marca de tiempo de una línea del registro, sin deserializar el resto
+fn entry_timestamp(line: &str) -> Option<i64> {
+    #[derive(Deserialize)]
+    struct Timestamp {
+        timestamp: String,
+    }
+    let entry: Timestamp = serde_json::from_str(line).ok()?;
+    DateTime::parse_from_rfc3339(&entry.timestamp).ok().map(|time| time.timestamp())
+}
+
+fn next_month(date: NaiveDate) -> NaiveDate {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1).unwrap_or(date)
+}
+
+/// Medianoche local de `date`; los archivos se nombran por el mes local
+fn local_timestamp(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+        .map_or(i64::MIN, |time| time.timestamp())
+}
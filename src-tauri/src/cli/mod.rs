@@ -0,0 +1,339 @@
+use crate::api::{self, PrintOptions, PrintRequest};
+use crate::config::{self, validate::{self, Severity}, Config};
+use crate::control::BridgeControl;
+use crate::error::{BridgeError, BridgeResult};
+use crate::history::JobHistory;
+use crate::printer::PrinterManager;
+use crate::server::Overrides;
+use crate::source;
+use base64::{engine::general_purpose, Engine as _};
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+/// Puente entre aplicaciones web y las impresoras locales
+#[derive(Debug, Parser)]
+#[command(name = "print-my-bridge", version, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Archivo de configuración en lugar del de la plataforma
+    #[arg(long, global = true, value_name = "RUTA")]
+    pub config: Option<PathBuf>,
+
+    /// Sin subcomando se arranca el servidor, como con `serve`
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Arranca el servidor HTTP, con la GUI salvo con --headless
+    Serve(ServeArgs),
+    /// Impresoras del sistema
+    Printers {
+        #[command(subcommand)]
+        command: PrintersCommand,
+    },
+    /// Imprime un archivo local por el mismo camino que la API, y queda en el historial
+    Print(PrintArgs),
+    /// Consulta o modifica el archivo de configuración
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Token de la API
+    Token {
+        #[command(subcommand)]
+        command: TokenCommand,
+    },
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct ServeArgs {
+    /// Sin ventana ni icono en la bandeja
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Puerto en lugar del de la configuración (no se guarda)
+    #[arg(long, value_name = "N")]
+    pub port: Option<u16>,
+
+    /// Atender con las impresoras simuladas en lugar de CUPS
+    #[arg(long)]
+    pub mock_printers: bool,
+
+    /// Servidor headless que imprime en una cola desechable
+    #[arg(long)]
+    pub test_mode: bool,
+}
+
+impl ServeArgs {
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            mock_printers: self.mock_printers,
+            port: self.port,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PrintersCommand {
+    /// Lista las impresoras disponibles
+    List,
+}
+
+#[derive(Debug, Args)]
+pub struct PrintArgs {
+    /// Documento a imprimir
+    pub file: PathBuf,
+
+    /// Impresora o alias; sin ella, la predeterminada
+    #[arg(long)]
+    pub printer: Option<String>,
+
+    /// Tipo de contenido (pdf, html, markdown, text, image); por defecto se
+    /// deduce de la extensión
+    #[arg(long, value_name = "TIPO")]
+    pub content_type: Option<String>,
+
+    /// Número de copias
+    #[arg(long, value_name = "N")]
+    pub copies: Option<u32>,
+
+    /// Imprimir a doble cara
+    #[arg(long)]
+    pub duplex: bool,
+
+    /// Preajuste de `[presets]` de la configuración
+    #[arg(long)]
+    pub preset: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Muestra la configuración en uso, con los tokens y secretos ocultos
+    Show,
+    /// Revisa el archivo sin aplicarlo; termina con error si no serviría para arrancar
+    Validate,
+    /// Cambia un campo, p. ej. `config set port 8766` o `config set logging.level debug`
+    Set {
+        /// Campo, con puntos para los anidados
+        key: String,
+        /// Valor en sintaxis TOML; lo que no se interpreta se toma como texto
+        value: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokenCommand {
+    /// Genera un token nuevo, lo guarda y lo muestra
+    Generate {
+        /// El de administración (`admin_token`) en lugar del de impresión
+        #[arg(long)]
+        admin: bool,
+    },
+}
+
+/// Ejecuta un subcomando distinto de `serve` y termina
+pub async fn run(command: Command) -> BridgeResult<()> {
+    attach_console();
+    match command {
+        Command::Serve(_) => unreachable!("`serve` lo arranca main"),
+        Command::Printers { command: PrintersCommand::List } => list_printers().await,
+        Command::Print(args) => print_file(args).await,
+        Command::Config { command: ConfigCommand::Show } => show_config().await,
+        Command::Config { command: ConfigCommand::Validate } => validate_config().await,
+        Command::Config { command: ConfigCommand::Set { key, value } } => set_config(&key, &value).await,
+        Command::Token { command: TokenCommand::Generate { admin } } => generate_token(admin).await,
+    }
+}
+
+async fn load() -> BridgeResult<Config> {
+    tokio::task::spawn_blocking(config::load_config)
+        .await
+        .map_err(|e| BridgeError::ConfigError(e.to_string()))?
+}
+
+async fn list_printers() -> BridgeResult<()> {
+    let config = load().await?;
+    let printers = PrinterManager::get_available_printers(&config).await?;
+    if printers.is_empty() {
+        println!("No hay impresoras configuradas");
+        return Ok(());
+    }
+    for printer in printers {
+        let default = if printer.is_default { " (predeterminada)" } else { "" };
+        println!("{}{}\t{}", printer.name, default, printer.status);
+        if !printer.aliases.is_empty() {
+            println!("    alias: {}", printer.aliases.join(", "));
+        }
+    }
+    Ok(())
+}
+
+async fn print_file(args: PrintArgs) -> BridgeResult<()> {
+    let config = load().await?;
+    let content_type = match args.content_type {
+        Some(content_type) => content_type,
+        None => content_type_for_path(&args.file).map(str::to_string).ok_or_else(|| {
+            BridgeError::InvalidRequest(format!(
+                "No se pudo deducir el tipo de {}; indícalo con --content-type",
+                args.file.display()
+            ))
+        })?,
+    };
+
+    let bytes = tokio::fs::read(&args.file).await?;
+    let content = if source::is_text_type(&content_type) {
+        String::from_utf8(bytes)
+            .map_err(|_| BridgeError::InvalidRequest(format!("{} no es texto UTF-8", args.file.display())))?
+    } else {
+        general_purpose::STANDARD.encode(bytes)
+    };
+
+    let request = PrintRequest {
+        printer_name: args.printer,
+        content,
+        url: None,
+        spooled: None,
+        content_type,
+        mime: None,
+        template_name: None,
+        data: None,
+        copies: args.copies,
+        options: args.duplex.then(|| PrintOptions { duplex: Some(true), ..Default::default() }),
+        preset: args.preset,
+        priority: Default::default(),
+        external_id: None,
+        metadata: Default::default(),
+        notify: None,
+        dry_run: false,
+        request_id: None,
+    };
+    let history = tokio::task::spawn_blocking(JobHistory::open)
+        .await
+        .map_err(|e| BridgeError::PrintError(e.to_string()))?;
+    let response = api::print_local(request, config, BridgeControl::new(), history).await?;
+    println!("✅ {}", response.message);
+    if let Some(printer) = response.printer {
+        println!("Impresora: {}", printer);
+    }
+    if let Some(job_id) = response.job_id {
+        println!("Trabajo: {}", job_id);
+    }
+    Ok(())
+}
+
+async fn show_config() -> BridgeResult<()> {
+    let config = load().await?;
+    // Los tokens ya vienen resueltos del llavero; no deben acabar en la terminal
+    let toml = toml::to_string_pretty(&config.redacted()).map_err(|e| BridgeError::ConfigError(e.to_string()))?;
+    println!("# {}", config::config_path().display());
+    print!("{}", toml);
+    Ok(())
+}
+
+async fn validate_config() -> BridgeResult<()> {
+    let report = validate::validate_file(&config::config_path()).await?;
+    println!("# {}", report.path);
+    for finding in &report.findings {
+        let icon = match finding.severity {
+            Severity::Error => "❌",
+            Severity::Warning => "⚠️",
+        };
+        if finding.field.is_empty() {
+            println!("{} {}", icon, finding.message);
+        } else {
+            println!("{} {}: {}", icon, finding.field, finding.message);
+        }
+    }
+    if !report.valid {
+        return Err(BridgeError::ConfigError("la configuración tiene errores".to_string()));
+    }
+    if report.findings.is_empty() {
+        println!("✅ Configuración válida");
+    }
+    Ok(())
+}
+
+async fn set_config(key: &str, value: &str) -> BridgeResult<()> {
+    let config = load().await?;
+    let updated = with_value(&config, key, value)?;
+    config::save_config(&updated)?;
+    println!("✅ {} = {}", key, value);
+    if config.server_settings_changed(&updated) {
+        println!("Si el servidor está en marcha, reinícialo para aplicar el cambio");
+    }
+    Ok(())
+}
+
+/// `config` con `key` (separado por puntos) cambiado a `value`
+fn with_value(config: &Config, key: &str, value: &str) -> BridgeResult<Config> {
+    let invalid = |message: String| BridgeError::ConfigError(message);
+
+    // Como TOML para admitir números, booleanos y listas; si no, es texto
+    let parsed = toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()));
+    let parsed = serde_json::to_value(parsed).map_err(|e| invalid(e.to_string()))?;
+
+    let mut json = serde_json::to_value(config).map_err(|e| invalid(e.to_string()))?;
+    let mut target = &mut json;
+    for part in key.split('.') {
+        target = target
+            .as_object_mut()
+            .and_then(|object| object.get_mut(part))
+            .ok_or_else(|| invalid(format!("Campo desconocido: {}", key)))?;
+    }
+    *target = parsed;
+
+    serde_json::from_value(json).map_err(|e| invalid(format!("Valor inválido para {}: {}", key, e)))
+}
+
+async fn generate_token(admin: bool) -> BridgeResult<()> {
+    let mut config = load().await?;
+    let token = config::generate_secure_token();
+    if admin {
+        config.admin_token = Some(token.clone());
+    } else {
+        config.api_token = Some(token.clone());
+    }
+    config::save_config(&config)?;
+    // El servidor en marcha lo toma al recargar la configuración
+    println!("{}", token);
+    Ok(())
+}
+
+/// Tipo de contenido del bridge según la extensión del archivo
+fn content_type_for_path(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "pdf" => Some("pdf"),
+        "html" | "htm" => Some("html"),
+        "md" | "markdown" => Some("markdown"),
+        "txt" | "text" | "log" => Some("text"),
+        "docx" => Some("docx"),
+        "odt" => Some("odt"),
+        "xlsx" => Some("xlsx"),
+        "svg" => Some("svg"),
+        "prn" | "zpl" | "pcl" => Some("raw"),
+        "png" | "jpg" | "jpeg" | "gif" | "bmp" | "tif" | "tiff" | "webp" => Some("image"),
+        _ => None,
+    }
+}
+
+/// En release el binario de Windows no tiene consola; se engancha a la de la
+/// terminal que lo lanzó para que se vea la salida
+#[cfg(windows)]
+fn attach_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
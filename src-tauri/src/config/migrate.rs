@@ -0,0 +1,33 @@
+use super::CONFIG_VERSION;
+use crate::error::{BridgeError, BridgeResult};
+
+/// Pasos de migración: el de la posición `i` lleva un archivo de la versión
+/// `i` a la `i + 1`. Al renombrar o dividir claves se sube `CONFIG_VERSION` y
+/// se añade aquí el paso que reescribe la tabla antigua.
+const MIGRATIONS: [fn(&mut toml::Table); CONFIG_VERSION as usize] = [unversioned];
+
+/// Lleva `table` a `CONFIG_VERSION`. Devuelve la versión de la que partía.
+pub fn migrate(table: &mut toml::Table) -> BridgeResult<u32> {
+    let from = match table.get("version") {
+        None => 0,
+        Some(toml::Value::Integer(version)) => u32::try_from(*version)
+            .map_err(|_| BridgeError::ConfigError(format!("`version` inválida: {}", version)))?,
+        Some(other) => return Err(BridgeError::ConfigError(format!("`version` inválida: {}", other))),
+    };
+    if from > CONFIG_VERSION {
+        return Err(BridgeError::ConfigError(format!(
+            "El archivo es de la versión {} de la configuración y este bridge solo entiende hasta la {}; actualízalo",
+            from, CONFIG_VERSION
+        )));
+    }
+
+    for (version, step) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        step(table);
+        log::info!("📦 Configuración migrada de la versión {} a la {}", version, version + 1);
+    }
+    table.insert("version".to_string(), toml::Value::Integer(CONFIG_VERSION.into()));
+    Ok(from)
+}
+
+/// 0 → 1: archivos de antes de que hubiera número de versión; el esquema no cambia
+fn unversioned(_: &mut toml::Table) {}
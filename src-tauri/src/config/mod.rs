@@ -1,70 +1,1031 @@
 use serde::{Deserialize, Serialize};
-use crate::error::BridgeResult;
+use sha2::{Digest, Sha256};
+use crate::api::PrintOptions;
+use crate::error::{BridgeError, BridgeResult};
+use crate::templates::LabelLayout;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+mod migrate;
+pub mod runtime;
+mod secrets;
+pub mod validate;
+
+/// Versión del esquema del archivo de configuración. Se sube al renombrar o
+/// dividir claves, junto con el paso correspondiente en `migrate`.
+pub const CONFIG_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
+    /// Versión del esquema con que se escribió el archivo (sin valor = 0);
+    /// los archivos antiguos se migran al cargarlos
+    pub version: u32,
     pub host: String,
     pub port: u16,
+    /// Puertos a probar, en orden, si `port` está ocupado
+    pub fallback_ports: Vec<u16>,
+    /// Sin `fallback_ports`, cuántos puertos siguientes a `port` probar (0 = ninguno)
+    pub port_fallback_count: u16,
+    /// Socket Unix (o nombre de named pipe en Windows) en el que servir también
+    /// la API, para aplicaciones del mismo equipo
+    pub ipc_path: Option<String>,
+    /// Direcciones adicionales en las que servir la misma API, cada una con
+    /// su TLS y su autenticación (p. ej. HTTPS en la LAN para quioscos)
+    pub listeners: Vec<Listener>,
     pub max_file_size_mb: u64,
+    /// Límite propio de algunos tipos de contenido (`text = 2`, `pdf = 50`);
+    /// los que no aparecen usan `max_file_size_mb`
+    pub max_file_size_mb_by_type: HashMap<String, u64>,
+    /// Espacio máximo de los documentos en espera en el spool (0 = sin límite)
+    pub spool_quota_mb: u64,
     pub rate_limit_per_minute: u32,
+    /// Tokens inválidos seguidos desde una IP antes de bloquearla (0 = nunca)
+    pub auth_max_failures: u32,
+    /// Segundos que dura el bloqueo
+    pub auth_lockout_secs: u64,
     pub api_token: Option<String>,
+    /// Token de las rutas de administración (tokens, cola, logs, configuración).
+    /// Si está configurado, `api_token` solo sirve para imprimir y consultar.
+    pub admin_token: Option<String>,
+    /// Guardar `api_token` y `admin_token` en el llavero del sistema; en el
+    /// archivo queda solo una referencia. Desactivar en servidores sin llavero.
+    pub keyring: bool,
+    /// Aceptar JWT de un proveedor de identidad en `Authorization: Bearer`
+    pub jwt: Option<JwtSettings>,
+    /// Exigir en las solicitudes con cuerpo o que cambian algo `X-Signature`
+    /// (HMAC-SHA256 con este secreto de método, ruta, `X-Timestamp` y cuerpo)
+    /// y `X-Timestamp`, contra trabajos capturados y reenviados o alterados
+    pub request_signing_secret: Option<String>,
+    /// Segundos de diferencia admitidos entre `X-Timestamp` y el reloj del bridge
+    pub request_signing_window_secs: u64,
     pub auto_start: bool,
     pub minimize_to_tray: bool,
     // Campos faltantes añadidos:
     pub allowed_origins: Vec<String>,
+    /// Preflight, credenciales y métodos de CORS para `allowed_origins`
+    pub cors: CorsSettings,
     pub allowed_file_types: Vec<String>,
     pub default_printer: Option<String>,
+    /// Nombres amigables ("cocina") hacia la cola CUPS o la URI de una impresora de red
+    pub printer_aliases: HashMap<String, String>,
+    /// Impresoras de respaldo por alias, en orden, si la principal falla o está deshabilitada
+    pub printer_fallbacks: HashMap<String, Vec<String>>,
+    /// Grupos de impresoras idénticas que se reparten los trabajos
+    pub printer_pools: HashMap<String, PrinterPool>,
+    /// Combinaciones con nombre de impresora y opciones ("ticket-80mm", "factura-duplex")
+    pub presets: HashMap<String, PrintPreset>,
+    /// Plantillas con nombre para `content_type = "template"` (tickets, comandas)
+    pub templates: HashMap<String, PrintTemplate>,
+    /// Etiquetas para `content_type = "label"` y las impresoras que reciben ZPL
+    pub labels: LabelSettings,
+    /// Cajón portamonedas y cuchilla de las impresoras de tickets
+    pub pos: PosSettings,
+    /// Segundos que se reutiliza la lista de impresoras (0 = consultar siempre)
+    pub printer_cache_ttl_secs: u64,
+    /// Segundos que dura la búsqueda de impresoras por mDNS
+    pub discovery_timeout_secs: u64,
+    /// Impresoras de red añadidas desde el descubrimiento, con su cola CUPS
+    pub network_printers: Vec<NetworkPrinter>,
+    /// Hosts desde los que se permite descargar documentos vía `url`
+    /// (admite `*.dominio.com`); vacío desactiva la descarga
+    pub allowed_url_hosts: Vec<String>,
+    /// Tokens adicionales administrados vía API, cada uno con sus permisos
+    pub tokens: Vec<ApiToken>,
+    /// Rutas explícitas a herramientas externas (lp, wkhtmltopdf, soffice, gs...)
+    pub tool_paths: HashMap<String, String>,
+    /// Servir la API por HTTPS
+    pub tls_enabled: bool,
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    /// Entradas del menú del tray, en orden
+    pub tray_menu: Vec<TrayMenuItem>,
+    /// Motor usado para convertir HTML a PDF
+    pub html_renderer: HtmlRenderer,
+    /// Qué hacer si la conversión de HTML a PDF falla
+    pub render_fallback: RenderFallback,
+    pub render: RenderConfig,
+    /// Cabecera y pie por defecto de los trabajos de texto, HTML y Markdown
+    pub header_footer: HeaderFooterSettings,
+    /// Hoja de estilos opcional para documentos Markdown
+    pub markdown_css: Option<String>,
+    /// Convertir a PNG las imágenes que CUPS no imprime directamente (p. ej. WebP)
+    pub transcode_images: bool,
+    /// Segundos antes de matar lpstat/lpoptions, lp y el renderizador
+    pub query_timeout_secs: u64,
+    pub spool_timeout_secs: u64,
+    pub render_timeout_secs: u64,
+    /// Trabajos pendientes a partir de los cuales se responde 503 (0 = sin límite)
+    pub max_pending_jobs: usize,
+    /// Valor de `Retry-After` cuando la cola está llena
+    pub queue_retry_after_secs: u64,
+    /// Segundos que se espera a los trabajos en curso al cerrar; los que no
+    /// terminen se retoman al volver a iniciar
+    pub shutdown_drain_secs: u64,
+    /// Segundos en los que un documento idéntico para la misma impresora se
+    /// considera repetido (0 = desactivado)
+    pub dedupe_window_secs: u64,
+    pub dedupe_action: DedupeAction,
+    /// Qué hacer al arrancar con los trabajos que quedaron a medias
+    pub interrupted_jobs: RecoveryPolicy,
+    /// Reintentos automáticos ante fallos pasajeros
+    pub retry: RetryPolicy,
+    /// Días que se conservan los trabajos, en la base o archivados (0 = siempre)
+    pub job_history_days: u32,
+    /// Filas como máximo en la base; las más antiguas se archivan (0 = sin límite)
+    pub max_history_rows: u64,
+    /// Días tras los cuales los trabajos pasan del historial a los archivos mensuales
+    /// (0 = nunca)
+    pub history_archive_days: u32,
+    /// Cada cuántas horas archivar y compactar el historial (0 = nunca)
+    pub history_maintenance_hours: u32,
+    /// Nivel, archivo y formato de los registros
+    pub logging: LoggingSettings,
+    /// Impresoras simuladas que sustituyen a CUPS, para pruebas de integración
+    pub mock: MockConfig,
+    /// Cola a la que se redirigen todos los trabajos en `--test-mode`; nunca se guarda
+    #[serde(skip)]
+    pub test_queue: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Listener {
+    pub host: String,
+    /// Sin puertos alternativos: si está ocupado este listener no arranca
+    pub port: u16,
+    /// HTTPS con el certificado de `tls_cert_path`/`tls_key_path` o el autofirmado
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default)]
+    pub auth: ListenerAuth,
+}
+
+impl Listener {
+    pub fn bind_ip(&self) -> BridgeResult<IpAddr> {
+        parse_host(&self.host)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerAuth {
+    /// Los tokens configurados, como en `host`/`port`
+    #[default]
+    Token,
+    /// Acceso completo sin token; solo en direcciones loopback
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HtmlRenderer {
+    Chromium,
+    Wkhtmltopdf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderFallback {
+    /// Devolver el error del renderizador al cliente
+    Error,
+    /// Reintentar con el otro motor (chromium <-> wkhtmltopdf)
+    AlternateEngine,
+    /// Abrir el HTML en el navegador del equipo para imprimirlo a mano
+    Browser,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CorsSettings {
+    /// Segundos que el navegador reutiliza un preflight (`Access-Control-Max-Age`);
+    /// sin él, cada impresión desde otro origen espera un OPTIONS (0 = no se envía)
+    pub max_age_secs: u64,
+    /// Enviar `Access-Control-Allow-Credentials` para peticiones con cookies o
+    /// autenticación del navegador
+    pub allow_credentials: bool,
+    /// Métodos permitidos en las rutas sin entrada en `route_methods`
+    pub methods: Vec<String>,
+    /// Métodos por prefijo de ruta, p. ej. `"/api/admin" = ["GET"]`; gana el
+    /// prefijo más largo
+    pub route_methods: HashMap<String, Vec<String>>,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        Self {
+            max_age_secs: 600,
+            allow_credentials: false,
+            methods: ["GET", "POST", "PUT", "DELETE", "OPTIONS"].iter().map(|method| method.to_string()).collect(),
+            route_methods: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RenderConfig {
+    pub page_size: String,
+    pub margin: String,
+    /// Esperar a que terminen las cargas de red (imágenes, fuentes) antes de imprimir
+    pub wait_for_network: bool,
+    pub network_timeout_ms: u64,
+    /// Quitar `<script>`, atributos `on*` y URL `javascript:` antes de renderizar
+    pub strip_scripts: bool,
+    /// Qué recursos externos puede cargar el HTML al renderizarse
+    pub resources: ResourcePolicy,
+    /// Hosts permitidos con `resources = "allowlist"`
+    pub resource_hosts: Vec<String>,
+    /// Resolución a la que se rasterizan los SVG
+    pub svg_dpi: u32,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            page_size: "A4".to_string(),
+            margin: "0.75in".to_string(),
+            wait_for_network: true,
+            network_timeout_ms: 5000,
+            strip_scripts: false,
+            resources: ResourcePolicy::Allow,
+            resource_hosts: Vec::new(),
+            svg_dpi: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HeaderFooterSettings {
+    /// Valor de `{business}`
+    pub business_name: String,
+    /// Plantilla de la cabecera, p. ej. `"{business} · {date} {time}"`
+    pub header: Option<String>,
+    /// Plantilla del pie, p. ej. `"Página {page} de {pages}"`
+    pub footer: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourcePolicy {
+    /// Cualquier URL, como un navegador
+    Allow,
+    /// Solo lo que va dentro del documento (`data:`, estilos en línea)
+    Block,
+    /// Además, los hosts de `resource_hosts`
+    Allowlist,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrintPreset {
+    /// Cola o alias; si falta se usa la impresora por defecto
+    pub printer: Option<String>,
+    pub copies: Option<u32>,
+    #[serde(default)]
+    pub options: PrintOptions,
+}
+
+/// Plantilla Handlebars que el bridge rellena con los datos del trabajo
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrintTemplate {
+    /// Lo que produce la plantilla: `html`, `markdown` o `text`
+    pub content_type: String,
+    pub body: String,
+    /// Preajuste para los trabajos que no indican uno
+    pub preset: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LabelSettings {
+    /// Colas o alias de las impresoras de etiquetas que reciben ZPL, con
+    /// su resolución en ppp (203, 300, 600); las demás reciben un PDF
+    pub zpl_printers: HashMap<String, u32>,
+    /// Diseños con nombre para `template_name`
+    pub layouts: HashMap<String, LabelLayout>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PosSettings {
+    /// Conector del cajón en la impresora: 2 (el habitual) o 5
+    pub drawer_pin: u8,
+    /// Duración del pulso que abre el cajón, en ms
+    pub drawer_pulse_ms: u32,
+}
+
+impl Default for PosSettings {
+    fn default() -> Self {
+        Self {
+            drawer_pin: 2,
+            drawer_pulse_ms: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrinterPool {
+    /// Colas o alias del grupo
+    pub printers: Vec<String>,
+    #[serde(default)]
+    pub strategy: PoolStrategy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolStrategy {
+    /// Una tras otra, en orden
+    #[default]
+    RoundRobin,
+    /// La que tenga menos trabajos pendientes
+    LeastQueue,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NetworkPrinter {
+    /// Nombre de la cola creada en CUPS
+    pub name: String,
+    /// `ipp://host:631/ipp/print` o `socket://host:9100`
+    pub uri: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LoggingSettings {
+    /// Nivel o directivas de `EnvFilter` (`info`, `print_my_bridge=debug,warp=warn`).
+    /// `RUST_LOG` tiene prioridad.
+    pub level: String,
+    /// Escribir también en archivo, en JSON y con rotación diaria
+    pub file: bool,
+    /// Directorio de los archivos; por defecto el de logs de la plataforma
+    pub dir: Option<String>,
+    /// Archivos diarios que se conservan; 0 = todos
+    pub max_log_files: usize,
+    /// Tamaño máximo de todos los archivos juntos; se borran los más antiguos.
+    /// 0 = sin límite
+    pub max_log_size_mb: u64,
+    /// JSON también en la consola
+    pub json: bool,
+    /// Enviar también a syslog (facilidad `daemon`); solo Linux y macOS
+    pub syslog: bool,
+    /// Enviar también al registro de Aplicación del Visor de eventos, de
+    /// `info` hacia arriba; solo Windows
+    pub eventlog: bool,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: true,
+            dir: None,
+            max_log_files: 14,
+            max_log_size_mb: 100,
+            json: false,
+            syslog: false,
+            eventlog: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MockConfig {
+    /// Atender los trabajos con las impresoras simuladas (también con `--mock-printers`)
+    pub enabled: bool,
+    /// Dónde se escriben los documentos impresos; por defecto `mock-output`
+    /// en el directorio de datos
+    pub output_dir: Option<String>,
+    pub printers: Vec<MockPrinter>,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            output_dir: None,
+            printers: vec![MockPrinter {
+                name: "mock".to_string(),
+                status: MockStatus::Idle,
+                fail_every: 0,
+                trays: Vec::new(),
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MockPrinter {
+    pub name: String,
+    #[serde(default)]
+    pub status: MockStatus,
+    /// Falla uno de cada N trabajos, empezando por el N-ésimo (0 = nunca)
+    #[serde(default)]
+    pub fail_every: u32,
+    /// Bandejas que acepta `options.tray`
+    #[serde(default)]
+    pub trays: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MockStatus {
+    #[default]
+    Idle,
+    /// La cola existe pero no acepta trabajos
+    Disabled,
+    /// El dispositivo no responde
+    Offline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupeAction {
+    /// Responder 409 DUPLICATE_JOB
+    Reject,
+    /// Responder como si se hubiera impreso, con los datos del original
+    Coalesce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryPolicy {
+    /// Volver a imprimir todos; los que ya estaban en CUPS pueden salir dos veces
+    Resume,
+    /// Volver a imprimir los que no llegaron a la impresora y dar por fallidos el resto
+    ResumeQueued,
+    /// Darlos todos por fallidos
+    Fail,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Intentos en total, contando el primero (1 = sin reintentos)
+    pub max_attempts: u32,
+    /// Espera antes del primer reintento; se duplica en cada uno
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Qué fallos se reintentan
+    pub retry_on: Vec<RetryCondition>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff_ms: 1000,
+            max_backoff_ms: 30_000,
+            retry_on: vec![RetryCondition::Offline, RetryCondition::Timeout, RetryCondition::Io],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn should_retry(&self, error: &BridgeError) -> bool {
+        self.retry_on.iter().any(|condition| condition.matches(error))
+    }
+    
+    /// Espera tras el intento número `attempt` (empezando en 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor).min(self.max_backoff_ms))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryCondition {
+    /// Cola deshabilitada o impresora de red sin respuesta
+    Offline,
+    /// lp, lpstat o el renderizador no respondieron a tiempo
+    Timeout,
+    /// `lp` rechazó el trabajo (p. ej. impresora ocupada)
+    PrinterError,
+    /// Errores de E/S, como sockets cortados
+    Io,
+}
+
+impl RetryCondition {
+    fn matches(&self, error: &BridgeError) -> bool {
+        match self {
+            RetryCondition::Offline => matches!(error, BridgeError::PrinterOffline { .. }),
+            RetryCondition::Timeout => matches!(error, BridgeError::Timeout { .. }),
+            RetryCondition::PrinterError => {
+                matches!(error, BridgeError::PrinterError(_) | BridgeError::PrintError(_))
+            }
+            RetryCondition::Io => matches!(error, BridgeError::IoError(_)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayMenuItem {
+    Show,
+    Hide,
+    Pause,
+    TestPage,
+    ReprintLast,
+    Quit,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JwtSettings {
+    /// Secreto compartido para tokens HS256/HS384/HS512
+    pub secret: Option<String>,
+    /// JWKS del proveedor de identidad, para tokens RS*/ES*/PS*/EdDSA
+    pub jwks_url: Option<String>,
+    /// `iss` exigido, si se indica
+    pub issuer: Option<String>,
+    /// `aud` exigido, si se indica
+    pub audience: Option<String>,
+    /// Claim con los permisos: cadena separada por espacios o lista
+    pub scope_claim: String,
+    /// Valor del claim que equivale a cada permiso; vacío = `print` y `admin` tal cual
+    pub scope_map: HashMap<String, TokenScope>,
+    /// Claim con la lista de impresoras permitidas; sin él, todas
+    pub printers_claim: String,
+    /// Segundos que se reutilizan las claves descargadas del JWKS
+    pub jwks_cache_secs: u64,
+    /// Margen de reloj al comprobar `exp` y `nbf`
+    pub leeway_secs: u64,
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            jwks_url: None,
+            issuer: None,
+            audience: None,
+            scope_claim: "scope".to_string(),
+            scope_map: HashMap::new(),
+            printers_claim: "printers".to_string(),
+            jwks_cache_secs: 3600,
+            leeway_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    Print,
+    Admin,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub token: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: u64,
+    /// Páginas al día, contando copias, que puede imprimir (sin valor = sin límite)
+    #[serde(default)]
+    pub daily_page_quota: Option<u64>,
+    /// Impresoras, alias o grupos a los que puede enviar (sin valor = todas)
+    #[serde(default)]
+    pub printers: Option<Vec<String>>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             host: "127.0.0.1".to_string(),
             port: 8765,
+            fallback_ports: Vec::new(),
+            port_fallback_count: 5,
+            ipc_path: None,
+            listeners: Vec::new(),
             max_file_size_mb: 50,
+            max_file_size_mb_by_type: HashMap::new(),
+            spool_quota_mb: 1024,
             rate_limit_per_minute: 60,
+            auth_max_failures: 5,
+            auth_lockout_secs: 300,
             api_token: None,
+            admin_token: None,
+            keyring: true,
+            jwt: None,
+            request_signing_secret: None,
+            request_signing_window_secs: 300,
             auto_start: false,
             minimize_to_tray: true,
             // Valores por defecto para los nuevos campos:
             allowed_origins: vec!["*".to_string()],
+            cors: CorsSettings::default(),
             allowed_file_types: vec![
                 "pdf".to_string(),
                 "html".to_string(),
+                "markdown".to_string(),
                 "text".to_string(),
                 "image".to_string()
             ],
             default_printer: None,
+            printer_aliases: HashMap::new(),
+            printer_fallbacks: HashMap::new(),
+            printer_pools: HashMap::new(),
+            presets: HashMap::new(),
+            templates: HashMap::new(),
+            labels: LabelSettings::default(),
+            pos: PosSettings::default(),
+            printer_cache_ttl_secs: 30,
+            discovery_timeout_secs: 3,
+            network_printers: Vec::new(),
+            allowed_url_hosts: Vec::new(),
+            tokens: Vec::new(),
+            tool_paths: HashMap::new(),
+            tls_enabled: false,
+            tls_cert_path: None,
+            tls_key_path: None,
+            tray_menu: vec![TrayMenuItem::Show, TrayMenuItem::Hide, TrayMenuItem::Quit],
+            html_renderer: HtmlRenderer::Chromium,
+            render_fallback: RenderFallback::Error,
+            render: RenderConfig::default(),
+            header_footer: HeaderFooterSettings::default(),
+            markdown_css: None,
+            transcode_images: true,
+            query_timeout_secs: 10,
+            spool_timeout_secs: 60,
+            render_timeout_secs: 120,
+            max_pending_jobs: 100,
+            queue_retry_after_secs: 10,
+            shutdown_drain_secs: 30,
+            dedupe_window_secs: 0,
+            dedupe_action: DedupeAction::Reject,
+            interrupted_jobs: RecoveryPolicy::ResumeQueued,
+            retry: RetryPolicy::default(),
+            job_history_days: 0,
+            max_history_rows: 100_000,
+            history_archive_days: 90,
+            history_maintenance_hours: 24,
+            logging: LoggingSettings::default(),
+            mock: MockConfig::default(),
+            test_queue: None,
         }
     }
 }
 
+impl Config {
+    /// URL base en la que escucha el servidor, según esté o no activo TLS
+    pub fn base_url(&self) -> String {
+        self.base_url_on(self.port)
+    }
+    
+    /// Como `base_url`, con el puerto en que escucha de verdad el servidor
+    pub fn base_url_on(&self, port: u16) -> String {
+        let scheme = if self.tls_enabled { "https" } else { "http" };
+        // Escuchando en todas las interfaces, desde este equipo se llega por loopback
+        let host = match self.bind_ip() {
+            Ok(ip) if ip.is_unspecified() => Ipv4Addr::LOCALHOST.to_string(),
+            Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+            _ => self.host.clone(),
+        };
+        format!("{}://{}:{}", scheme, host, port)
+    }
+    
+    /// Dirección en la que escucha el servidor. `localhost` equivale a
+    /// 127.0.0.1; `0.0.0.0` escucha en todas las interfaces.
+    pub fn bind_ip(&self) -> BridgeResult<IpAddr> {
+        parse_host(&self.host)
+    }
+    
+    /// Si `host` o algún listener escucha fuera de loopback
+    pub fn listens_beyond_loopback(&self) -> bool {
+        let exposed = |ip: BridgeResult<IpAddr>| ip.is_ok_and(|ip| !ip.is_loopback());
+        exposed(self.bind_ip()) || self.listeners.iter().any(|listener| exposed(listener.bind_ip()))
+    }
+    
+    /// Si hay algún token configurado; sin ninguno el bridge no pide autenticación
+    pub fn has_credentials(&self) -> bool {
+        self.api_token.is_some() || self.admin_token.is_some() || !self.tokens.is_empty() || self.jwt.is_some()
+    }
+    
+    /// `port` seguido de los puertos alternativos, sin repetir
+    pub fn candidate_ports(&self) -> Vec<u16> {
+        let fallbacks: Vec<u16> = if self.fallback_ports.is_empty() {
+            (1..=self.port_fallback_count)
+                .filter_map(|offset| self.port.checked_add(offset))
+                .collect()
+        } else {
+            self.fallback_ports.clone()
+        };
+        
+        let mut ports = vec![self.port];
+        for port in fallbacks {
+            if !ports.contains(&port) {
+                ports.push(port);
+            }
+        }
+        ports
+    }
+    
+    /// Si pasar de `self` a `other` cambia algo que solo se aplica al
+    /// reiniciar el servidor, o uno de los tokens principales
+    pub fn server_settings_changed(&self, other: &Config) -> bool {
+        self.changed_fields(other)
+            .iter()
+            .any(|field| RESTART_FIELDS.contains(&field.as_str()) || matches!(field.as_str(), "api_token" | "admin_token"))
+    }
+    
+    /// Claves de primer nivel cuyo valor difiere entre `self` y `other`
+    pub fn changed_fields(&self, other: &Config) -> Vec<String> {
+        let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+            (serde_json::to_value(self), serde_json::to_value(other))
+        else {
+            return Vec::new();
+        };
+        let mut fields: Vec<String> = before
+            .iter()
+            .filter(|(key, value)| after.get(key.as_str()) != Some(value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        fields.sort();
+        fields
+    }
+    
+    /// Copia para mostrar: tokens y secretos sustituidos por `***`
+    pub fn redacted(&self) -> Config {
+        const REDACTED: &str = "***";
+        let mut config = self.clone();
+        if config.api_token.is_some() {
+            config.api_token = Some(REDACTED.to_string());
+        }
+        if config.admin_token.is_some() {
+            config.admin_token = Some(REDACTED.to_string());
+        }
+        for token in &mut config.tokens {
+            token.token = REDACTED.to_string();
+        }
+        if config.request_signing_secret.is_some() {
+            config.request_signing_secret = Some(REDACTED.to_string());
+        }
+        if let Some(secret) = config.jwt.as_mut().and_then(|jwt| jwt.secret.as_mut()) {
+            *secret = REDACTED.to_string();
+        }
+        config
+    }
+    
+    /// SHA-256 de la configuración en uso, para detectar equipos con una
+    /// configuración distinta de la esperada. Pasa por `serde_json::Value`,
+    /// que ordena las claves de los `HashMap`. Los secretos se dejan fuera:
+    /// el hash se publica y con él se podría comprobar un token adivinado.
+    pub fn content_hash(&self) -> String {
+        let mut public = self.clone();
+        public.api_token = None;
+        public.admin_token = None;
+        public.request_signing_secret = None;
+        for token in &mut public.tokens {
+            token.token.clear();
+        }
+        if let Some(jwt) = &mut public.jwt {
+            jwt.secret = None;
+        }
+        let value = serde_json::to_value(&public).unwrap_or_default();
+        format!("{:x}", Sha256::digest(value.to_string().as_bytes()))
+    }
+    
+    /// Traduce un alias a la cola real; los nombres sin alias pasan tal cual.
+    /// Un alias con URI se resuelve a la cola creada para esa impresora de red.
+    pub fn resolve_printer<'a>(&'a self, name: &'a str) -> BridgeResult<&'a str> {
+        let Some(target) = self.printer_aliases.get(name) else {
+            return Ok(name);
+        };
+        
+        if !target.contains("://") {
+            return Ok(target);
+        }
+        
+        self.network_printers
+            .iter()
+            .find(|printer| printer.uri == *target)
+            .map(|printer| printer.name.as_str())
+            .ok_or_else(|| BridgeError::ConfigError(format!(
+                "El alias '{}' apunta a {}, que no tiene cola; añádela desde el descubrimiento",
+                name, target
+            )))
+    }
+    
+    /// Colas a probar para `name`: la suya y luego las de respaldo, sin repetir
+    pub fn printer_chain(&self, name: &str) -> BridgeResult<Vec<String>> {
+        let mut chain = vec![self.resolve_printer(name)?.to_string()];
+        for fallback in self.printer_fallbacks.get(name).into_iter().flatten() {
+            let queue = self.resolve_printer(fallback)?;
+            if !chain.iter().any(|existing| existing == queue) {
+                chain.push(queue.to_string());
+            }
+        }
+        Ok(chain)
+    }
+    
+    /// Resolución de `queue` si es una impresora de etiquetas ZPL, indicada
+    /// por su nombre o por cualquiera de sus alias
+    pub fn zpl_dpi(&self, queue: &str) -> Option<u32> {
+        self.labels
+            .zpl_printers
+            .iter()
+            .find(|(name, _)| self.resolve_printer(name).ok() == Some(queue))
+            .map(|(_, dpi)| *dpi)
+    }
+    
+    /// Alias que apuntan a `queue`, en orden alfabético
+    pub fn aliases_for(&self, queue: &str) -> Vec<String> {
+        let mut aliases: Vec<String> = self.printer_aliases
+            .keys()
+            .filter(|alias| self.resolve_printer(alias).ok() == Some(queue))
+            .cloned()
+            .collect();
+        aliases.sort();
+        aliases
+    }
+    
+    /// Bytes que puede ocupar un documento de `content_type`, ya decodificado
+    pub fn max_file_size(&self, content_type: &str) -> u64 {
+        let mb = self.max_file_size_mb_by_type
+            .get(content_type)
+            .copied()
+            .unwrap_or(self.max_file_size_mb);
+        mb * 1024 * 1024
+    }
+    
+    /// El mayor de los límites, para lo que se recibe antes de saber el tipo
+    pub fn largest_file_size(&self) -> u64 {
+        let mb = self.max_file_size_mb_by_type
+            .values()
+            .copied()
+            .fold(self.max_file_size_mb, u64::max);
+        mb * 1024 * 1024
+    }
+    
+    /// Límite para consultas a CUPS (lpstat, lpoptions)
+    pub fn query_timeout(&self) -> Duration {
+        Duration::from_secs(self.query_timeout_secs)
+    }
+    
+    /// Duración de la búsqueda mDNS
+    pub fn discovery_timeout(&self) -> Duration {
+        Duration::from_secs(self.discovery_timeout_secs)
+    }
+    
+    /// Límite para entregar un trabajo a `lp`
+    pub fn spool_timeout(&self) -> Duration {
+        Duration::from_secs(self.spool_timeout_secs)
+    }
+    
+    /// Límite para convertir HTML a PDF
+    pub fn render_timeout(&self) -> Duration {
+        Duration::from_secs(self.render_timeout_secs)
+    }
+}
+
+/// Campos que el servidor solo lee al arrancar: dónde escucha, TLS, CORS, el
+/// tamaño máximo del cuerpo y el registro. El resto se aplica en caliente.
+pub const RESTART_FIELDS: &[&str] = &[
+    "host",
+    "port",
+    "fallback_ports",
+    "port_fallback_count",
+    "ipc_path",
+    "listeners",
+    "tls_enabled",
+    "tls_cert_path",
+    "tls_key_path",
+    "allowed_origins",
+    "cors",
+    "max_file_size_mb",
+    "max_file_size_mb_by_type",
+    "logging",
+];
+
+/// IP de `host` o de un listener. `localhost` equivale a 127.0.0.1.
+fn parse_host(host: &str) -> BridgeResult<IpAddr> {
+    if host.eq_ignore_ascii_case("localhost") {
+        return Ok(Ipv4Addr::LOCALHOST.into());
+    }
+    host.parse().map_err(|_| BridgeError::ConfigError(format!(
+        "host inválido: '{}'; debe ser una IP, p. ej. 127.0.0.1 o 0.0.0.0",
+        host
+    )))
+}
+
+/// Ruta elegida con `--config`, si la hay
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Archivo que versiones anteriores leían del directorio de trabajo
+const LEGACY_CONFIG_FILE: &str = "print-my-bridge.toml";
+
+/// Usa `path` en lugar del archivo de la plataforma. Hay que llamarla antes
+/// de la primera lectura; después no cambia nada.
+pub fn set_config_path(path: PathBuf) {
+    if CONFIG_PATH.set(path).is_err() {
+        log::warn!("⚠️ La ruta de configuración ya estaba fijada, se ignora la nueva");
+    }
+}
+
+/// Archivo de configuración en uso: el de `--config` o
+/// `<config de la plataforma>/print-my-bridge/config.toml`
+pub fn config_path() -> PathBuf {
+    CONFIG_PATH.get().cloned().unwrap_or_else(|| {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("print-my-bridge")
+            .join("config.toml")
+    })
+}
+
 pub fn load_config() -> BridgeResult<Config> {
-    let config_path = "print-my-bridge.toml";
+    let config_path = config_path();
     
-    if Path::new(config_path).exists() {
-        let config_str = fs::read_to_string(config_path)?;
-        let config: Config = toml::from_str(&config_str)
+    if !config_path.exists() && CONFIG_PATH.get().is_none() {
+        migrate_legacy_config(&config_path)?;
+    }
+    
+    if config_path.exists() {
+        let config_str = fs::read_to_string(&config_path)?;
+        let mut table: toml::Table = toml::from_str(&config_str)
+            .map_err(|e| crate::error::BridgeError::ConfigError(e.to_string()))?;
+        let from = migrate::migrate(&mut table)?;
+        let mut config = Config::deserialize(toml::Value::Table(table))
             .map_err(|e| crate::error::BridgeError::ConfigError(e.to_string()))?;
-        log::info!("📄 Configuración cargada desde {}", config_path);
+        let plaintext = secrets::has_plaintext(&config);
+        secrets::resolve(&mut config)?;
+        log::info!("📄 Configuración cargada desde {}", config_path.display());
+        
+        if from < CONFIG_VERSION {
+            // Se conserva el original por si hay que volver a un bridge anterior
+            let backup = config_path.with_extension(format!("v{}.toml.bak", from));
+            fs::copy(&config_path, &backup)?;
+            save_config(&config)?;
+            log::info!("📦 Archivo actualizado a la versión {}; el anterior queda en {}", CONFIG_VERSION, backup.display());
+        } else if plaintext {
+            // Solo se reescribe si el llavero los aceptó; si no, cada recarga
+            // volvería a escribir el archivo y a disparar otra
+            let stored = secrets::externalize(&config);
+            if !secrets::has_plaintext(&stored) {
+                write_config(&stored)?;
+                log::info!("🔐 Tokens movidos del archivo al llavero del sistema");
+            }
+        }
         Ok(config)
     } else {
         let config = Config::default();
         save_config(&config)?;
-        log::info!("📄 Configuración por defecto creada en {}", config_path);
+        log::info!("📄 Configuración por defecto creada en {}", config_path.display());
         Ok(config)
     }
 }
 
+/// Guarda `config`; con `keyring` activo los tokens van al llavero
 pub fn save_config(config: &Config) -> BridgeResult<()> {
+    write_config(&secrets::externalize(config))
+}
+
+fn write_config(config: &Config) -> BridgeResult<()> {
     let config_str = toml::to_string_pretty(config)
         .map_err(|e| crate::error::BridgeError::ConfigError(e.to_string()))?;
-    fs::write("print-my-bridge.toml", config_str)?;
+    let config_path = config_path();
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(config_path, config_str)?;
+    Ok(())
+}
+
+/// Mueve el `print-my-bridge.toml` del directorio de trabajo, si lo hay, al
+/// archivo de la plataforma. Antes cada forma de lanzar la app (GUI, terminal)
+/// podía leer uno distinto.
+fn migrate_legacy_config(target: &Path) -> BridgeResult<()> {
+    let legacy = Path::new(LEGACY_CONFIG_FILE);
+    if !legacy.is_file() {
+        return Ok(());
+    }
+    
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(legacy, target)?;
+    log::info!("📦 Configuración migrada de {} a {}", legacy.display(), target.display());
+    if let Err(e) = fs::remove_file(legacy) {
+        log::warn!("⚠️ No se pudo borrar {}: {}", legacy.display(), e);
+    }
     Ok(())
 }
 
+/// Directorio de datos de la aplicación (certificados, historial, etc.)
+pub fn app_data_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("print-my-bridge")
+}
+
 pub fn generate_secure_token() -> String {
     use rand::Rng;
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
@@ -78,4 +1039,20 @@ pub fn generate_secure_token() -> String {
             CHARSET[idx] as char
         })
         .collect()
+}
+
+pub fn generate_token_id() -> String {
+    generate_id("tok")
+}
+
+/// Identificador corto y legible con prefijo, p. ej. `batch_k3x9...`
+pub fn generate_id(prefix: &str) -> String {
+    use rand::Rng;
+    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = rand::thread_rng();
+    
+    let suffix: String = (0..12)
+        .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+        .collect();
+    format!("{}_{}", prefix, suffix)
 }
\ No newline at end of file
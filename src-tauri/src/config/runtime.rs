@@ -0,0 +1,55 @@
+use super::app_data_dir;
+use crate::error::BridgeResult;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Dónde escucha de verdad la instancia en marcha. Puede no coincidir con
+/// `port` si estaba ocupado y se usó un puerto alternativo; lo leen los
+/// scripts y la GUI en lugar de suponer el de la configuración.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeState {
+    pub pid: u32,
+    pub port: u16,
+    pub tls: bool,
+    /// RFC 3339 en hora local
+    pub started_at: String,
+    /// Puerto configurado, si se tuvo que usar otro
+    pub configured_port: Option<u16>,
+}
+
+impl RuntimeState {
+    pub fn new(port: u16, tls: bool, configured_port: u16) -> Self {
+        Self {
+            pid: std::process::id(),
+            port,
+            tls,
+            started_at: chrono::Local::now().to_rfc3339(),
+            configured_port: (port != configured_port).then_some(configured_port),
+        }
+    }
+
+    pub fn save(&self) -> BridgeResult<()> {
+        std::fs::create_dir_all(app_data_dir())?;
+        let json = serde_json::to_vec_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path(), json)?;
+        Ok(())
+    }
+
+    /// Estado que dejó la última instancia; puede estar desfasado si terminó
+    /// sin limpiar
+    pub fn load() -> Option<Self> {
+        let json = std::fs::read(path()).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Lo borra al detenerse el servidor, solo si es de este proceso
+    pub fn clear() {
+        if Self::load().is_some_and(|state| state.pid == std::process::id()) {
+            let _ = std::fs::remove_file(path());
+        }
+    }
+}
+
+fn path() -> PathBuf {
+    app_data_dir().join("runtime.json")
+}
@@ -0,0 +1,68 @@
+use super::Config;
+use crate::error::{BridgeError, BridgeResult};
+
+/// Servicio con que se guardan las entradas en el llavero del sistema
+const SERVICE: &str = "print-my-bridge";
+
+/// Lo que queda en el TOML en lugar del secreto: `keyring:<entrada>`
+const REFERENCE_PREFIX: &str = "keyring:";
+
+/// Campos del archivo que se guardan en el llavero, con su entrada
+fn secret_fields(config: &mut Config) -> [(&'static str, &mut Option<String>); 2] {
+    [("api_token", &mut config.api_token), ("admin_token", &mut config.admin_token)]
+}
+
+/// Cambia las referencias al llavero por el secreto. Sin el secreto no se
+/// carga la configuración: seguir sin token dejaría el bridge abierto.
+pub fn resolve(config: &mut Config) -> BridgeResult<()> {
+    for (field, value) in secret_fields(config) {
+        let Some(entry) = value.as_deref().and_then(|value| value.strip_prefix(REFERENCE_PREFIX)) else {
+            continue;
+        };
+        let secret = keyring::Entry::new(SERVICE, entry)
+            .and_then(|entry| entry.get_password())
+            .map_err(|e| BridgeError::ConfigError(format!("No se pudo leer {} del llavero del sistema: {}", field, e)))?;
+        *value = Some(secret);
+    }
+    Ok(())
+}
+
+/// Si algún secreto está en claro en `config` tal como se leyó del archivo y
+/// debería ir al llavero
+pub fn has_plaintext(config: &Config) -> bool {
+    config.keyring
+        && [&config.api_token, &config.admin_token]
+            .into_iter()
+            .flatten()
+            .any(|value| !value.starts_with(REFERENCE_PREFIX))
+}
+
+/// Copia para escribir en disco: con `keyring` activo los secretos van al
+/// llavero y en el archivo queda la referencia. Si el llavero no está
+/// disponible (p. ej. un servidor sin sesión gráfica) se quedan en claro.
+pub fn externalize(config: &Config) -> Config {
+    let mut stored = config.clone();
+    if !stored.keyring {
+        return stored;
+    }
+
+    for (field, value) in secret_fields(&mut stored) {
+        let Some(secret) = value.as_deref().filter(|value| !value.starts_with(REFERENCE_PREFIX)) else {
+            continue;
+        };
+        match store(field, secret) {
+            Ok(()) => *value = Some(format!("{}{}", REFERENCE_PREFIX, field)),
+            Err(e) => log::warn!("⚠️ No se pudo guardar {} en el llavero, queda en el archivo: {}", field, e),
+        }
+    }
+    stored
+}
+
+fn store(field: &str, secret: &str) -> keyring::Result<()> {
+    let entry = keyring::Entry::new(SERVICE, field)?;
+    // Escribir solo si cambia: en macOS cada escritura puede pedir permiso
+    if entry.get_password().ok().as_deref() == Some(secret) {
+        return Ok(());
+    }
+    entry.set_password(secret)
+}
@@ -0,0 +1,409 @@
+use super::{migrate, Config, ListenerAuth, ResourcePolicy, CONFIG_VERSION};
+use crate::api::PrinterInfo;
+use crate::error::BridgeResult;
+use crate::printer::{check_header_footer, PrinterManager};
+use crate::templates;
+use serde::Serialize;
+use std::path::Path;
+
+/// Tipos de contenido que admiten `allowed_file_types` y `max_file_size_mb_by_type`
+const CONTENT_TYPES: [&str; 12] = ["pdf", "html", "markdown", "text", "image", "svg", "docx", "odt", "xlsx", "template", "label", "raw"];
+
+/// Métodos que admiten `cors.methods` y `cors.route_methods`
+const STANDARD_METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// El bridge no arranca, o falla en cuanto se use el campo
+    Error,
+    /// Arranca, pero probablemente no hace lo que se espera
+    Warning,
+}
+
+/// Un problema de la configuración y el campo donde está
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Ruta del campo (`port`, `presets.ticket.printer`); vacía si es del archivo
+    pub field: String,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, field: field.into(), message: message.into() }
+    }
+
+    fn warning(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, field: field.into(), message: message.into() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub path: String,
+    /// Sin errores; puede tener avisos
+    pub valid: bool,
+    pub findings: Vec<Finding>,
+}
+
+/// Revisa el archivo `path` sin aplicarlo: sintaxis, claves desconocidas,
+/// valores que no encajan entre sí e impresoras que no existen
+pub async fn validate_file(path: &Path) -> BridgeResult<ValidationReport> {
+    let text = tokio::fs::read_to_string(path).await?;
+    let (config, mut findings) = check_text(&text);
+
+    if let Some(config) = &config {
+        match PrinterManager::get_available_printers(config).await {
+            Ok(printers) => findings.extend(check_printers(config, &printers)),
+            Err(e) => findings.push(Finding::warning(
+                "",
+                format!("No se pudieron consultar las impresoras para comprobar las referencias: {}", e),
+            )),
+        }
+    }
+
+    Ok(ValidationReport {
+        path: path.display().to_string(),
+        valid: !findings.iter().any(|finding| finding.severity == Severity::Error),
+        findings,
+    })
+}
+
+/// Lo que se puede comprobar sin consultar el sistema. Devuelve la
+/// configuración si el archivo se puede cargar.
+pub fn check_text(text: &str) -> (Option<Config>, Vec<Finding>) {
+    let mut findings = Vec::new();
+
+    let mut table: toml::Table = match toml::from_str(text) {
+        Ok(table) => table,
+        Err(e) => {
+            findings.push(Finding::error("", e.to_string().trim_end()));
+            return (None, findings);
+        }
+    };
+    // Las claves renombradas se comprueban ya con su nombre nuevo
+    match migrate::migrate(&mut table) {
+        Ok(from) if from < CONFIG_VERSION => findings.push(Finding::warning(
+            "version",
+            format!("Archivo de la versión {}; se migrará a la {} al cargarlo", from, CONFIG_VERSION),
+        )),
+        Ok(_) => {}
+        Err(e) => {
+            findings.push(Finding::error("version", e.to_string()));
+            return (None, findings);
+        }
+    }
+
+    // Las claves que serde descarta suelen ser erratas o campos renombrados
+    let config: Result<Config, _> = serde_ignored::deserialize(toml::Value::Table(table), |path| {
+        findings.push(Finding::warning(path.to_string(), "Clave desconocida, se ignora"));
+    });
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            findings.push(Finding::error("", e.to_string().trim_end()));
+            return (None, findings);
+        }
+    };
+
+    findings.extend(check_config(&config));
+    (Some(config), findings)
+}
+
+/// Valores válidos por separado pero que no funcionan, o se contradicen
+pub fn check_config(config: &Config) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for (index, origin) in config.allowed_origins.iter().enumerate() {
+        let field = format!("allowed_origins[{}]", index);
+        if origin == "*" {
+            if config.allowed_origins.len() > 1 {
+                findings.push(Finding::warning(field, "Con \"*\" el resto de orígenes sobra"));
+            }
+            continue;
+        }
+        // warp entra en pánico al arrancar con un origen que no sea esquema + host
+        match reqwest::Url::parse(origin) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() && url.path() == "/" && url.query().is_none() => {
+                if origin.ends_with('/') {
+                    findings.push(Finding::error(field, format!("Sobra la barra final: {}", origin.trim_end_matches('/'))));
+                }
+            }
+            _ => findings.push(Finding::error(
+                field,
+                format!("'{}' no es un origen; debe ser esquema y host, p. ej. https://pos.ejemplo.com", origin),
+            )),
+        }
+    }
+
+    if config.cors.allow_credentials && config.allowed_origins.iter().any(|origin| origin == "*") {
+        findings.push(Finding::warning(
+            "cors.allow_credentials",
+            "Con allowed_origins = [\"*\"] cualquier web puede enviar peticiones con credenciales",
+        ));
+    }
+    let route_methods = config.cors.route_methods.iter().flat_map(|(prefix, methods)| {
+        methods.iter().enumerate().map(move |(index, method)| (format!("cors.route_methods.\"{}\"[{}]", prefix, index), method))
+    });
+    let methods = config.cors.methods.iter().enumerate().map(|(index, method)| (format!("cors.methods[{}]", index), method));
+    for (field, method) in methods.chain(route_methods) {
+        if !STANDARD_METHODS.contains(&method.to_ascii_uppercase().as_str()) {
+            findings.push(Finding::error(field, format!("Método HTTP desconocido '{}'", method)));
+        }
+    }
+    for prefix in config.cors.route_methods.keys().filter(|prefix| !prefix.starts_with('/')) {
+        findings.push(Finding::error(format!("cors.route_methods.\"{}\"", prefix), "La ruta debe empezar por /, p. ej. /api/print"));
+    }
+
+    match config.bind_ip() {
+        Ok(ip) if !ip.is_loopback() && !config.has_credentials() => {
+            findings.push(Finding::error("host", format!("Para escuchar en {} hace falta configurar api_token", ip)));
+        }
+        Ok(ip) if !ip.is_loopback() => {
+            findings.push(Finding::warning("host", format!("Con {} el bridge es accesible desde otros equipos de la red", ip)));
+        }
+        Ok(_) => {}
+        Err(e) => findings.push(Finding::error("host", e.to_string())),
+    }
+    for (index, listener) in config.listeners.iter().enumerate() {
+        let field = format!("listeners[{}]", index);
+        match listener.bind_ip() {
+            Ok(ip) if !ip.is_loopback() && listener.auth == ListenerAuth::None => {
+                findings.push(Finding::error(format!("{}.auth", field), format!("auth = \"none\" solo se permite en loopback, no en {}", ip)));
+            }
+            Ok(ip) if !ip.is_loopback() && !config.has_credentials() => {
+                findings.push(Finding::error(format!("{}.host", field), format!("Para escuchar en {} hace falta configurar api_token", ip)));
+            }
+            Ok(ip) if !ip.is_loopback() && !listener.tls => {
+                findings.push(Finding::warning(format!("{}.tls", field), format!("Los tokens viajan sin cifrar por la red en {}", ip)));
+            }
+            Ok(_) => {}
+            Err(e) => findings.push(Finding::error(format!("{}.host", field), e.to_string())),
+        }
+        if listener.port == 0 {
+            findings.push(Finding::warning(format!("{}.port", field), "Con 0 el sistema elige un puerto distinto en cada arranque"));
+        }
+        if config.listeners[..index].iter().any(|other| other.host == listener.host && other.port == listener.port)
+            || (listener.host == config.host && config.candidate_ports().contains(&listener.port))
+        {
+            findings.push(Finding::error(format!("{}.port", field), format!("{}:{} ya se usa en otro listener", listener.host, listener.port)));
+        }
+    }
+    if config.port == 0 {
+        findings.push(Finding::warning("port", "Con 0 el sistema elige un puerto distinto en cada arranque"));
+    }
+    if config.fallback_ports.contains(&config.port) {
+        findings.push(Finding::warning("fallback_ports", "Incluye el propio `port`"));
+    }
+
+    // Los listeners con TLS usan el mismo certificado
+    let tls = config.tls_enabled || config.listeners.iter().any(|listener| listener.tls);
+    if tls && config.tls_cert_path.is_some() != config.tls_key_path.is_some() {
+        findings.push(Finding::error("tls_cert_path", "tls_cert_path y tls_key_path deben configurarse juntos"));
+    }
+    if tls {
+        for (field, path) in [("tls_cert_path", &config.tls_cert_path), ("tls_key_path", &config.tls_key_path)] {
+            if let Some(path) = path.as_deref().filter(|path| !Path::new(path).is_file()) {
+                findings.push(Finding::error(field, format!("No existe el archivo {}", path)));
+            }
+        }
+    }
+
+    if let Some(jwt) = &config.jwt {
+        if jwt.secret.is_none() && jwt.jwks_url.is_none() {
+            findings.push(Finding::error("jwt", "Hace falta jwt.secret o jwt.jwks_url para verificar los tokens"));
+        }
+        if jwt.secret.as_ref().is_some_and(|secret| secret.len() < 32) {
+            findings.push(Finding::warning("jwt.secret", "Menos de 32 caracteres; un secreto corto se puede adivinar"));
+        }
+        if let Some(url) = &jwt.jwks_url {
+            match reqwest::Url::parse(url) {
+                Ok(url) if url.scheme() == "https" => {}
+                Ok(_) => findings.push(Finding::warning("jwt.jwks_url", "Sin HTTPS las claves se pueden suplantar")),
+                Err(e) => findings.push(Finding::error("jwt.jwks_url", format!("URL inválida: {}", e))),
+            }
+        }
+    }
+
+    if config.request_signing_secret.as_ref().is_some_and(|secret| secret.len() < 32) {
+        findings.push(Finding::warning("request_signing_secret", "Menos de 32 caracteres; un secreto corto se puede adivinar"));
+    }
+    if config.request_signing_secret.is_some() && config.request_signing_window_secs == 0 {
+        findings.push(Finding::error("request_signing_window_secs", "Con 0 casi ninguna firma llega a tiempo"));
+    }
+
+    if config.max_file_size_mb == 0 {
+        findings.push(Finding::error("max_file_size_mb", "Con 0 se rechaza cualquier documento"));
+    }
+    for (content_type, mb) in &config.max_file_size_mb_by_type {
+        let field = format!("max_file_size_mb_by_type.{}", content_type);
+        if !CONTENT_TYPES.contains(&content_type.as_str()) {
+            findings.push(Finding::warning(field, format!("Tipo desconocido; los válidos son {}", CONTENT_TYPES.join(", "))));
+        } else if *mb == 0 {
+            findings.push(Finding::error(field, "Con 0 se rechaza cualquier documento de este tipo"));
+        }
+    }
+    if config.spool_quota_mb > 0 && config.spool_quota_mb < config.largest_file_size() / (1024 * 1024) {
+        findings.push(Finding::warning(
+            "spool_quota_mb",
+            "Es menor que el tamaño máximo de documento; los más grandes no caben en el spool",
+        ));
+    }
+
+    for (index, content_type) in config.allowed_file_types.iter().enumerate() {
+        if !CONTENT_TYPES.contains(&content_type.as_str()) {
+            findings.push(Finding::warning(
+                format!("allowed_file_types[{}]", index),
+                format!("Tipo desconocido '{}'; los válidos son {}", content_type, CONTENT_TYPES.join(", ")),
+            ));
+        }
+    }
+
+    if config.retry.max_attempts == 0 {
+        findings.push(Finding::warning("retry.max_attempts", "Cuenta el primer intento; 0 equivale a 1"));
+    }
+    if config.retry.backoff_ms > config.retry.max_backoff_ms {
+        findings.push(Finding::warning("retry.backoff_ms", "Es mayor que retry.max_backoff_ms, que lo recorta"));
+    }
+    if config.job_history_days > 0
+        && config.history_archive_days > 0
+        && config.history_archive_days >= config.job_history_days
+    {
+        findings.push(Finding::warning(
+            "history_archive_days",
+            "Los trabajos se borran (job_history_days) antes de llegar a archivarse",
+        ));
+    }
+
+    if config.render.resources == ResourcePolicy::Allowlist && config.render.resource_hosts.is_empty() {
+        findings.push(Finding::warning("render.resource_hosts", "Lista vacía; equivale a resources = \"block\""));
+    }
+    for (index, host) in config.render.resource_hosts.iter().enumerate() {
+        if host.contains("://") || host.contains('/') {
+            findings.push(Finding::error(format!("render.resource_hosts[{}]", index), format!("'{}' debe ser solo el host, sin esquema ni ruta", host)));
+        }
+    }
+    if !(72..=1200).contains(&config.render.svg_dpi) {
+        findings.push(Finding::error("render.svg_dpi", "Debe estar entre 72 y 1200"));
+    }
+
+    let templates = [("header_footer.header", &config.header_footer.header), ("header_footer.footer", &config.header_footer.footer)];
+    for (field, template) in templates {
+        let Some(template) = template else {
+            continue;
+        };
+        if let Err(message) = check_header_footer(template) {
+            findings.push(Finding::error(field, message));
+        } else if template.contains("{business}") && config.header_footer.business_name.is_empty() {
+            findings.push(Finding::warning(field, "Usa {business} pero header_footer.business_name está vacío"));
+        }
+    }
+
+    for (name, preset) in &config.presets {
+        if preset.copies == Some(0) {
+            findings.push(Finding::error(format!("presets.{}.copies", name), "Debe ser al menos 1"));
+        }
+        for problem in preset.options.problems() {
+            findings.push(Finding::error(format!("presets.{}.{}", name, problem.field), problem.message));
+        }
+    }
+
+    for (name, template) in &config.templates {
+        if let Err(message) = templates::check(template) {
+            findings.push(Finding::error(format!("templates.{}", name), message));
+        }
+        if let Some(preset) = template.preset.as_deref().filter(|preset| !config.presets.contains_key(*preset)) {
+            findings.push(Finding::error(format!("templates.{}.preset", name), format!("Preajuste desconocido: {}", preset)));
+        }
+    }
+    if !config.templates.is_empty() && !config.allowed_file_types.iter().any(|content_type| content_type == "template") {
+        findings.push(Finding::warning("allowed_file_types", "Hay plantillas pero \"template\" no está permitido"));
+    }
+    for (name, layout) in &config.labels.layouts {
+        for message in templates::check_label(layout) {
+            findings.push(Finding::error(format!("labels.layouts.{}", name), message));
+        }
+    }
+    if !config.labels.layouts.is_empty() && !config.allowed_file_types.iter().any(|content_type| content_type == "label") {
+        findings.push(Finding::warning("allowed_file_types", "Hay etiquetas pero \"label\" no está permitido"));
+    }
+    for (printer, dpi) in &config.labels.zpl_printers {
+        if !matches!(dpi, 150..=600) {
+            findings.push(Finding::error(format!("labels.zpl_printers.{}", printer), "La resolución debe estar entre 150 y 600 ppp"));
+        }
+    }
+    if !matches!(config.pos.drawer_pin, 2 | 5) {
+        findings.push(Finding::error("pos.drawer_pin", "El cajón va en el conector 2 o en el 5"));
+    }
+    if !(2..=510).contains(&config.pos.drawer_pulse_ms) {
+        findings.push(Finding::error("pos.drawer_pulse_ms", "Debe estar entre 2 y 510 ms"));
+    }
+
+    if let Err(e) = tracing_subscriber::EnvFilter::try_new(&config.logging.level) {
+        findings.push(Finding::error("logging.level", format!("Filtro inválido: {}", e)));
+    }
+
+    for (name, pool) in &config.printer_pools {
+        if pool.printers.is_empty() {
+            findings.push(Finding::error(format!("printer_pools.{}.printers", name), "El grupo no tiene impresoras"));
+        }
+    }
+
+    findings
+}
+
+/// Referencias a impresoras (predeterminada, alias, respaldos, grupos,
+/// preajustes y listas de los tokens) que no llevan a ninguna cola de `printers`
+pub fn check_printers(config: &Config, printers: &[PrinterInfo]) -> Vec<Finding> {
+    let mut references: Vec<(String, &str)> = Vec::new();
+    if let Some(printer) = &config.default_printer {
+        references.push(("default_printer".to_string(), printer));
+    }
+    for (alias, printers) in &config.printer_fallbacks {
+        references.push((format!("printer_fallbacks.{}", alias), alias));
+        for (index, printer) in printers.iter().enumerate() {
+            references.push((format!("printer_fallbacks.{}[{}]", alias, index), printer));
+        }
+    }
+    for (name, pool) in &config.printer_pools {
+        for (index, printer) in pool.printers.iter().enumerate() {
+            references.push((format!("printer_pools.{}.printers[{}]", name, index), printer));
+        }
+    }
+    for (name, preset) in &config.presets {
+        if let Some(printer) = &preset.printer {
+            references.push((format!("presets.{}.printer", name), printer));
+        }
+    }
+    for printer in config.labels.zpl_printers.keys() {
+        references.push((format!("labels.zpl_printers.{}", printer), printer));
+    }
+    for alias in config.printer_aliases.keys() {
+        references.push((format!("printer_aliases.{}", alias), alias));
+    }
+    for (index, token) in config.tokens.iter().enumerate() {
+        for (printer_index, printer) in token.printers.iter().flatten().enumerate() {
+            references.push((format!("tokens[{}].printers[{}]", index, printer_index), printer));
+        }
+    }
+
+    let mut findings = Vec::new();
+    for (field, name) in references {
+        // Un grupo se resuelve a sus miembros, que se comprueban aparte
+        if config.printer_pools.contains_key(name) {
+            continue;
+        }
+        match config.resolve_printer(name) {
+            Ok(queue) if !printers.iter().any(|printer| printer.name == queue) => {
+                findings.push(Finding::warning(field, format!("La impresora '{}' no existe en el sistema", queue)));
+            }
+            Ok(_) => {}
+            Err(e) => findings.push(Finding::error(field, e.to_string())),
+        }
+    }
+    findings.sort_by(|a, b| a.field.cmp(&b.field));
+    findings.dedup_by(|a, b| a.field == b.field);
+    findings
+}
@@ -0,0 +1,310 @@
+use crate::api::PrintRequest;
+use crate::config::Config;
+use crate::metrics::Metrics;
+use crate::queue::JobQueue;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
+
+/// Función que muestra una notificación de escritorio (título, cuerpo)
+pub type DesktopNotifier = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+/// Momento en que arrancó el bridge
+#[derive(Clone, Copy)]
+struct Started {
+    at: SystemTime,
+    instant: Instant,
+}
+
+impl Default for Started {
+    fn default() -> Self {
+        Self {
+            at: SystemTime::now(),
+            instant: Instant::now(),
+        }
+    }
+}
+
+/// Estado del servidor HTTP tal como lo publica la tarea que lo ejecuta
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ServerState {
+    Starting,
+    Running { address: SocketAddr, tls: bool },
+    /// Ya no acepta conexiones; espera a los trabajos en curso para salir
+    Draining,
+    /// No llegó a escuchar o terminó con error (p. ej. puerto en uso)
+    Failed { error: String },
+    Stopped,
+}
+
+impl ServerState {
+    pub fn is_running(&self) -> bool {
+        matches!(self, Self::Running { .. })
+    }
+}
+
+/// Canal del estado del servidor; `watch::Sender` no implementa `Default`
+#[derive(Clone)]
+struct ServerChannel(watch::Sender<ServerState>);
+
+impl Default for ServerChannel {
+    fn default() -> Self {
+        Self(watch::channel(ServerState::Starting).0)
+    }
+}
+
+/// Se pone a `true` una sola vez, al pedir el cierre del bridge
+#[derive(Clone)]
+struct ShutdownChannel(watch::Sender<bool>);
+
+impl Default for ShutdownChannel {
+    fn default() -> Self {
+        Self(watch::channel(false).0)
+    }
+}
+
+/// Cuenta las peticiones de reinicio del servidor; importa que cambie, no el valor
+#[derive(Clone)]
+struct RestartChannel(watch::Sender<u64>);
+
+impl Default for RestartChannel {
+    fn default() -> Self {
+        Self(watch::channel(0).0)
+    }
+}
+
+/// Resultado de la última recarga de la configuración desde el disco
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigReload {
+    /// RFC 3339 en hora local
+    pub at: String,
+    /// Campos aplicados en caliente
+    pub applied: Vec<String>,
+    /// Campos guardados que no cuentan hasta reiniciar el servidor
+    pub restart_required: Vec<String>,
+}
+
+/// Configuración en uso; el servidor la lee en cada solicitud
+#[derive(Clone)]
+struct ConfigChannel(watch::Sender<Arc<Config>>);
+
+impl Default for ConfigChannel {
+    fn default() -> Self {
+        Self(watch::channel(Arc::new(Config::default())).0)
+    }
+}
+
+/// Último fallo de un trabajo o del servidor, para el panel de la GUI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastError {
+    /// RFC 3339 en hora local
+    pub at: String,
+    pub message: String,
+    /// `X-Request-Id` del trabajo; `None` si no vino de una solicitud
+    pub request_id: Option<String>,
+}
+
+/// Estado compartido entre el servidor HTTP y la GUI (tray, comandos)
+#[derive(Clone, Default)]
+pub struct BridgeControl {
+    started: Started,
+    paused: Arc<AtomicBool>,
+    last_job: Arc<Mutex<Option<PrintRequest>>>,
+    last_error: Arc<Mutex<Option<LastError>>>,
+    desktop_notifier: Arc<Mutex<Option<DesktopNotifier>>>,
+    /// Avisos emitidos antes de que la GUI registrara su notificador
+    pending_notices: Arc<Mutex<Vec<(String, String)>>>,
+    pool_cursors: Arc<Mutex<HashMap<String, usize>>>,
+    jobs: JobQueue,
+    metrics: Metrics,
+    server: ServerChannel,
+    shutdown: ShutdownChannel,
+    restart: RestartChannel,
+    config: ConfigChannel,
+    last_reload: Arc<Mutex<Option<ConfigReload>>>,
+}
+
+impl BridgeControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn started_at(&self) -> SystemTime {
+        self.started.at
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.instant.elapsed()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+        if paused {
+            log::info!("⏸️ Bridge pausado");
+        } else {
+            log::info!("▶️ Bridge reanudado");
+        }
+    }
+
+    /// Recuerda la última solicitud impresa para poder reimprimirla
+    pub fn remember_job(&self, request: &PrintRequest) {
+        *self.last_job.lock().unwrap() = Some(request.clone());
+    }
+
+    pub fn last_job(&self) -> Option<PrintRequest> {
+        self.last_job.lock().unwrap().clone()
+    }
+
+    pub fn record_error(&self, request_id: Option<&str>, message: &str) {
+        *self.last_error.lock().unwrap() = Some(LastError {
+            at: chrono::Local::now().to_rfc3339(),
+            message: message.to_string(),
+            request_id: request_id.map(str::to_string),
+        });
+    }
+
+    pub fn last_error(&self) -> Option<LastError> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    /// Cola compartida por la API y la reimpresión desde el tray
+    pub fn jobs(&self) -> &JobQueue {
+        &self.jobs
+    }
+
+    /// Contadores de solicitudes y trabajos desde el arranque
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn server_state(&self) -> ServerState {
+        self.server.0.borrow().clone()
+    }
+
+    /// Puerto en que escucha el servidor; puede ser uno alternativo
+    pub fn server_port(&self) -> Option<u16> {
+        match *self.server.0.borrow() {
+            ServerState::Running { address, .. } => Some(address.port()),
+            _ => None,
+        }
+    }
+
+    /// Para esperar cambios de estado del servidor sin sondearlo
+    pub fn watch_server(&self) -> watch::Receiver<ServerState> {
+        self.server.0.subscribe()
+    }
+
+    /// Solo lo llama la tarea del servidor HTTP
+    pub fn set_server_state(&self, state: ServerState) {
+        self.server.0.send_replace(state);
+    }
+
+    /// Pide un cierre ordenado: el servidor deja de aceptar solicitudes y
+    /// espera a los trabajos en curso antes de terminar
+    pub fn request_shutdown(&self) {
+        if !self.shutdown.0.send_replace(true) {
+            log::info!("🛑 Cierre solicitado");
+        }
+    }
+
+    pub fn shutdown_requested(&self) -> bool {
+        *self.shutdown.0.borrow()
+    }
+
+    /// Se completa cuando se pide el cierre (o ya se había pedido)
+    pub fn shutdown_signal(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut shutdown = self.shutdown.0.subscribe();
+        async move {
+            let _ = shutdown.wait_for(|requested| *requested).await;
+        }
+    }
+
+    /// Configuración en uso, incluidos los cambios aplicados en caliente
+    pub fn config(&self) -> Arc<Config> {
+        self.config.0.borrow().clone()
+    }
+
+    pub fn set_config(&self, config: Config) {
+        self.config.0.send_replace(Arc::new(config));
+    }
+
+    /// Para seguir los cambios de configuración (p. ej. la lista de tokens)
+    pub fn watch_config(&self) -> watch::Receiver<Arc<Config>> {
+        self.config.0.subscribe()
+    }
+
+    pub fn record_reload(&self, reload: ConfigReload) {
+        *self.last_reload.lock().unwrap() = Some(reload);
+    }
+
+    pub fn clear_reload(&self) {
+        *self.last_reload.lock().unwrap() = None;
+    }
+
+    pub fn last_reload(&self) -> Option<ConfigReload> {
+        self.last_reload.lock().unwrap().clone()
+    }
+
+    /// Pide al supervisor que vuelva a arrancar el servidor con la
+    /// configuración del disco, sin cerrar la aplicación
+    pub fn request_restart(&self) {
+        log::info!("🔄 Reinicio del servidor solicitado");
+        self.restart.0.send_modify(|requests| *requests += 1);
+    }
+
+    /// Se completa con la próxima petición de reinicio
+    pub fn restart_signal(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut restart = self.restart.0.subscribe();
+        async move {
+            let _ = restart.changed().await;
+        }
+    }
+
+    /// Turno de la siguiente impresora de un grupo de `size` en reparto rotativo
+    pub fn next_in_pool(&self, pool: &str, size: usize) -> usize {
+        let mut cursors = self.pool_cursors.lock().unwrap();
+        let cursor = cursors.entry(pool.to_string()).or_default();
+        let turn = *cursor % size.max(1);
+        *cursor = turn + 1;
+        turn
+    }
+
+    /// La GUI registra aquí cómo mostrar notificaciones; en modo headless no hay
+    pub fn set_desktop_notifier(&self, notifier: DesktopNotifier) {
+        *self.desktop_notifier.lock().unwrap() = Some(notifier.clone());
+        for (title, body) in self.pending_notices.lock().unwrap().drain(..) {
+            notifier(&title, &body);
+        }
+    }
+
+    /// Como `notify_desktop`, pero si la GUI aún no arrancó el aviso se muestra
+    /// en cuanto lo haga
+    pub fn announce(&self, title: &str, body: &str) {
+        let mut pending = self.pending_notices.lock().unwrap();
+        if !self.notify_desktop(title, body) {
+            pending.push((title.to_string(), body.to_string()));
+        }
+    }
+
+    /// Devuelve `false` si no hay ninguna GUI que pueda mostrar la notificación
+    pub fn notify_desktop(&self, title: &str, body: &str) -> bool {
+        let notifier = self.desktop_notifier.lock().unwrap().clone();
+        match notifier {
+            Some(notifier) => {
+                notifier(title, body);
+                true
+            }
+            None => false,
+        }
+    }
+}
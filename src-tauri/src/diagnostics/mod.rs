@@ -0,0 +1,116 @@
+use crate::config::Config;
+use crate::error::{BridgeError, BridgeResult};
+use crate::logging::{self, LoggingConfig};
+use crate::printer::PrinterManager;
+use crate::tools;
+use chrono::Local;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// Archivos de log, los más recientes, que entran en el paquete
+const LOG_FILES: usize = 3;
+/// De cada archivo solo el final: lo que interesa es lo último que pasó
+const LOG_TAIL_BYTES: u64 = 5 * 1024 * 1024;
+
+#[derive(Serialize)]
+struct SystemInfo {
+    version: &'static str,
+    os: &'static str,
+    family: &'static str,
+    arch: &'static str,
+    generated_at: String,
+    mock_printers: bool,
+    test_queue: Option<String>,
+}
+
+/// Nombre con que se guarda el paquete, con la fecha y hora local
+pub fn file_name() -> String {
+    format!("print-my-bridge-diagnostics-{}.zip", Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+/// Paquete para adjuntar a una incidencia: versión y sistema, configuración
+/// sin secretos, impresoras, herramientas externas y el final de los logs
+/// más recientes, en un ZIP
+pub async fn export(config: &Config) -> BridgeResult<Vec<u8>> {
+    // Si CUPS no responde, eso también es un diagnóstico
+    let printers = match PrinterManager::get_available_printers(config).await {
+        Ok(printers) => serde_json::to_value(printers).map_err(std::io::Error::other)?,
+        Err(e) => serde_json::json!({ "error": e.to_string() }),
+    };
+    
+    let config = config.clone();
+    tokio::task::spawn_blocking(move || build(&config, &printers))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+fn build(config: &Config, printers: &serde_json::Value) -> BridgeResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    
+    let system = SystemInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        family: std::env::consts::FAMILY,
+        arch: std::env::consts::ARCH,
+        generated_at: Local::now().to_rfc3339(),
+        mock_printers: config.mock.enabled,
+        test_queue: config.test_queue.clone(),
+    };
+    add_json(&mut zip, "system.json", &system)?;
+    add(&mut zip, "config.toml", sanitized_config(config)?.as_bytes())?;
+    add_json(&mut zip, "printers.json", printers)?;
+    add_json(&mut zip, "tools.json", tools::detect_all(config).statuses())?;
+    
+    let log_dir = LoggingConfig::from(&config.logging).log_dir;
+    for path in logging::recent_log_files(&log_dir, LOG_FILES) {
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        match read_tail(&path) {
+            // Los logs de versiones anteriores se escribieron sin filtrar
+            Ok(contents) => {
+                let contents = String::from_utf8_lossy(&contents);
+                add(&mut zip, &format!("logs/{}", name), logging::redact::redact(&contents).as_bytes())?
+            }
+            Err(e) => log::warn!("⚠️ No se pudo leer {} para el diagnóstico: {}", path.display(), e),
+        }
+    }
+    
+    let cursor = zip.finish().map_err(zip_error)?;
+    Ok(cursor.into_inner())
+}
+
+/// La configuración en TOML con los tokens principales y los adicionales ocultos
+fn sanitized_config(config: &Config) -> BridgeResult<String> {
+    toml::to_string_pretty(&config.redacted()).map_err(|e| BridgeError::ConfigError(e.to_string()))
+}
+
+fn read_tail(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if len > LOG_TAIL_BYTES {
+        file.seek(SeekFrom::Start(len - LOG_TAIL_BYTES))?;
+    }
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+fn add(zip: &mut ZipWriter<Cursor<Vec<u8>>>, name: &str, contents: &[u8]) -> BridgeResult<()> {
+    zip.start_file(name, SimpleFileOptions::default()).map_err(zip_error)?;
+    zip.write_all(contents)?;
+    Ok(())
+}
+
+fn add_json(zip: &mut ZipWriter<Cursor<Vec<u8>>>, name: &str, value: &(impl Serialize + ?Sized)) -> BridgeResult<()> {
+    let contents = serde_json::to_vec_pretty(value).map_err(std::io::Error::other)?;
+    add(zip, name, &contents)
+}
+
+fn zip_error(e: zip::result::ZipError) -> BridgeError {
+    BridgeError::IoError(std::io::Error::other(e))
+}
@@ -0,0 +1,127 @@
+use crate::error::BridgeResult;
+use dns_parser::{Builder, Packet, QueryClass, QueryType, RData};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+
+const MDNS_ADDR: (Ipv4Addr, u16) = (Ipv4Addr::new(224, 0, 0, 251), 5353);
+
+/// Servicios que anuncian las impresoras: IPP y el puerto 9100 "raw"
+const SERVICES: [&str; 2] = ["_ipp._tcp.local", "_pdl-datastream._tcp.local"];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DiscoveredPrinter {
+    /// Nombre de la instancia anunciada ("HP LaserJet Pro M404")
+    pub name: String,
+    /// `ipp` o `pdl-datastream`
+    pub service: String,
+    pub host: String,
+    pub address: Option<String>,
+    pub port: u16,
+    /// Modelo según el registro TXT `ty`
+    pub model: Option<String>,
+    /// URI lista para usar como destino (`ipp://...` o `socket://...`)
+    pub uri: String,
+}
+
+/// Lo que se va juntando de las respuestas para cada instancia
+#[derive(Default)]
+struct Instance {
+    target: Option<String>,
+    port: u16,
+    txt: HashMap<String, String>,
+}
+
+/// Busca impresoras en la red local por mDNS/Bonjour durante `timeout`.
+/// Usa consultas "legacy unicast" (puerto efímero), así que no compite por
+/// el 5353 con Avahi o mDNSResponder.
+pub async fn browse(timeout: Duration) -> BridgeResult<Vec<DiscoveredPrinter>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+
+    let mut query = Builder::new_query(0, false);
+    for service in SERVICES {
+        query.add_question(service, false, QueryType::PTR, QueryClass::IN);
+    }
+    let query = query.build().unwrap_or_else(|truncated| truncated);
+    socket.send_to(&query, SocketAddr::from(MDNS_ADDR)).await?;
+
+    let mut instances: HashMap<String, Instance> = HashMap::new();
+    let mut addresses: HashMap<String, Ipv4Addr> = HashMap::new();
+    let deadline = Instant::now() + timeout;
+    let mut buffer = [0u8; 9000];
+
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+        let (len, from) = received?;
+        match Packet::parse(&buffer[..len]) {
+            Ok(packet) => collect(&packet, &mut instances, &mut addresses),
+            Err(e) => log::debug!("🔎 Respuesta mDNS inválida de {}: {}", from, e),
+        }
+    }
+
+    let mut printers: Vec<DiscoveredPrinter> = instances
+        .into_iter()
+        .filter_map(|(instance, found)| {
+            // "Nombre._ipp._tcp.local": el servicio va en el propio nombre
+            let (name, service) = SERVICES.iter().find_map(|service| {
+                let name = instance.strip_suffix(service)?.strip_suffix('.')?;
+                Some((name.to_string(), service.trim_start_matches('_').split('.').next()?.to_string()))
+            })?;
+            let host = found.target?;
+            let address = addresses.get(&host).map(|ip| ip.to_string());
+            let authority = format!("{}:{}", address.as_deref().unwrap_or(&host), found.port);
+            let uri = if service == "ipp" {
+                let resource = found.txt.get("rp").map(String::as_str).unwrap_or("ipp/print");
+                format!("ipp://{}/{}", authority, resource)
+            } else {
+                format!("socket://{}", authority)
+            };
+
+            Some(DiscoveredPrinter {
+                name,
+                service,
+                host,
+                address,
+                port: found.port,
+                model: found.txt.get("ty").cloned(),
+                uri,
+            })
+        })
+        .collect();
+    printers.sort_by(|a, b| a.name.cmp(&b.name).then(a.service.cmp(&b.service)));
+
+    log::info!("🔎 {} impresoras encontradas por mDNS", printers.len());
+    Ok(printers)
+}
+
+/// Los respondedores mezclan PTR, SRV, TXT y A entre respuestas y adicionales
+fn collect(packet: &Packet, instances: &mut HashMap<String, Instance>, addresses: &mut HashMap<String, Ipv4Addr>) {
+    for record in packet.answers.iter().chain(&packet.additional) {
+        let name = record.name.to_string();
+        match &record.data {
+            RData::PTR(ptr) if SERVICES.contains(&name.as_str()) => {
+                instances.entry(ptr.0.to_string()).or_default();
+            }
+            RData::SRV(srv) => {
+                let instance = instances.entry(name).or_default();
+                instance.target = Some(srv.target.to_string());
+                instance.port = srv.port;
+            }
+            RData::TXT(txt) => {
+                let instance = instances.entry(name).or_default();
+                for entry in txt.iter() {
+                    let entry = String::from_utf8_lossy(entry);
+                    if let Some((key, value)) = entry.split_once('=') {
+                        instance.txt.insert(key.to_ascii_lowercase(), value.to_string());
+                    }
+                }
+            }
+            RData::A(a) => {
+                addresses.insert(name, a.0);
+            }
+            _ => {}
+        }
+    }
+}
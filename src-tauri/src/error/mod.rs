@@ -1,8 +1,38 @@
+use serde::Serialize;
+use std::fmt;
 use thiserror::Error;
+use warp::http::StatusCode;
 use warp::reject::Reject;
 
 pub type BridgeResult<T> = Result<T, BridgeError>;
 
+/// Campo de la solicitud que no pasa la validación
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldProblem {
+    /// Ruta del campo (`copies`, `options.orientation`)
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldProblem {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+fn join_problems(problems: &[FieldProblem]) -> String {
+    problems.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
 #[derive(Error, Debug)]
 pub enum BridgeError {
     #[error("Error de impresora: {0}")]
@@ -11,26 +41,212 @@ pub enum BridgeError {
     #[error("Error de impresión: {0}")]
     PrintError(String),
     
+    #[error("Impresora no encontrada: {0}")]
+    PrinterNotFound(String),
+    
+    #[error("Impresora fuera de línea: {printer} ({reason})")]
+    PrinterOffline { printer: String, reason: String },
+    
     #[error("Formato no soportado: {0}")]
     UnsupportedFormat(String),
     
+    #[error("PDF inválido: {0}")]
+    InvalidPdf(String),
+    
+    #[error("PDF protegido: {0}")]
+    PdfEncrypted(String),
+    
+    #[error("Error de renderizado ({renderer}): {stderr}")]
+    RenderError { renderer: String, stderr: String },
+    
+    #[error("Dependencia faltante: {0}")]
+    DependencyMissing(String),
+    
     #[error("Error de IO: {0}")]
     IoError(#[from] std::io::Error),
     
     #[error("Error de decodificación base64: {0}")]
     Base64Error(#[from] base64::DecodeError),
     
+    #[error("Solicitud inválida: {0}")]
+    InvalidRequest(String),
+    
+    #[error("Campos inválidos: {}", join_problems(.0))]
+    ValidationFailed(Vec<FieldProblem>),
+    
     #[error("Error de configuración: {0}")]
     ConfigError(String),
     
+    #[error("Error de base de datos: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
+    
+    #[error("Tiempo de espera agotado: {command} no respondió en {seconds}s")]
+    Timeout { command: String, seconds: u64 },
+    
+    #[error("No se pudo descargar el documento: {0}")]
+    FetchError(String),
+    
+    #[error("Error de TLS: {0}")]
+    TlsError(String),
+    
     #[error("No autorizado")]
     Unauthorized,
     
+    #[error("Permisos insuficientes")]
+    Forbidden,
+    
+    #[error("La credencial no permite imprimir en {0}")]
+    PrinterForbidden(String),
+    
+    #[error("Firma inválida: {0}")]
+    InvalidSignature(String),
+    
+    #[error("No encontrado: {0}")]
+    NotFound(String),
+    
+    #[error("Documento idéntico enviado hace {seconds_ago}s")]
+    DuplicateJob { seconds_ago: u64, job_id: Option<String> },
+    
+    #[error("Cola llena: {pending} trabajos pendientes")]
+    QueueFull { pending: usize, retry_after_secs: u64 },
+    
+    #[error("Cuota diaria de páginas agotada: {used} de {quota} usadas, el trabajo necesita {requested}")]
+    QuotaExceeded { used: u64, quota: u64, requested: u64, retry_after_secs: u64 },
+    
+    #[error("Bridge en pausa")]
+    Paused,
+    
+    #[error("El bridge se está cerrando")]
+    ShuttingDown,
+    
     #[error("Límite de velocidad excedido")]
     RateLimitExceeded,
     
+    #[error("Demasiados tokens inválidos; reintentar en {retry_after_secs}s")]
+    AuthLocked { retry_after_secs: u64 },
+    
     #[error("Archivo demasiado grande")]
     FileTooLarge,
+    
+    #[error("Spool lleno: se superaría la cuota de {quota_mb} MB")]
+    SpoolFull { quota_mb: u64 },
+}
+
+impl BridgeError {
+    /// Código estable que los clientes pueden usar para distinguir errores
+    pub fn code(&self) -> &'static str {
+        match self {
+            BridgeError::PrinterError(_) => "PRINTER_ERROR",
+            BridgeError::PrintError(_) => "PRINT_ERROR",
+            BridgeError::PrinterNotFound(_) => "PRINTER_NOT_FOUND",
+            BridgeError::PrinterOffline { .. } => "PRINTER_OFFLINE",
+            BridgeError::UnsupportedFormat(_) => "UNSUPPORTED_FORMAT",
+            BridgeError::InvalidPdf(_) => "INVALID_PDF",
+            BridgeError::PdfEncrypted(_) => "PDF_ENCRYPTED",
+            BridgeError::RenderError { .. } => "RENDER_ERROR",
+            BridgeError::DependencyMissing(_) => "DEPENDENCY_MISSING",
+            BridgeError::IoError(_) => "IO_ERROR",
+            BridgeError::Base64Error(_) => "INVALID_BASE64",
+            BridgeError::InvalidRequest(_) => "INVALID_REQUEST",
+            BridgeError::ValidationFailed(_) => "VALIDATION_FAILED",
+            BridgeError::ConfigError(_) => "CONFIG_ERROR",
+            BridgeError::DatabaseError(_) => "DATABASE_ERROR",
+            BridgeError::Timeout { .. } => "TIMEOUT",
+            BridgeError::FetchError(_) => "FETCH_FAILED",
+            BridgeError::TlsError(_) => "TLS_ERROR",
+            BridgeError::Unauthorized => "UNAUTHORIZED",
+            BridgeError::Forbidden => "FORBIDDEN",
+            BridgeError::PrinterForbidden(_) => "PRINTER_FORBIDDEN",
+            BridgeError::InvalidSignature(_) => "INVALID_SIGNATURE",
+            BridgeError::NotFound(_) => "NOT_FOUND",
+            BridgeError::DuplicateJob { .. } => "DUPLICATE_JOB",
+            BridgeError::QueueFull { .. } => "QUEUE_FULL",
+            BridgeError::QuotaExceeded { .. } => "QUOTA_EXCEEDED",
+            BridgeError::Paused => "PAUSED",
+            BridgeError::ShuttingDown => "SHUTTING_DOWN",
+            BridgeError::RateLimitExceeded => "RATE_LIMITED",
+            BridgeError::AuthLocked { .. } => "AUTH_LOCKED",
+            BridgeError::FileTooLarge => "FILE_TOO_LARGE",
+            BridgeError::SpoolFull { .. } => "SPOOL_FULL",
+        }
+    }
+    
+    pub fn status(&self) -> StatusCode {
+        match self {
+            BridgeError::UnsupportedFormat(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            BridgeError::DependencyMissing(_)
+            | BridgeError::Paused
+            | BridgeError::ShuttingDown
+            | BridgeError::QueueFull { .. }
+            | BridgeError::PrinterOffline { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            BridgeError::Base64Error(_) | BridgeError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            BridgeError::ValidationFailed(_) | BridgeError::InvalidPdf(_) | BridgeError::PdfEncrypted(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            BridgeError::Unauthorized | BridgeError::InvalidSignature(_) => StatusCode::UNAUTHORIZED,
+            BridgeError::Forbidden | BridgeError::PrinterForbidden(_) => StatusCode::FORBIDDEN,
+            BridgeError::NotFound(_) | BridgeError::PrinterNotFound(_) => StatusCode::NOT_FOUND,
+            BridgeError::FetchError(_) => StatusCode::BAD_GATEWAY,
+            BridgeError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            BridgeError::DuplicateJob { .. } => StatusCode::CONFLICT,
+            BridgeError::RateLimitExceeded
+            | BridgeError::QuotaExceeded { .. }
+            | BridgeError::AuthLocked { .. } => StatusCode::TOO_MANY_REQUESTS,
+            BridgeError::FileTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            BridgeError::SpoolFull { .. } => StatusCode::INSUFFICIENT_STORAGE,
+            BridgeError::PrinterError(_)
+            | BridgeError::PrintError(_)
+            | BridgeError::RenderError { .. }
+            | BridgeError::IoError(_)
+            | BridgeError::ConfigError(_)
+            | BridgeError::DatabaseError(_)
+            | BridgeError::TlsError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+    
+    /// Información adicional estructurada para la respuesta, si la hay
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            BridgeError::RenderError { renderer, stderr } => Some(serde_json::json!({
+                "renderer": renderer,
+                "stderr": stderr,
+            })),
+            BridgeError::PrinterOffline { printer, reason } => Some(serde_json::json!({
+                "printer": printer,
+                "reason": reason,
+            })),
+            BridgeError::DuplicateJob { seconds_ago, job_id } => Some(serde_json::json!({
+                "seconds_ago": seconds_ago,
+                "job_id": job_id,
+            })),
+            BridgeError::QueueFull { pending, retry_after_secs } => Some(serde_json::json!({
+                "pending": pending,
+                "retry_after_secs": retry_after_secs,
+            })),
+            BridgeError::ValidationFailed(problems) => Some(serde_json::json!({
+                "problems": problems,
+            })),
+            BridgeError::QuotaExceeded { used, quota, requested, retry_after_secs } => Some(serde_json::json!({
+                "used": used,
+                "quota": quota,
+                "requested": requested,
+                "retry_after_secs": retry_after_secs,
+            })),
+            BridgeError::Timeout { command, seconds } => Some(serde_json::json!({
+                "command": command,
+                "timeout_secs": seconds,
+            })),
+            _ => None,
+        }
+    }
+    
+    /// Segundos para la cabecera `Retry-After`, si conviene reintentar más tarde
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            BridgeError::QueueFull { retry_after_secs, .. }
+            | BridgeError::QuotaExceeded { retry_after_secs, .. }
+            | BridgeError::AuthLocked { retry_after_secs } => Some(*retry_after_secs),
+            _ => None,
+        }
+    }
 }
 
 impl Reject for BridgeError {}
\ No newline at end of file
@@ -1,14 +1,40 @@
-use crate::config::{Config, save_config, generate_secure_token};
+use crate::config::{Config, NetworkPrinter, save_config, generate_secure_token};
+use crate::control::{BridgeControl, ConfigReload, LastError, ServerState};
+use crate::discovery::{self, DiscoveredPrinter};
+use crate::metrics::MetricsSnapshot;
+use crate::printer::PrinterManager;
+use crate::tools::{self, Tool, ToolStatus};
 use serde::{Deserialize, Serialize};
-use tauri::command;
+use tauri::{command, State};
 use auto_launch::AutoLaunchBuilder;
 
 #[derive(Serialize, Deserialize)]
 pub struct BridgeStatus {
     pub active: bool,
+    /// Detalle de `active`: dirección en uso o por qué no arrancó
+    pub server: ServerState,
+    /// Puerto en uso; distinto de `configured_port` si estaba ocupado
     pub port: u16,
+    pub configured_port: u16,
     pub version: String,
-    pub requests_processed: u32,
+    pub requests_processed: u64,
+    /// Trabajos desde el arranque, en total, por tipo y por impresora
+    pub metrics: MetricsSnapshot,
+    /// RFC 3339 en hora local
+    pub started_at: String,
+    pub uptime_seconds: u64,
+    pub last_error: Option<LastError>,
+    /// Última recarga en caliente, con lo que falta por aplicar
+    pub last_reload: Option<ConfigReload>,
+    /// Trabajos en cola o imprimiéndose ahora mismo
+    pub active_jobs: usize,
+}
+
+#[derive(Serialize)]
+pub struct QueueStatus {
+    pub paused: bool,
+    pub pending: usize,
+    pub waiting: usize,
 }
 
 #[command]
@@ -17,7 +43,7 @@ pub async fn get_config() -> Result<Config, String> {
 }
 
 #[command]
-pub async fn update_config(config: Config) -> Result<(), String> {
+pub async fn update_config(config: Config, control: State<'_, BridgeControl>) -> Result<(), String> {
     let old_config = crate::config::load_config().map_err(|e| e.to_string())?;
     
     // Manejar cambios en auto-inicio
@@ -25,7 +51,19 @@ pub async fn update_config(config: Config) -> Result<(), String> {
         handle_auto_start_change(config.auto_start).map_err(|e| e.to_string())?;
     }
     
-    save_config(&config).map_err(|e| e.to_string())
+    save_config(&config).map_err(|e| e.to_string())?;
+    
+    // Puerto, TLS o token nuevos solo se aplican con el servidor reiniciado
+    if config.server_settings_changed(&old_config) {
+        control.request_restart();
+    }
+    Ok(())
+}
+
+#[command]
+pub async fn restart_server(control: State<'_, BridgeControl>) -> Result<(), String> {
+    control.request_restart();
+    Ok(())
 }
 
 #[command]
@@ -115,33 +153,162 @@ fn handle_auto_start_change(enable: bool) -> Result<(), Box<dyn std::error::Erro
 }
 
 #[command]
-pub async fn generate_new_token() -> Result<String, String> {
+pub async fn generate_new_token(control: State<'_, BridgeControl>) -> Result<String, String> {
     let mut config = crate::config::load_config().map_err(|e| e.to_string())?;
     let new_token = generate_secure_token();
     config.api_token = Some(new_token.clone());
     save_config(&config).map_err(|e| e.to_string())?;
+    control.request_restart();
     Ok(new_token)
 }
 
 #[command]
-pub async fn get_bridge_status() -> Result<BridgeStatus, String> {
+pub async fn get_bridge_status(control: State<'_, BridgeControl>) -> Result<BridgeStatus, String> {
     let config = crate::config::load_config().map_err(|e| e.to_string())?;
     
-    // Verificar si el servidor está activo
-    let client = reqwest::Client::new();
-    let is_active = match client
-        .get(&format!("http://{}:{}/health", config.host, config.port))
-        .send()
-        .await
-    {
-        Ok(response) => response.status().is_success(),
-        Err(_) => false,
-    };
+    // El servidor publica su estado; no hace falta sondear /health
+    let server = control.server_state();
+    let port = control.server_port().unwrap_or(config.port);
     
     Ok(BridgeStatus {
-        active: is_active,
-        port: config.port,
+        active: server.is_running(),
+        server,
+        port,
+        configured_port: config.port,
         version: env!("CARGO_PKG_VERSION").to_string(),
-        requests_processed: 0, // TODO: Implementar contador real
+        requests_processed: control.metrics().requests(),
+        metrics: control.metrics().snapshot(),
+        started_at: chrono::DateTime::<chrono::Local>::from(control.started_at()).to_rfc3339(),
+        uptime_seconds: control.uptime().as_secs(),
+        last_error: control.last_error(),
+        last_reload: control.last_reload(),
+        active_jobs: control.jobs().pending(),
     })
-}
\ No newline at end of file
+}
+
+#[command]
+pub async fn get_tool_status() -> Result<Vec<ToolStatus>, String> {
+    let config = crate::config::load_config().map_err(|e| e.to_string())?;
+    Ok(tools::detect_all(&config).statuses().to_vec())
+}
+
+#[command]
+pub async fn set_tool_path(tool: String, path: Option<String>) -> Result<ToolStatus, String> {
+    let tool = Tool::from_key(&tool).ok_or_else(|| format!("Herramienta desconocida: {}", tool))?;
+    let mut config = crate::config::load_config().map_err(|e| e.to_string())?;
+    
+    match path {
+        Some(path) => {
+            if !std::path::Path::new(&path).is_file() {
+                return Err(format!("No existe el archivo: {}", path));
+            }
+            config.tool_paths.insert(tool.key().to_string(), path);
+        }
+        // Sin ruta se vuelve a la detección automática
+        None => {
+            config.tool_paths.remove(tool.key());
+        }
+    }
+    
+    save_config(&config).map_err(|e| e.to_string())?;
+    
+    let located = tools::locate(tool, &config);
+    Ok(ToolStatus {
+        tool,
+        name: tool.display_name(),
+        available: located.is_some(),
+        path: located,
+    })
+}
+
+#[command]
+pub async fn get_tls_fingerprint() -> Result<Option<String>, String> {
+    let config = crate::config::load_config().map_err(|e| e.to_string())?;
+    let files = crate::tls::resolve(&config).map_err(|e| e.to_string())?;
+    Ok(files.map(|f| f.fingerprint))
+}
+#[command]
+pub async fn discover_printers() -> Result<Vec<DiscoveredPrinter>, String> {
+    let config = crate::config::load_config().map_err(|e| e.to_string())?;
+    discovery::browse(config.discovery_timeout()).await.map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn add_discovered_printer(name: String, uri: String) -> Result<NetworkPrinter, String> {
+    let mut config = crate::config::load_config().map_err(|e| e.to_string())?;
+    
+    // CUPS solo admite letras, números, guiones y guiones bajos en el nombre de la cola
+    let queue: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if queue.is_empty() {
+        return Err("El nombre de la impresora está vacío".to_string());
+    }
+    
+    PrinterManager::add_network_printer(&queue, &uri, &config)
+        .await
+        .map_err(|e| e.to_string())?;
+    
+    let printer = NetworkPrinter { name: queue, uri };
+    config.network_printers.retain(|existing| existing.name != printer.name);
+    config.network_printers.push(printer.clone());
+    save_config(&config).map_err(|e| e.to_string())?;
+    
+    Ok(printer)
+}
+
+#[command]
+pub fn get_queue_status(control: State<'_, BridgeControl>) -> QueueStatus {
+    queue_status(&control)
+}
+
+/// Detiene la cola (p. ej. para sacar un atasco de papel) sin perder trabajos
+#[command]
+pub fn set_queue_paused(paused: bool, control: State<'_, BridgeControl>) -> QueueStatus {
+    control.jobs().set_held(paused);
+    queue_status(&control)
+}
+
+/// Guarda el paquete de diagnóstico en `path` o, sin ruta, en Descargas.
+/// Devuelve dónde quedó.
+#[command]
+pub async fn export_diagnostics(path: Option<String>) -> Result<String, String> {
+    let config = crate::config::load_config().map_err(|e| e.to_string())?;
+    let bundle = crate::diagnostics::export(&config).await.map_err(|e| e.to_string())?;
+    
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => dirs::download_dir()
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(|| std::path::PathBuf::from("."))
+            .join(crate::diagnostics::file_name()),
+    };
+    std::fs::write(&path, bundle).map_err(|e| format!("No se pudo guardar {}: {}", path.display(), e))?;
+    
+    log::info!("🩺 Paquete de diagnóstico guardado en {}", path.display());
+    Ok(path.display().to_string())
+}
+
+#[command]
+pub fn get_log_level() -> Option<String> {
+    crate::logging::current_level()
+}
+
+/// Cambia el nivel de log sin reiniciar, para diagnosticar un equipo sin
+/// perder el estado que reproduce el problema. No se guarda en la configuración.
+#[command]
+pub fn set_log_level(level: String) -> Result<Option<String>, String> {
+    crate::logging::set_level(level.trim()).map_err(|e| e.to_string())?;
+    Ok(crate::logging::current_level())
+}
+
+fn queue_status(control: &BridgeControl) -> QueueStatus {
+    let queue = control.jobs();
+    QueueStatus {
+        paused: queue.is_held(),
+        pending: queue.pending(),
+        waiting: queue.waiting(),
+    }
+}
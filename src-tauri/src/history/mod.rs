@@ -0,0 +1,796 @@
+use crate::api::PrintRequest;
+use crate::config::{self, Config};
+use crate::error::BridgeResult;
+use crate::notifications::JobOutcome;
+use crate::source::{self, SpooledFile};
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Antigüedad a partir de la cual un documento del spool sin trabajo se borra
+const SPOOL_ORPHAN_AGE: Duration = Duration::from_secs(24 * 3600);
+
+/// Migraciones del esquema, en orden. `PRAGMA user_version` guarda cuántas
+/// se aplicaron; nunca editar una ya publicada, solo añadir al final.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS jobs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job_id TEXT,
+        printer TEXT,
+        content_type TEXT NOT NULL,
+        success INTEGER NOT NULL,
+        message TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_jobs_created_at ON jobs (created_at);
+    CREATE INDEX IF NOT EXISTS idx_jobs_printer ON jobs (printer, created_at);",
+    "ALTER TABLE jobs ADD COLUMN batch_id TEXT;
+    CREATE INDEX IF NOT EXISTS idx_jobs_batch ON jobs (batch_id);",
+    "ALTER TABLE jobs ADD COLUMN attempts INTEGER NOT NULL DEFAULT 1;
+    CREATE TABLE IF NOT EXISTS job_attempts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        job INTEGER NOT NULL REFERENCES jobs (id),
+        attempt INTEGER NOT NULL,
+        printer TEXT,
+        success INTEGER NOT NULL,
+        message TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_job_attempts_job ON job_attempts (job);",
+    "ALTER TABLE jobs ADD COLUMN external_id TEXT;
+    ALTER TABLE jobs ADD COLUMN metadata TEXT;
+    CREATE INDEX IF NOT EXISTS idx_jobs_external_id ON jobs (external_id);",
+    "CREATE TABLE IF NOT EXISTS active_jobs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        state TEXT NOT NULL,
+        request TEXT NOT NULL,
+        document TEXT,
+        batch_id TEXT,
+        created_at INTEGER NOT NULL
+    );",
+    "ALTER TABLE jobs ADD COLUMN pages INTEGER;
+    ALTER TABLE jobs ADD COLUMN token_id TEXT;
+    CREATE INDEX IF NOT EXISTS idx_jobs_token ON jobs (token_id, created_at);
+    ALTER TABLE active_jobs ADD COLUMN token_id TEXT;",
+    "ALTER TABLE jobs ADD COLUMN request_id TEXT;
+    CREATE INDEX IF NOT EXISTS idx_jobs_request ON jobs (request_id);
+    ALTER TABLE active_jobs ADD COLUMN request_id TEXT;",
+];
+
+/// Etapa de un trabajo aceptado que todavía no terminó
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    /// Aceptado, aún no se entregó a la impresora
+    Queued,
+    /// Convirtiéndose o enviándose a CUPS; puede que ya se haya impreso
+    Rendering,
+}
+
+impl JobState {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Rendering => "rendering",
+        }
+    }
+}
+
+/// Un intento de impresión dentro de un trabajo con reintentos
+#[derive(Debug, Clone, Serialize)]
+pub struct JobAttempt {
+    pub printer: Option<String>,
+    pub success: bool,
+    pub message: String,
+    pub created_at: i64,
+}
+
+impl JobAttempt {
+    pub fn new(printer: Option<String>, success: bool, message: String) -> Self {
+        Self {
+            printer,
+            success,
+            message,
+            created_at: Utc::now().timestamp(),
+        }
+    }
+}
+
+/// Criterios de búsqueda en el historial; los campos vacíos no filtran
+#[derive(Debug, Default)]
+pub struct JobFilter {
+    pub success: Option<bool>,
+    /// Cualquiera de estos nombres (alias y cola real)
+    pub printers: Vec<String>,
+    pub external_id: Option<String>,
+    pub request_id: Option<String>,
+    /// Clave y valor que debe tener `metadata`
+    pub metadata: Option<(String, String)>,
+    /// Marcas de tiempo Unix: `from` incluido, `to` excluido
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+/// Trabajo tal como lo devuelve la API de historial
+#[derive(Debug, Serialize)]
+pub struct JobRecord {
+    pub id: i64,
+    pub job_id: Option<String>,
+    pub printer: Option<String>,
+    pub content_type: String,
+    pub success: bool,
+    pub message: String,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    pub attempts: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+    /// Páginas impresas contando las copias, si se pudieron contar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<i64>,
+    /// Token adicional con que se envió el trabajo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<String>,
+    /// `X-Request-Id` de la solicitud HTTP que lo creó
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Páginas y trabajos impresos de un token o una impresora
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub key: Option<String>,
+    pub jobs: u64,
+    pub pages: u64,
+}
+
+/// Agrupación de `JobHistory::usage`
+#[derive(Debug, Clone, Copy)]
+pub enum UsageGroup {
+    Token,
+    Printer,
+}
+
+impl UsageGroup {
+    fn column(self) -> &'static str {
+        match self {
+            UsageGroup::Token => "token_id",
+            UsageGroup::Printer => "printer",
+        }
+    }
+}
+
+/// Fila del historial tal como se escribe en los archivos mensuales
+#[derive(Serialize)]
+struct ArchivedJob {
+    id: i64,
+    job_id: Option<String>,
+    printer: Option<String>,
+    content_type: String,
+    success: bool,
+    message: String,
+    created_at: i64,
+    batch_id: Option<String>,
+    attempts: i64,
+    external_id: Option<String>,
+    metadata: Option<serde_json::Value>,
+    pages: Option<i64>,
+    token_id: Option<String>,
+    request_id: Option<String>,
+}
+
+/// Trabajo en curso registrado en `active_jobs`. Si el proceso muere antes de
+/// `finish`, la fila sigue ahí al arrancar; si la solicitud se cancela, se borra.
+pub struct ActiveJob {
+    history: JobHistory,
+    id: Option<i64>,
+    token_id: Option<String>,
+    request_id: Option<String>,
+}
+
+impl ActiveJob {
+    pub fn set_state(&self, state: JobState) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let conn = self.history.conn.lock().unwrap();
+        if let Err(e) = conn.execute("UPDATE active_jobs SET state = ?1 WHERE id = ?2", params![state.as_str(), id]) {
+            log::warn!("⚠️ No se pudo actualizar el estado del trabajo: {}", e);
+        }
+    }
+
+    /// Pasa el trabajo al historial y, si hubo más de uno, cada intento
+    pub fn finish(mut self, content_type: &str, outcome: &JobOutcome, attempts: &[JobAttempt]) {
+        if let Err(e) = self.history.insert(
+            content_type,
+            outcome,
+            attempts,
+            self.id.take(),
+            self.token_id.as_deref(),
+            self.request_id.as_deref(),
+        ) {
+            log::warn!("⚠️ No se pudo registrar el trabajo en el historial: {}", e);
+        }
+    }
+}
+
+impl Drop for ActiveJob {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            let conn = self.history.conn.lock().unwrap();
+            if let Err(e) = conn.execute("DELETE FROM active_jobs WHERE id = ?1", [id]) {
+                log::warn!("⚠️ No se pudo quitar el trabajo de la cola persistente: {}", e);
+            }
+        }
+    }
+}
+
+/// Trabajo que quedó a medias en una ejecución anterior
+pub struct InterruptedJob {
+    pub job: ActiveJob,
+    pub state: JobState,
+    pub request: PrintRequest,
+    pub batch_id: Option<String>,
+    pub token_id: Option<String>,
+    pub request_id: Option<String>,
+}
+
+/// Historial de trabajos en SQLite, compartido entre el servidor y las tareas de fondo
+#[derive(Clone)]
+pub struct JobHistory {
+    conn: Arc<Mutex<Connection>>,
+    /// Archivo de la base; `None` si el historial vive en memoria
+    path: Option<PathBuf>,
+}
+
+impl JobHistory {
+    /// Abre `history.db` en el directorio de datos. Si no se puede, el historial
+    /// vive en memoria para que la impresión siga funcionando.
+    pub fn open() -> Self {
+        let path = config::app_data_dir().join("history.db");
+        match Self::open_at(&path) {
+            Ok(history) => history,
+            Err(e) => {
+                log::error!("❌ No se pudo abrir el historial {}: {}; se usará memoria", path.display(), e);
+                let conn = Connection::open_in_memory().expect("SQLite en memoria");
+                migrate(&conn).expect("esquema del historial");
+                Self { conn: Arc::new(Mutex::new(conn)), path: None }
+            }
+        }
+    }
+
+    fn open_at(path: &Path) -> BridgeResult<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        migrate(&conn)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)), path: Some(path.to_path_buf()) })
+    }
+
+    /// Registra un trabajo aceptado para poder retomarlo tras un cierre inesperado.
+    /// El documento tiene que estar ya en `content` o en el spool.
+    pub fn enqueue(
+        &self,
+        request: &PrintRequest,
+        batch_id: Option<&str>,
+        token_id: Option<&str>,
+        request_id: Option<&str>,
+    ) -> ActiveJob {
+        let id = serde_json::to_string(request)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                let document = request.spooled.as_ref().map(|file| file.path().to_string_lossy().into_owned());
+                let conn = self.conn.lock().unwrap();
+                conn.execute(
+                    "INSERT INTO active_jobs (state, request, document, batch_id, created_at, token_id, request_id)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![JobState::Queued.as_str(), json, document, batch_id, Utc::now().timestamp(), token_id, request_id],
+                )
+                .map(|_| conn.last_insert_rowid())
+                .map_err(|e| e.to_string())
+            });
+
+        let job = |id| ActiveJob {
+            history: self.clone(),
+            id,
+            token_id: token_id.map(str::to_string),
+            request_id: request_id.map(str::to_string),
+        };
+        match id {
+            Ok(id) => job(Some(id)),
+            Err(e) => {
+                log::warn!("⚠️ No se pudo guardar el trabajo en la cola persistente: {}", e);
+                job(None)
+            }
+        }
+    }
+
+    /// Trabajos que siguen en `active_jobs` de una ejecución anterior. Llamar al
+    /// arrancar, antes de aceptar trabajos nuevos.
+    pub fn interrupted(&self) -> BridgeResult<Vec<InterruptedJob>> {
+        let rows = {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT id, state, request, document, batch_id, token_id, request_id FROM active_jobs ORDER BY id",
+            )?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                        row.get::<_, Option<String>>(5)?,
+                        row.get::<_, Option<String>>(6)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+
+        let mut jobs = Vec::new();
+        for (id, state, request, document, batch_id, token_id, request_id) in rows {
+            let job = ActiveJob {
+                history: self.clone(),
+                id: Some(id),
+                token_id: token_id.clone(),
+                request_id: request_id.clone(),
+            };
+            let state = if state == JobState::Queued.as_str() { JobState::Queued } else { JobState::Rendering };
+            let request = serde_json::from_str::<PrintRequest>(&request)
+                .map_err(|e| e.to_string())
+                .and_then(|mut request| {
+                    if let Some(document) = document {
+                        request.spooled = Some(SpooledFile::adopt(PathBuf::from(document)).map_err(|e| e.to_string())?);
+                    }
+                    Ok(request)
+                });
+
+            match request {
+                Ok(request) => jobs.push(InterruptedJob { job, state, request, batch_id, token_id, request_id }),
+                // Sin documento no hay nada que retomar; `job` se suelta y la fila se borra
+                Err(e) => log::warn!("⚠️ Trabajo interrumpido {} descartado: {}", id, e),
+            }
+        }
+        Ok(jobs)
+    }
+
+    fn insert(
+        &self,
+        content_type: &str,
+        outcome: &JobOutcome,
+        attempts: &[JobAttempt],
+        active: Option<i64>,
+        token_id: Option<&str>,
+        request_id: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        let metadata = (!outcome.metadata.is_empty())
+            .then(|| serde_json::to_string(&outcome.metadata).ok())
+            .flatten();
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO jobs (job_id, printer, content_type, success, message, created_at, batch_id, attempts,
+                               external_id, metadata, pages, token_id, request_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                outcome.job_id,
+                outcome.printer,
+                content_type,
+                outcome.success,
+                outcome.message,
+                Utc::now().timestamp(),
+                outcome.batch_id,
+                attempts.len().max(1) as i64,
+                outcome.external_id,
+                metadata,
+                outcome.pages.map(|pages| pages as i64),
+                token_id,
+                request_id,
+            ],
+        )?;
+
+        if attempts.len() > 1 {
+            let job = tx.last_insert_rowid();
+            let mut stmt = tx.prepare(
+                "INSERT INTO job_attempts (job, attempt, printer, success, message, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (index, attempt) in attempts.iter().enumerate() {
+                stmt.execute(params![
+                    job,
+                    index as i64 + 1,
+                    attempt.printer,
+                    attempt.success,
+                    attempt.message,
+                    attempt.created_at,
+                ])?;
+            }
+        }
+
+        // En la misma transacción: un trabajo nunca está a la vez en ambas tablas
+        if let Some(active) = active {
+            tx.execute("DELETE FROM active_jobs WHERE id = ?1", [active])?;
+        }
+
+        tx.commit()
+    }
+
+    /// Trabajos que cumplen `filter`, del más reciente al más antiguo, y el
+    /// total sin paginar
+    pub fn search(&self, filter: &JobFilter) -> BridgeResult<(Vec<JobRecord>, u64)> {
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+
+        if let Some(success) = filter.success {
+            conditions.push("success = ?".to_string());
+            values.push(Value::Integer(i64::from(success)));
+        }
+        if !filter.printers.is_empty() {
+            let placeholders = vec!["?"; filter.printers.len()].join(", ");
+            conditions.push(format!("printer IN ({})", placeholders));
+            values.extend(filter.printers.iter().cloned().map(Value::Text));
+        }
+        if let Some(external_id) = &filter.external_id {
+            conditions.push("external_id = ?".to_string());
+            values.push(Value::Text(external_id.clone()));
+        }
+        if let Some(request_id) = &filter.request_id {
+            conditions.push("request_id = ?".to_string());
+            values.push(Value::Text(request_id.clone()));
+        }
+        if let Some((key, value)) = &filter.metadata {
+            // Ruta JSON entre comillas para admitir claves con puntos o espacios;
+            // los valores numéricos se comparan como texto
+            conditions.push("CAST(json_extract(metadata, '$.\"' || ? || '\"') AS TEXT) = ?".to_string());
+            values.push(Value::Text(key.clone()));
+            values.push(Value::Text(value.clone()));
+        }
+        if let Some(from) = filter.from {
+            conditions.push("created_at >= ?".to_string());
+            values.push(Value::Integer(from));
+        }
+        if let Some(to) = filter.to {
+            conditions.push("created_at < ?".to_string());
+            values.push(Value::Integer(to));
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM jobs {}", where_clause),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
+
+        values.push(Value::Integer(i64::from(filter.limit)));
+        values.push(Value::Integer(i64::from(filter.offset)));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, job_id, printer, content_type, success, message, created_at, batch_id, attempts,
+                    external_id, metadata, pages, token_id, request_id
+             FROM jobs {} ORDER BY created_at DESC, id DESC LIMIT ? OFFSET ?",
+            where_clause
+        ))?;
+        let jobs = stmt
+            .query_map(params_from_iter(values.iter()), |row| {
+                let created_at: i64 = row.get(6)?;
+                Ok(JobRecord {
+                    id: row.get(0)?,
+                    job_id: row.get(1)?,
+                    printer: row.get(2)?,
+                    content_type: row.get(3)?,
+                    success: row.get(4)?,
+                    message: row.get(5)?,
+                    created_at: DateTime::from_timestamp(created_at, 0)
+                        .map(|d| d.to_rfc3339())
+                        .unwrap_or_default(),
+                    batch_id: row.get(7)?,
+                    attempts: row.get(8)?,
+                    external_id: row.get(9)?,
+                    metadata: row
+                        .get::<_, Option<String>>(10)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    pages: row.get(11)?,
+                    token_id: row.get(12)?,
+                    request_id: row.get(13)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((jobs, total as u64))
+    }
+
+    /// Marca de tiempo Unix del último trabajo terminado, si hay alguno
+    pub fn last_job_at(&self) -> BridgeResult<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let last = conn.query_row("SELECT MAX(created_at) FROM jobs", [], |row| row.get(0))?;
+        Ok(last)
+    }
+
+    /// Páginas impresas con `token_id` desde `since` (marca de tiempo Unix)
+    pub fn pages_since(&self, token_id: &str, since: i64) -> BridgeResult<u64> {
+        let conn = self.conn.lock().unwrap();
+        let pages: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(pages), 0) FROM jobs WHERE token_id = ?1 AND success = 1 AND created_at >= ?2",
+            params![token_id, since],
+            |row| row.get(0),
+        )?;
+        Ok(pages as u64)
+    }
+
+    /// Trabajos y páginas impresos entre `from` (incluido) y `to` (excluido),
+    /// por token o por impresora, de más a menos páginas
+    pub fn usage(&self, group: UsageGroup, from: Option<i64>, to: Option<i64>) -> BridgeResult<Vec<Usage>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {column}, COUNT(*), COALESCE(SUM(pages), 0) FROM jobs
+             WHERE success = 1 AND created_at >= ?1 AND created_at < ?2
+             GROUP BY {column} ORDER BY 3 DESC",
+            column = group.column()
+        ))?;
+        let usage = stmt
+            .query_map(params![from.unwrap_or(i64::MIN), to.unwrap_or(i64::MAX)], |row| {
+                Ok(Usage {
+                    key: row.get(0)?,
+                    jobs: row.get::<_, i64>(1)? as u64,
+                    pages: row.get::<_, i64>(2)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(usage)
+    }
+
+    /// Mueve las filas más antiguas que `days` a `archive/jobs-AAAA-MM.jsonl.gz`
+    /// y las borra de la base. Devuelve cuántas filas se archivaron.
+    pub fn archive_older_than(&self, days: u32) -> BridgeResult<usize> {
+        let cutoff = Utc::now().timestamp() - i64::from(days) * 86_400;
+        self.archive_where("created_at < ?1", cutoff)
+    }
+
+    /// Archiva las filas más antiguas hasta dejar como mucho `max_rows`
+    pub fn archive_beyond(&self, max_rows: u64) -> BridgeResult<usize> {
+        let newest_excess: Option<i64> = {
+            let conn = self.conn.lock().unwrap();
+            conn.query_row(
+                "SELECT id FROM jobs ORDER BY id DESC LIMIT 1 OFFSET ?1",
+                params![max_rows as i64],
+                |row| row.get(0),
+            )
+            .optional()?
+        };
+        match newest_excess {
+            Some(id) => self.archive_where("id <= ?1", id),
+            None => Ok(0),
+        }
+    }
+
+    /// Borra sin archivar las filas más antiguas que `days` y los archivos
+    /// mensuales que ya quedan enteros fuera de plazo
+    pub fn prune_older_than(&self, days: u32) -> BridgeResult<usize> {
+        let cutoff = Utc::now().timestamp() - i64::from(days) * 86_400;
+        let deleted = {
+            let mut conn = self.conn.lock().unwrap();
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM job_attempts WHERE job IN (SELECT id FROM jobs WHERE created_at < ?1)",
+                params![cutoff],
+            )?;
+            let deleted = tx.execute("DELETE FROM jobs WHERE created_at < ?1", params![cutoff])?;
+            tx.commit()?;
+            deleted
+        };
+
+        // "jobs-AAAA-MM": un mes anterior al del corte queda entero fuera de plazo
+        let cutoff_month = DateTime::from_timestamp(cutoff, 0)
+            .map(|d| d.format("%Y-%m").to_string())
+            .unwrap_or_default();
+        if let Ok(entries) = std::fs::read_dir(config::app_data_dir().join("archive")) {
+            for path in entries.flatten().map(|entry| entry.path()) {
+                let month = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_prefix("jobs-"))
+                    .and_then(|name| name.strip_suffix(".jsonl.gz"));
+                if month.is_some_and(|month| month < cutoff_month.as_str()) {
+                    std::fs::remove_file(&path)?;
+                    log::info!("🗑️ Archivo de historial caducado: {}", path.display());
+                }
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    /// Documentos del spool que pertenecen a trabajos sin terminar
+    pub fn active_documents(&self) -> BridgeResult<Vec<PathBuf>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT document FROM active_jobs WHERE document IS NOT NULL")?;
+        let documents = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|document| document.map(PathBuf::from))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(documents)
+    }
+
+    fn archive_where(&self, condition: &str, value: i64) -> BridgeResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let rows = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT id, job_id, printer, content_type, success, message, created_at, batch_id, attempts,
+                        external_id, metadata, pages, token_id, request_id
+                 FROM jobs WHERE {} ORDER BY id",
+                condition
+            ))?;
+            let rows = stmt.query_map(params![value], |row| {
+                Ok(ArchivedJob {
+                    id: row.get(0)?,
+                    job_id: row.get(1)?,
+                    printer: row.get(2)?,
+                    content_type: row.get(3)?,
+                    success: row.get(4)?,
+                    message: row.get(5)?,
+                    created_at: row.get(6)?,
+                    batch_id: row.get(7)?,
+                    attempts: row.get(8)?,
+                    external_id: row.get(9)?,
+                    metadata: row
+                        .get::<_, Option<String>>(10)?
+                        .and_then(|json| serde_json::from_str(&json).ok()),
+                    pages: row.get(11)?,
+                    token_id: row.get(12)?,
+                    request_id: row.get(13)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let mut by_month: BTreeMap<String, Vec<&ArchivedJob>> = BTreeMap::new();
+        for job in &rows {
+            let month = DateTime::from_timestamp(job.created_at, 0)
+                .map(|d| d.format("%Y-%m").to_string())
+                .unwrap_or_else(|| "desconocido".to_string());
+            by_month.entry(month).or_default().push(job);
+        }
+
+        let archive_dir = config::app_data_dir().join("archive");
+        std::fs::create_dir_all(&archive_dir)?;
+
+        // Cada pasada añade un miembro gzip nuevo; zcat los lee como un solo archivo
+        for (month, jobs) in by_month {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(archive_dir.join(format!("jobs-{}.jsonl.gz", month)))?;
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            for job in jobs {
+                serde_json::to_writer(&mut encoder, job).map_err(std::io::Error::from)?;
+                encoder.write_all(b"\n")?;
+            }
+            encoder.finish()?;
+        }
+
+        // Solo se borra después de escribir todo el archivo
+        tx.execute(
+            &format!("DELETE FROM job_attempts WHERE job IN (SELECT id FROM jobs WHERE {})", condition),
+            params![value],
+        )?;
+        tx.execute(&format!("DELETE FROM jobs WHERE {}", condition), params![value])?;
+        tx.commit()?;
+
+        Ok(rows.len())
+    }
+
+    /// Reconstruye índices y compacta el archivo de la base. Usa una conexión
+    /// propia para no retener el candado compartido mientras VACUUM reescribe
+    /// el archivo: las peticiones solo esperan el bloqueo de SQLite, con su
+    /// `busy_timeout`, en vez de quedarse paradas en el `Mutex`.
+    pub fn compact(&self) -> BridgeResult<()> {
+        const COMPACT: &str = "REINDEX; VACUUM; PRAGMA optimize;";
+        match &self.path {
+            Some(path) => Connection::open(path)?.execute_batch(COMPACT)?,
+            // Una base en memoria solo existe en su conexión
+            None => self.conn.lock().unwrap().execute_batch(COMPACT)?,
+        }
+        Ok(())
+    }
+
+    /// Vuelca el WAL, si lo hay, y deja las estadísticas al día antes de salir
+    pub fn flush(&self) -> BridgeResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE); PRAGMA optimize;")?;
+        Ok(())
+    }
+
+    fn run_maintenance(&self, config: &Config) -> BridgeResult<()> {
+        if config.job_history_days > 0 {
+            let pruned = self.prune_older_than(config.job_history_days)?;
+            if pruned > 0 {
+                log::info!("🗑️ {} trabajos caducados borrados del historial", pruned);
+            }
+        }
+
+        let mut archived = 0;
+        if config.history_archive_days > 0 {
+            archived += self.archive_older_than(config.history_archive_days)?;
+        }
+        if config.max_history_rows > 0 {
+            archived += self.archive_beyond(config.max_history_rows)?;
+        }
+        if archived > 0 {
+            log::info!("🗄️ {} trabajos archivados del historial", archived);
+        }
+
+        // Los trabajos en curso llevan poco en el spool; lo antiguo que no está
+        // en la cola persistente quedó huérfano
+        source::remove_orphans(&self.active_documents()?, SPOOL_ORPHAN_AGE);
+
+        self.compact()?;
+        log::debug!("🧹 Historial compactado");
+        Ok(())
+    }
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        conn.execute_batch(migration)?;
+        conn.pragma_update(None, "user_version", (index + 1) as i64)?;
+    }
+    Ok(())
+}
+
+/// Lanza el mantenimiento periódico del historial (caducidad, archivado,
+/// limpieza del spool y VACUUM). Se ejecuta al arrancar y luego cada
+/// `history_maintenance_hours`.
+pub fn spawn_maintenance(history: JobHistory, config: &Config) {
+    if config.history_maintenance_hours == 0 {
+        log::info!("🗄️ Mantenimiento del historial desactivado");
+        return;
+    }
+
+    let period = Duration::from_secs(u64::from(config.history_maintenance_hours) * 3600);
+    let config = config.clone();
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            let history = history.clone();
+            let config = config.clone();
+            // VACUUM reescribe el archivo entero; no bloquear el runtime
+            match tokio::task::spawn_blocking(move || history.run_maintenance(&config)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::warn!("⚠️ Error en el mantenimiento del historial: {}", e),
+                Err(e) => log::error!("❌ La tarea de mantenimiento falló: {}", e),
+            }
+        }
+    });
+}
@@ -1,14 +1,34 @@
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use crate::config::LoggingSettings;
+use crate::error::{BridgeError, BridgeResult};
+
+mod native;
+pub mod redact;
+
+/// Nombre base de los archivos; el appender añade la fecha (`.2024-05-01`)
+const LOG_FILE_NAME: &str = "print-my-bridge.log";
+
+/// Cada cuánto se aplica la retención de los archivos de log
+const CLEANUP_PERIOD: Duration = Duration::from_secs(3600);
+
+/// Filtro activo, para cambiar el nivel sin reiniciar
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
 
 pub struct LoggingConfig {
     pub level: String,
     pub file_enabled: bool,
     pub console_enabled: bool,
     pub json_format: bool,
+    pub syslog_enabled: bool,
+    pub eventlog_enabled: bool,
     pub log_dir: PathBuf,
+    pub max_log_files: usize,
+    pub max_log_size_mb: u64,
 }
 
 impl Default for LoggingConfig {
@@ -18,7 +38,27 @@ impl Default for LoggingConfig {
             file_enabled: true,
             console_enabled: true,
             json_format: false,
+            syslog_enabled: false,
+            eventlog_enabled: false,
             log_dir: get_default_log_dir(),
+            max_log_files: 14,
+            max_log_size_mb: 100,
+        }
+    }
+}
+
+impl From<&LoggingSettings> for LoggingConfig {
+    fn from(settings: &LoggingSettings) -> Self {
+        Self {
+            level: settings.level.clone(),
+            file_enabled: settings.file,
+            console_enabled: true,
+            json_format: settings.json,
+            syslog_enabled: settings.syslog,
+            eventlog_enabled: settings.eventlog,
+            log_dir: settings.dir.as_ref().map(PathBuf::from).unwrap_or_else(get_default_log_dir),
+            max_log_files: settings.max_log_files,
+            max_log_size_mb: settings.max_log_size_mb,
         }
     }
 }
@@ -34,14 +74,19 @@ pub fn init_logging(config: Option<LoggingConfig>) -> Result<(), Box<dyn std::er
     // Configurar filtro de nivel
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(&config.level));
+    let (env_filter, handle) = reload::Layer::new(env_filter);
     
     let mut layers = Vec::new();
     
+    // Todas las salidas pasan por `redact`: con debug activado los logs de
+    // warp y hyper pueden incluir cabeceras y cuerpos completos
+    
     // Capa de consola
     if config.console_enabled {
         if config.json_format {
             let console_layer = tracing_subscriber::fmt::layer()
                 .json()
+                .with_writer(redact::Redacting(std::io::stdout))
                 .with_target(true)
                 .with_thread_ids(true)
                 .with_file(true)
@@ -50,6 +95,7 @@ pub fn init_logging(config: Option<LoggingConfig>) -> Result<(), Box<dyn std::er
         } else {
             let console_layer = tracing_subscriber::fmt::layer()
                 .pretty()
+                .with_writer(redact::Redacting(std::io::stdout))
                 .with_target(true)
                 .with_thread_ids(true)
                 .with_file(true)
@@ -61,14 +107,14 @@ pub fn init_logging(config: Option<LoggingConfig>) -> Result<(), Box<dyn std::er
     // Capa de archivo
     if config.file_enabled {
         let file_appender = RollingFileAppender::new(
-            Rotation::daily(),
+            Rotation::DAILY,
             &config.log_dir,
-            "print-my-bridge.log"
+            LOG_FILE_NAME
         );
         
         let file_layer = tracing_subscriber::fmt::layer()
             .json()
-            .with_writer(file_appender)
+            .with_writer(redact::Redacting(file_appender))
             .with_target(true)
             .with_thread_ids(true)
             .with_file(true)
@@ -76,19 +122,138 @@ pub fn init_logging(config: Option<LoggingConfig>) -> Result<(), Box<dyn std::er
         layers.push(file_layer.boxed());
     }
     
+    // Registro del sistema. Si no está disponible se sigue con el resto y se
+    // avisa cuando el subscriber ya funciona.
+    let mut warnings = Vec::new();
+    if config.syslog_enabled {
+        #[cfg(unix)]
+        match native::SyslogLayer::connect() {
+            Ok(syslog_layer) => layers.push(syslog_layer.boxed()),
+            Err(e) => warnings.push(format!("No se pudo conectar con syslog: {}", e)),
+        }
+        #[cfg(not(unix))]
+        warnings.push("syslog solo está disponible en Linux y macOS".to_string());
+    }
+    if config.eventlog_enabled {
+        #[cfg(windows)]
+        match native::EventLogLayer::register() {
+            Ok(eventlog_layer) => layers.push(eventlog_layer.boxed()),
+            Err(e) => warnings.push(format!("No se pudo registrar el origen en el Visor de eventos: {}", e)),
+        }
+        #[cfg(not(windows))]
+        warnings.push("El Visor de eventos solo está disponible en Windows".to_string());
+    }
+    
     // Inicializar subscriber
     tracing_subscriber::registry()
         .with(env_filter)
         .with(layers)
         .init();
+    let _ = FILTER.set(handle);
     
     tracing::info!("🚀 Sistema de logging inicializado");
     tracing::info!("📁 Directorio de logs: {}", config.log_dir.display());
     tracing::info!("📊 Nivel de logging: {}", config.level);
+    for warning in warnings {
+        tracing::warn!("⚠️ {}", warning);
+    }
     
     Ok(())
 }
 
+/// Directivas del filtro activo, p. ej. `info` o `info,print_my_bridge::api=debug`
+pub fn current_level() -> Option<String> {
+    FILTER.get()?.with_current(|filter| filter.to_string()).ok()
+}
+
+/// Sustituye el filtro activo por `directives`, con la sintaxis de `RUST_LOG`.
+/// Dura hasta el próximo arranque, que vuelve al nivel de la configuración.
+/// Devuelve el filtro anterior.
+pub fn set_level(directives: &str) -> BridgeResult<String> {
+    let handle = FILTER
+        .get()
+        .ok_or_else(|| BridgeError::ConfigError("El sistema de logging no está inicializado".to_string()))?;
+    let filter = EnvFilter::try_new(directives)
+        .map_err(|e| BridgeError::InvalidRequest(format!("Nivel de log inválido '{}': {}", directives, e)))?;
+    
+    let previous = current_level().unwrap_or_default();
+    handle
+        .reload(filter)
+        .map_err(|e| BridgeError::ConfigError(format!("No se pudo cambiar el nivel de log: {}", e)))?;
+    
+    tracing::warn!("📊 Nivel de logging cambiado: {} → {}", previous, directives);
+    Ok(previous)
+}
+
+/// Aplica cada hora `max_log_files` y `max_log_size_mb`: el appender rota a
+/// diario pero nunca borra, y un kiosco puede pasar años sin que nadie mire
+pub fn spawn_cleanup(config: &LoggingConfig) {
+    if !config.file_enabled || (config.max_log_files == 0 && config.max_log_size_mb == 0) {
+        return;
+    }
+    
+    let dir = config.log_dir.clone();
+    let max_files = config.max_log_files;
+    let max_bytes = config.max_log_size_mb * 1024 * 1024;
+    
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CLEANUP_PERIOD);
+        loop {
+            ticker.tick().await;
+            let dir = dir.clone();
+            match tokio::task::spawn_blocking(move || cleanup_logs(&dir, max_files, max_bytes)).await {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!("🧹 {} archivos de log antiguos eliminados", removed),
+                Err(e) => tracing::error!("❌ La limpieza de logs falló: {}", e),
+            }
+        }
+    });
+}
+
+/// Archivos de log de `dir` con su fecha y tamaño, del más reciente al más antiguo
+fn log_files(dir: &Path) -> Vec<(SystemTime, u64, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(LOG_FILE_NAME))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+        })
+        .collect();
+    files.sort_by_key(|(modified, _, _)| std::cmp::Reverse(*modified));
+    files
+}
+
+/// Los `limit` archivos de log más recientes de `dir`
+pub fn recent_log_files(dir: &Path, limit: usize) -> Vec<PathBuf> {
+    log_files(dir).into_iter().take(limit).map(|(_, _, path)| path).collect()
+}
+
+/// Borra los archivos más antiguos hasta quedar dentro de los límites (0 = sin
+/// límite). El más reciente, en el que se está escribiendo, nunca se borra.
+fn cleanup_logs(dir: &Path, max_files: usize, max_bytes: u64) -> usize {
+    let files = log_files(dir);
+    
+    let mut kept_bytes = 0;
+    let mut removed = 0;
+    for (index, (_, size, path)) in files.into_iter().enumerate() {
+        kept_bytes += size;
+        let over_count = max_files > 0 && index >= max_files;
+        let over_size = max_bytes > 0 && kept_bytes > max_bytes;
+        if index == 0 || !(over_count || over_size) {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => removed += 1,
+            Err(e) => tracing::warn!("⚠️ No se pudo borrar {}: {}", path.display(), e),
+        }
+    }
+    removed
+}
+
 fn get_default_log_dir() -> PathBuf {
     #[cfg(target_os = "windows")]
     {
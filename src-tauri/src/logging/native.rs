@@ -0,0 +1,160 @@
+use std::fmt::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::Event;
+
+/// Nombre con que aparecen los mensajes en syslog y en el Visor de eventos
+const SOURCE: &str = "print-my-bridge";
+
+/// Mensaje del evento seguido de sus campos como `clave=valor`. Los campos
+/// `log.*` que añade el puente con el crate `log` se omiten.
+#[derive(Default)]
+struct EventText {
+    message: String,
+    fields: String,
+}
+
+impl Visit for EventText {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+    
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {}={}", name, value);
+            }
+        }
+    }
+}
+
+fn event_text(event: &Event<'_>) -> String {
+    let mut text = EventText::default();
+    event.record(&mut text);
+    super::redact::redact(&format!("{}{}", text.message, text.fields)).into_owned()
+}
+
+#[cfg(unix)]
+pub use self::unix::SyslogLayer;
+
+#[cfg(unix)]
+mod unix {
+    use super::{event_text, SOURCE};
+    use std::sync::Mutex;
+    use tracing::{Event, Level, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer};
+
+    pub struct SyslogLayer(Mutex<syslog::Logger<syslog::LoggerBackend, syslog::Formatter3164>>);
+
+    impl SyslogLayer {
+        /// Conecta con el socket local de syslog, con la facilidad `daemon`
+        pub fn connect() -> Result<Self, syslog::Error> {
+            let formatter = syslog::Formatter3164 {
+                facility: syslog::Facility::LOG_DAEMON,
+                hostname: None,
+                process: SOURCE.to_string(),
+                pid: std::process::id(),
+            };
+            Ok(Self(Mutex::new(syslog::unix(formatter)?)))
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for SyslogLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let text = event_text(event);
+            let Ok(mut logger) = self.0.lock() else {
+                return;
+            };
+            // Si syslog no responde no hay dónde avisarlo sin entrar en un bucle
+            let _ = match *event.metadata().level() {
+                Level::ERROR => logger.err(text),
+                Level::WARN => logger.warning(text),
+                Level::INFO => logger.info(text),
+                _ => logger.debug(text),
+            };
+        }
+    }
+}
+
+#[cfg(windows)]
+pub use self::windows::EventLogLayer;
+
+#[cfg(windows)]
+mod windows {
+    use super::{event_text, SOURCE};
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use tracing::{Event, Level, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer};
+    use winapi::um::winbase::{DeregisterEventSource, RegisterEventSourceW, ReportEventW};
+    use winapi::um::winnt::{HANDLE, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE};
+
+    fn wide(text: &str) -> Vec<u16> {
+        OsStr::new(text).encode_wide().chain(Some(0)).collect()
+    }
+
+    /// Origen del registro de Aplicación. Sin un archivo de mensajes
+    /// registrado el Visor avisa de que falta la descripción del evento 0,
+    /// pero muestra el texto completo debajo.
+    pub struct EventLogLayer(HANDLE);
+
+    // El manejador de un origen de eventos se puede usar desde cualquier hilo
+    unsafe impl Send for EventLogLayer {}
+    unsafe impl Sync for EventLogLayer {}
+
+    impl EventLogLayer {
+        pub fn register() -> std::io::Result<Self> {
+            let source = wide(SOURCE);
+            let handle = unsafe { RegisterEventSourceW(ptr::null(), source.as_ptr()) };
+            if handle.is_null() {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(Self(handle))
+        }
+    }
+
+    impl Drop for EventLogLayer {
+        fn drop(&mut self) {
+            unsafe {
+                DeregisterEventSource(self.0);
+            }
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for EventLogLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            // Debug y trace llenarían el registro de Aplicación; se quedan en el archivo
+            let kind = match *event.metadata().level() {
+                Level::ERROR => EVENTLOG_ERROR_TYPE,
+                Level::WARN => EVENTLOG_WARNING_TYPE,
+                Level::INFO => EVENTLOG_INFORMATION_TYPE,
+                _ => return,
+            };
+            let text = wide(&event_text(event));
+            let mut strings = [text.as_ptr()];
+            unsafe {
+                ReportEventW(
+                    self.0,
+                    kind,
+                    0,
+                    0,
+                    ptr::null_mut(),
+                    1,
+                    0,
+                    strings.as_mut_ptr(),
+                    ptr::null_mut(),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use tracing::Metadata;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Lo que queda en el log en lugar de un secreto
+const REDACTED: &str = "[REDACTED]";
+
+/// A partir de esta longitud una tira base64 se trata como el contenido de un
+/// documento; los hashes y los ids son bastante más cortos
+const MIN_BASE64_LEN: usize = 120;
+
+/// Quita de una línea de log las credenciales y el contenido de los documentos:
+/// cabeceras `X-API-Token`, `Authorization` y `X-Signature`, campos de
+/// configuración con secretos (`api_token`, `secret`, `password`…) y tiras
+/// base64 largas. Funciona igual sobre texto plano y sobre JSON escapado.
+pub fn redact(text: &str) -> Cow<'_, str> {
+    static HEADER: OnceLock<Regex> = OnceLock::new();
+    static SECRET_FIELD: OnceLock<Regex> = OnceLock::new();
+    static BASE64: OnceLock<Regex> = OnceLock::new();
+
+    let header = HEADER.get_or_init(|| {
+        Regex::new(r#"(?i)(x-api-token|authorization|x-signature)(\\?"?\s*[:=]\s*\\?"?)(bearer\s+)?[^\s"\\,;}]+"#).unwrap()
+    });
+    let secret_field = SECRET_FIELD.get_or_init(|| {
+        Regex::new(r#"(?i)((?:[a-z_]*token|[a-z_]*secret|[a-z_]*password|token_hash)\\?"?\s*[:=]\s*(?:Some\()?)(\\?")(?:[^"\\]|\\[^"])*\\?""#)
+            .unwrap()
+    });
+    let base64 = BASE64.get_or_init(|| Regex::new(&format!(r"[A-Za-z0-9+/]{{{},}}={{0,2}}", MIN_BASE64_LEN)).unwrap());
+
+    let mut text = header.replace_all(text, |captures: &Captures| {
+        format!("{}{}{}{}", &captures[1], &captures[2], captures.get(3).map_or("", |bearer| bearer.as_str()), REDACTED)
+    });
+    // Casi ninguna línea tiene nada que quitar; entonces no se copia
+    if secret_field.is_match(&text) {
+        let replaced = secret_field
+            .replace_all(&text, |captures: &Captures| format!("{}{}{}{}", &captures[1], &captures[2], REDACTED, &captures[2]))
+            .into_owned();
+        text = Cow::Owned(replaced);
+    }
+    if base64.is_match(&text) {
+        let replaced = base64
+            .replace_all(&text, |captures: &Captures| format!("[base64 {} bytes]", captures[0].len() * 3 / 4))
+            .into_owned();
+        text = Cow::Owned(replaced);
+    }
+    text
+}
+
+/// Envoltorio de un `MakeWriter` que pasa cada evento formateado por `redact`
+/// antes de escribirlo
+pub struct Redacting<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        RedactingWriter(self.0.make_writer_for(meta))
+    }
+}
+
+/// `fmt` formatea el evento entero y lo escribe de una vez, así que cada
+/// `write` recibe una línea completa
+pub struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
@@ -2,91 +2,160 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod api;
+mod cli;
+mod control;
 mod printer;
+mod renderer;
 mod config;
 mod error;
 mod gui;
+mod notifications;
+mod tools;
+mod tls;
+mod test_mode;
+mod history;
+mod source;
+mod discovery;
+mod queue;
+mod mock;
+mod audit;
+mod logging;
+mod diagnostics;
+mod selftest;
+mod metrics;
+mod server;
+mod templates;
 
-use warp::Filter;
-use std::env;
-use tauri::{Manager, WindowEvent, tray::{TrayIconBuilder, TrayIconEvent}, menu::{MenuBuilder, MenuItemBuilder}};
+use clap::Parser;
+use tauri::{Manager, WindowEvent, tray::{TrayIconBuilder, TrayIconEvent}, menu::{CheckMenuItemBuilder, MenuBuilder, MenuItemBuilder}};
+use config::TrayMenuItem;
+use control::{BridgeControl, ServerState};
+use server::{OnFailure, Overrides};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Inicializar logging solo en debug
-    #[cfg(debug_assertions)]
-    env_logger::init();
+    let cli = cli::Cli::parse();
+    
+    // `--config <ruta>` en lugar del archivo del directorio de la plataforma
+    if let Some(path) = cli.config {
+        config::set_config_path(path);
+    }
+    
+    // Sin subcomando se arranca el servidor; el resto son herramientas que
+    // terminan enseguida
+    let serve = match cli.command {
+        None => cli.serve,
+        Some(cli::Command::Serve(serve)) => serve,
+        Some(command) => return Ok(cli::run(command).await?),
+    };
     
     // Cargar configuración de forma asíncrona
-    let config = tokio::task::spawn_blocking(|| config::load_config()).await??;
+    let mut config = tokio::task::spawn_blocking(|| config::load_config()).await??;
     
-    #[cfg(debug_assertions)]
+    // Consola y archivo según `[logging]`, también en release; los `log::`
+    // del resto del código llegan a tracing a través del subscriber
+    if let Err(e) = logging::init_logging(Some(logging::LoggingConfig::from(&config.logging))) {
+        eprintln!("⚠️ No se pudo iniciar el registro: {}", e);
+    }
+    logging::spawn_cleanup(&logging::LoggingConfig::from(&config.logging));
     log::info!("🚀 Iniciando Print My Bridge v{}", env!("CARGO_PKG_VERSION"));
     
-    // Verificar si se debe ejecutar en modo GUI o headless
-    let args: Vec<String> = env::args().collect();
-    let headless_mode = args.contains(&"--headless".to_string());
+    // Impresoras simuladas o puerto de la línea de comandos; no se guardan
+    let mut overrides = serve.overrides();
+    overrides.apply(&mut config);
+    if config.mock.enabled {
+        let names: Vec<&str> = config.mock.printers.iter().map(|printer| printer.name.as_str()).collect();
+        log::info!("🧪 Impresoras simuladas: {}", names.join(", "));
+    }
     
-    if headless_mode {
-        start_http_server(config).await?;
+    let control = BridgeControl::new();
+    tokio::spawn(shutdown_on_signal(control.clone()));
+    
+    if serve.test_mode {
+        // Servidor headless imprimiendo a una cola desechable; se elimina al salir
+        let queue = test_mode::TestQueue::create(&config)?;
+        overrides.test_queue = Some(queue.name().to_string());
+        overrides.apply(&mut config);
+        log::info!("🧪 Modo de prueba: todos los trabajos van a {}", queue.name());
+        
+        let result = server::run(config, control, overrides, OnFailure::Exit).await;
+        log::info!("🛑 Saliendo del modo de prueba");
+        drop(queue);
+        result?;
+    } else if serve.headless {
+        server::run(config, control, overrides, OnFailure::Exit).await?;
     } else {
-        start_gui_app(config).await?;
+        start_gui_app(config, control, overrides).await?;
     }
     
     Ok(())
 }
 
-async fn start_http_server(config: config::Config) -> Result<(), Box<dyn std::error::Error>> {
-    // Configurar CORS
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_headers(vec!["content-type", "authorization", "x-api-token"])
-        .allow_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"]);
-    
-    // Rutas de la API
-    let api_routes = api::routes(config.clone())
-        .with(cors)
-        .with(warp::log("print_my_bridge"));
-    
-    // Iniciar servidor
-    warp::serve(api_routes)
-        .run(([127, 0, 0, 1], config.port))
-        .await;
-    
-    Ok(())
+/// Ctrl+C o SIGTERM piden un cierre ordenado; una segunda señal sale sin
+/// esperar a los trabajos
+async fn shutdown_on_signal(control: BridgeControl) {
+    loop {
+        wait_for_signal().await;
+        if control.shutdown_requested() {
+            log::warn!("⚠️ Segunda señal de cierre, saliendo sin esperar a los trabajos");
+            std::process::exit(1);
+        }
+        control.request_shutdown();
+    }
 }
 
-async fn start_gui_app(config: config::Config) -> Result<(), Box<dyn std::error::Error>> {
-    // Iniciar servidor HTTP en background
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        Err(_) => {
+            if tokio::signal::ctrl_c().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    if tokio::signal::ctrl_c().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+async fn start_gui_app(config: config::Config, control: BridgeControl, overrides: Overrides) -> Result<(), Box<dyn std::error::Error>> {
+    // Iniciar servidor HTTP en background; si no arranca, espera a que se
+    // corrija la configuración desde la GUI
     let config_clone = config.clone();
+    let control_clone = control.clone();
     let _server_handle = tokio::spawn(async move {
         log::info!("🚀 Iniciando servidor HTTP en background...");
-        if let Err(e) = start_http_server(config_clone).await {
-            log::error!("❌ Error crítico en servidor HTTP: {}", e);
-            eprintln!("❌ Error crítico en servidor HTTP: {}", e);
-        } else {
-            log::info!("✅ Servidor HTTP iniciado correctamente");
+        if server::run(config_clone, control_clone, overrides, OnFailure::WaitForRestart).await.is_ok() {
+            log::info!("🛑 Servidor HTTP detenido");
         }
     });
 
-    // Dar tiempo al servidor para iniciarse
-    tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
-    
     // Verificar si el servidor se inició correctamente
-    let config_test = config.clone();
+    let mut server_state = control.watch_server();
     tokio::spawn(async move {
-        tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
-        let client = reqwest::Client::new();
-        match client.get(&format!("http://{}:{}/health", config_test.host, config_test.port)).send().await {
-            Ok(response) if response.status().is_success() => {
-                log::info!("✅ Servidor HTTP respondiendo correctamente en puerto {}", config_test.port);
+        let state = server_state
+            .wait_for(|state| *state != ServerState::Starting)
+            .await
+            .map(|state| state.clone());
+        match state {
+            Ok(ServerState::Running { address, .. }) => {
+                log::info!("✅ Servidor HTTP escuchando en {}", address);
             }
-            Ok(response) => {
-                log::error!("⚠️ Servidor HTTP respondió con estado: {}", response.status());
-            }
-            Err(e) => {
-                log::error!("❌ No se puede conectar al servidor HTTP: {}", e);
+            Ok(ServerState::Failed { error }) => {
+                log::error!("❌ El servidor HTTP no pudo iniciarse: {}", error);
             }
+            _ => {}
         }
     });
 
@@ -97,27 +166,67 @@ async fn start_gui_app(config: config::Config) -> Result<(), Box<dyn std::error:
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_shell::init())
-        .setup(|app| {
-            // Crear menú del tray
-            let show = MenuItemBuilder::with_id("show", "Mostrar").build(app)?;
-            let hide = MenuItemBuilder::with_id("hide", "Ocultar").build(app)?;
-            let quit = MenuItemBuilder::with_id("quit", "Cerrar").build(app)?;
+        .manage(control.clone())
+        .setup(move |app| {
+            // Notificaciones de escritorio para los avisos por trabajo
+            let handle = app.handle().clone();
+            control.set_desktop_notifier(std::sync::Arc::new(move |title: &str, body: &str| {
+                use tauri_plugin_notification::NotificationExt;
+                if let Err(e) = handle.notification().builder().title(title).body(body).show() {
+                    log::warn!("⚠️ No se pudo mostrar la notificación: {}", e);
+                }
+            }));
             
-            let menu = MenuBuilder::new(app)
-                .items(&[&show, &hide])
-                .separator()
-                .item(&quit)
-                .build()?;
+            // Al pedir el cierre (tray o señal) la app sale cuando el servidor
+            // termina de esperar a los trabajos en curso
+            let handle = app.handle().clone();
+            let shutdown = control.clone();
+            tauri::async_runtime::spawn(async move {
+                shutdown.shutdown_signal().await;
+                let mut server_state = shutdown.watch_server();
+                let _ = server_state
+                    .wait_for(|state| matches!(state, ServerState::Stopped | ServerState::Failed { .. }))
+                    .await;
+                handle.exit(0);
+            });
+            
+            // Crear menú del tray según la configuración
+            let mut menu_builder = MenuBuilder::new(app);
+            for (index, entry) in config.tray_menu.iter().enumerate() {
+                menu_builder = match entry {
+                    TrayMenuItem::Show => menu_builder.item(&MenuItemBuilder::with_id("show", "Mostrar").build(app)?),
+                    TrayMenuItem::Hide => menu_builder.item(&MenuItemBuilder::with_id("hide", "Ocultar").build(app)?),
+                    TrayMenuItem::Pause => menu_builder.item(
+                        &CheckMenuItemBuilder::with_id("pause", "Pausar bridge")
+                            .checked(control.is_paused())
+                            .build(app)?,
+                    ),
+                    TrayMenuItem::TestPage => menu_builder.item(&MenuItemBuilder::with_id("test_page", "Abrir página de prueba").build(app)?),
+                    TrayMenuItem::ReprintLast => menu_builder.item(&MenuItemBuilder::with_id("reprint_last", "Reimprimir último trabajo").build(app)?),
+                    TrayMenuItem::Quit => {
+                        // Separar "Cerrar" del resto de entradas
+                        if index > 0 {
+                            menu_builder = menu_builder.separator();
+                        }
+                        menu_builder.item(&MenuItemBuilder::with_id("quit", "Cerrar").build(app)?)
+                    }
+                };
+            }
+            let menu = menu_builder.build()?;
             
             // Crear tray icon SOLO si no existe uno ya
             if app.tray_by_id("main-tray").is_none() {
-                let _tray = TrayIconBuilder::new()
+                let mut server_state = control.watch_server();
+                let control = control.clone();
+                let config = config.clone();
+                let tray = TrayIconBuilder::new()
                     .menu(&menu)
                     .icon(app.default_window_icon().unwrap().clone())
                     .tooltip("Print My Bridge")
                     .on_menu_event(move |app, event| match event.id.as_ref() {
                         "quit" => {
-                            app.exit(0);
+                            // Sale cuando el servidor termine de drenar
+                            control.request_shutdown();
                         }
                         "show" => {
                             if let Some(window) = app.get_webview_window("main") {
@@ -130,6 +239,42 @@ async fn start_gui_app(config: config::Config) -> Result<(), Box<dyn std::error:
                                 let _ = window.hide();
                             }
                         }
+                        "pause" => {
+                            control.set_paused(!control.is_paused());
+                        }
+                        "test_page" => {
+                            let port = control.server_port().unwrap_or(config.port);
+                            let url = format!("{}/test-page", config.base_url_on(port));
+                            if let Err(e) = tools::open_url(&url) {
+                                log::error!("❌ No se pudo abrir {}: {}", url, e);
+                            }
+                        }
+                        "reprint_last" => {
+                            let control = control.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let Some(job) = control.last_job() else {
+                                    log::warn!("⚠️ No hay trabajos para reimprimir");
+                                    return;
+                                };
+                                if control.is_paused() {
+                                    log::warn!("⏸️ Reimpresión rechazada: bridge en pausa");
+                                    return;
+                                }
+                                // Por el mismo camino que la API (cola, cuotas, historial y
+                                // auditoría) y con la configuración vigente
+                                let history = match tauri::async_runtime::spawn_blocking(history::JobHistory::open).await {
+                                    Ok(history) => history,
+                                    Err(e) => {
+                                        log::error!("❌ Error reimprimiendo: {}", e);
+                                        return;
+                                    }
+                                };
+                                match api::print_local(job, control.config(), control.clone(), history).await {
+                                    Ok(_) => log::info!("🔁 Último trabajo reimpreso"),
+                                    Err(e) => log::error!("❌ Error reimprimiendo: {}", e),
+                                }
+                            });
+                        }
                         _ => {}
                     })
                     .on_tray_icon_event(|tray, event| {
@@ -146,6 +291,23 @@ async fn start_gui_app(config: config::Config) -> Result<(), Box<dyn std::error:
                         }
                     })
                     .build(app)?;
+                
+                // El tooltip dice en qué puerto escucha o por qué no arrancó
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        let tooltip = match &*server_state.borrow_and_update() {
+                            ServerState::Starting => "Print My Bridge (iniciando...)".to_string(),
+                            ServerState::Running { address, .. } => format!("Print My Bridge · puerto {}", address.port()),
+                            ServerState::Draining => "Print My Bridge (cerrando...)".to_string(),
+                            ServerState::Failed { error } => format!("Print My Bridge · error: {}", error),
+                            ServerState::Stopped => "Print My Bridge (detenido)".to_string(),
+                        };
+                        let _ = tray.set_tooltip(Some(tooltip));
+                        if server_state.changed().await.is_err() {
+                            break;
+                        }
+                    }
+                });
             }
             
             Ok(())
@@ -163,7 +325,18 @@ async fn start_gui_app(config: config::Config) -> Result<(), Box<dyn std::error:
             gui::update_config,
             gui::generate_new_token,
             gui::get_bridge_status,
-            gui::toggle_auto_start
+            gui::restart_server,
+            gui::toggle_auto_start,
+            gui::get_tool_status,
+            gui::set_tool_path,
+            gui::get_tls_fingerprint,
+            gui::discover_printers,
+            gui::add_discovered_printer,
+            gui::get_queue_status,
+            gui::set_queue_paused,
+            gui::get_log_level,
+            gui::set_log_level,
+            gui::export_diagnostics
         ])
         .run(tauri::generate_context!())
         .expect("Error ejecutando aplicación Tauri");
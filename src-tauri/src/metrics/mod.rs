@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Trabajos terminados bien y mal
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Outcomes {
+    pub succeeded: u64,
+    pub failed: u64,
+}
+
+impl Outcomes {
+    fn add(&mut self, success: bool) {
+        if success {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+}
+
+/// Contadores desde que arrancó el bridge; no sobreviven a un reinicio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// Solicitudes HTTP atendidas, de cualquier ruta y resultado
+    pub requests: u64,
+    pub jobs: Outcomes,
+    pub by_content_type: BTreeMap<String, Outcomes>,
+    pub by_printer: BTreeMap<String, Outcomes>,
+}
+
+/// Contadores en memoria que actualizan la API y los trabajos, y leen la GUI
+/// y `/api/stats`. El historial da los mismos números por periodo, pero
+/// consultarlo en cada refresco del panel sería caro.
+#[derive(Clone, Default)]
+pub struct Metrics(Arc<Counters>);
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    by_content_type: Mutex<BTreeMap<String, Outcomes>>,
+    by_printer: Mutex<BTreeMap<String, Outcomes>>,
+}
+
+impl Metrics {
+    pub fn record_request(&self) {
+        self.0.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Un trabajo terminado; `printer` es la cola en que acabó, si se llegó a elegir
+    pub fn record_job(&self, content_type: &str, printer: Option<&str>, success: bool) {
+        let counter = if success { &self.0.succeeded } else { &self.0.failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        self.0
+            .by_content_type
+            .lock()
+            .unwrap()
+            .entry(content_type.to_string())
+            .or_default()
+            .add(success);
+        if let Some(printer) = printer {
+            self.0.by_printer.lock().unwrap().entry(printer.to_string()).or_default().add(success);
+        }
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.0.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            requests: self.requests(),
+            jobs: Outcomes {
+                succeeded: self.0.succeeded.load(Ordering::Relaxed),
+                failed: self.0.failed.load(Ordering::Relaxed),
+            },
+            by_content_type: self.0.by_content_type.lock().unwrap().clone(),
+            by_printer: self.0.by_printer.lock().unwrap().clone(),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::api::{PrintRequest, PrintResponse, PrinterDetail, PrinterInfo, PrinterOption};
+use crate::config::{Config, MockPrinter, MockStatus};
+use crate::error::{BridgeError, BridgeResult};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Clave con la que las impresoras simuladas exponen sus bandejas
+const TRAY_OPTION_KEY: &str = "InputSlot";
+
+/// Trabajos recibidos por cada impresora simulada desde que arrancó el bridge.
+/// Con el mismo orden de envíos, los fallos caen siempre en los mismos trabajos.
+static JOB_COUNTS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+/// Impresora simulada con ese nombre; `default` es la predeterminada
+fn find<'a>(name: &str, config: &'a Config) -> BridgeResult<&'a MockPrinter> {
+    let name = match name {
+        "default" => default_printer(config).unwrap_or(name),
+        name => name,
+    };
+    config
+        .mock
+        .printers
+        .iter()
+        .find(|printer| printer.name == name)
+        .ok_or_else(|| BridgeError::PrinterNotFound(name.to_string()))
+}
+
+/// `default_printer` si es una de las simuladas; si no, la primera
+fn default_printer(config: &Config) -> Option<&str> {
+    let printers = &config.mock.printers;
+    config
+        .default_printer
+        .as_deref()
+        .and_then(|name| config.resolve_printer(name).ok())
+        .filter(|name| printers.iter().any(|printer| printer.name == *name))
+        .or_else(|| printers.first().map(|printer| printer.name.as_str()))
+}
+
+fn status(printer: &MockPrinter) -> &'static str {
+    match printer.status {
+        MockStatus::Idle => "idle",
+        MockStatus::Disabled => "disabled",
+        MockStatus::Offline => "unknown",
+    }
+}
+
+pub fn printers(config: &Config) -> Vec<PrinterInfo> {
+    let default = default_printer(config);
+    config
+        .mock
+        .printers
+        .iter()
+        .map(|printer| PrinterInfo {
+            name: printer.name.clone(),
+            status: status(printer).to_string(),
+            is_default: default == Some(printer.name.as_str()),
+            supports_color: true,
+            paper_sizes: vec!["A4".to_string(), "Letter".to_string()],
+            trays: printer.trays.clone(),
+            aliases: config.aliases_for(&printer.name),
+        })
+        .collect()
+}
+
+pub fn detail(name: &str, config: &Config) -> BridgeResult<PrinterDetail> {
+    let printer = find(name, config)?;
+    let options = if printer.trays.is_empty() {
+        Vec::new()
+    } else {
+        vec![PrinterOption {
+            key: TRAY_OPTION_KEY.to_string(),
+            label: "Bandeja".to_string(),
+            default: printer.trays.first().cloned(),
+            choices: printer.trays.clone(),
+        }]
+    };
+
+    Ok(PrinterDetail {
+        name: printer.name.clone(),
+        status: status(printer).to_string(),
+        is_default: default_printer(config) == Some(printer.name.as_str()),
+        aliases: config.aliases_for(&printer.name),
+        description: Some("Impresora simulada".to_string()),
+        location: None,
+        queue_length: 0,
+        options,
+        supplies: Vec::new(),
+    })
+}
+
+/// Igual que la comprobación previa de CUPS, con el estado configurado.
+/// Devuelve el dispositivo simulado.
+pub fn preflight(name: &str, config: &Config) -> BridgeResult<String> {
+    let printer = find(name, config)?;
+    match printer.status {
+        MockStatus::Idle => Ok(format!("mock://{}", printer.name)),
+        MockStatus::Disabled => Err(BridgeError::PrinterOffline {
+            printer: printer.name.clone(),
+            reason: "la cola está deshabilitada".to_string(),
+        }),
+        MockStatus::Offline => Err(BridgeError::PrinterOffline {
+            printer: printer.name.clone(),
+            reason: "el dispositivo simulado no responde".to_string(),
+        }),
+    }
+}
+
+/// Valida `tray` contra las bandejas de la impresora simulada
+pub fn tray_option_key(name: &str, tray: &str, config: &Config) -> BridgeResult<String> {
+    let printer = find(name, config)?;
+    if printer.trays.is_empty() {
+        return Err(BridgeError::InvalidRequest(format!("La impresora {} no permite elegir bandeja", name)));
+    }
+    if !printer.trays.iter().any(|choice| choice == tray) {
+        return Err(BridgeError::InvalidRequest(format!(
+            "Bandeja '{}' no disponible en {}; opciones: {}",
+            tray,
+            name,
+            printer.trays.join(", ")
+        )));
+    }
+    Ok(TRAY_OPTION_KEY.to_string())
+}
+
+fn output_dir(config: &Config) -> PathBuf {
+    config
+        .mock
+        .output_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate::config::app_data_dir().join("mock-output"))
+}
+
+/// "Imprime" el documento ya convertido escribiéndolo en `output_dir`, junto
+/// a un JSON con los datos del trabajo. Los fallos de `fail_every` se
+/// producen antes de escribir nada, como si `lp` hubiera rechazado el trabajo.
+pub fn print(
+    name: &str,
+    request: &PrintRequest,
+    document: &[u8],
+    extension: &str,
+    lp_args: &[String],
+    config: &Config,
+) -> BridgeResult<PrintResponse> {
+    let printer = find(name, config)?;
+
+    let number = {
+        let mut counts = JOB_COUNTS.lock().unwrap();
+        let count = counts.entry(printer.name.clone()).or_default();
+        *count += 1;
+        *count
+    };
+
+    if printer.fail_every > 0 && number % u64::from(printer.fail_every) == 0 {
+        log::warn!("🧪 Fallo simulado en {} (trabajo {})", printer.name, number);
+        return Err(BridgeError::PrintError(format!(
+            "Fallo simulado: trabajo {} de {}",
+            number, printer.name
+        )));
+    }
+
+    let job_id = format!("{}-{}", printer.name, number);
+    let dir = output_dir(config);
+    std::fs::create_dir_all(&dir)?;
+
+    let document_path = dir.join(format!("{}.{}", job_id, extension));
+    std::fs::write(&document_path, document)?;
+
+    let metadata = serde_json::json!({
+        "job_id": job_id,
+        "printer": printer.name,
+        "content_type": request.content_type,
+        "copies": request.copies.unwrap_or(1),
+        "options": request.options,
+        "lp_args": lp_args,
+        "document": document_path.file_name().map(|name| name.to_string_lossy()),
+    });
+    std::fs::write(
+        dir.join(format!("{}.json", job_id)),
+        serde_json::to_vec_pretty(&metadata).map_err(|e| BridgeError::IoError(e.into()))?,
+    )?;
+
+    log::info!("🧪 Trabajo {} simulado en {}", job_id, document_path.display());
+    Ok(PrintResponse {
+        success: true,
+        message: "Documento enviado a la impresora simulada".to_string(),
+        job_id: Some(job_id),
+        printer: None,
+        deprecation: Vec::new(),
+        dry_run: None,
+        pages: None,
+    })
+}
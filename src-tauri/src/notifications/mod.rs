@@ -0,0 +1,93 @@
+use crate::control::BridgeControl;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::time::Duration;
+
+/// Avisos a disparar cuando un trabajo termina (con éxito o con error)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NotifyOptions {
+    #[serde(default)]
+    pub sound: bool,
+    #[serde(default)]
+    pub desktop: bool,
+    pub webhook: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct JobOutcome {
+    pub success: bool,
+    pub message: String,
+    pub job_id: Option<String>,
+    pub printer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<u64>,
+}
+
+/// Lanza los avisos en segundo plano para no retrasar la respuesta HTTP
+pub fn dispatch(options: NotifyOptions, outcome: JobOutcome, control: BridgeControl) {
+    tokio::spawn(async move {
+        if options.sound {
+            if let Err(e) = play_sound(outcome.success) {
+                log::warn!("⚠️ No se pudo reproducir el sonido: {}", e);
+            }
+        }
+
+        if options.desktop {
+            let title = if outcome.success { "Impresión completada" } else { "Impresión fallida" };
+            if !control.notify_desktop(title, &outcome.message) {
+                log::debug!("Notificación de escritorio no disponible en modo headless");
+            }
+        }
+
+        if let Some(url) = &options.webhook {
+            if let Err(e) = send_webhook(url, &outcome).await {
+                log::warn!("⚠️ Error enviando webhook a {}: {}", url, e);
+            }
+        }
+    });
+}
+
+async fn send_webhook(url: &str, outcome: &JobOutcome) -> Result<(), reqwest::Error> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    client.post(url).json(outcome).send().await?.error_for_status()?;
+    log::debug!("📨 Webhook enviado a {}", url);
+    Ok(())
+}
+
+/// Reproduce un sonido del sistema distinto para éxito y error
+fn play_sound(success: bool) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let sound = if success { "Glass" } else { "Basso" };
+        Command::new("afplay")
+            .arg(format!("/System/Library/Sounds/{}.aiff", sound))
+            .spawn()?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let sound = if success { "Asterisk" } else { "Hand" };
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command", &format!("[System.Media.SystemSounds]::{}.Play()", sound)])
+            .spawn()?;
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let sound = if success { "complete" } else { "dialog-error" };
+        Command::new("paplay")
+            .arg(format!("/usr/share/sounds/freedesktop/stereo/{}.oga", sound))
+            .spawn()?;
+    }
+
+    Ok(())
+}
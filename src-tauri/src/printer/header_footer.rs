@@ -0,0 +1,155 @@
+use super::TEXT_LINES_PER_PAGE;
+use crate::api::PrintRequest;
+use crate::config::Config;
+use chrono::{DateTime, Local};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// Marcadores de las plantillas, además de `{metadata.<clave>}`
+const PLACEHOLDERS: [&str; 7] = ["business", "date", "time", "page", "pages", "external_id", "request_id"];
+
+fn placeholder() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| Regex::new(r"\{([a-z_]+(?:\.[^{}]+)?)\}").unwrap())
+}
+
+/// Comprueba que la plantilla solo usa marcadores conocidos
+pub fn check(template: &str) -> Result<(), String> {
+    for captures in placeholder().captures_iter(template) {
+        let name = &captures[1];
+        if !PLACEHOLDERS.contains(&name) && !name.starts_with("metadata.") {
+            return Err(format!(
+                "marcador desconocido {{{}}}; use {} o {{metadata.<clave>}}",
+                name,
+                PLACEHOLDERS.map(|name| format!("{{{}}}", name)).join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Cabecera y pie de un trabajo, con los datos para rellenar sus marcadores
+pub struct HeaderFooter<'a> {
+    header: Option<&'a str>,
+    footer: Option<&'a str>,
+    request: &'a PrintRequest,
+    business: &'a str,
+    printed_at: DateTime<Local>,
+}
+
+impl<'a> HeaderFooter<'a> {
+    /// `None` si el trabajo no lleva ni cabecera ni pie. Los de la solicitud
+    /// ganan a los de `[header_footer]`; uno vacío quita el configurado.
+    pub fn for_request(request: &'a PrintRequest, config: &'a Config) -> Option<Self> {
+        let options = request.options.as_ref();
+        let header = choose(options.and_then(|options| options.header.as_ref()), config.header_footer.header.as_ref());
+        let footer = choose(options.and_then(|options| options.footer.as_ref()), config.header_footer.footer.as_ref());
+        if header.is_none() && footer.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            header,
+            footer,
+            request,
+            business: &config.header_footer.business_name,
+            printed_at: Local::now(),
+        })
+    }
+
+    pub fn header(&self, page: u32, pages: u32) -> Option<String> {
+        self.header.map(|template| self.fill(template, page, pages))
+    }
+
+    pub fn footer(&self, page: u32, pages: u32) -> Option<String> {
+        self.footer.map(|template| self.fill(template, page, pages))
+    }
+
+    /// El texto con la cabecera al principio de cada página y el pie al
+    /// final. Se pagina como estima `text_pages`, con los saltos (`\f`)
+    /// explícitos para que los números de página coincidan.
+    pub fn apply_to_text(&self, text: &str) -> String {
+        let header_lines = self.header.map_or(0, |header| header.lines().count() + 1);
+        let footer_lines = self.footer.map_or(0, |footer| footer.lines().count() + 1);
+        let body_lines = TEXT_LINES_PER_PAGE.saturating_sub(header_lines + footer_lines).max(1);
+
+        let text = text.strip_suffix('\u{c}').unwrap_or(text);
+        let pages: Vec<Vec<&str>> = text
+            .split('\u{c}')
+            .flat_map(|page| {
+                let lines: Vec<&str> = page.lines().collect();
+                if lines.is_empty() {
+                    vec![Vec::new()]
+                } else {
+                    lines.chunks(body_lines).map(<[&str]>::to_vec).collect()
+                }
+            })
+            .collect();
+
+        let total = pages.len() as u32;
+        let pages: Vec<String> = pages
+            .iter()
+            .zip(1..)
+            .map(|(lines, number)| {
+                let mut page = String::new();
+                if let Some(header) = self.header(number, total) {
+                    page.push_str(&header);
+                    page.push_str("\n\n");
+                }
+                for line in lines {
+                    page.push_str(line);
+                    page.push('\n');
+                }
+                if let Some(footer) = self.footer(number, total) {
+                    page.push('\n');
+                    page.push_str(&footer);
+                    page.push('\n');
+                }
+                page
+            })
+            .collect();
+        pages.join("\u{c}")
+    }
+
+    /// Para papel continuo: la cabecera al principio y el pie al final, como
+    /// una sola página
+    pub fn apply_to_roll(&self, text: &str) -> String {
+        let mut roll = String::new();
+        if let Some(header) = self.header(1, 1) {
+            roll.push_str(&header);
+            roll.push_str("\n\n");
+        }
+        roll.push_str(text.trim_end_matches(['\n', '\u{c}']));
+        roll.push('\n');
+        if let Some(footer) = self.footer(1, 1) {
+            roll.push('\n');
+            roll.push_str(&footer);
+            roll.push('\n');
+        }
+        roll
+    }
+
+    fn fill(&self, template: &str, page: u32, pages: u32) -> String {
+        placeholder()
+            .replace_all(template, |captures: &Captures| match &captures[1] {
+                "business" => self.business.to_string(),
+                "date" => self.printed_at.format("%Y-%m-%d").to_string(),
+                "time" => self.printed_at.format("%H:%M").to_string(),
+                "page" => page.to_string(),
+                "pages" => pages.to_string(),
+                "external_id" => self.request.external_id.clone().unwrap_or_default(),
+                "request_id" => self.request.request_id.clone().unwrap_or_default(),
+                name => match name.strip_prefix("metadata.").and_then(|key| self.request.metadata.get(key)) {
+                    Some(serde_json::Value::String(value)) => value.clone(),
+                    Some(serde_json::Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                },
+            })
+            .into_owned()
+    }
+}
+
+/// La plantilla de la solicitud si la hay, si no la configurada
+fn choose<'a>(own: Option<&'a String>, configured: Option<&'a String>) -> Option<&'a str> {
+    own.or(configured).map(String::as_str).filter(|template| !template.trim().is_empty())
+}
@@ -0,0 +1,214 @@
+use crate::api::{ImageFit, PrintOptions};
+use crate::config::Config;
+use crate::error::{BridgeError, BridgeResult};
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+use std::io::Cursor;
+
+/// Margen alrededor de la imagen, dentro del área imprimible habitual (0.25in)
+const PAGE_MARGIN_PT: f32 = 18.0;
+
+/// Formatos que los filtros de imagen de CUPS imprimen directamente
+const NATIVE_FORMATS: [ImageFormat; 5] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Tiff,
+    ImageFormat::Gif,
+    ImageFormat::Bmp,
+];
+
+/// Imagen lista para enviar a `lp`, con la extensión que corresponde a su formato
+pub struct PreparedImage {
+    pub data: Vec<u8>,
+    pub extension: &'static str,
+}
+
+/// Detecta el formato (por `mime` o por los bytes mágicos) y, si la impresora
+/// no lo entiende, lo convierte a PNG cuando la configuración lo permite.
+pub fn prepare(data: Vec<u8>, mime: Option<&str>, config: &Config) -> BridgeResult<PreparedImage> {
+    let format = detect(&data, mime)?;
+
+    if NATIVE_FORMATS.contains(&format) {
+        return Ok(PreparedImage {
+            data,
+            extension: extension(format),
+        });
+    }
+
+    if !config.transcode_images {
+        return Err(BridgeError::UnsupportedFormat(format!("imagen {:?}", format)));
+    }
+
+    log::debug!("🖼️ Convirtiendo imagen {:?} a PNG", format);
+    let decoded = image::load_from_memory_with_format(&data, format)
+        .map_err(|e| BridgeError::InvalidRequest(format!("Imagen {:?} inválida: {}", format, e)))?;
+
+    let mut png = Cursor::new(Vec::new());
+    decoded
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|e| BridgeError::PrintError(format!("No se pudo convertir la imagen: {}", e)))?;
+
+    Ok(PreparedImage {
+        data: png.into_inner(),
+        extension: "png",
+    })
+}
+
+fn detect(data: &[u8], mime: Option<&str>) -> BridgeResult<ImageFormat> {
+    if let Some(mime) = mime {
+        match ImageFormat::from_mime_type(mime) {
+            Some(format) => return Ok(format),
+            None => log::warn!("⚠️ MIME de imagen desconocido {}; detectando por contenido", mime),
+        }
+    }
+
+    image::guess_format(data)
+        .map_err(|_| BridgeError::UnsupportedFormat("formato de imagen no reconocido".to_string()))
+}
+
+fn extension(format: ImageFormat) -> &'static str {
+    format.extensions_str().first().copied().unwrap_or("img")
+}
+
+
+/// Compone la imagen sobre una página PDF del tamaño pedido, ajustada según
+/// `fit` y girada si `auto_rotate` lo indica.
+pub fn compose_pdf(data: &[u8], options: &PrintOptions, config: &Config) -> BridgeResult<Vec<u8>> {
+    let mut decoded = image::load_from_memory(data)
+        .map_err(|e| BridgeError::InvalidRequest(format!("Imagen inválida: {}", e)))?;
+
+    let (page_width, page_height) = page_size(options, config)?;
+
+    let landscape_image = decoded.width() > decoded.height();
+    if options.auto_rotate.unwrap_or(false) && landscape_image != (page_width > page_height) {
+        decoded = decoded.rotate90();
+    }
+
+    let area_width = page_width - 2.0 * PAGE_MARGIN_PT;
+    let area_height = page_height - 2.0 * PAGE_MARGIN_PT;
+    let (pixel_width, pixel_height) = (decoded.width() as f32, decoded.height() as f32);
+
+    let scale = match options.fit.unwrap_or(ImageFit::Contain) {
+        ImageFit::Contain => (area_width / pixel_width).min(area_height / pixel_height),
+        ImageFit::Cover => (area_width / pixel_width).max(area_height / pixel_height),
+        // 96 ppp, la misma convención que CSS
+        ImageFit::Actual => 0.75,
+    };
+    let draw_width = pixel_width * scale;
+    let draw_height = pixel_height * scale;
+    let x = (page_width - draw_width) / 2.0;
+    let y = (page_height - draw_height) / 2.0;
+
+    let rgb = flatten(&decoded);
+    let mut image_stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => rgb.width() as i64,
+            "Height" => rgb.height() as i64,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+        },
+        rgb.into_raw(),
+    );
+    let _ = image_stream.compress();
+
+    let content = Content {
+        operations: vec![
+            Operation::new("q", vec![]),
+            // Recortar al área útil para que "cover" no se salga del margen
+            Operation::new("re", vec![
+                PAGE_MARGIN_PT.into(),
+                PAGE_MARGIN_PT.into(),
+                area_width.into(),
+                area_height.into(),
+            ]),
+            Operation::new("W", vec![]),
+            Operation::new("n", vec![]),
+            Operation::new("cm", vec![
+                draw_width.into(),
+                0.into(),
+                0.into(),
+                draw_height.into(),
+                x.into(),
+                y.into(),
+            ]),
+            Operation::new("Do", vec!["Im1".into()]),
+            Operation::new("Q", vec![]),
+        ],
+    };
+
+    build_single_page(image_stream, content, page_width, page_height)
+        .map_err(|e| BridgeError::PrintError(format!("No se pudo generar el PDF de la imagen: {}", e)))
+}
+
+pub(super) fn build_single_page(image: Stream, content: Content, width: f32, height: f32) -> lopdf::Result<Vec<u8>> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let image_id = doc.add_object(image);
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode()?));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+        "Resources" => dictionary! {
+            "XObject" => dictionary! { "Im1" => image_id },
+        },
+    });
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page_id.into()],
+        "Count" => 1,
+    }));
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut pdf = Vec::new();
+    doc.save_to(&mut pdf)?;
+    Ok(pdf)
+}
+
+/// DeviceRGB no tiene canal alfa: las zonas transparentes quedan en blanco
+fn flatten(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let pixel = rgba.get_pixel(x, y);
+        let alpha = u16::from(pixel[3]);
+        let blend = |channel: u8| ((u16::from(channel) * alpha + 255 * (255 - alpha)) / 255) as u8;
+        Rgb([blend(pixel[0]), blend(pixel[1]), blend(pixel[2])])
+    })
+}
+
+/// Ancho y alto en puntos de la página pedida en `paper_size` y
+/// `orientation`, o del tamaño de `[render]`
+pub(super) fn page_size(options: &PrintOptions, config: &Config) -> BridgeResult<(f32, f32)> {
+    let paper = options.paper_size.as_deref().unwrap_or(&config.render.page_size);
+    let (width, height) = page_size_points(paper).ok_or_else(|| {
+        BridgeError::InvalidRequest(format!("Tamaño de papel desconocido: {}", paper))
+    })?;
+
+    match options.orientation.as_deref() {
+        Some("landscape") => Ok((height, width)),
+        Some("portrait") | None => Ok((width, height)),
+        Some(other) => Err(BridgeError::InvalidRequest(format!("Orientación desconocida: {}", other))),
+    }
+}
+
+/// Tamaño de página en puntos (1/72in), en vertical
+fn page_size_points(name: &str) -> Option<(f32, f32)> {
+    match name.to_ascii_lowercase().as_str() {
+        "a3" => Some((842.0, 1191.0)),
+        "a4" => Some((595.0, 842.0)),
+        "a5" => Some((420.0, 595.0)),
+        "a6" => Some((298.0, 420.0)),
+        "letter" => Some((612.0, 792.0)),
+        "legal" => Some((612.0, 1008.0)),
+        "tabloid" => Some((792.0, 1224.0)),
+        _ => None,
+    }
+}
@@ -0,0 +1,204 @@
+use super::pdf;
+use crate::error::{BridgeError, BridgeResult};
+use crate::templates::{ean13_payload, LabelElement, LabelLayout, Symbology};
+use barcoders::sym::code128::Code128;
+use barcoders::sym::ean13::EAN13;
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+use qrcode::QrCode;
+
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+/// Altura del texto bajo las barras, igual que la que usa la Zebra a 203 ppp
+const HUMAN_READABLE_MM: f32 = 2.5;
+
+/// Ancho medio de un carácter de Helvetica en proporción al cuerpo
+const HELVETICA_CHAR_WIDTH: f32 = 0.55;
+
+/// La etiqueta en ZPL para una impresora de `dpi` puntos por pulgada. Los
+/// códigos de barras los dibuja la propia impresora.
+pub fn to_zpl(layout: &LabelLayout, dpi: u32) -> Vec<u8> {
+    let dots = |mm: f32| ((mm * dpi as f32 / 25.4).round() as i64).max(1);
+    let mut zpl = format!("^XA^CI28^PW{}^LL{}\n", dots(layout.width_mm), dots(layout.height_mm));
+    for element in &layout.elements {
+        let (x_mm, y_mm) = element.position();
+        let origin = format!("^FO{},{}", dots(x_mm), dots(y_mm));
+        let field = match element {
+            LabelElement::Text { text, height_mm, .. } => {
+                format!("^A0N,{0},{0}^FH_^FD{1}^FS", dots(*height_mm), zpl_escape(text))
+            }
+            LabelElement::Barcode { value, symbology, height_mm, module_mm, human_readable, .. } => {
+                let module = dots(module_mm.unwrap_or(symbology.default_module_mm())).min(10);
+                let interpretation = if *human_readable { "Y" } else { "N" };
+                match symbology {
+                    Symbology::Code128 => format!(
+                        "^BY{}^BCN,{},{},N,N,A^FH_^FD{}^FS",
+                        module,
+                        dots(*height_mm),
+                        interpretation,
+                        zpl_escape(value)
+                    ),
+                    // La impresora calcula el dígito de control
+                    Symbology::Ean13 => format!(
+                        "^BY{}^BEN,{},{},N^FD{}^FS",
+                        module,
+                        dots(*height_mm),
+                        interpretation,
+                        ean13_payload(value).unwrap_or(value)
+                    ),
+                    Symbology::Qr => format!("^BQN,2,{}^FH_^FDMA,{}^FS", module, zpl_escape(value)),
+                }
+            }
+            LabelElement::Rect { width_mm, height_mm, thickness_mm, .. } => {
+                format!("^GB{},{},{}^FS", dots(*width_mm), dots(*height_mm), dots(*thickness_mm))
+            }
+        };
+        zpl.push_str(&origin);
+        zpl.push_str(&field);
+        zpl.push('\n');
+    }
+    zpl.push_str("^XZ\n");
+    zpl.into_bytes()
+}
+
+/// Con `^FH_` los caracteres de control de ZPL van en hexadecimal
+fn zpl_escape(text: &str) -> String {
+    text.replace('_', "_5F").replace('^', "_5E").replace('~', "_7E")
+}
+
+/// La etiqueta en una página PDF de su tamaño, para las impresoras que no
+/// hablan ZPL y para la vista previa
+pub fn to_pdf(layout: &LabelLayout) -> BridgeResult<Vec<u8>> {
+    let page_width = layout.width_mm * POINTS_PER_MM;
+    let page_height = layout.height_mm * POINTS_PER_MM;
+    // Las posiciones se miden desde arriba; en PDF el origen está abajo
+    let top = |y_mm: f32| page_height - y_mm * POINTS_PER_MM;
+
+    let mut operations = Vec::new();
+    for element in &layout.elements {
+        let (x_mm, y_mm) = element.position();
+        let x = x_mm * POINTS_PER_MM;
+        match element {
+            LabelElement::Text { text, height_mm, .. } => {
+                let size = height_mm * POINTS_PER_MM;
+                push_text(&mut operations, text, size, x, top(y_mm) - size * 0.8);
+            }
+            LabelElement::Barcode { value, symbology: Symbology::Qr, module_mm, .. } => {
+                let code = QrCode::new(value.as_bytes())
+                    .map_err(|e| BridgeError::InvalidRequest(format!("'{}' no cabe en un QR: {}", value, e)))?;
+                let module = module_mm.unwrap_or(Symbology::Qr.default_module_mm()) * POINTS_PER_MM;
+                let width = code.width();
+                for (index, color) in code.to_colors().into_iter().enumerate() {
+                    if color == qrcode::Color::Dark {
+                        let (column, row) = ((index % width) as f32, (index / width) as f32);
+                        push_rect(&mut operations, x + column * module, top(y_mm) - (row + 1.0) * module, module, module);
+                    }
+                }
+            }
+            LabelElement::Barcode { value, symbology, height_mm, module_mm, human_readable, .. } => {
+                let modules = linear_modules(value, *symbology)?;
+                let module = module_mm.unwrap_or(symbology.default_module_mm()) * POINTS_PER_MM;
+                let height = height_mm * POINTS_PER_MM;
+                let bottom = top(y_mm) - height;
+                let mut start = None;
+                // Cada tramo de módulos oscuros seguidos es una barra
+                for (index, bar) in modules.iter().chain([&0]).enumerate() {
+                    match (*bar == 1, start) {
+                        (true, None) => start = Some(index),
+                        (false, Some(first)) => {
+                            push_rect(&mut operations, x + first as f32 * module, bottom, (index - first) as f32 * module, height);
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+                if *human_readable {
+                    let size = HUMAN_READABLE_MM * POINTS_PER_MM;
+                    let text_width = value.chars().count() as f32 * size * HELVETICA_CHAR_WIDTH;
+                    let centered = x + (modules.len() as f32 * module - text_width).max(0.0) / 2.0;
+                    push_text(&mut operations, value, size, centered, bottom - size);
+                }
+            }
+            LabelElement::Rect { width_mm, height_mm, thickness_mm, .. } => {
+                let (width, height) = (width_mm * POINTS_PER_MM, height_mm * POINTS_PER_MM);
+                let thickness = thickness_mm * POINTS_PER_MM;
+                let bottom = top(y_mm) - height;
+                if width <= 2.0 * thickness || height <= 2.0 * thickness {
+                    // Más grueso que hueco: una línea o un bloque macizo
+                    push_rect(&mut operations, x, bottom, width, height);
+                } else {
+                    // Como en ZPL, el borde va por dentro del recuadro
+                    let half = thickness / 2.0;
+                    operations.push(Operation::new("w", vec![thickness.into()]));
+                    operations.push(Operation::new("re", vec![
+                        (x + half).into(),
+                        (bottom + half).into(),
+                        (width - thickness).into(),
+                        (height - thickness).into(),
+                    ]));
+                    operations.push(Operation::new("S", vec![]));
+                }
+            }
+        }
+    }
+
+    let content = Content { operations }
+        .encode()
+        .map_err(|e| BridgeError::PrintError(format!("No se pudo generar el PDF de la etiqueta: {}", e)))?;
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let font_id = document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let content_id = document.add_object(Stream::new(dictionary! {}, content));
+    let page_id = document.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "MediaBox" => vec![0.into(), 0.into(), page_width.into(), page_height.into()],
+        "Resources" => dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        },
+    });
+    document.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page_id.into()],
+        "Count" => 1,
+    }));
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    document.trailer.set("Root", catalog_id);
+    pdf::save(&mut document)
+}
+
+/// Los módulos (1 oscuro, 0 claro) de un código lineal, con sus guardas
+fn linear_modules(value: &str, symbology: Symbology) -> BridgeResult<Vec<u8>> {
+    let encoded = match symbology {
+        // 'Ɓ' elige el juego B de Code 128, el que cubre todo el ASCII imprimible
+        Symbology::Code128 => Code128::new(format!("Ɓ{}", value)).map(|code| code.encode()),
+        Symbology::Ean13 => {
+            let payload = ean13_payload(value).map_err(BridgeError::InvalidRequest)?;
+            EAN13::new(payload).map(|code| code.encode())
+        }
+        Symbology::Qr => unreachable!("el QR no es un código lineal"),
+    };
+    encoded.map_err(|e| BridgeError::InvalidRequest(format!("'{}' no se puede codificar: {}", value, e)))
+}
+
+fn push_text(operations: &mut Vec<Operation>, text: &str, size: f32, x: f32, baseline: f32) {
+    operations.push(Operation::new("BT", vec![]));
+    operations.push(Operation::new("Tf", vec!["F1".into(), size.into()]));
+    operations.push(Operation::new("Td", vec![x.into(), baseline.into()]));
+    operations.push(Operation::new("Tj", vec![Object::string_literal(pdf::win_ansi(text))]));
+    operations.push(Operation::new("ET", vec![]));
+}
+
+fn push_rect(operations: &mut Vec<Operation>, x: f32, y: f32, width: f32, height: f32) {
+    operations.push(Operation::new("re", vec![x.into(), y.into(), width.into(), height.into()]));
+    operations.push(Operation::new("f", vec![]));
+}
@@ -1,7 +1,31 @@
-use crate::api::{PrintRequest, PrintResponse, PrinterInfo};
-use crate::error::BridgeResult;
-use crate::config::Config;
-use std::process::Command;
+mod header_footer;
+mod images;
+mod label;
+mod pdf;
+mod pos;
+mod supplies;
+mod svg;
+mod text;
+
+pub use header_footer::check as check_header_footer;
+pub use pdf::PageRange;
+pub use pos::check_display as check_display_message;
+
+use crate::api::{DryRunReport, JobPriority, PrintRequest, PrintResponse, PrinterDetail, PrinterInfo, PrinterOption, TextOptions, TextOutput, Watermark};
+use crate::error::{BridgeError, BridgeResult};
+use crate::config::{Config, PoolStrategy, RenderFallback};
+use crate::control::BridgeControl;
+use crate::mock;
+use crate::renderer;
+use crate::source::SpooledFile;
+use crate::templates::LabelLayout;
+use crate::tools::{self, Tool};
+use header_footer::HeaderFooter;
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
 use tempfile::NamedTempFile;
 use std::io::Write;
 use base64::{Engine as _, engine::general_purpose};
@@ -9,17 +33,59 @@ use regex::Regex;
 
 pub struct PrinterManager;
 
+/// Espera máxima al comprobar que una impresora de red acepta conexiones
+const PREFLIGHT_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Líneas por página con que se estima el texto, que pagina el filtro de CUPS
+const TEXT_LINES_PER_PAGE: usize = 60;
+
+/// Opciones de PPD con las que los drivers exponen la bandeja de entrada
+const TRAY_OPTION_KEYS: [&str; 2] = ["InputSlot", "MediaSource"];
+
+/// Lista de impresoras y momento en que se consultó
+type CachedPrinters = Option<(Instant, Vec<PrinterInfo>)>;
+
+/// Última lista de impresoras, para no lanzar lpstat/lpoptions en cada petición
+#[derive(Clone, Default)]
+pub struct PrinterCache {
+    entry: Arc<tokio::sync::Mutex<CachedPrinters>>,
+}
+
+impl PrinterCache {
+    /// Devuelve la lista en caché si sigue vigente. El candado se mantiene
+    /// durante la consulta para que peticiones simultáneas no la repitan.
+    pub async fn printers(&self, config: &Config, refresh: bool) -> BridgeResult<Vec<PrinterInfo>> {
+        let mut entry = self.entry.lock().await;
+        let ttl = Duration::from_secs(config.printer_cache_ttl_secs);
+        
+        if let Some((fetched_at, printers)) = entry.as_ref() {
+            if !refresh && fetched_at.elapsed() < ttl {
+                return Ok(printers.clone());
+            }
+        }
+        
+        let printers = PrinterManager::get_available_printers(config).await?;
+        *entry = Some((Instant::now(), printers.clone()));
+        Ok(printers)
+    }
+}
+
 impl PrinterManager {
-    pub async fn get_available_printers() -> BridgeResult<Vec<PrinterInfo>> {
+    pub async fn get_available_printers(config: &Config) -> BridgeResult<Vec<PrinterInfo>> {
+        if config.mock.enabled {
+            return Ok(mock::printers(config));
+        }
+        
         let mut printers = Vec::new();
         
         // Obtener impresora por defecto
-        let default_printer = Self::get_default_printer().await?;
+        let default_printer = Self::get_default_printer(config).await?;
         
         // En macOS, usar lpstat para obtener impresoras
-        let output = Command::new("lpstat")
-            .args(["-p", "-d"])
-            .output()?;
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpstat, config)).args(["-p", "-d"]),
+            config.query_timeout(),
+        ).await?;
         
         let stdout = String::from_utf8_lossy(&output.stdout);
         
@@ -28,14 +94,16 @@ impl PrinterManager {
                 let parts: Vec<&str> = line.split_whitespace().collect();
                 if parts.len() >= 2 {
                     let name = parts[1].to_string();
-                    let capabilities = Self::get_printer_capabilities(&name).await?;
+                    let capabilities = Self::get_printer_capabilities(&name, config).await?;
                     
                     printers.push(PrinterInfo {
                         name: name.clone(),
-                        status: Self::get_printer_status(&name).await?,
+                        status: Self::get_printer_status(&name, config).await?,
                         is_default: Some(&name) == default_printer.as_ref(),
                         supports_color: capabilities.supports_color,
                         paper_sizes: capabilities.paper_sizes,
+                        trays: capabilities.trays,
+                        aliases: config.aliases_for(&name),
                     });
                 }
             }
@@ -44,10 +112,159 @@ impl PrinterManager {
         Ok(printers)
     }
     
-    async fn get_default_printer() -> BridgeResult<Option<String>> {
-        let output = Command::new("lpstat")
-            .args(["-d"])
-            .output()?;
+    /// Información completa de una impresora para construir diálogos de impresión
+    pub async fn get_printer_detail(name: &str, config: &Config) -> BridgeResult<PrinterDetail> {
+        if config.mock.enabled {
+            return mock::detail(name, config);
+        }
+        
+        // `lpstat -l -p` falla si la cola no existe y trae descripción y ubicación
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpstat, config)).args(["-l", "-p", name]),
+            config.query_timeout(),
+        ).await?;
+        
+        if !output.status.success() {
+            return Err(BridgeError::PrinterNotFound(name.to_string()));
+        }
+        
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let field = |label: &str| {
+            stdout
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(label))
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let description = field("Description:");
+        let location = field("Location:");
+        
+        let options_output = tools::run(
+            Command::new(tools::command_path(Tool::Lpoptions, config)).args(["-p", name, "-l"]),
+            config.query_timeout(),
+        ).await?;
+        let options = Self::parse_options(&String::from_utf8_lossy(&options_output.stdout));
+        
+        let queue_length = Self::queue_length(name, config).await?;
+        
+        // Los consumibles son informativos: si CUPS no los da, el detalle sigue
+        let supplies = supplies::query(name, config).await.unwrap_or_else(|e| {
+            log::debug!("🔎 Sin niveles de consumibles para {}: {}", name, e);
+            Vec::new()
+        });
+        
+        let default_printer = Self::get_default_printer(config).await?;
+        
+        Ok(PrinterDetail {
+            name: name.to_string(),
+            status: Self::get_printer_status(name, config).await?,
+            is_default: default_printer.as_deref() == Some(name),
+            aliases: config.aliases_for(name),
+            description,
+            location,
+            queue_length,
+            options,
+            supplies,
+        })
+    }
+    
+    /// Crea una cola CUPS para una impresora de red. Las IPP usan el driver
+    /// sin controlador (`everywhere`); las de puerto 9100 reciben los datos tal cual.
+    pub async fn add_network_printer(name: &str, uri: &str, config: &Config) -> BridgeResult<()> {
+        if cfg!(target_os = "windows") {
+            return Err(BridgeError::ConfigError("Añadir impresoras de red requiere CUPS (macOS/Linux)".to_string()));
+        }
+        
+        let model = if uri.starts_with("ipp://") || uri.starts_with("ipps://") { "everywhere" } else { "raw" };
+        
+        // `everywhere` consulta a la impresora, puede tardar como un trabajo
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpadmin, config)).args(["-p", name, "-E", "-v", uri, "-m", model]),
+            config.spool_timeout(),
+        ).await?;
+        
+        if !output.status.success() {
+            return Err(BridgeError::PrinterError(format!(
+                "No se pudo crear la cola {}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        
+        log::info!("🖨️ Cola {} creada para {}", name, uri);
+        Ok(())
+    }
+    
+    /// Trabajos pendientes en la cola; `lpstat -o` da una línea por trabajo
+    async fn queue_length(name: &str, config: &Config) -> BridgeResult<usize> {
+        if config.mock.enabled {
+            return Ok(0);
+        }
+        
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpstat, config)).args(["-o", name]),
+            config.query_timeout(),
+        ).await?;
+        
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count())
+    }
+    
+    /// Interpreta `lpoptions -l`: `Clave/Etiqueta: valor *predeterminado otro`
+    fn parse_options(lpoptions_output: &str) -> Vec<PrinterOption> {
+        lpoptions_output
+            .lines()
+            .filter_map(|line| {
+                let (name, values) = line.split_once(':')?;
+                let (key, label) = match name.split_once('/') {
+                    Some((key, label)) => (key.trim(), label.trim()),
+                    None => (name.trim(), name.trim()),
+                };
+                
+                let mut default = None;
+                let choices = values
+                    .split_whitespace()
+                    .map(|choice| match choice.strip_prefix('*') {
+                        Some(choice) => {
+                            default = Some(choice.to_string());
+                            choice.to_string()
+                        }
+                        None => choice.to_string(),
+                    })
+                    .collect();
+                
+                Some(PrinterOption {
+                    key: key.to_string(),
+                    label: label.to_string(),
+                    default,
+                    choices,
+                })
+            })
+            .collect()
+    }
+    
+    /// Si el planificador de CUPS responde (`lpstat -r`)
+    pub async fn scheduler_running(config: &Config) -> BridgeResult<bool> {
+        if config.mock.enabled {
+            return Ok(true);
+        }
+        
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpstat, config)).args(["-r"]),
+            config.query_timeout(),
+        ).await?;
+        
+        // "scheduler is running" o "scheduler is not running"
+        Ok(String::from_utf8_lossy(&output.stdout).contains("scheduler is running"))
+    }
+    
+    async fn get_default_printer(config: &Config) -> BridgeResult<Option<String>> {
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpstat, config)).args(["-d"]),
+            config.query_timeout(),
+        ).await?;
         
         let stdout = String::from_utf8_lossy(&output.stdout);
         
@@ -61,10 +278,11 @@ impl PrinterManager {
         Ok(None)
     }
     
-    async fn get_printer_status(printer_name: &str) -> BridgeResult<String> {
-        let output = Command::new("lpstat")
-            .args(["-p", printer_name])
-            .output()?;
+    async fn get_printer_status(printer_name: &str, config: &Config) -> BridgeResult<String> {
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpstat, config)).args(["-p", printer_name]),
+            config.query_timeout(),
+        ).await?;
         
         let stdout = String::from_utf8_lossy(&output.stdout);
         
@@ -79,10 +297,11 @@ impl PrinterManager {
         }
     }
     
-    async fn get_printer_capabilities(printer_name: &str) -> BridgeResult<PrinterCapabilities> {
-        let output = Command::new("lpoptions")
-            .args(["-p", printer_name, "-l"])
-            .output()?;
+    async fn get_printer_capabilities(printer_name: &str, config: &Config) -> BridgeResult<PrinterCapabilities> {
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpoptions, config)).args(["-p", printer_name, "-l"]),
+            config.query_timeout(),
+        ).await?;
         
         let stdout = String::from_utf8_lossy(&output.stdout);
         
@@ -91,9 +310,16 @@ impl PrinterManager {
         
         let paper_sizes = Self::extract_paper_sizes(&stdout);
         
+        let trays = Self::parse_options(&stdout)
+            .into_iter()
+            .find(|option| TRAY_OPTION_KEYS.contains(&option.key.as_str()))
+            .map(|option| option.choices)
+            .unwrap_or_default();
+        
         Ok(PrinterCapabilities {
             supports_color,
             paper_sizes,
+            trays,
         })
     }
     
@@ -121,30 +347,654 @@ impl PrinterManager {
         sizes
     }
     
-    pub async fn print(request: PrintRequest, config: &Config) -> BridgeResult<PrintResponse> {
-        let printer_name = request.printer_name
-            .or_else(|| config.default_printer.clone())
-            .unwrap_or_else(|| "default".to_string());
+    pub async fn print(request: &PrintRequest, control: &BridgeControl, config: &Config) -> BridgeResult<PrintResponse> {
+        // En modo de prueba todo va a la cola desechable
+        if let Some(queue) = &config.test_queue {
+            return Self::print_to(queue, request, control, config).await;
+        }
+        
+        let requested = request.printer_name.as_deref()
+            .or(config.default_printer.as_deref())
+            .unwrap_or("default");
+        let mut chain = Self::printer_chain(requested, control, config).await?;
+        let last = chain.pop().expect("la cadena incluye la impresora pedida");
+        
+        // Las de respaldo solo se consultan si la anterior no puede imprimir
+        for printer in &chain {
+            match Self::print_to(printer, request, control, config).await {
+                Ok(response) => return Ok(response),
+                Err(e) if Self::should_fail_over(&e) => {
+                    log::warn!("⚠️ Falló {} ({}); se usa la siguiente de '{}'", printer, e, requested);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        
+        Self::print_to(&last, request, control, config).await
+    }
+    
+    /// Recorre la misma cadena que `print` y se queda con la primera impresora
+    /// disponible, validando el documento y las opciones sin enviar nada
+    pub async fn dry_run(request: &PrintRequest, control: &BridgeControl, config: &Config) -> BridgeResult<DryRunReport> {
+        let chain = match &config.test_queue {
+            Some(queue) => vec![queue.clone()],
+            None => {
+                let requested = request.printer_name.as_deref()
+                    .or(config.default_printer.as_deref())
+                    .unwrap_or("default");
+                Self::printer_chain(requested, control, config).await?
+            }
+        };
+        
+        let mut last_error = None;
+        let mut target = None;
+        for printer in chain {
+            match Self::preflight(&printer, config).await {
+                Ok(device) => {
+                    target = Some((printer, device));
+                    break;
+                }
+                Err(e) if Self::should_fail_over(&e) => {
+                    log::debug!("🧪 {} no disponible en la simulación: {}", printer, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let Some((printer, device)) = target else {
+            return Err(last_error.expect("la cadena incluye la impresora pedida"));
+        };
+        
+        let lp_args = Self::lp_args(&printer, request, config).await?;
+        let rendered_bytes = match request.content_type.as_str() {
+            "text" => match Self::text_options(request).map(|options| options.output) {
+                Some(TextOutput::Escpos) => Some(Self::text_escpos(request, config)?.len() as u64),
+                Some(TextOutput::Pdf) => Some(Self::render_pdf(request, config).await?.len() as u64),
+                None => {
+                    Self::printable_text(request, config)?;
+                    None
+                }
+            },
+            "raw" => Some(Self::binary_content(request)?.len() as u64),
+            "label" => match config.zpl_dpi(&printer) {
+                Some(dpi) => Some(label::to_zpl(&Self::label_layout(request)?, dpi).len() as u64),
+                None => Some(Self::render_pdf(request, config).await?.len() as u64),
+            },
+            _ => Some(Self::render_pdf(request, config).await?.len() as u64),
+        };
+        
+        Ok(DryRunReport {
+            printer,
+            device,
+            lp_args,
+            rendered_bytes,
+        })
+    }
+    
+    /// Colas a probar en orden. En un grupo, la elegida va primero y el resto
+    /// queda de respaldo; fuera de un grupo, la cadena de `printer_fallbacks`.
+    async fn printer_chain(requested: &str, control: &BridgeControl, config: &Config) -> BridgeResult<Vec<String>> {
+        let Some(pool) = config.printer_pools.get(requested) else {
+            return config.printer_chain(requested);
+        };
+        
+        let mut members = pool.printers
+            .iter()
+            .map(|printer| config.resolve_printer(printer).map(str::to_string))
+            .collect::<BridgeResult<Vec<_>>>()?;
+        if members.is_empty() {
+            return Err(BridgeError::ConfigError(format!("El grupo '{}' no tiene impresoras", requested)));
+        }
+        
+        let turn = control.next_in_pool(requested, members.len());
+        members.rotate_left(turn);
+        
+        if pool.strategy == PoolStrategy::LeastQueue {
+            // Orden estable: a igual cola se respeta el turno rotativo
+            let mut lengths = Vec::with_capacity(members.len());
+            for member in &members {
+                lengths.push(Self::queue_length(member, config).await.unwrap_or(usize::MAX));
+            }
+            let mut ranked: Vec<(usize, String)> = lengths.into_iter().zip(members).collect();
+            ranked.sort_by_key(|(length, _)| *length);
+            members = ranked.into_iter().map(|(_, member)| member).collect();
+        }
+        
+        log::debug!("🎯 Grupo '{}': {}", requested, members.join(" > "));
+        Ok(members)
+    }
+    
+    /// Comprueba antes de enviar que la cola existe y acepta trabajos, y que
+    /// las impresoras de red responden, para no devolver el stderr de `lp`.
+    /// Devuelve la URI del dispositivo, que identifica la impresora física.
+    async fn preflight(printer: &str, config: &Config) -> BridgeResult<String> {
+        if config.mock.enabled {
+            return mock::preflight(printer, config);
+        }
+        
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpstat, config)).args(["-p", printer, "-a", printer]),
+            config.query_timeout(),
+        ).await?;
+        
+        if !output.status.success() {
+            return Err(BridgeError::PrinterNotFound(printer.to_string()));
+        }
+        
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.contains("disabled") {
+            return Err(BridgeError::PrinterOffline {
+                printer: printer.to_string(),
+                reason: "la cola está deshabilitada".to_string(),
+            });
+        }
+        if stdout.contains("not accepting") {
+            return Err(BridgeError::PrinterOffline {
+                printer: printer.to_string(),
+                reason: "la cola no acepta trabajos".to_string(),
+            });
+        }
+        
+        // Sin URI (p. ej. clases de CUPS) la propia cola hace de dispositivo
+        let Some(device) = Self::device_uri(printer, config).await? else {
+            return Ok(printer.to_string());
+        };
+        let Some(address) = Self::network_address(&device) else {
+            return Ok(device);
+        };
+        
+        match tokio::time::timeout(PREFLIGHT_CONNECT_TIMEOUT, tokio::net::TcpStream::connect(&address)).await {
+            Ok(Ok(_)) => Ok(device),
+            Ok(Err(e)) => Err(BridgeError::PrinterOffline {
+                printer: printer.to_string(),
+                reason: format!("{} no responde: {}", address, e),
+            }),
+            Err(_) => Err(BridgeError::PrinterOffline {
+                printer: printer.to_string(),
+                reason: format!("{} no respondió en {}s", address, PREFLIGHT_CONNECT_TIMEOUT.as_secs()),
+            }),
+        }
+    }
+    
+    async fn device_uri(printer: &str, config: &Config) -> BridgeResult<Option<String>> {
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpstat, config)).args(["-v", printer]),
+            config.query_timeout(),
+        ).await?;
+        
+        // "device for COLA: socket://192.168.1.50:9100"
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.split_once(": ").map(|(_, uri)| uri.trim().to_string()))
+            .filter(|uri| !uri.is_empty()))
+    }
+    
+    /// `host:puerto` del dispositivo si la URI apunta a una impresora de red
+    fn network_address(uri: &str) -> Option<String> {
+        let url = reqwest::Url::parse(uri).ok()?;
+        
+        let default_port = match url.scheme() {
+            "socket" => 9100,
+            "ipp" | "http" => 631,
+            "ipps" | "https" => 443,
+            "lpd" => 515,
+            _ => return None,
+        };
+        
+        url.host_str().map(|host| format!("{}:{}", host, url.port().unwrap_or(default_port)))
+    }
+    
+    /// Errores del dispositivo, no del documento, en los que el trabajo no
+    /// llegó a CUPS: otra impresora podría imprimirlo. Un `lp` que no termina a
+    /// tiempo puede haberlo encolado ya, y pasarlo a otra lo imprimiría dos veces.
+    fn should_fail_over(error: &BridgeError) -> bool {
+        matches!(
+            error,
+            BridgeError::PrinterError(_)
+                | BridgeError::PrintError(_)
+                | BridgeError::PrinterNotFound(_)
+                | BridgeError::PrinterOffline { .. }
+        )
+    }
+    
+    async fn print_to(printer_name: &str, request: &PrintRequest, control: &BridgeControl, config: &Config) -> BridgeResult<PrintResponse> {
+        let device = Self::preflight(printer_name, config).await?;
+        
+        // Dos trabajos intercalados en la misma impresora se corrompen (ESC/POS);
+        // impresoras distintas sí imprimen en paralelo
+        let _turn = control.jobs().acquire(&device, request.priority).await;
+        
+        let lp_args = Self::lp_args(printer_name, request, config).await?;
+        
+        let mut response = match request.content_type.as_str() {
+            _ if config.mock.enabled => Self::print_mock(printer_name, request, &lp_args, config).await,
+            "pdf" => match &request.spooled {
+                Some(file) => Self::print_pdf_file(printer_name, file.path(), &lp_args, config).await,
+                None => Self::print_pdf(printer_name, &request.content, &lp_args, config).await,
+            },
+            "html" => Self::print_html(printer_name, &Self::text_content(request)?, request, &lp_args, config).await,
+            "markdown" => {
+                let html = renderer::markdown_to_html(&Self::text_content(request)?, config)?;
+                Self::print_html(printer_name, &html, request, &lp_args, config).await
+            }
+            "text" => match Self::text_options(request).map(|options| options.output) {
+                Some(TextOutput::Escpos) => Self::print_raw(printer_name, &Self::text_escpos(request, config)?, &lp_args, config).await,
+                Some(TextOutput::Pdf) => {
+                    let pdf_file = Self::text_pdf(request, config)?;
+                    Self::print_pdf_file(printer_name, pdf_file.path(), &lp_args, config).await
+                }
+                None => Self::print_text(printer_name, &Self::printable_text(request, config)?, &lp_args, config).await,
+            },
+            "image" => Self::print_image(printer_name, request, &lp_args, config).await,
+            "svg" => {
+                let mut pdf_file = NamedTempFile::with_suffix(".pdf")?;
+                pdf_file.write_all(&svg::to_pdf(&Self::binary_content(request)?, config)?)?;
+                Self::print_pdf_file(printer_name, pdf_file.path(), &lp_args, config).await
+            }
+            "docx" | "odt" | "xlsx" => {
+                let pdf_file = Self::office_pdf(request, config).await?;
+                Self::print_pdf_file(printer_name, pdf_file.path(), &lp_args, config).await
+            }
+            // PCL, PostScript o ZPL ya generados: van a la cola sin filtros
+            "raw" => Self::print_raw(printer_name, &Self::binary_content(request)?, &lp_args, config).await,
+            "label" => {
+                let layout = Self::label_layout(request)?;
+                match config.zpl_dpi(printer_name) {
+                    Some(dpi) => Self::print_raw(printer_name, &label::to_zpl(&layout, dpi), &lp_args, config).await,
+                    None => {
+                        let mut pdf_file = NamedTempFile::with_suffix(".pdf")?;
+                        pdf_file.write_all(&label::to_pdf(&layout)?)?;
+                        Self::print_pdf_file(printer_name, pdf_file.path(), &lp_args, config).await
+                    }
+                }
+            }
+            _ => Err(crate::error::BridgeError::UnsupportedFormat(request.content_type.clone())),
+        }?;
+        
+        // El ESC/POS ya lleva el corte, el cajón, el pitido y el visor al final
+        // del ticket; con el resto van en un trabajo raw aparte, todavía con el
+        // turno de la impresora
+        let commands = Self::pos_commands(request, config);
+        if !commands.is_empty() && !Self::is_escpos(request) {
+            if config.mock.enabled {
+                log::info!("🧪 Acciones ESC/POS simuladas tras el trabajo en {}", printer_name);
+            } else if let Err(e) = Self::print_raw(printer_name, &commands, &[], config).await {
+                // El ticket ya salió: reintentarlo lo imprimiría dos veces
+                log::warn!("⚠️ No se pudieron enviar las acciones ESC/POS a {}: {}", printer_name, e);
+            }
+        }
+        
+        response.printer = Some(printer_name.to_string());
+        response.pages = response.pages.map(|pages| pages * u64::from(request.copies.unwrap_or(1)));
+        Ok(response)
+    }
+    
+    /// Abre el cajón portamonedas conectado a `printer` (cola o alias). Va por
+    /// delante de los trabajos en espera de esa impresora, sin cortarlos.
+    pub async fn open_drawer(printer: &str, control: &BridgeControl, config: &Config) -> BridgeResult<PrintResponse> {
+        let queue = config.resolve_printer(printer)?;
+        let device = Self::preflight(queue, config).await?;
+        let _turn = control.jobs().acquire(&device, JobPriority::High).await;
         
+        let mut response = if config.mock.enabled {
+            log::info!("🧪 Apertura de cajón simulada en {}", queue);
+            PrintResponse {
+                success: true,
+                message: "Cajón abierto (simulado)".to_string(),
+                job_id: None,
+                printer: None,
+                deprecation: Vec::new(),
+                dry_run: None,
+                pages: None,
+            }
+        } else {
+            let mut response = Self::print_raw(queue, &pos::drawer_pulse(&config.pos), &[], config).await?;
+            log::info!("💰 Cajón abierto en {}", queue);
+            response.message = "Cajón abierto".to_string();
+            response
+        };
+        response.printer = Some(queue.to_string());
+        Ok(response)
+    }
+    
+    /// Páginas de una copia del documento, sin convertirlo. `None` si no se
+    /// pueden saber antes de renderizar (HTML, Markdown) o el PDF no se lee.
+    pub fn count_pages(request: &PrintRequest) -> Option<u64> {
         match request.content_type.as_str() {
-            "pdf" => Self::print_pdf(&printer_name, &request.content, request.copies).await,
-            "html" => Self::print_html(&printer_name, &request.content, request.copies).await,
-            "text" => Self::print_text(&printer_name, &request.content, request.copies).await,
-            "image" => Self::print_image(&printer_name, &request.content, request.copies).await,
-            _ => Err(crate::error::BridgeError::UnsupportedFormat(request.content_type)),
+            "pdf" => match &request.spooled {
+                Some(file) => Self::pdf_file_pages(file.path()),
+                None => Self::pdf_pages(&Self::binary_content(request).ok()?),
+            },
+            "text" => Some(Self::text_pages(&Self::text_content(request).ok()?)),
+            "image" | "svg" | "label" => Some(1),
+            _ => None,
+        }
+    }
+    
+    /// Prepara un PDF recibido antes de encolarlo: lo descifra con `password`,
+    /// lo valida (ver `pdf::inspect`), deja solo las páginas de `page_range` y
+    /// estampa `watermark`. Devuelve las páginas y, si cambió, el documento nuevo.
+    pub fn prepare_pdf(
+        request: &PrintRequest,
+        password: Option<&str>,
+        page_range: Option<&PageRange>,
+        watermark: Option<&Watermark>,
+    ) -> BridgeResult<(u64, Option<Vec<u8>>)> {
+        let mut document = Self::load_pdf(request)?;
+        let mut changed = match password {
+            Some(password) => pdf::decrypt(&mut document, password)?,
+            None => false,
+        };
+        let mut pages = pdf::inspect(&document)?;
+        
+        if page_range.is_some() || watermark.is_some() {
+            // Para reescribirlo hay que descifrarlo; si llegó aquí se abre sin contraseña
+            pdf::decrypt(&mut document, "")?;
+            changed = true;
+        }
+        if let Some(range) = page_range {
+            pages = pdf::keep_pages(&mut document, range)?;
+        }
+        if let Some(watermark) = watermark {
+            pdf::watermark(&mut document, watermark)?;
+        }
+        
+        let rewritten = if changed { Some(pdf::save(&mut document)?) } else { None };
+        Ok((pages, rewritten))
+    }
+    
+    /// Une los PDF de `files`, en orden, en un solo documento
+    pub fn merge_pdfs(files: &[SpooledFile]) -> BridgeResult<Vec<u8>> {
+        let mut documents = Vec::with_capacity(files.len());
+        for file in files {
+            let mut document = pdf::open(lopdf::Document::load(file.path()))?;
+            // Los cifrados solo con contraseña de propietario se unen ya descifrados
+            pdf::decrypt(&mut document, "")?;
+            documents.push(document);
+        }
+        pdf::save(&mut pdf::merge(documents))
+    }
+    
+    /// PDF renderizado con la cabecera y el pie del trabajo, solo las páginas
+    /// de `options.page_range` y la marca de `options.watermark`
+    fn finish_rendered_pdf(pdf_file: NamedTempFile, request: &PrintRequest, config: &Config) -> BridgeResult<NamedTempFile> {
+        let marks = HeaderFooter::for_request(request, config);
+        let options = request.options.as_ref();
+        let page_range = options.and_then(|options| options.page_range.as_deref());
+        let watermark = options.and_then(|options| options.watermark.as_ref());
+        if marks.is_none() && page_range.is_none() && watermark.is_none() {
+            return Ok(pdf_file);
+        }
+        
+        let mut document = pdf::open(lopdf::Document::load(pdf_file.path()))?;
+        // Numeradas antes de recortar: una página suelta conserva su número
+        if let Some(marks) = &marks {
+            pdf::header_footer(&mut document, marks)?;
+        }
+        if let Some(range) = page_range {
+            let range = PageRange::parse(range).map_err(BridgeError::InvalidRequest)?;
+            pdf::keep_pages(&mut document, &range)?;
+        }
+        if let Some(watermark) = watermark {
+            pdf::watermark(&mut document, watermark)?;
+        }
+        let mut edited = NamedTempFile::with_suffix(".pdf")?;
+        edited.write_all(&pdf::save(&mut document)?)?;
+        Ok(edited)
+    }
+    
+    fn load_pdf(request: &PrintRequest) -> BridgeResult<lopdf::Document> {
+        pdf::open(match &request.spooled {
+            Some(file) => lopdf::Document::load(file.path()),
+            None => lopdf::Document::load_mem(&Self::binary_content(request)?),
+        })
+    }
+    
+    fn pdf_pages(data: &[u8]) -> Option<u64> {
+        lopdf::Document::load_mem(data).ok().map(|document| document.get_pages().len() as u64)
+    }
+    
+    fn pdf_file_pages(path: &Path) -> Option<u64> {
+        lopdf::Document::load(path).ok().map(|document| document.get_pages().len() as u64)
+    }
+    
+    /// Estimación: cada salto de página (`\f`) empieza una nueva
+    fn text_pages(text: &str) -> u64 {
+        text.split('\u{c}')
+            .map(|page| page.lines().count().div_ceil(TEXT_LINES_PER_PAGE).max(1) as u64)
+            .sum()
+    }
+    
+    /// Convierte el documento como para CUPS y lo entrega a la impresora simulada.
+    /// El texto sin `options.text` se guarda tal cual porque su conversión es
+    /// cosa del filtro de CUPS.
+    async fn print_mock(printer: &str, request: &PrintRequest, lp_args: &[String], config: &Config) -> BridgeResult<PrintResponse> {
+        let text_output = Self::text_options(request).map(|options| options.output);
+        let (document, extension, pages) = match request.content_type.as_str() {
+            "text" if text_output == Some(TextOutput::Escpos) => (Self::text_escpos(request, config)?, "bin", None),
+            "raw" => (Self::binary_content(request)?, "bin", None),
+            "label" => match config.zpl_dpi(printer) {
+                Some(dpi) => (label::to_zpl(&Self::label_layout(request)?, dpi), "zpl", Some(1)),
+                None => (label::to_pdf(&Self::label_layout(request)?)?, "pdf", Some(1)),
+            },
+            "text" if text_output.is_none() => {
+                let text = Self::printable_text(request, config)?;
+                (text.as_bytes().to_vec(), "txt", Some(Self::text_pages(&text)))
+            }
+            _ => {
+                let pdf = Self::render_pdf(request, config).await?;
+                let pages = Self::pdf_pages(&pdf);
+                (pdf, "pdf", pages)
+            }
+        };
+        
+        let mut response = mock::print(printer, request, &document, extension, lp_args, config)?;
+        response.pages = pages;
+        Ok(response)
+    }
+    
+    /// PDF del documento tal como lo convierte el bridge, sin enviarlo a CUPS.
+    /// Las imágenes sin `fit` van tal cual a CUPS; aquí se muestran ajustadas a
+    /// la página, que es como las escala CUPS por defecto.
+    pub async fn render_pdf(request: &PrintRequest, config: &Config) -> BridgeResult<Vec<u8>> {
+        let pdf_file = match request.content_type.as_str() {
+            "pdf" => return Self::binary_content(request),
+            "image" => {
+                let options = request.options.clone().unwrap_or_default();
+                return images::compose_pdf(&Self::binary_content(request)?, &options, config);
+            }
+            "svg" => return svg::to_pdf(&Self::binary_content(request)?, config),
+            "label" => return label::to_pdf(&Self::label_layout(request)?),
+            "html" => renderer::html_to_pdf(&Self::text_content(request)?, config).await?,
+            "markdown" => {
+                let html = renderer::markdown_to_html(&Self::text_content(request)?, config)?;
+                renderer::html_to_pdf(&html, config).await?
+            }
+            "docx" | "odt" | "xlsx" => {
+                // Los documentos de oficina ya traen su propia cabecera y pie
+                let pdf_file = Self::office_pdf(request, config).await?;
+                return Ok(std::fs::read(pdf_file.path())?);
+            }
+            "text" if Self::text_options(request).is_some_and(|options| options.output == TextOutput::Pdf) => {
+                Self::text_pdf(request, config)?
+            }
+            // El resto del texto lo convierte el filtro de CUPS al imprimir
+            other => return Err(BridgeError::UnsupportedFormat(format!("{} (sin vista previa)", other))),
+        };
+        
+        let pdf_file = Self::finish_rendered_pdf(pdf_file, request, config)?;
+        Ok(std::fs::read(pdf_file.path())?)
+    }
+    
+    /// Argumentos de `lp` comunes a todos los tipos: copias, papel, orientación,
+    /// color, doble cara y bandeja
+    async fn lp_args(printer: &str, request: &PrintRequest, config: &Config) -> BridgeResult<Vec<String>> {
+        let mut args = vec!["-n".to_string(), request.copies.unwrap_or(1).to_string()];
+        
+        // El id de la solicitud como título del trabajo, para encontrarlo en CUPS
+        if let Some(request_id) = &request.request_id {
+            args.push("-t".to_string());
+            args.push(request_id.clone());
+        }
+        
+        if let Some(options) = &request.options {
+            let mut push = |option: String| {
+                args.push("-o".to_string());
+                args.push(option);
+            };
+            if let Some(paper_size) = &options.paper_size {
+                push(format!("media={}", paper_size));
+            }
+            // Valores IPP de orientation-requested: 3 vertical, 4 apaisado
+            match options.orientation.as_deref() {
+                Some("portrait") => push("orientation-requested=3".to_string()),
+                Some("landscape") => push("orientation-requested=4".to_string()),
+                _ => {}
+            }
+            if let Some(color) = options.color {
+                push(format!("print-color-mode={}", if color { "color" } else { "monochrome" }));
+            }
+            if let Some(duplex) = options.duplex {
+                push(format!("sides={}", if duplex { "two-sided-long-edge" } else { "one-sided" }));
+            }
+        }
+
+        let tray = request.options.as_ref().and_then(|o| o.tray.as_deref());
+        if let Some(tray) = tray {
+            // La cola de prueba es "raw" y no tiene bandejas que validar
+            if config.test_queue.is_none() {
+                let key = Self::tray_option_key(printer, tray, config).await?;
+                args.push("-o".to_string());
+                args.push(format!("{}={}", key, tray));
+            }
+        }
+        
+        Ok(args)
+    }
+    
+    /// Cada driver llama distinto a la bandeja (InputSlot, MediaSource);
+    /// se usa la que tenga la impresora y se valida que `tray` exista.
+    async fn tray_option_key(printer: &str, tray: &str, config: &Config) -> BridgeResult<String> {
+        if config.mock.enabled {
+            return mock::tray_option_key(printer, tray, config);
+        }
+        
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lpoptions, config)).args(["-p", printer, "-l"]),
+            config.query_timeout(),
+        ).await?;
+        
+        let option = Self::parse_options(&String::from_utf8_lossy(&output.stdout))
+            .into_iter()
+            .find(|option| TRAY_OPTION_KEYS.contains(&option.key.as_str()))
+            .ok_or_else(|| BridgeError::InvalidRequest(format!("La impresora {} no permite elegir bandeja", printer)))?;
+        
+        if !option.choices.iter().any(|choice| choice == tray) {
+            return Err(BridgeError::InvalidRequest(format!(
+                "Bandeja '{}' no disponible en {}; opciones: {}",
+                tray,
+                printer,
+                option.choices.join(", ")
+            )));
+        }
+        
+        Ok(option.key)
+    }
+    
+    fn text_options(request: &PrintRequest) -> Option<&TextOptions> {
+        request.options.as_ref().and_then(|options| options.text.as_ref())
+    }
+    
+    /// Texto convertido a PDF según `options.text`, con la cabecera y el pie
+    fn text_pdf(request: &PrintRequest, config: &Config) -> BridgeResult<NamedTempFile> {
+        let options = request.options.clone().unwrap_or_default();
+        let text_options = options.text.clone().unwrap_or_default();
+        let pdf = text::to_pdf(&Self::text_content(request)?, &text_options, &options, config)?;
+        
+        let mut pdf_file = NamedTempFile::with_suffix(".pdf")?;
+        pdf_file.write_all(&pdf)?;
+        Self::finish_rendered_pdf(pdf_file, request, config)
+    }
+    
+    /// Texto convertido a ESC/POS según `options.text`, con la cabecera al
+    /// principio y el pie al final del ticket
+    fn text_escpos(request: &PrintRequest, config: &Config) -> BridgeResult<Vec<u8>> {
+        let text = Self::text_content(request)?;
+        let text = match HeaderFooter::for_request(request, config) {
+            Some(marks) => Cow::Owned(marks.apply_to_roll(&text)),
+            None => text,
+        };
+        let mut data = text::to_escpos(&text, &Self::text_options(request).cloned().unwrap_or_default());
+        data.extend(Self::pos_commands(request, config));
+        Ok(data)
+    }
+    
+    /// Corte, cajón, pitido y visor pedidos en `options`, como ESC/POS
+    fn pos_commands(request: &PrintRequest, config: &Config) -> Vec<u8> {
+        request.options.as_ref().map(|options| pos::after_receipt(options, &config.pos)).unwrap_or_default()
+    }
+    
+    fn is_escpos(request: &PrintRequest) -> bool {
+        request.content_type == "text" && Self::text_options(request).is_some_and(|options| options.output == TextOutput::Escpos)
+    }
+    
+    /// Texto del documento con la cabecera y el pie del trabajo, si los lleva
+    fn printable_text<'a>(request: &'a PrintRequest, config: &Config) -> BridgeResult<Cow<'a, str>> {
+        let text = Self::text_content(request)?;
+        Ok(match HeaderFooter::for_request(request, config) {
+            Some(marks) => Cow::Owned(marks.apply_to_text(&text)),
+            None => text,
+        })
+    }
+    
+    /// Texto de la solicitud, en línea o leído del archivo recibido
+    fn text_content(request: &PrintRequest) -> BridgeResult<Cow<'_, str>> {
+        match &request.spooled {
+            Some(file) => Ok(Cow::Owned(std::fs::read_to_string(file.path())?)),
+            None => Ok(Cow::Borrowed(&request.content)),
+        }
+    }
+    
+    /// Diseño de la etiqueta, ya rellenado al recibir el trabajo
+    fn label_layout(request: &PrintRequest) -> BridgeResult<LabelLayout> {
+        serde_json::from_str(&Self::text_content(request)?)
+            .map_err(|e| BridgeError::InvalidRequest(format!("etiqueta inválida: {}", e)))
+    }
+    
+    /// PDF de un documento de oficina. Las hojas de cálculo se ajustan antes a
+    /// `options.spreadsheet`.
+    async fn office_pdf(request: &PrintRequest, config: &Config) -> BridgeResult<NamedTempFile> {
+        let mut document = Self::binary_content(request)?;
+        if let Some(options) = request.options.as_ref().and_then(|options| options.spreadsheet.as_ref()) {
+            document = renderer::prepare_spreadsheet(&document, options)?;
+        }
+        renderer::office_to_pdf(&document, &request.content_type, config).await
+    }
+    
+    /// Bytes del documento, decodificando base64 o leyendo el archivo recibido
+    fn binary_content(request: &PrintRequest) -> BridgeResult<Vec<u8>> {
+        match &request.spooled {
+            Some(file) => Ok(std::fs::read(file.path())?),
+            None => Ok(general_purpose::STANDARD.decode(&request.content)?),
         }
     }
     
-    async fn print_pdf(printer: &str, content: &str, copies: Option<u32>) -> BridgeResult<PrintResponse> {
+    async fn print_pdf(printer: &str, content: &str, lp_args: &[String], config: &Config) -> BridgeResult<PrintResponse> {
         let pdf_data = general_purpose::STANDARD.decode(content)?;
         let mut temp_file = NamedTempFile::new()?;
         temp_file.write_all(&pdf_data)?;
         
-        let copies_str = copies.unwrap_or(1).to_string();
-        
-        let output = Command::new("lp")
-            .args(["-d", printer, "-n", &copies_str, temp_file.path().to_str().unwrap()])
-            .output()?;
+        Self::print_pdf_file(printer, temp_file.path(), lp_args, config).await
+    }
+    
+    async fn print_pdf_file(printer: &str, path: &Path, lp_args: &[String], config: &Config) -> BridgeResult<PrintResponse> {
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lp, config))
+                .args(["-d", printer])
+                .args(lp_args)
+                .arg(path),
+            config.spool_timeout(),
+        ).await?;
         
         if output.status.success() {
             let job_id = Self::extract_job_id(&output.stdout);
@@ -152,6 +1002,10 @@ impl PrinterManager {
                 success: true,
                 message: "PDF enviado a impresora exitosamente".to_string(),
                 job_id,
+                printer: None,
+                deprecation: Vec::new(),
+                dry_run: None,
+                pages: Self::pdf_file_pages(path),
             })
         } else {
             let error = String::from_utf8_lossy(&output.stderr);
@@ -159,42 +1013,33 @@ impl PrinterManager {
         }
     }
     
-    async fn print_html(_printer: &str, content: &str, _copies: Option<u32>) -> BridgeResult<PrintResponse> {
-        // Convertir HTML a PDF usando wkhtmltopdf
-        let mut html_file = NamedTempFile::with_suffix(".html")?;
-        html_file.write_all(content.as_bytes())?;
-        
-        let pdf_file = NamedTempFile::with_suffix(".pdf")?;
-        
-        let output = Command::new("wkhtmltopdf")
-            .args([
-                "--page-size", "A4",
-                "--margin-top", "0.75in",
-                "--margin-right", "0.75in",
-                "--margin-bottom", "0.75in",
-                "--margin-left", "0.75in",
-                html_file.path().to_str().unwrap(),
-                pdf_file.path().to_str().unwrap()
-            ])
-            .output()?;
-        
-        if output.status.success() {
-            // Ahora imprimir el PDF generado
-            let pdf_data = std::fs::read(pdf_file.path())?;
-            let pdf_base64 = general_purpose::STANDARD.encode(&pdf_data);
-            
-            Self::print_pdf(_printer, &pdf_base64, _copies).await
-        } else {
-            // Fallback: abrir en navegador
-            Command::new("open")
-                .args(["-a", "Safari", html_file.path().to_str().unwrap()])
-                .spawn()?;
-            
-            Ok(PrintResponse {
-                success: true,
-                message: "HTML convertido y enviado a impresora".to_string(),
-                job_id: None,
-            })
+    async fn print_html(printer: &str, content: &str, request: &PrintRequest, lp_args: &[String], config: &Config) -> BridgeResult<PrintResponse> {
+        match renderer::html_to_pdf(content, config).await {
+            Ok(pdf_file) => {
+                // Ahora imprimir el PDF generado
+                let pdf_file = Self::finish_rendered_pdf(pdf_file, request, config)?;
+                Self::print_pdf_file(printer, pdf_file.path(), lp_args, config).await
+            }
+            Err(e) if config.render_fallback == RenderFallback::Browser => {
+                log::warn!("⚠️ {}; abriendo el HTML en el navegador", e);
+                
+                // El archivo debe sobrevivir a la respuesta para que el navegador lo lea
+                let mut html_file = NamedTempFile::with_suffix(".html")?;
+                html_file.write_all(content.as_bytes())?;
+                let (_, path) = html_file.keep().map_err(|e| e.error)?;
+                tools::open_url(&path.display().to_string())?;
+                
+                Ok(PrintResponse {
+                    success: true,
+                    message: "No se pudo convertir el HTML; se abrió en el navegador para imprimirlo manualmente".to_string(),
+                    job_id: None,
+                    printer: None,
+                    deprecation: Vec::new(),
+                    dry_run: None,
+                    pages: None,
+                })
+            }
+            Err(e) => Err(e),
         }
     }
     
@@ -211,21 +1056,27 @@ impl PrinterManager {
         None
     }
     
-    async fn print_text(printer: &str, content: &str, copies: Option<u32>) -> BridgeResult<PrintResponse> {
+    async fn print_text(printer: &str, content: &str, lp_args: &[String], config: &Config) -> BridgeResult<PrintResponse> {
         let mut temp_file = NamedTempFile::with_suffix(".txt")?;
         temp_file.write_all(content.as_bytes())?;
         
-        let copies_str = copies.unwrap_or(1).to_string();
-        
-        let output = Command::new("lp")
-            .args(["-d", printer, "-n", &copies_str, temp_file.path().to_str().unwrap()])
-            .output()?;
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lp, config))
+                .args(["-d", printer])
+                .args(lp_args)
+                .arg(temp_file.path()),
+            config.spool_timeout(),
+        ).await?;
         
         if output.status.success() {
             Ok(PrintResponse {
                 success: true,
                 message: "Texto enviado a impresora exitosamente".to_string(),
                 job_id: Some("text_job_123".to_string()),
+                printer: None,
+                deprecation: Vec::new(),
+                dry_run: None,
+                pages: Some(Self::text_pages(content)),
             })
         } else {
             let error = String::from_utf8_lossy(&output.stderr);
@@ -233,22 +1084,70 @@ impl PrinterManager {
         }
     }
     
-    async fn print_image(printer: &str, content: &str, copies: Option<u32>) -> BridgeResult<PrintResponse> {
-        let image_data = general_purpose::STANDARD.decode(content)?;
-        let mut temp_file = NamedTempFile::with_suffix(".png")?;
-        temp_file.write_all(&image_data)?;
+    /// Envía los datos tal cual (`-o raw`), sin pasar por los filtros de CUPS
+    async fn print_raw(printer: &str, data: &[u8], lp_args: &[String], config: &Config) -> BridgeResult<PrintResponse> {
+        let mut temp_file = NamedTempFile::with_suffix(".bin")?;
+        temp_file.write_all(data)?;
+        
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lp, config))
+                .args(["-d", printer, "-o", "raw"])
+                .args(lp_args)
+                .arg(temp_file.path()),
+            config.spool_timeout(),
+        ).await?;
+        
+        if output.status.success() {
+            Ok(PrintResponse {
+                success: true,
+                message: "Datos enviados a impresora exitosamente".to_string(),
+                job_id: Self::extract_job_id(&output.stdout),
+                printer: None,
+                deprecation: Vec::new(),
+                dry_run: None,
+                pages: None,
+            })
+        } else {
+            let error = String::from_utf8_lossy(&output.stderr);
+            Err(crate::error::BridgeError::PrintError(error.to_string()))
+        }
+    }
+    
+    async fn print_image(printer: &str, request: &PrintRequest, lp_args: &[String], config: &Config) -> BridgeResult<PrintResponse> {
+        let image_data = Self::binary_content(request)?;
+        
+        // Con ajuste o rotación la imagen se compone sobre una página PDF
+        let (data, extension) = match &request.options {
+            Some(options) if options.fit.is_some() || options.auto_rotate.unwrap_or(false) => {
+                (images::compose_pdf(&image_data, options, config)?, "pdf")
+            }
+            _ => {
+                let image = images::prepare(image_data, request.mime.as_deref(), config)?;
+                (image.data, image.extension)
+            }
+        };
         
-        let copies_str = copies.unwrap_or(1).to_string();
+        // Algunos drivers deciden el filtro por la extensión del archivo
+        let mut temp_file = NamedTempFile::with_suffix(format!(".{}", extension))?;
+        temp_file.write_all(&data)?;
         
-        let output = Command::new("lp")
-            .args(["-d", printer, "-n", &copies_str, temp_file.path().to_str().unwrap()])
-            .output()?;
+        let output = tools::run(
+            Command::new(tools::command_path(Tool::Lp, config))
+                .args(["-d", printer])
+                .args(lp_args)
+                .arg(temp_file.path()),
+            config.spool_timeout(),
+        ).await?;
         
         if output.status.success() {
             Ok(PrintResponse {
                 success: true,
                 message: "Imagen enviada a impresora exitosamente".to_string(),
                 job_id: Some("image_job_123".to_string()),
+                printer: None,
+                deprecation: Vec::new(),
+                dry_run: None,
+                pages: Some(1),
             })
         } else {
             let error = String::from_utf8_lossy(&output.stderr);
@@ -260,4 +1159,5 @@ impl PrinterManager {
 struct PrinterCapabilities {
     supports_color: bool,
     paper_sizes: Vec<String>,
+    trays: Vec<String>,
 }
\ No newline at end of file
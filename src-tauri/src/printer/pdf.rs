@@ -0,0 +1,426 @@
+use super::header_footer::HeaderFooter;
+use crate::api::{Watermark, WatermarkPosition};
+use crate::error::{BridgeError, BridgeResult};
+use lopdf::content::{Content, Operation};
+use lopdf::encryption::{self, DecryptionError};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::BTreeSet;
+
+/// Atributos que una página hereda del árbol de páginas si no los define
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Niveles del árbol de páginas que se recorren buscando atributos heredados
+const MAX_TREE_DEPTH: usize = 32;
+
+/// Opacidad de la marca de agua si no se indica
+const DEFAULT_WATERMARK_OPACITY: f32 = 0.3;
+
+/// Cuerpo de la marca en `top` y `bottom`, legible en un ticket de 58 mm
+const STAMP_FONT_SIZE: f32 = 10.0;
+
+/// Distancia de la marca en `top` y `bottom` al borde de la página
+const STAMP_MARGIN_PT: f32 = 6.0;
+
+/// Ancho medio de un carácter de Helvetica en proporción al cuerpo; no se
+/// miden los glifos, basta para centrar y que no se salga de la página
+const HELVETICA_CHAR_WIDTH: f32 = 0.6;
+
+/// Cuerpo de la cabecera y el pie
+const MARK_FONT_SIZE: f32 = 9.0;
+
+/// Distancia entre líneas de una cabecera o pie de varias líneas
+const MARK_LEADING: f32 = 11.0;
+
+/// Distancia de la cabecera y el pie al borde, dentro del área imprimible
+/// habitual (0.25in)
+const MARK_MARGIN_PT: f32 = 18.0;
+
+/// Selección de páginas con la sintaxis de `lp -o page-ranges`: `1-3,5,8-`.
+/// Las páginas salen en el orden del documento, no en el de la lista.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRange(Vec<(u32, Option<u32>)>);
+
+impl PageRange {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let page = |number: &str, part: &str| {
+            number
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|page| *page > 0)
+                .ok_or_else(|| format!("'{}' no es una página ni un rango como 1-3 o 8-", part))
+        };
+
+        let mut parts = Vec::new();
+        for part in text.split(',').map(str::trim) {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) if last.trim().is_empty() => (page(first, part)?, None),
+                Some((first, last)) => (page(first, part)?, Some(page(last, part)?)),
+                None => {
+                    let single = page(part, part)?;
+                    (single, Some(single))
+                }
+            };
+            if last.is_some_and(|last| last < first) {
+                return Err(format!("'{}' está al revés", part));
+            }
+            parts.push((first, last));
+        }
+        Ok(Self(parts))
+    }
+
+    /// Páginas seleccionadas de un documento de `total` páginas
+    fn pages(&self, total: u32) -> BridgeResult<BTreeSet<u32>> {
+        let mut pages = BTreeSet::new();
+        for &(first, last) in &self.0 {
+            if first > total {
+                return Err(BridgeError::InvalidRequest(format!(
+                    "page_range pide la página {} y el documento tiene {}",
+                    first, total
+                )));
+            }
+            pages.extend(first..=last.unwrap_or(total).min(total));
+        }
+        Ok(pages)
+    }
+}
+
+/// El documento ya leído, o `INVALID_PDF` si no se pudo leer
+pub fn open(document: lopdf::Result<Document>) -> BridgeResult<Document> {
+    document.map_err(|e| BridgeError::InvalidPdf(format!("no se pudo leer: {}", e)))
+}
+
+/// Comprueba que el PDF se puede imprimir: que no pide contraseña para
+/// abrirse y que tiene páginas. Devuelve el número de páginas.
+///
+/// Sin esto el trabajo llega a CUPS, la API responde que se envió y es el
+/// filtro del controlador el que falla, sin que nadie se entere.
+pub fn inspect(document: &Document) -> BridgeResult<u64> {
+    check_encryption(document)?;
+
+    match document.get_pages().len() {
+        0 => Err(BridgeError::InvalidPdf("el documento no tiene páginas".to_string())),
+        pages => Ok(pages as u64),
+    }
+}
+
+/// Quita la protección con la contraseña de usuario. `false` si el documento
+/// no estaba cifrado.
+pub fn decrypt(document: &mut Document, password: &str) -> BridgeResult<bool> {
+    if !document.is_encrypted() {
+        return Ok(false);
+    }
+
+    document.decrypt(password).map_err(|e| match e {
+        lopdf::Error::Decryption(DecryptionError::IncorrectPassword) => {
+            BridgeError::PdfEncrypted("contraseña incorrecta".to_string())
+        }
+        lopdf::Error::Decryption(DecryptionError::UnsupportedEncryption) => {
+            BridgeError::PdfEncrypted("solo se pueden descifrar PDF con RC4 (revisiones 2 y 3), no con AES".to_string())
+        }
+        e => BridgeError::InvalidPdf(format!("no se pudo descifrar: {}", e)),
+    })?;
+    Ok(true)
+}
+
+/// Deja en el documento solo las páginas de `range`; devuelve cuántas quedan
+pub fn keep_pages(document: &mut Document, range: &PageRange) -> BridgeResult<u64> {
+    let total = document.get_pages().len() as u32;
+    let keep = range.pages(total)?;
+    let remove: Vec<u32> = (1..=total).filter(|page| !keep.contains(page)).collect();
+    if !remove.is_empty() {
+        document.delete_pages(&remove);
+        document.prune_objects();
+    }
+    Ok(keep.len() as u64)
+}
+
+/// Une los documentos, en orden, en uno solo. Deben estar descifrados.
+pub fn merge(documents: Vec<Document>) -> Document {
+    let mut merged = Document::with_version("1.5");
+    let mut pages = Vec::new();
+
+    for mut document in documents {
+        // Cada documento numera sus objetos desde 1; se desplazan para que no choquen
+        document.renumber_objects_with(merged.max_id + 1);
+        let document_pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+        for &page_id in &document_pages {
+            inherit_attributes(&mut document, page_id);
+        }
+        merged.max_id = document.max_id;
+        merged.objects.extend(document.objects);
+        pages.extend(document_pages);
+    }
+
+    let pages_id = merged.new_object_id();
+    for &page_id in &pages {
+        if let Ok(page) = merged.get_object_mut(page_id).and_then(Object::as_dict_mut) {
+            page.set("Parent", pages_id);
+        }
+    }
+    merged.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Count" => pages.len() as i64,
+        "Kids" => pages.into_iter().map(Object::Reference).collect::<Vec<_>>(),
+    }));
+    let catalog_id = merged.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    merged.trailer.set("Root", catalog_id);
+
+    // Los catálogos y árboles de páginas de los originales ya no se usan
+    merged.prune_objects();
+    merged
+}
+
+/// Estampa `watermark` encima del contenido de cada página. Debe estar descifrado.
+///
+/// `center` la cruza en diagonal, tan grande como quepa; `top` y `bottom` la
+/// dejan como un sello pequeño en el margen, para no tapar el texto de un ticket.
+pub fn watermark(document: &mut Document, watermark: &Watermark) -> BridgeResult<()> {
+    let opacity = watermark.opacity.unwrap_or(DEFAULT_WATERMARK_OPACITY);
+    let position = watermark.position.unwrap_or(WatermarkPosition::Center);
+    let text = win_ansi(&watermark.text);
+
+    stamp_pages(document, opacity, |_, _, (x, y, width, height)| {
+        let line = match position {
+            WatermarkPosition::Center => diagonal(text.clone(), x, y, width, height),
+            WatermarkPosition::Top => centered(text.clone(), STAMP_FONT_SIZE, x, width, y + height - STAMP_MARGIN_PT - STAMP_FONT_SIZE),
+            WatermarkPosition::Bottom => centered(text.clone(), STAMP_FONT_SIZE, x, width, y + STAMP_MARGIN_PT),
+        };
+        vec![line]
+    })
+}
+
+/// Estampa la cabecera y el pie de `marks` centrados en el margen de cada
+/// página, con su número. Debe estar descifrado.
+pub fn header_footer(document: &mut Document, marks: &HeaderFooter) -> BridgeResult<()> {
+    stamp_pages(document, 1.0, |page, pages, (x, y, width, height)| {
+        let mut lines = Vec::new();
+        if let Some(header) = marks.header(page, pages) {
+            for (index, line) in header.lines().enumerate() {
+                let baseline = y + height - MARK_MARGIN_PT - MARK_FONT_SIZE - index as f32 * MARK_LEADING;
+                lines.push(centered(win_ansi(line), MARK_FONT_SIZE, x, width, baseline));
+            }
+        }
+        if let Some(footer) = marks.footer(page, pages) {
+            // La última línea del pie queda junto al borde y el resto, encima
+            for (index, line) in footer.lines().rev().enumerate() {
+                let baseline = y + MARK_MARGIN_PT + index as f32 * MARK_LEADING;
+                lines.push(centered(win_ansi(line), MARK_FONT_SIZE, x, width, baseline));
+            }
+        }
+        lines
+    })
+}
+
+/// Una línea de texto a estampar en una página
+struct TextLine {
+    /// Bytes en WinAnsiEncoding
+    text: Vec<u8>,
+    size: f32,
+    /// Matriz de texto (`Tm`): giro y posición de la línea base
+    matrix: [f32; 6],
+}
+
+/// Añade encima del contenido de cada página las líneas que devuelve
+/// `lines_for(página, páginas, (x, y, ancho, alto))`, en Helvetica negra
+/// con la opacidad indicada
+fn stamp_pages<F>(document: &mut Document, opacity: f32, mut lines_for: F) -> BridgeResult<()>
+where
+    F: FnMut(u32, u32, (f32, f32, f32, f32)) -> Vec<TextLine>,
+{
+    let font_id = document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let state_id = document.add_object(dictionary! {
+        "Type" => "ExtGState",
+        "ca" => opacity,
+        "CA" => opacity,
+    });
+    // Nombres propios de cada llamada: la marca de agua y el pie van en la
+    // misma página con distinta opacidad
+    let font_name = format!("PmbFont{}", font_id.0);
+    let state_name = format!("PmbState{}", state_id.0);
+    // El contenido original puede dejar cambiado el estado gráfico (escala,
+    // color); se encierra entre q/Q para que el texto empiece desde cero
+    let save_id = document.add_object(Stream::new(dictionary! {}, b"q\n".to_vec()));
+
+    let pages = document.get_pages();
+    let total = pages.len() as u32;
+    for (number, page_id) in pages {
+        inherit_attributes(document, page_id);
+        let lines = lines_for(number, total, media_box(document, page_id));
+        if lines.is_empty() {
+            continue;
+        }
+
+        let stamp = stamp_content(&lines, &font_name, &state_name)
+            .map_err(|e| BridgeError::PrintError(format!("No se pudo estampar el texto en el PDF: {}", e)))?;
+        let stamp_id = document.add_object(Stream::new(dictionary! {}, stamp));
+        let contents: Vec<Object> = std::iter::once(save_id)
+            .chain(document.get_page_contents(page_id))
+            .chain(std::iter::once(stamp_id))
+            .map(Object::Reference)
+            .collect();
+
+        let resources = page_resources(document, page_id, (&font_name, font_id), (&state_name, state_id));
+        if let Ok(page) = document.get_object_mut(page_id).and_then(Object::as_dict_mut) {
+            page.set("Resources", resources);
+            page.set("Contents", contents);
+        }
+    }
+    Ok(())
+}
+
+/// Helvetica con WinAnsiEncoding cubre Latin-1; el resto sale como '?'
+pub(super) fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars().map(|c| u8::try_from(c).unwrap_or(b'?')).collect()
+}
+
+fn text_width(text: &[u8], size: f32) -> f32 {
+    text.len() as f32 * HELVETICA_CHAR_WIDTH * size
+}
+
+/// Línea horizontal centrada, con el cuerpo reducido si no cabe a lo ancho
+fn centered(text: Vec<u8>, size: f32, x: f32, width: f32, baseline: f32) -> TextLine {
+    let size = size.min((width - 2.0 * STAMP_MARGIN_PT) / text_width(&text, 1.0).max(1.0));
+    let start_x = x + (width - text_width(&text, size)) / 2.0;
+    TextLine { text, size, matrix: [1.0, 0.0, 0.0, 1.0, start_x, baseline] }
+}
+
+/// Línea que cruza la página de esquina a esquina
+fn diagonal(text: Vec<u8>, x: f32, y: f32, width: f32, height: f32) -> TextLine {
+    let angle = height.atan2(width);
+    let (sin, cos) = angle.sin_cos();
+    let length = width.hypot(height);
+    let size = (0.7 * length / text_width(&text, 1.0).max(1.0)).min(width.min(height) / 4.0);
+    let half_width = text_width(&text, size) / 2.0;
+    // Centrada en la página a lo largo de la diagonal y a media altura de las mayúsculas
+    let half_height = 0.35 * size;
+    let start_x = x + width / 2.0 - cos * half_width + sin * half_height;
+    let start_y = y + height / 2.0 - sin * half_width - cos * half_height;
+    TextLine { text, size, matrix: [cos, sin, -sin, cos, start_x, start_y] }
+}
+
+fn stamp_content(lines: &[TextLine], font_name: &str, state_name: &str) -> lopdf::Result<Vec<u8>> {
+    let mut operations = vec![
+        // Cierra el `q` que se puso delante del contenido original
+        Operation::new("Q", vec![]),
+        Operation::new("q", vec![]),
+        Operation::new("gs", vec![Object::Name(state_name.as_bytes().to_vec())]),
+        Operation::new("g", vec![0.into()]),
+        Operation::new("BT", vec![]),
+    ];
+    for line in lines {
+        operations.push(Operation::new("Tf", vec![Object::Name(font_name.as_bytes().to_vec()), line.size.into()]));
+        operations.push(Operation::new("Tm", line.matrix.into_iter().map(Object::Real).collect()));
+        operations.push(Operation::new("Tj", vec![Object::string_literal(line.text.clone())]));
+    }
+    operations.push(Operation::new("ET", vec![]));
+    operations.push(Operation::new("Q", vec![]));
+    Content { operations }.encode()
+}
+
+/// Recursos de la página más la fuente y el estado gráfico del texto
+/// estampado. Se copian en la propia página porque el original puede
+/// compartirlos con otras.
+fn page_resources(document: &Document, page_id: ObjectId, font: (&str, ObjectId), state: (&str, ObjectId)) -> Dictionary {
+    let page = document.get_dictionary(page_id).ok();
+    let mut resources = resolve_dictionary(document, page.and_then(|page| page.get(b"Resources").ok()));
+    for (key, (name, id)) in [(&b"Font"[..], font), (&b"ExtGState"[..], state)] {
+        let mut entries = resolve_dictionary(document, resources.get(key).ok());
+        entries.set(name, id);
+        resources.set(key, entries);
+    }
+    resources
+}
+
+fn resolve_dictionary(document: &Document, value: Option<&Object>) -> Dictionary {
+    value
+        .and_then(|value| document.dereference(value).ok())
+        .and_then(|(_, value)| value.as_dict().ok())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Origen y tamaño de la página; Carta si no tiene un `MediaBox` válido
+fn media_box(document: &Document, page_id: ObjectId) -> (f32, f32, f32, f32) {
+    let corners: Option<Vec<f32>> = document
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"MediaBox"))
+        .and_then(|value| document.dereference(value))
+        .and_then(|(_, value)| value.as_array())
+        .ok()
+        .and_then(|values| values.iter().map(|value| value.as_float().ok()).collect());
+
+    match corners.as_deref() {
+        Some(&[x0, y0, x1, y1]) if x1 != x0 && y1 != y0 => (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs()),
+        _ => (0.0, 0.0, 612.0, 792.0),
+    }
+}
+
+/// Copia en la página los atributos que hereda de sus antecesores, que se
+/// pierden al colgarla de otro árbol
+fn inherit_attributes(document: &mut Document, page_id: ObjectId) {
+    let Ok(page) = document.get_dictionary(page_id) else {
+        return;
+    };
+    let mut missing: Vec<&[u8]> = INHERITABLE.into_iter().filter(|key| !page.has(key)).collect();
+    let mut inherited = Vec::new();
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+
+    for _ in 0..MAX_TREE_DEPTH {
+        let Some(node) = parent.and_then(|id| document.get_dictionary(id).ok()) else {
+            break;
+        };
+        missing.retain(|key| match node.get(key) {
+            Ok(value) => {
+                inherited.push((key.to_vec(), value.clone()));
+                false
+            }
+            Err(_) => true,
+        });
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+
+    if let Ok(page) = document.get_object_mut(page_id).and_then(Object::as_dict_mut) {
+        for (key, value) in inherited {
+            page.set(key, value);
+        }
+    }
+}
+
+/// El documento serializado de nuevo, p. ej. tras descifrarlo
+pub fn save(document: &mut Document) -> BridgeResult<Vec<u8>> {
+    let mut data = Vec::new();
+    document
+        .save_to(&mut data)
+        .map_err(|e| BridgeError::InvalidPdf(format!("no se pudo escribir: {}", e)))?;
+    Ok(data)
+}
+
+/// Los PDF cifrados solo con contraseña de propietario (sin copiar, sin
+/// editar) se abren con la contraseña vacía y se imprimen con normalidad
+fn check_encryption(document: &Document) -> BridgeResult<()> {
+    if !document.is_encrypted() {
+        return Ok(());
+    }
+
+    match encryption::get_encryption_key(document, "", true) {
+        Ok(_) => Ok(()),
+        Err(DecryptionError::IncorrectPassword) => Err(BridgeError::PdfEncrypted(
+            "el documento pide contraseña para abrirse; envíala en options.pdf_password".to_string(),
+        )),
+        // lopdf solo sabe comprobar RC4; con AES decide el filtro de CUPS
+        Err(DecryptionError::UnsupportedEncryption) => {
+            log::warn!("⚠️ PDF cifrado con un algoritmo que no se puede comprobar; se envía tal cual");
+            Ok(())
+        }
+        Err(e) => Err(BridgeError::InvalidPdf(format!("cifrado inválido: {}", e))),
+    }
+}
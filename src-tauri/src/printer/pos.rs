@@ -0,0 +1,73 @@
+use super::text::encode;
+use crate::api::{BeepOptions, PrintOptions, TextEncoding};
+use crate::config::PosSettings;
+
+/// GS V 66 0: avanza hasta la cuchilla y corta dejando una lengüeta
+const PARTIAL_CUT: [u8; 4] = [0x1d, b'V', 66, 0];
+
+/// Tiempo de reposo tras el pulso, en unidades de 2 ms (500 ms)
+const DRAWER_REST: u8 = 250;
+
+/// Visor de cliente habitual: VFD de 2 líneas de 20 columnas
+const DISPLAY_LINES: usize = 2;
+const DISPLAY_COLUMNS: usize = 20;
+
+/// Comprueba que el mensaje cabe en el visor
+pub fn check_display(message: &str) -> Result<(), String> {
+    let lines: Vec<&str> = message.split('\n').collect();
+    if lines.len() > DISPLAY_LINES {
+        return Err(format!("el visor tiene {} líneas", DISPLAY_LINES));
+    }
+    match lines.iter().find(|line| line.chars().count() > DISPLAY_COLUMNS) {
+        Some(line) => Err(format!("'{}' no cabe en las {} columnas del visor", line, DISPLAY_COLUMNS)),
+        None => Ok(()),
+    }
+}
+
+/// ESC p: pulso en el conector del cajón
+pub fn drawer_pulse(settings: &PosSettings) -> Vec<u8> {
+    let pin = if settings.drawer_pin == 5 { 1 } else { 0 };
+    let pulse = (settings.drawer_pulse_ms / 2).clamp(1, 255) as u8;
+    vec![0x1b, b'p', pin, pulse, DRAWER_REST.max(pulse)]
+}
+
+/// Lo que va detrás del ticket según `cut`, `open_drawer`, `beep` y
+/// `display_message`: primero se corta, para que el ticket ya esté fuera
+/// cuando se abre el cajón o suena el aviso
+pub fn after_receipt(options: &PrintOptions, settings: &PosSettings) -> Vec<u8> {
+    let mut commands = Vec::new();
+    if options.cut == Some(true) {
+        commands.extend(PARTIAL_CUT);
+    }
+    if options.open_drawer == Some(true) {
+        commands.extend(drawer_pulse(settings));
+    }
+    if let Some(beep) = &options.beep {
+        commands.extend(beep_pattern(beep));
+    }
+    if let Some(message) = &options.display_message {
+        commands.extend(display(message));
+    }
+    commands
+}
+
+/// ESC B n t: `n` pitidos de `t` x 50 ms
+fn beep_pattern(beep: &BeepOptions) -> Vec<u8> {
+    let times = beep.times.unwrap_or(1).clamp(1, 9);
+    let duration = (beep.duration_ms.unwrap_or(200) as f32 / 50.0).round().clamp(1.0, 9.0) as u8;
+    vec![0x1b, b'B', times, duration]
+}
+
+/// El mensaje en el visor conectado a la impresora, en la página de códigos 437
+fn display(message: &str) -> Vec<u8> {
+    // ESC = 2 manda lo que sigue al visor; FF lo borra
+    let mut data = vec![0x1b, b'=', 2, 0x0c];
+    for (index, line) in message.split('\n').take(DISPLAY_LINES).enumerate() {
+        // US $ x y: cursor al principio de la línea
+        data.extend([0x1f, b'$', 1, index as u8 + 1]);
+        data.extend(line.chars().take(DISPLAY_COLUMNS).filter_map(|c| encode(c, TextEncoding::Cp437)));
+    }
+    // ESC = 1: lo siguiente vuelve a ser para la impresora
+    data.extend([0x1b, b'=', 1]);
+    data
+}
@@ -0,0 +1,178 @@
+use crate::api::SupplyLevel;
+use crate::config::Config;
+use crate::error::{BridgeError, BridgeResult};
+use std::collections::HashMap;
+
+/// Atributos `marker-*` que CUPS mantiene por cola. Para impresoras de red los
+/// rellena el propio backend de CUPS consultando la Printer MIB por SNMP.
+const MARKER_ATTRIBUTES: [&str; 5] = [
+    "marker-names",
+    "marker-levels",
+    "marker-types",
+    "marker-colors",
+    "marker-low-levels",
+];
+
+const GET_PRINTER_ATTRIBUTES: u16 = 0x000B;
+
+const TAG_OPERATION_ATTRIBUTES: u8 = 0x01;
+const TAG_END_OF_ATTRIBUTES: u8 = 0x03;
+const TAG_INTEGER: u8 = 0x21;
+const TAG_URI: u8 = 0x45;
+const TAG_KEYWORD: u8 = 0x44;
+const TAG_CHARSET: u8 = 0x47;
+const TAG_NATURAL_LANGUAGE: u8 = 0x48;
+
+#[derive(Debug)]
+enum IppValue {
+    Integer(i32),
+    Text(String),
+}
+
+/// Consulta a CUPS por IPP los niveles de tóner, tinta y papel de una cola
+pub async fn query(printer: &str, config: &Config) -> BridgeResult<Vec<SupplyLevel>> {
+    let server = cups_server();
+    let printer_uri = format!("ipp://{}/printers/{}", server, printer);
+
+    let client = reqwest::Client::builder()
+        .timeout(config.query_timeout())
+        .build()
+        .map_err(|e| BridgeError::PrintError(e.to_string()))?;
+
+    let response = client
+        .post(format!("http://{}/printers/{}", server, printer))
+        .header("Content-Type", "application/ipp")
+        .body(encode_request(&printer_uri))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| BridgeError::PrintError(format!("CUPS no respondió por IPP: {}", e)))?;
+
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| BridgeError::PrintError(e.to_string()))?;
+
+    let attributes = decode_response(&body)
+        .ok_or_else(|| BridgeError::PrintError("Respuesta IPP inválida".to_string()))?;
+
+    Ok(supplies_from(&attributes))
+}
+
+/// `CUPS_SERVER` puede apuntar a otro host; los sockets locales usan localhost
+fn cups_server() -> String {
+    match std::env::var("CUPS_SERVER") {
+        Ok(server) if !server.is_empty() && !server.starts_with('/') => {
+            if server.contains(':') {
+                server
+            } else {
+                format!("{}:631", server)
+            }
+        }
+        _ => "localhost:631".to_string(),
+    }
+}
+
+fn encode_request(printer_uri: &str) -> Vec<u8> {
+    let mut body = vec![2, 0];
+    body.extend_from_slice(&GET_PRINTER_ATTRIBUTES.to_be_bytes());
+    body.extend_from_slice(&1u32.to_be_bytes());
+    body.push(TAG_OPERATION_ATTRIBUTES);
+
+    push_attribute(&mut body, TAG_CHARSET, "attributes-charset", b"utf-8");
+    push_attribute(&mut body, TAG_NATURAL_LANGUAGE, "attributes-natural-language", b"en");
+    push_attribute(&mut body, TAG_URI, "printer-uri", printer_uri.as_bytes());
+    for (index, attribute) in MARKER_ATTRIBUTES.iter().enumerate() {
+        // Los valores adicionales de un 1setOf van con nombre vacío
+        let name = if index == 0 { "requested-attributes" } else { "" };
+        push_attribute(&mut body, TAG_KEYWORD, name, attribute.as_bytes());
+    }
+
+    body.push(TAG_END_OF_ATTRIBUTES);
+    body
+}
+
+fn push_attribute(body: &mut Vec<u8>, tag: u8, name: &str, value: &[u8]) {
+    body.push(tag);
+    body.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    body.extend_from_slice(name.as_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    body.extend_from_slice(value);
+}
+
+fn decode_response(body: &[u8]) -> Option<HashMap<String, Vec<IppValue>>> {
+    let status = u16::from_be_bytes([*body.get(2)?, *body.get(3)?]);
+    // 0x0000-0x00FF son respuestas correctas (con o sin avisos)
+    if status > 0x00FF {
+        log::debug!("🔎 CUPS devolvió el estado IPP {:#06x}", status);
+        return None;
+    }
+
+    let mut attributes: HashMap<String, Vec<IppValue>> = HashMap::new();
+    let mut current = String::new();
+    let mut position = 8;
+
+    while let Some(&tag) = body.get(position) {
+        position += 1;
+        if tag == TAG_END_OF_ATTRIBUTES {
+            break;
+        }
+        // Delimitador de grupo, sin nombre ni valor
+        if tag < 0x10 {
+            continue;
+        }
+
+        let name = read_field(body, &mut position)?;
+        let value = read_field(body, &mut position)?;
+        if !name.is_empty() {
+            current = String::from_utf8_lossy(name).into_owned();
+        }
+
+        let value = match (tag, value) {
+            (TAG_INTEGER, [a, b, c, d]) => IppValue::Integer(i32::from_be_bytes([*a, *b, *c, *d])),
+            _ => IppValue::Text(String::from_utf8_lossy(value).into_owned()),
+        };
+        attributes.entry(current.clone()).or_default().push(value);
+    }
+
+    Some(attributes)
+}
+
+/// Campo con longitud de 2 bytes delante
+fn read_field<'a>(body: &'a [u8], position: &mut usize) -> Option<&'a [u8]> {
+    let len = u16::from_be_bytes([*body.get(*position)?, *body.get(*position + 1)?]) as usize;
+    let start = *position + 2;
+    let field = body.get(start..start + len)?;
+    *position = start + len;
+    Some(field)
+}
+
+fn supplies_from(attributes: &HashMap<String, Vec<IppValue>>) -> Vec<SupplyLevel> {
+    let text = |name: &str, index: usize| match attributes.get(name).and_then(|values| values.get(index)) {
+        Some(IppValue::Text(value)) => Some(value.clone()),
+        _ => None,
+    };
+    let integer = |name: &str, index: usize| match attributes.get(name).and_then(|values| values.get(index)) {
+        Some(IppValue::Integer(value)) => Some(*value),
+        _ => None,
+    };
+
+    let count = attributes.get("marker-names").map_or(0, Vec::len);
+    (0..count)
+        .map(|index| {
+            // Negativos: -1 no disponible, -2 desconocido, -3 "queda algo"
+            let level = integer("marker-levels", index)
+                .filter(|level| (0..=100).contains(level))
+                .map(|level| level as u8);
+            let low_level = integer("marker-low-levels", index).unwrap_or(0);
+
+            SupplyLevel {
+                name: text("marker-names", index).unwrap_or_default(),
+                kind: text("marker-types", index),
+                color: text("marker-colors", index),
+                level,
+                low: level.is_some_and(|level| i32::from(level) <= low_level),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,99 @@
+use super::images::build_single_page;
+use crate::config::Config;
+use crate::error::{BridgeError, BridgeResult};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Stream};
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use resvg::usvg::{fontdb, ImageHrefResolver, Options, Tree};
+use std::sync::{Arc, OnceLock};
+
+/// Unidades de usuario de SVG por pulgada (las mismas que los px de CSS)
+const SVG_UNITS_PER_INCH: f32 = 96.0;
+
+/// Píxeles como máximo de la imagen rasterizada (unos 200 MB en RGBA)
+const MAX_PIXELS: u64 = 50_000_000;
+
+/// Las fuentes del sistema se cargan una sola vez: leerlas cuesta más que
+/// rasterizar una etiqueta
+fn system_fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = fontdb::Database::new();
+            fonts.load_system_fonts();
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+/// Rasteriza el SVG a `render.svg_dpi` y lo pone en una página PDF de su
+/// mismo tamaño, para que una etiqueta salga a la medida con que se diseñó
+pub fn to_pdf(data: &[u8], config: &Config) -> BridgeResult<Vec<u8>> {
+    let mut options = Options {
+        fontdb: system_fonts(),
+        ..Options::default()
+    };
+    // Un `href` a un archivo se leería del disco del bridge; solo se
+    // admiten las imágenes incrustadas como data:
+    options.image_href_resolver = ImageHrefResolver {
+        resolve_string: Box::new(|_: &str, _: &Options| None),
+        ..ImageHrefResolver::default()
+    };
+
+    let tree = Tree::from_data(data, &options)
+        .map_err(|e| BridgeError::InvalidRequest(format!("SVG inválido: {}", e)))?;
+    let size = tree.size();
+    let scale = config.render.svg_dpi as f32 / SVG_UNITS_PER_INCH;
+    let width = (size.width() * scale).ceil() as u32;
+    let height = (size.height() * scale).ceil() as u32;
+    if u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return Err(BridgeError::InvalidRequest(format!(
+            "el SVG ocupa {}x{} píxeles a {} ppp; reduzca render.svg_dpi o el tamaño del dibujo",
+            width, height, config.render.svg_dpi
+        )));
+    }
+
+    let mut pixmap = Pixmap::new(width.max(1), height.max(1))
+        .ok_or_else(|| BridgeError::InvalidRequest("el SVG no tiene tamaño".to_string()))?;
+    // DeviceRGB no tiene canal alfa: lo transparente se imprime en blanco
+    pixmap.fill(Color::WHITE);
+    resvg::render(&tree, Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    log::debug!("🖼️ SVG rasterizado a {}x{} píxeles ({} ppp)", pixmap.width(), pixmap.height(), config.render.svg_dpi);
+
+    // Con fondo opaco los colores premultiplicados ya son los finales
+    let rgb: Vec<u8> = pixmap.data().chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    let mut image_stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => pixmap.width() as i64,
+            "Height" => pixmap.height() as i64,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+        },
+        rgb,
+    );
+    let _ = image_stream.compress();
+
+    // Puntos (1/72in) a partir de las unidades del SVG
+    let page_width = size.width() * 72.0 / SVG_UNITS_PER_INCH;
+    let page_height = size.height() * 72.0 / SVG_UNITS_PER_INCH;
+    let content = Content {
+        operations: vec![
+            Operation::new("q", vec![]),
+            Operation::new("cm", vec![
+                page_width.into(),
+                0.into(),
+                0.into(),
+                page_height.into(),
+                0.into(),
+                0.into(),
+            ]),
+            Operation::new("Do", vec!["Im1".into()]),
+            Operation::new("Q", vec![]),
+        ],
+    };
+
+    build_single_page(image_stream, content, page_width, page_height)
+        .map_err(|e| BridgeError::PrintError(format!("No se pudo generar el PDF del SVG: {}", e)))
+}
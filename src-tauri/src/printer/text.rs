@@ -0,0 +1,231 @@
+use super::images::page_size;
+use super::pdf;
+use crate::api::{PrintOptions, TextEncoding, TextFont, TextOptions};
+use crate::config::Config;
+use crate::error::{BridgeError, BridgeResult};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, Stream};
+
+const DEFAULT_FONT_SIZE: f32 = 10.0;
+const DEFAULT_MARGIN_MM: f32 = 10.0;
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+/// Interlineado en proporción al cuerpo
+const LINE_SPACING: f32 = 1.2;
+
+const TAB_WIDTH: usize = 8;
+
+/// Columnas de una impresora de 80 mm con la fuente A
+const DEFAULT_ESCPOS_COLUMNS: usize = 42;
+
+/// Puntos por milímetro de las impresoras de tickets (203 ppp)
+const ESCPOS_DOTS_PER_MM: f32 = 8.0;
+
+/// Líneas que se avanzan al final para que el texto pase la cuchilla
+const ESCPOS_FEED_LINES: u8 = 4;
+
+/// Caracteres de 0x80 a 0xFF de cada página de códigos
+const CP437: [&str; 8] = [
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜ¢£¥₧ƒ",
+    "áíóúñÑªº¿⌐¬½¼¡«»",
+    "░▒▓│┤╡╢╖╕╣║╗╝╜╛┐",
+    "└┴┬├─┼╞╟╚╔╩╦╠═╬╧",
+    "╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀",
+    "αßΓπΣσµτΦΘΩδ∞φε∩",
+    "≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}",
+];
+const CP850: [&str; 8] = [
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜø£Ø×ƒ",
+    "áíóúñÑªº¿®¬½¼¡«»",
+    "░▒▓│┤ÁÂÀ©╣║╗╝¢¥┐",
+    "└┴┬├─┼ãÃ╚╔╩╦╠═╬¤",
+    "ðÐÊËÈıÍÎÏ┘┌█▄¦Ì▀",
+    "ÓßÔÒõÕµþÞÚÛÙýÝ¯´",
+    "\u{ad}±‗¾¶§÷¸°¨·¹³²■\u{a0}",
+];
+
+/// El texto en páginas del tamaño de `print` (o de `[render]`), con la
+/// fuente, el cuerpo y los márgenes de `options`. Sin cuerpo se elige el que
+/// da `chars_per_line` columnas; sin ninguno de los dos, 10 pt.
+pub fn to_pdf(text: &str, options: &TextOptions, print: &PrintOptions, config: &Config) -> BridgeResult<Vec<u8>> {
+    let (width, height) = page_size(print, config)?;
+    let margin = options.margin_mm.unwrap_or(DEFAULT_MARGIN_MM) * POINTS_PER_MM;
+    let (base_font, char_width) = font_metrics(options.font.unwrap_or(TextFont::Courier));
+    let (area_width, area_height) = (width - 2.0 * margin, height - 2.0 * margin);
+    if area_width <= 0.0 || area_height <= 0.0 {
+        return Err(BridgeError::InvalidRequest("options.text.margin_mm no deja sitio en la página".to_string()));
+    }
+
+    let fitting_columns = |size: f32| ((area_width / (size * char_width)).floor() as usize).max(1);
+    let (size, columns) = match (options.font_size, options.chars_per_line) {
+        (Some(size), Some(columns)) => (size, columns as usize),
+        (Some(size), None) => (size, fitting_columns(size)),
+        (None, Some(columns)) => (area_width / (columns as f32 * char_width), columns as usize),
+        (None, None) => (DEFAULT_FONT_SIZE, fitting_columns(DEFAULT_FONT_SIZE)),
+    };
+    let leading = size * LINE_SPACING;
+    let lines_per_page = ((area_height / leading).floor() as usize).max(1);
+
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let font_id = document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => base_font,
+        "Encoding" => "WinAnsiEncoding",
+    });
+
+    let mut kids = Vec::new();
+    for lines in paginate(text, columns, lines_per_page) {
+        let mut operations = vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["F1".into(), size.into()]),
+            Operation::new("TL", vec![leading.into()]),
+            Operation::new("Td", vec![margin.into(), (height - margin - size).into()]),
+        ];
+        for line in lines {
+            operations.push(Operation::new("Tj", vec![Object::string_literal(pdf::win_ansi(&line))]));
+            operations.push(Operation::new("T*", vec![]));
+        }
+        operations.push(Operation::new("ET", vec![]));
+
+        let content = Content { operations }
+            .encode()
+            .map_err(|e| BridgeError::PrintError(format!("No se pudo generar el PDF del texto: {}", e)))?;
+        let content_id = document.add_object(Stream::new(dictionary! {}, content));
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            "Resources" => dictionary! {
+                "Font" => dictionary! { "F1" => font_id },
+            },
+        });
+        kids.push(Object::Reference(page_id));
+    }
+
+    document.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Count" => kids.len() as i64,
+        "Kids" => kids,
+    }));
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    document.trailer.set("Root", catalog_id);
+    pdf::save(&mut document)
+}
+
+/// El texto como comandos ESC/POS: página de códigos, margen izquierdo,
+/// líneas partidas a `chars_per_line` y avance final. Los caracteres que no
+/// existen en la página de códigos salen como '?'.
+pub fn to_escpos(text: &str, options: &TextOptions) -> Vec<u8> {
+    let encoding = options.encoding.unwrap_or(TextEncoding::Cp437);
+    let columns = options.chars_per_line.map_or(DEFAULT_ESCPOS_COLUMNS, |columns| columns as usize);
+
+    // ESC @ deja la impresora como al encenderla; ESC t elige la página de códigos
+    let mut data = vec![0x1b, b'@', 0x1b, b't', code_page_number(encoding)];
+    if let Some(margin) = options.margin_mm.filter(|margin| *margin > 0.0) {
+        // GS L: margen izquierdo en puntos
+        let dots = (margin * ESCPOS_DOTS_PER_MM).round() as u16;
+        data.extend([0x1d, b'L']);
+        data.extend(dots.to_le_bytes());
+    }
+    // En papel continuo un salto de página no tiene sentido; cada página sigue a la anterior
+    for line in text.split('\u{c}').flat_map(|page| wrap(page, columns)) {
+        data.extend(line.chars().filter_map(|c| encode(c, encoding)));
+        data.push(b'\n');
+    }
+    // ESC d: avanzar n líneas
+    data.extend([0x1b, b'd', ESCPOS_FEED_LINES]);
+    data
+}
+
+/// Páginas de como mucho `lines_per_page` líneas; un `\f` empieza otra
+fn paginate(text: &str, columns: usize, lines_per_page: usize) -> Vec<Vec<String>> {
+    let text = text.strip_suffix('\u{c}').unwrap_or(text);
+    text.split('\u{c}')
+        .flat_map(|page| {
+            let lines = wrap(page, columns);
+            if lines.is_empty() {
+                vec![Vec::new()]
+            } else {
+                lines.chunks(lines_per_page).map(<[String]>::to_vec).collect()
+            }
+        })
+        .collect()
+}
+
+/// Líneas de como mucho `columns` caracteres, partidas por el último espacio
+/// que quepa o, si no hay ninguno, a la fuerza
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut rest = expand_tabs(line);
+        while rest.len() > columns {
+            match rest[..=columns].iter().rposition(|c| *c == ' ').filter(|at| *at > 0) {
+                Some(at) => {
+                    lines.push(rest[..at].iter().collect());
+                    rest.drain(..=at);
+                }
+                None => {
+                    lines.push(rest[..columns].iter().collect());
+                    rest.drain(..columns);
+                }
+            }
+        }
+        lines.push(rest.into_iter().collect());
+    }
+    lines
+}
+
+fn expand_tabs(line: &str) -> Vec<char> {
+    let mut chars = Vec::new();
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - chars.len() % TAB_WIDTH;
+            chars.resize(chars.len() + spaces, ' ');
+        } else {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+/// Nombre PostScript y ancho medio de un carácter en proporción al cuerpo
+fn font_metrics(font: TextFont) -> (&'static str, f32) {
+    match font {
+        TextFont::Courier => ("Courier", 0.6),
+        TextFont::Helvetica => ("Helvetica", 0.55),
+        TextFont::Times => ("Times-Roman", 0.5),
+    }
+}
+
+/// Número de la página de códigos en `ESC t` (tabla de Epson)
+fn code_page_number(encoding: TextEncoding) -> u8 {
+    match encoding {
+        TextEncoding::Cp437 => 0,
+        TextEncoding::Cp850 => 2,
+    }
+}
+
+/// El byte del carácter en la página de códigos. Los caracteres de control se
+/// quitan para que el texto no pueda colar comandos a la impresora.
+pub(super) fn encode(c: char, encoding: TextEncoding) -> Option<u8> {
+    if c.is_ascii() {
+        return (!c.is_ascii_control()).then_some(c as u8);
+    }
+    if c.is_control() {
+        return None;
+    }
+    let table = match encoding {
+        TextEncoding::Cp437 => CP437,
+        TextEncoding::Cp850 => CP850,
+    };
+    let position = table.iter().flat_map(|row| row.chars()).position(|candidate| candidate == c);
+    Some(position.map_or(b'?', |position| 0x80 + position as u8))
+}
@@ -0,0 +1,58 @@
+use crate::api::PrintResponse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// SHA-256 del documento junto con la impresora y las opciones
+pub type ContentHash = [u8; 32];
+
+/// Trabajos enviados hace poco, por huella, para descartar envíos repetidos
+/// (el doble clic en el navegador que imprime dos tickets)
+#[derive(Clone, Default)]
+pub struct RecentJobs {
+    entries: Arc<Mutex<HashMap<ContentHash, RecentJob>>>,
+}
+
+struct RecentJob {
+    submitted_at: Instant,
+    /// `None` mientras el original sigue imprimiéndose
+    response: Option<Box<PrintResponse>>,
+}
+
+pub enum Claim {
+    New,
+    Duplicate {
+        seconds_ago: u64,
+        response: Option<Box<PrintResponse>>,
+    },
+}
+
+impl RecentJobs {
+    /// Registra `hash` salvo que ya se enviara dentro de `window`
+    pub fn claim(&self, hash: ContentHash, window: Duration) -> Claim {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|_, job| now.duration_since(job.submitted_at) < window);
+
+        if let Some(job) = entries.get(&hash) {
+            return Claim::Duplicate {
+                seconds_ago: now.duration_since(job.submitted_at).as_secs(),
+                response: job.response.clone(),
+            };
+        }
+
+        entries.insert(hash, RecentJob { submitted_at: now, response: None });
+        Claim::New
+    }
+
+    pub fn complete(&self, hash: &ContentHash, response: &PrintResponse) {
+        if let Some(job) = self.entries.lock().unwrap().get_mut(hash) {
+            job.response = Some(Box::new(response.clone()));
+        }
+    }
+
+    /// Un trabajo fallido no debe impedir que el usuario lo reintente
+    pub fn forget(&self, hash: &ContentHash) {
+        self.entries.lock().unwrap().remove(hash);
+    }
+}
@@ -0,0 +1,177 @@
+pub mod dedupe;
+
+use crate::api::JobPriority;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, watch};
+
+/// Cola de trabajos con un carril por impresora física: en cada carril los
+/// trabajos se envían de uno en uno y, cuando hay que esperar, pasan primero
+/// los de mayor prioridad (y, a igual prioridad, los que llegaron antes).
+/// Carriles distintos avanzan en paralelo.
+#[derive(Clone, Default)]
+pub struct JobQueue {
+    lanes: Arc<Mutex<HashMap<String, Lane>>>,
+    /// Trabajos aceptados que todavía no terminaron (descargando, en espera o imprimiendo)
+    pending: Arc<AtomicUsize>,
+    /// Con la cola detenida los trabajos se aceptan pero no llegan a la impresora
+    held: Arc<watch::Sender<bool>>,
+}
+
+#[derive(Default)]
+struct Lane {
+    busy: bool,
+    next_seq: u64,
+    waiting: BinaryHeap<Waiter>,
+}
+
+struct Waiter {
+    priority: JobPriority,
+    seq: u64,
+    wake: oneshot::Sender<JobSlot>,
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap saca el mayor: más prioridad y, después, menor secuencia
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+/// Plaza de un trabajo aceptado; al soltarla deja de contar como pendiente
+pub struct PendingJob {
+    pending: Arc<AtomicUsize>,
+}
+
+impl Drop for PendingJob {
+    fn drop(&mut self) {
+        self.pending.fetch_sub(1, AtomicOrdering::SeqCst);
+    }
+}
+
+/// Turno en un carril; al soltarlo pasa al siguiente trabajo en espera
+pub struct JobSlot {
+    owner: Option<(JobQueue, String)>,
+}
+
+impl Drop for JobSlot {
+    fn drop(&mut self) {
+        if let Some((queue, device)) = self.owner.take() {
+            queue.release(&device);
+        }
+    }
+}
+
+impl JobQueue {
+    /// Acepta un trabajo salvo que ya haya `limit` pendientes (0 = sin límite)
+    pub fn admit(&self, limit: usize) -> Option<PendingJob> {
+        self.pending
+            .fetch_update(AtomicOrdering::SeqCst, AtomicOrdering::SeqCst, |pending| {
+                (limit == 0 || pending < limit).then_some(pending + 1)
+            })
+            .ok()?;
+        Some(PendingJob {
+            pending: self.pending.clone(),
+        })
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Trabajos esperando turno en alguna impresora
+    pub fn waiting(&self) -> usize {
+        self.lanes.lock().unwrap().values().map(|lane| lane.waiting.len()).sum()
+    }
+
+    /// Detiene o reanuda el envío a las impresoras sin descartar trabajos
+    pub fn set_held(&self, held: bool) {
+        self.held.send_replace(held);
+        if held {
+            log::info!("⏸️ Cola detenida: los trabajos esperan hasta reanudarla");
+        } else {
+            log::info!("▶️ Cola reanudada");
+        }
+    }
+
+    pub fn is_held(&self) -> bool {
+        *self.held.borrow()
+    }
+
+    /// Espera el turno de `device` con la prioridad indicada
+    pub async fn acquire(&self, device: &str, priority: JobPriority) -> JobSlot {
+        let receiver = {
+            let mut lanes = self.lanes.lock().unwrap();
+            let lane = lanes.entry(device.to_string()).or_default();
+            if lane.busy {
+                let (wake, receiver) = oneshot::channel();
+                let seq = lane.next_seq;
+                lane.next_seq += 1;
+                lane.waiting.push(Waiter { priority, seq, wake });
+                log::debug!("⏳ Trabajo en espera para {} ({} por delante)", device, lane.waiting.len());
+                Some(receiver)
+            } else {
+                lane.busy = true;
+                None
+            }
+        };
+
+        let slot = match receiver {
+            // El emisor solo se suelta después de enviar el turno
+            Some(receiver) => receiver.await.expect("la cola de trabajos sigue activa"),
+            None => self.slot(device),
+        };
+
+        // Se espera con el turno en la mano para que, al reanudar, el orden
+        // por prioridad se mantenga
+        let mut held = self.held.subscribe();
+        if *held.borrow() {
+            log::debug!("⏸️ Trabajo para {} retenido con la cola detenida", device);
+            let _ = held.wait_for(|held| !held).await;
+        }
+        slot
+    }
+
+    fn slot(&self, device: &str) -> JobSlot {
+        JobSlot {
+            owner: Some((self.clone(), device.to_string())),
+        }
+    }
+
+    /// Cede el turno al siguiente en espera o deja el carril libre
+    fn release(&self, device: &str) {
+        let mut lanes = self.lanes.lock().unwrap();
+        let Some(lane) = lanes.get_mut(device) else {
+            return;
+        };
+
+        while let Some(waiter) = lane.waiting.pop() {
+            match waiter.wake.send(self.slot(device)) {
+                Ok(()) => return,
+                // La solicitud se canceló mientras esperaba; probar con la siguiente
+                Err(mut slot) => slot.owner = None,
+            }
+        }
+
+        // Sin nadie esperando el carril sobra
+        lanes.remove(device);
+    }
+}
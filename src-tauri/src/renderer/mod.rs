@@ -0,0 +1,240 @@
+use crate::config::{Config, HtmlRenderer, RenderConfig, RenderFallback, ResourcePolicy};
+use crate::error::{BridgeError, BridgeResult};
+use crate::tools::{self, Tool};
+use std::io::Write;
+use std::path::Path;
+use tokio::process::Command;
+use tempfile::NamedTempFile;
+
+mod office;
+mod sanitize;
+mod spreadsheet;
+
+pub use office::office_to_pdf;
+pub use spreadsheet::{check_range as check_print_range, prepare_spreadsheet};
+
+/// Estilo por defecto para documentos Markdown sin tema configurado
+const DEFAULT_MARKDOWN_CSS: &str = "body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; \
+font-size: 11pt; line-height: 1.45; color: #111; } \
+h1, h2, h3 { margin: 0.8em 0 0.4em; } \
+table { border-collapse: collapse; width: 100%; } \
+th, td { border: 1px solid #999; padding: 4px 6px; text-align: left; } \
+code, pre { font-family: Menlo, Consolas, monospace; font-size: 9.5pt; } \
+pre { background: #f4f4f4; padding: 8px; white-space: pre-wrap; }";
+
+/// Convierte Markdown a un documento HTML completo con el tema configurado
+pub fn markdown_to_html(markdown: &str, config: &Config) -> BridgeResult<String> {
+    let css = match &config.markdown_css {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| {
+            BridgeError::ConfigError(format!("No se pudo leer markdown_css {}: {}", path, e))
+        })?,
+        None => DEFAULT_MARKDOWN_CSS.to_string(),
+    };
+
+    let mut options = comrak::Options::default();
+    options.extension.table = true;
+    options.extension.strikethrough = true;
+    options.extension.autolink = true;
+    options.extension.tasklist = true;
+
+    let body = comrak::markdown_to_html(markdown, &options);
+
+    Ok(format!(
+        "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><style>{}</style></head><body>{}</body></html>",
+        css, body
+    ))
+}
+
+/// Convierte HTML a PDF con el motor configurado y devuelve el PDF generado
+pub async fn html_to_pdf(html: &str, config: &Config) -> BridgeResult<NamedTempFile> {
+    // El renderizador corre en este equipo: con HTML arbitrario podría leer
+    // archivos locales o hacer peticiones a la red interna
+    let html = if config.render.strip_scripts {
+        sanitize::strip_scripts(html)
+    } else {
+        html.to_string()
+    };
+    let mut html_file = NamedTempFile::with_suffix(".html")?;
+    html_file.write_all(inject_page_style(&html, &config.render).as_bytes())?;
+    html_file.flush()?;
+
+    let pdf_file = NamedTempFile::with_suffix(".pdf")?;
+
+    let primary = render_with(config.html_renderer, &html_file, &pdf_file, config).await;
+
+    match (primary, config.render_fallback) {
+        (Ok(()), _) => Ok(pdf_file),
+        (Err(e), RenderFallback::AlternateEngine) => {
+            let alternate = match config.html_renderer {
+                HtmlRenderer::Chromium => HtmlRenderer::Wkhtmltopdf,
+                HtmlRenderer::Wkhtmltopdf => HtmlRenderer::Chromium,
+            };
+            log::warn!("⚠️ {}; reintentando con {:?}", e, alternate);
+            render_with(alternate, &html_file, &pdf_file, config).await?;
+            Ok(pdf_file)
+        }
+        (Err(e), _) => Err(e),
+    }
+}
+
+/// Rasteriza la primera página de un PDF a PNG con Ghostscript
+pub async fn rasterize_first_page(pdf: &[u8], config: &Config) -> BridgeResult<Vec<u8>> {
+    let mut pdf_file = NamedTempFile::with_suffix(".pdf")?;
+    pdf_file.write_all(pdf)?;
+    pdf_file.flush()?;
+
+    let png_file = NamedTempFile::with_suffix(".png")?;
+
+    let mut command = Command::new(tools::command_path(Tool::Ghostscript, config));
+    command
+        .args(["-q", "-dSAFER", "-dBATCH", "-dNOPAUSE", "-sDEVICE=png16m", "-r150"])
+        .args(["-dFirstPage=1", "-dLastPage=1"])
+        .arg(format!("-sOutputFile={}", png_file.path().display()))
+        .arg(pdf_file.path());
+    let output = tools::run(&mut command, config.render_timeout()).await?;
+
+    if !output.status.success() {
+        return Err(BridgeError::RenderError {
+            renderer: "ghostscript".to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+
+    Ok(std::fs::read(png_file.path())?)
+}
+
+async fn render_with(renderer: HtmlRenderer, html_file: &NamedTempFile, pdf_file: &NamedTempFile, config: &Config) -> BridgeResult<()> {
+    match renderer {
+        HtmlRenderer::Chromium => render_chromium(html_file, pdf_file, config).await?,
+        HtmlRenderer::Wkhtmltopdf => render_wkhtmltopdf(html_file, pdf_file, config).await?,
+    }
+
+    // Algunos motores terminan con éxito sin escribir nada
+    if pdf_file.as_file().metadata()?.len() == 0 {
+        return Err(BridgeError::RenderError {
+            renderer: renderer_name(renderer).to_string(),
+            stderr: "El renderizador no generó ningún PDF".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+fn renderer_name(renderer: HtmlRenderer) -> &'static str {
+    match renderer {
+        HtmlRenderer::Chromium => "chromium",
+        HtmlRenderer::Wkhtmltopdf => "wkhtmltopdf",
+    }
+}
+
+async fn render_chromium(html_file: &NamedTempFile, pdf_file: &NamedTempFile, config: &Config) -> BridgeResult<()> {
+    // Perfil descartable para no chocar con un Chrome abierto por el usuario
+    let profile_dir = tempfile::tempdir()?;
+
+    let mut command = Command::new(tools::command_path(Tool::Chromium, config));
+    command
+        .arg("--headless")
+        .arg("--disable-gpu")
+        .arg("--no-first-run")
+        .arg("--no-pdf-header-footer")
+        .arg("--print-to-pdf-no-header")
+        .arg(format!("--user-data-dir={}", profile_dir.path().display()))
+        .arg(format!("--print-to-pdf={}", pdf_file.path().display()));
+
+    if config.render.wait_for_network {
+        // Chromium espera a que la red quede inactiva, hasta este presupuesto
+        command.arg(format!("--virtual-time-budget={}", config.render.network_timeout_ms));
+    }
+    if config.render.resources != ResourcePolicy::Allow {
+        // `<-loopback>` quita la excepción implícita de localhost, que si no
+        // iría directo a los servicios del equipo
+        let bypass: Vec<String> = std::iter::once("<-loopback>".to_string())
+            .chain(sanitize::proxy_bypass(&config.render))
+            .collect();
+        command
+            .arg(format!("--proxy-server={}", sanitize::BLACKHOLE_PROXY))
+            .arg(format!("--proxy-bypass-list={}", bypass.join(";")));
+    }
+    if config.render.strip_scripts {
+        command.arg("--blink-settings=scriptEnabled=false");
+    }
+
+    command.arg(file_url(html_file.path()));
+    let output = tools::run(&mut command, config.render_timeout()).await?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(BridgeError::RenderError {
+            renderer: renderer_name(HtmlRenderer::Chromium).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+}
+
+async fn render_wkhtmltopdf(html_file: &NamedTempFile, pdf_file: &NamedTempFile, config: &Config) -> BridgeResult<()> {
+    let settings = &config.render;
+
+    let mut command = Command::new(tools::command_path(Tool::Wkhtmltopdf, config));
+    command.args([
+        "--page-size", &settings.page_size,
+        "--margin-top", &settings.margin,
+        "--margin-right", &settings.margin,
+        "--margin-bottom", &settings.margin,
+        "--margin-left", &settings.margin,
+    ]);
+
+    if settings.wait_for_network {
+        command.args(["--javascript-delay", &settings.network_timeout_ms.to_string()]);
+    }
+    if settings.resources != ResourcePolicy::Allow {
+        command.arg("--disable-local-file-access");
+        command.args(["--proxy", &format!("http://{}", sanitize::BLACKHOLE_PROXY)]);
+        for host in sanitize::proxy_bypass(settings) {
+            command.args(["--bypass-proxy-for", &host]);
+        }
+    }
+    if settings.strip_scripts {
+        command.arg("--disable-javascript");
+    }
+
+    command.arg(html_file.path()).arg(pdf_file.path());
+    let output = tools::run(&mut command, config.render_timeout()).await?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(BridgeError::RenderError {
+            renderer: renderer_name(HtmlRenderer::Wkhtmltopdf).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+}
+
+/// Chromium no acepta tamaño de página ni márgenes por línea de comandos,
+/// así que se fijan con una regla `@page` al inicio del documento, junto con
+/// la política de recursos.
+fn inject_page_style(html: &str, settings: &RenderConfig) -> String {
+    let style = format!(
+        "{}<style>@page {{ size: {}; margin: {}; }}</style>",
+        sanitize::csp_meta(settings).unwrap_or_default(),
+        settings.page_size, settings.margin
+    );
+
+    match html.find("<head>") {
+        Some(index) => {
+            let insert_at = index + "<head>".len();
+            format!("{}{}{}", &html[..insert_at], style, &html[insert_at..])
+        }
+        None => format!("{}{}", style, html),
+    }
+}
+
+fn file_url(path: &Path) -> String {
+    let path = path.display().to_string().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
@@ -0,0 +1,42 @@
+use super::file_url;
+use crate::config::Config;
+use crate::error::{BridgeError, BridgeResult};
+use crate::tools::{self, Tool};
+use std::io::Write;
+use tempfile::NamedTempFile;
+use tokio::process::Command;
+
+/// Convierte un documento de oficina a PDF con LibreOffice. `extension`
+/// (`docx`, `odt`) le dice a LibreOffice qué filtro de importación usar.
+pub async fn office_to_pdf(document: &[u8], extension: &str, config: &Config) -> BridgeResult<NamedTempFile> {
+    // LibreOffice no admite dos instancias con el mismo perfil, y el de un
+    // LibreOffice abierto por el usuario bloquearía la conversión
+    let profile_dir = tempfile::tempdir()?;
+    // La salida se escribe en --outdir con el nombre de la entrada
+    let work_dir = tempfile::tempdir()?;
+    let input = work_dir.path().join(format!("document.{}", extension));
+    std::fs::write(&input, document)?;
+
+    let mut command = Command::new(tools::command_path(Tool::Soffice, config));
+    command
+        .args(["--headless", "--norestore", "--nolockcheck", "--nodefault", "--nofirststartwizard"])
+        .arg(format!("-env:UserInstallation={}", file_url(profile_dir.path())))
+        .args(["--convert-to", "pdf", "--outdir"])
+        .arg(work_dir.path())
+        .arg(&input);
+    let output = tools::run(&mut command, config.render_timeout()).await?;
+
+    // soffice termina con éxito aunque no haya podido abrir el documento
+    let converted = work_dir.path().join("document.pdf");
+    if !output.status.success() || !converted.is_file() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(BridgeError::RenderError {
+            renderer: "soffice".to_string(),
+            stderr: if stderr.is_empty() { "LibreOffice no generó ningún PDF".to_string() } else { stderr },
+        });
+    }
+
+    let mut pdf_file = NamedTempFile::with_suffix(".pdf")?;
+    pdf_file.write_all(&std::fs::read(&converted)?)?;
+    Ok(pdf_file)
+}
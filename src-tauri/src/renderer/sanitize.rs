@@ -0,0 +1,57 @@
+use crate::config::{RenderConfig, ResourcePolicy};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// Proxy inexistente por el que se obliga a pasar a los renderizadores: lo
+/// que no esté en la lista de excepciones no llega a ningún sitio
+pub const BLACKHOLE_PROXY: &str = "127.0.0.1:9";
+
+/// Quita `<script>`, los atributos `on*` y las URL `javascript:`. Es una
+/// limpieza de texto, no un parser; con `resources = "block"` el CSP y el
+/// proxy impiden además que lo que se escape salga a la red.
+pub fn strip_scripts(html: &str) -> String {
+    static SCRIPT: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    static HANDLER: OnceLock<Regex> = OnceLock::new();
+    static JAVASCRIPT_URL: OnceLock<Regex> = OnceLock::new();
+
+    let script = SCRIPT.get_or_init(|| Regex::new(r"(?is)<script\b[^>]*>.*?</script\s*>|<script\b[^>]*>").unwrap());
+    let tag = TAG.get_or_init(|| Regex::new(r"<[a-zA-Z][^>]*>").unwrap());
+    let handler = HANDLER.get_or_init(|| Regex::new(r#"(?i)\s+on[a-z]+\s*=\s*("[^"]*"|'[^']*'|[^\s>]+)"#).unwrap());
+    let javascript_url = JAVASCRIPT_URL.get_or_init(|| Regex::new(r"(?i)javascript\s*:").unwrap());
+
+    let html = script.replace_all(html, "");
+    tag.replace_all(&html, |captures: &Captures| {
+        let without_handlers = handler.replace_all(&captures[0], "");
+        javascript_url.replace_all(&without_handlers, "about:blank#").into_owned()
+    })
+    .into_owned()
+}
+
+/// `<meta>` con la Content-Security-Policy de `resources`, o `None` si se
+/// permite todo. Solo se aceptan recursos `data:` y los hosts de la lista.
+pub fn csp_meta(settings: &RenderConfig) -> Option<String> {
+    let sources = match settings.resources {
+        ResourcePolicy::Allow => return None,
+        ResourcePolicy::Block => "data:".to_string(),
+        ResourcePolicy::Allowlist => std::iter::once("data:".to_string())
+            .chain(settings.resource_hosts.iter().map(|host| format!("https://{} http://{}", host, host)))
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    let scripts = if settings.strip_scripts { "'none'".to_string() } else { format!("'unsafe-inline' {}", sources) };
+
+    Some(format!(
+        "<meta http-equiv=\"Content-Security-Policy\" content=\"default-src 'none'; img-src {0}; font-src {0}; \
+         style-src 'unsafe-inline' {0}; script-src {1}\">",
+        sources, scripts
+    ))
+}
+
+/// Hosts que pueden saltarse el proxy de `BLACKHOLE_PROXY`
+pub fn proxy_bypass(settings: &RenderConfig) -> Vec<String> {
+    match settings.resources {
+        ResourcePolicy::Allowlist => settings.resource_hosts.clone(),
+        _ => Vec::new(),
+    }
+}
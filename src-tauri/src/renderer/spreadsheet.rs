@@ -0,0 +1,301 @@
+use crate::api::SpreadsheetOptions;
+use crate::error::{BridgeError, BridgeResult};
+use regex::{Captures, Regex};
+use std::io::{Cursor, Read, Write};
+use std::sync::OnceLock;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const WORKBOOK: &str = "xl/workbook.xml";
+const WORKBOOK_RELS: &str = "xl/_rels/workbook.xml.rels";
+
+/// Márgenes que pone Excel a una hoja nueva, por si la hoja no trae los suyos
+const DEFAULT_PAGE_MARGINS: &str =
+    r#"<pageMargins left="0.7" right="0.7" top="0.75" bottom="0.75" header="0.3" footer="0.3"/>"#;
+
+/// Elementos que van detrás de `pageSetup` en una hoja, en el orden del esquema
+const AFTER_PAGE_SETUP: &str = "headerFooter|rowBreaks|colBreaks|customProperties|cellWatches|ignoredErrors|\
+smartTags|drawing|legacyDrawing|legacyDrawingHF|drawingHF|picture|oleObjects|controls|webPublishItems|tableParts|extLst";
+
+macro_rules! pattern {
+    ($name:ident, $regex:expr) => {
+        fn $name() -> &'static Regex {
+            static PATTERN: OnceLock<Regex> = OnceLock::new();
+            PATTERN.get_or_init(|| Regex::new($regex).unwrap())
+        }
+    };
+}
+
+pattern!(sheet_tag, r"<sheet\s[^>]*>");
+pattern!(workbook_view_tag, r"<workbookView\b[^>]*>");
+pattern!(defined_name, r"(<definedName\s[^>]*>)[^<]*</definedName>");
+pattern!(relationship_tag, r"<Relationship\s[^>]*>");
+pattern!(worksheet_tag, r"<worksheet\b[^>]*>");
+pattern!(sheet_pr_empty, r"<sheetPr\b[^>]*/>");
+pattern!(page_setup_pr_tag, r"<pageSetUpPr\b[^>]*>");
+pattern!(page_setup_tag, r"<pageSetup\b[^>]*>");
+pattern!(page_margins_tag, r"<pageMargins\b[^>]*>");
+pattern!(cell, r"([A-Za-z]{1,3})([0-9]{1,7})");
+pattern!(cell_range, r"^[A-Za-z]{1,3}[1-9][0-9]{0,6}:[A-Za-z]{1,3}[1-9][0-9]{0,6}$");
+
+/// Comprueba que `range` es un rango de celdas como `A1:F40`
+pub fn check_range(range: &str) -> Result<(), String> {
+    if cell_range().is_match(range.trim()) {
+        Ok(())
+    } else {
+        Err(format!("'{}' no es un rango de celdas como A1:F40", range))
+    }
+}
+
+/// Una hoja de `xl/workbook.xml`
+struct Sheet {
+    name: String,
+    relationship: Option<String>,
+    visible: bool,
+}
+
+impl Sheet {
+    fn parse(tag: &str) -> Self {
+        Self {
+            name: attribute(tag, "name").unwrap_or_default(),
+            relationship: attribute(tag, "r:id"),
+            visible: !matches!(attribute(tag, "state").as_deref(), Some("hidden" | "veryHidden")),
+        }
+    }
+}
+
+/// Aplica `options.spreadsheet` al libro antes de convertirlo: LibreOffice no
+/// tiene opciones de línea de comandos para elegir hoja, área de impresión o
+/// escala, pero respeta las que trae el propio XLSX. Devuelve el libro sin
+/// tocar si no hay nada que cambiar.
+pub fn prepare_spreadsheet(data: &[u8], options: &SpreadsheetOptions) -> BridgeResult<Vec<u8>> {
+    let fit_to_width = options.fit_to_width.unwrap_or(false);
+    if options.sheet.is_none() && options.range.is_none() && !fit_to_width {
+        return Ok(data.to_vec());
+    }
+
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|e| not_a_workbook(e.to_string()))?;
+    let mut workbook = read_entry(&mut archive, WORKBOOK)?;
+    let sheets: Vec<Sheet> = sheet_tag().find_iter(&workbook).map(|tag| Sheet::parse(tag.as_str())).collect();
+
+    // Sin `sheet`, LibreOffice imprime todas las visibles; el área y la
+    // escala se aplican a la primera
+    let index = match options.sheet.as_deref() {
+        Some(name) => sheets
+            .iter()
+            .position(|sheet| sheet.name.to_lowercase() == name.trim().to_lowercase())
+            .ok_or_else(|| {
+                let names: Vec<String> = sheets.iter().map(|sheet| format!("'{}'", sheet.name)).collect();
+                BridgeError::InvalidRequest(format!("el libro no tiene la hoja '{}'; tiene {}", name, names.join(", ")))
+            })?,
+        None => sheets
+            .iter()
+            .position(|sheet| sheet.visible)
+            .ok_or_else(|| not_a_workbook("no tiene hojas visibles".to_string()))?,
+    };
+    let sheet = &sheets[index];
+
+    let mut changed = Vec::new();
+    if options.sheet.is_some() {
+        workbook = show_only(&workbook, index);
+    }
+    if let Some(range) = options.range.as_deref() {
+        workbook = set_print_area(&workbook, index, &sheet.name, range.trim());
+    }
+    if options.sheet.is_some() || options.range.is_some() {
+        changed.push((WORKBOOK.to_string(), workbook));
+    }
+    if fit_to_width {
+        let path = sheet_path(&mut archive, sheet)?;
+        let worksheet = read_entry(&mut archive, &path)?;
+        changed.push((path, fit_to_page_width(&worksheet)));
+    }
+
+    log::debug!("📊 Hoja '{}' preparada para imprimir", sheet.name);
+    rewrite(&mut archive, &changed)
+}
+
+/// Oculta todas las hojas menos la elegida y la deja como activa
+fn show_only(workbook: &str, index: usize) -> String {
+    let mut position = 0;
+    let workbook = sheet_tag().replace_all(workbook, |tag: &Captures| {
+        let tag = &tag[0];
+        let state = match attribute(tag, "state").as_deref() {
+            _ if position == index => None,
+            Some("veryHidden") => Some("veryHidden"),
+            _ => Some("hidden"),
+        };
+        position += 1;
+        set_attribute(tag, "state", state)
+    });
+    // Una hoja oculta no puede ser la activa
+    workbook_view_tag()
+        .replace_all(&workbook, |tag: &Captures| set_attribute(&tag[0], "activeTab", Some(&index.to_string())))
+        .into_owned()
+}
+
+/// Sustituye el área de impresión de la hoja `index` por `range`
+fn set_print_area(workbook: &str, index: usize, sheet: &str, range: &str) -> String {
+    let local_id = index.to_string();
+    let workbook = defined_name().replace_all(workbook, |name: &Captures| {
+        let tag = &name[1];
+        let is_print_area = attribute(tag, "name").as_deref() == Some("_xlnm.Print_Area")
+            && attribute(tag, "localSheetId").as_deref() == Some(local_id.as_str());
+        if is_print_area {
+            String::new()
+        } else {
+            name[0].to_string()
+        }
+    });
+
+    let absolute = cell().replace_all(range, |cell: &Captures| format!("${}${}", cell[1].to_uppercase(), &cell[2]));
+    let reference = format!("'{}'!{}", sheet.replace('\'', "''"), absolute);
+    let print_area = format!(
+        r#"<definedName name="_xlnm.Print_Area" localSheetId="{}">{}</definedName>"#,
+        local_id,
+        xml_escape(&reference)
+    );
+
+    // `definedNames` va detrás de `sheets` y, si las hay, de las referencias externas
+    if let Some(start) = workbook.find("<definedNames>") {
+        insert(&workbook, start + "<definedNames>".len(), &print_area)
+    } else if let Some(start) = workbook.find("<definedNames/>") {
+        replace(&workbook, start..start + "<definedNames/>".len(), &format!("<definedNames>{}</definedNames>", print_area))
+    } else {
+        let anchor = ["</externalReferences>", "</functionGroups>", "</sheets>"]
+            .iter()
+            .find_map(|tag| workbook.find(tag).map(|start| start + tag.len()));
+        match anchor {
+            Some(end) => insert(&workbook, end, &format!("<definedNames>{}</definedNames>", print_area)),
+            None => workbook.into_owned(),
+        }
+    }
+}
+
+/// Escala la hoja para que quepa a lo ancho de la página, con tantas
+/// páginas a lo alto como haga falta
+fn fit_to_page_width(worksheet: &str) -> String {
+    let fit = r#"<pageSetUpPr fitToPage="1"/>"#;
+    let worksheet = if let Some(found) = page_setup_pr_tag().find(worksheet) {
+        replace(worksheet, found.range(), &set_attribute(found.as_str(), "fitToPage", Some("1")))
+    } else if let Some(found) = sheet_pr_empty().find(worksheet) {
+        let open = found.as_str().trim_end_matches("/>").trim_end();
+        replace(worksheet, found.range(), &format!("{}>{}</sheetPr>", open, fit))
+    } else if let Some(start) = worksheet.find("</sheetPr>") {
+        // `pageSetUpPr` es el último hijo de `sheetPr`
+        insert(worksheet, start, fit)
+    } else if let Some(found) = worksheet_tag().find(worksheet) {
+        // `sheetPr` es el primer hijo de `worksheet`
+        insert(worksheet, found.end(), &format!("<sheetPr>{}</sheetPr>", fit))
+    } else {
+        worksheet.to_string()
+    };
+
+    if let Some(found) = page_setup_tag().find(&worksheet) {
+        let tag = set_attribute(found.as_str(), "fitToWidth", Some("1"));
+        let tag = set_attribute(&tag, "fitToHeight", Some("0"));
+        return replace(&worksheet, found.range(), &tag);
+    }
+    let page_setup = r#"<pageSetup fitToWidth="1" fitToHeight="0"/>"#;
+    if let Some(found) = page_margins_tag().find(&worksheet) {
+        return insert(&worksheet, found.end(), page_setup);
+    }
+    let after = Regex::new(&format!(r"<(?:{})\b|</worksheet>", AFTER_PAGE_SETUP)).unwrap();
+    match after.find(&worksheet) {
+        Some(found) => insert(&worksheet, found.start(), &format!("{}{}", DEFAULT_PAGE_MARGINS, page_setup)),
+        None => worksheet,
+    }
+}
+
+/// Ruta dentro del ZIP de la hoja, según las relaciones del libro
+fn sheet_path(archive: &mut ZipArchive<Cursor<&[u8]>>, sheet: &Sheet) -> BridgeResult<String> {
+    let relationships = read_entry(archive, WORKBOOK_RELS)?;
+    let target = sheet.relationship.as_deref().and_then(|id| {
+        relationship_tag()
+            .find_iter(&relationships)
+            .find(|tag| attribute(tag.as_str(), "Id").as_deref() == Some(id))
+            .and_then(|tag| attribute(tag.as_str(), "Target"))
+    });
+    match target {
+        Some(target) => Ok(match target.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("xl/{}", target),
+        }),
+        None => Err(not_a_workbook(format!("no se encuentra el archivo de la hoja '{}'", sheet.name))),
+    }
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> BridgeResult<String> {
+    let mut entry = archive.by_name(name).map_err(|e| not_a_workbook(format!("{}: {}", name, e)))?;
+    let mut contents = String::new();
+    entry
+        .read_to_string(&mut contents)
+        .map_err(|e| not_a_workbook(format!("{}: {}", name, e)))?;
+    Ok(contents)
+}
+
+/// El libro con las entradas de `changed` sustituidas; el resto se copia sin
+/// descomprimir
+fn rewrite(archive: &mut ZipArchive<Cursor<&[u8]>>, changed: &[(String, String)]) -> BridgeResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index).map_err(|e| not_a_workbook(e.to_string()))?;
+        match changed.iter().find(|(name, _)| name == entry.name()) {
+            Some((name, contents)) => {
+                zip.start_file(name.as_str(), SimpleFileOptions::default()).map_err(zip_error)?;
+                zip.write_all(contents.as_bytes())?;
+            }
+            None => zip.raw_copy_file(entry).map_err(zip_error)?,
+        }
+    }
+    Ok(zip.finish().map_err(zip_error)?.into_inner())
+}
+
+/// Valor de un atributo de la etiqueta, ya sin las entidades XML
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = Regex::new(&format!(r#"\s{}\s*=\s*"([^"]*)""#, regex::escape(name))).unwrap();
+    pattern.captures(tag).map(|captures| xml_unescape(&captures[1]))
+}
+
+/// La etiqueta con el atributo cambiado, añadido o, con `None`, quitado
+fn set_attribute(tag: &str, name: &str, value: Option<&str>) -> String {
+    let pattern = Regex::new(&format!(r#"\s{}\s*=\s*"[^"]*""#, regex::escape(name))).unwrap();
+    let mut tag = pattern.replace_all(tag, "").into_owned();
+    if let Some(value) = value {
+        let end = tag.len() - if tag.ends_with("/>") { 2 } else { 1 };
+        tag.insert_str(end, &format!(r#" {}="{}""#, name, xml_escape(value)));
+    }
+    tag
+}
+
+fn insert(text: &str, at: usize, inserted: &str) -> String {
+    format!("{}{}{}", &text[..at], inserted, &text[at..])
+}
+
+fn replace(text: &str, range: std::ops::Range<usize>, replacement: &str) -> String {
+    format!("{}{}{}", &text[..range.start], replacement, &text[range.end..])
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn not_a_workbook(detail: String) -> BridgeError {
+    BridgeError::InvalidRequest(format!("el documento no es un libro XLSX válido: {}", detail))
+}
+
+fn zip_error(e: zip::result::ZipError) -> BridgeError {
+    BridgeError::IoError(std::io::Error::other(e))
+}
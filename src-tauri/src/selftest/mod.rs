@@ -0,0 +1,169 @@
+use crate::config::{Config, HtmlRenderer, RenderFallback};
+use crate::printer::PrinterManager;
+use crate::source;
+use crate::tools::{self, Tool, ToolReport};
+use chrono::Local;
+use serde::Serialize;
+
+/// Un punto de la lista de comprobación
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// Identificador estable (`lp`, `html_renderer`, `soffice`, `spool`, `default_printer`)
+    pub id: &'static str,
+    pub label: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTest {
+    /// Todas las comprobaciones pasaron
+    pub passed: bool,
+    pub checked_at: String,
+    pub checks: Vec<Check>,
+}
+
+impl Check {
+    fn new(id: &'static str, label: impl Into<String>, passed: bool, detail: impl Into<String>) -> Self {
+        Self {
+            id,
+            label: label.into(),
+            passed,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Comprueba lo que el bridge necesita para imprimir: las herramientas de
+/// CUPS, el renderizador de HTML y LibreOffice, que el spool admite escritura
+/// y que la impresora predeterminada existe. Detecta de nuevo las
+/// herramientas por si se instalaron después de arrancar.
+pub async fn run(config: &Config) -> SelfTest {
+    let report = tools::detect_all(config);
+    let mut checks = Vec::new();
+    
+    for tool in [Tool::Lp, Tool::Lpstat] {
+        checks.push(if config.mock.enabled {
+            Check::new(tool.key(), tool.display_name(), true, "No se usa con las impresoras simuladas")
+        } else {
+            tool_check(tool, &report)
+        });
+    }
+    checks.push(renderer_check(config, &report));
+    checks.push(office_check(config, &report));
+    
+    let spool = match tokio::task::spawn_blocking(source::check_spool_writable).await {
+        Ok(Ok(dir)) => Check::new("spool", "Directorio del spool", true, dir.display().to_string()),
+        Ok(Err(e)) => Check::new("spool", "Directorio del spool", false, format!("No se puede escribir: {}", e)),
+        Err(e) => Check::new("spool", "Directorio del spool", false, e.to_string()),
+    };
+    checks.push(spool);
+    checks.push(default_printer_check(config).await);
+    
+    SelfTest {
+        passed: checks.iter().all(|check| check.passed),
+        checked_at: Local::now().to_rfc3339(),
+        checks,
+    }
+}
+
+fn tool_check(tool: Tool, report: &ToolReport) -> Check {
+    match report.statuses().iter().find(|status| status.tool == tool).and_then(|status| status.path.as_ref()) {
+        Some(path) => Check::new(tool.key(), tool.display_name(), true, path.display().to_string()),
+        None => Check::new(tool.key(), tool.display_name(), false, "No encontrado; indica la ruta en `tool_paths`"),
+    }
+}
+
+/// El motor configurado, o el otro si `render_fallback` permite cambiar.
+/// Solo cuenta si se aceptan documentos HTML o Markdown.
+fn renderer_check(config: &Config, report: &ToolReport) -> Check {
+    let (configured, alternate) = match config.html_renderer {
+        HtmlRenderer::Chromium => (Tool::Chromium, Tool::Wkhtmltopdf),
+        HtmlRenderer::Wkhtmltopdf => (Tool::Wkhtmltopdf, Tool::Chromium),
+    };
+    let label = format!("Renderizador de HTML ({})", configured.display_name());
+    
+    let renders_html = config
+        .allowed_file_types
+        .iter()
+        .any(|content_type| tools::required_tools(content_type, config).contains(&configured));
+    if !renders_html {
+        return Check::new("html_renderer", label, true, "Ningún tipo permitido lo necesita");
+    }
+    
+    if report.is_available(configured) {
+        let mut check = tool_check(configured, report);
+        check.id = "html_renderer";
+        check.label = label;
+        return check;
+    }
+    if config.render_fallback == RenderFallback::AlternateEngine && report.is_available(alternate) {
+        return Check::new(
+            "html_renderer",
+            label,
+            true,
+            format!("No encontrado; se usará {}", alternate.display_name()),
+        );
+    }
+    Check::new("html_renderer", label, false, "No encontrado; los documentos HTML y Markdown se rechazarán")
+}
+
+/// LibreOffice, si se aceptan documentos de oficina
+fn office_check(config: &Config, report: &ToolReport) -> Check {
+    let converts_office = config
+        .allowed_file_types
+        .iter()
+        .any(|content_type| tools::required_tools(content_type, config).contains(&Tool::Soffice));
+    if !converts_office {
+        return Check::new(Tool::Soffice.key(), Tool::Soffice.display_name(), true, "Ningún tipo permitido lo necesita");
+    }
+    tool_check(Tool::Soffice, report)
+}
+
+/// `default_printer` de la configuración, o la predeterminada del sistema si
+/// no hay ninguna, debe estar entre las impresoras disponibles
+async fn default_printer_check(config: &Config) -> Check {
+    let label = "Impresora predeterminada";
+    if let Some(queue) = &config.test_queue {
+        return Check::new("default_printer", label, true, format!("Modo de prueba: {}", queue));
+    }
+    
+    let printers = match PrinterManager::get_available_printers(config).await {
+        Ok(printers) => printers,
+        Err(e) => return Check::new("default_printer", label, false, format!("No se pudo consultar CUPS: {}", e)),
+    };
+    
+    match config.default_printer.as_deref().filter(|name| !name.is_empty()) {
+        Some(name) => match config.resolve_printer(name) {
+            Ok(queue) if printers.iter().any(|printer| printer.name == queue) => {
+                Check::new("default_printer", label, true, queue)
+            }
+            Ok(queue) => Check::new("default_printer", label, false, format!("La cola '{}' no existe", queue)),
+            Err(e) => Check::new("default_printer", label, false, e.to_string()),
+        },
+        None => match printers.iter().find(|printer| printer.is_default) {
+            Some(printer) => Check::new("default_printer", label, true, format!("{} (del sistema)", printer.name)),
+            None => Check::new(
+                "default_printer",
+                label,
+                false,
+                "Sin `default_printer` ni predeterminada del sistema; las solicitudes deben indicar la impresora",
+            ),
+        },
+    }
+}
+
+/// Corre la comprobación al arrancar y deja en el log lo que falla
+pub fn spawn_on_boot(config: &Config) {
+    let config = config.clone();
+    tokio::spawn(async move {
+        let result = run(&config).await;
+        if result.passed {
+            log::info!("✅ Autocomprobación superada ({} puntos)", result.checks.len());
+            return;
+        }
+        for check in result.checks.iter().filter(|check| !check.passed) {
+            log::warn!("⚠️ Autocomprobación: {}: {}", check.label, check.detail);
+        }
+    });
+}
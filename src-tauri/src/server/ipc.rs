@@ -0,0 +1,92 @@
+use futures_util::Stream;
+use std::io;
+use std::time::Duration;
+
+/// Pausa tras un error al aceptar (p. ej. sin descriptores libres), para no
+/// entrar en un bucle que consuma toda la CPU
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+/// Abre el socket Unix `path` solo para el usuario que ejecuta el bridge.
+/// Un socket que quedó de una ejecución anterior se reemplaza; cualquier
+/// otro archivo en esa ruta es un error.
+#[cfg(unix)]
+pub fn bind(path: &str) -> io::Result<impl Stream<Item = io::Result<tokio::net::UnixStream>> + Send> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} existe y no es un socket", path),
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+    Ok(futures_util::stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => return Some((Ok(stream), listener)),
+                Err(e) => {
+                    log::warn!("⚠️ Error aceptando una conexión local: {}", e);
+                    tokio::time::sleep(ACCEPT_RETRY).await;
+                }
+            }
+        }
+    }))
+}
+
+/// Borra el socket al detenerse el servidor
+#[cfg(unix)]
+pub fn remove(path: &str) {
+    let _ = std::fs::remove_file(path);
+}
+
+/// Abre la named pipe `path` (`\\.\pipe\<nombre>`; basta con el nombre).
+/// Cada conexión usa su propia instancia, así que se crea la siguiente antes
+/// de entregar la conectada.
+#[cfg(windows)]
+pub fn bind(path: &str) -> io::Result<impl Stream<Item = io::Result<tokio::net::windows::named_pipe::NamedPipeServer>> + Send> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let path = if path.starts_with(r"\\.\pipe\") {
+        path.to_string()
+    } else {
+        format!(r"\\.\pipe\{}", path)
+    };
+    // `first_pipe_instance` falla si otro proceso ya atiende esa pipe
+    let first = ServerOptions::new().first_pipe_instance(true).create(&path)?;
+
+    Ok(futures_util::stream::unfold((first, path), |(mut server, path)| async move {
+        loop {
+            if let Err(e) = server.connect().await {
+                log::warn!("⚠️ Error aceptando una conexión local: {}", e);
+                tokio::time::sleep(ACCEPT_RETRY).await;
+                continue;
+            }
+            match ServerOptions::new().create(&path) {
+                Ok(next) => {
+                    let connected = std::mem::replace(&mut server, next);
+                    return Some((Ok(connected), (server, path)));
+                }
+                Err(e) => {
+                    log::warn!("⚠️ No se pudo abrir otra instancia de {}: {}", path, e);
+                    let _ = server.disconnect();
+                    tokio::time::sleep(ACCEPT_RETRY).await;
+                }
+            }
+        }
+    }))
+}
+
+/// Las named pipes desaparecen al cerrarse la última instancia
+#[cfg(windows)]
+pub fn remove(_path: &str) {}
@@ -0,0 +1,310 @@
+use crate::api;
+use crate::config::{self, runtime::RuntimeState, Config, ListenerAuth};
+use crate::control::{BridgeControl, ServerState};
+use crate::history::{self, JobHistory};
+use crate::selftest;
+use crate::tls::{self, TlsFiles};
+use futures_util::future::{self, BoxFuture, Shared};
+use futures_util::FutureExt;
+use std::net::SocketAddr;
+use std::time::Duration;
+use warp::filters::BoxedFilter;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+mod ipc;
+mod reload;
+
+pub type ServerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Lo que llega por la línea de comandos en lugar de la configuración. Se
+/// vuelve a aplicar cada vez que se recarga para reiniciar el servidor.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub mock_printers: bool,
+    pub port: Option<u16>,
+    pub test_queue: Option<String>,
+}
+
+impl Overrides {
+    pub fn apply(&self, config: &mut Config) {
+        if self.mock_printers {
+            config.mock.enabled = true;
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if self.test_queue.is_some() {
+            config.test_queue = self.test_queue.clone();
+        }
+    }
+}
+
+/// Qué hacer si el servidor no llega a arrancar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnFailure {
+    /// Terminar con el error, para que lo vea el gestor de servicios
+    Exit,
+    /// Esperar a que se corrija la configuración y se pida un reinicio
+    WaitForRestart,
+}
+
+/// Por qué terminó una ejecución del servidor
+enum Stop {
+    Shutdown,
+    Restart,
+}
+
+/// Supervisa el servidor HTTP: lo arranca, lo vuelve a arrancar con la
+/// configuración del disco cuando se pide un reinicio y publica su estado en
+/// `control` para la GUI. Termina al pedirse el cierre.
+pub async fn run(config: Config, control: BridgeControl, overrides: Overrides, on_failure: OnFailure) -> Result<(), ServerError> {
+    // Una vez por proceso: un reinicio no vuelve a abrir el historial ni a
+    // lanzar sus tareas
+    let history = JobHistory::open();
+    history::spawn_maintenance(history.clone(), &config);
+    selftest::spawn_on_boot(&config);
+    reload::spawn_watcher(control.clone(), overrides.clone());
+
+    let mut config = config;
+    loop {
+        // Con el servidor recién arrancado no queda nada pendiente de reinicio
+        control.set_config(config.clone());
+        control.clear_reload();
+        control.set_server_state(ServerState::Starting);
+        match serve(&config, &control, &history).await {
+            Ok(Stop::Shutdown) => {
+                control.set_server_state(ServerState::Stopped);
+                return Ok(());
+            }
+            Ok(Stop::Restart) => log::info!("🔄 Reiniciando el servidor HTTP"),
+            Err(e) => {
+                log::error!("❌ Error en servidor HTTP: {}", e);
+                control.record_error(None, &format!("Servidor HTTP: {}", e));
+                control.set_server_state(ServerState::Failed { error: e.to_string() });
+                if on_failure == OnFailure::Exit {
+                    return Err(e);
+                }
+
+                let restart = control.restart_signal();
+                tokio::select! {
+                    _ = restart => log::info!("🔄 Reintentando arrancar el servidor HTTP"),
+                    _ = control.shutdown_signal() => return Ok(()),
+                }
+            }
+        }
+
+        config = match tokio::task::spawn_blocking(config::load_config).await {
+            Ok(Ok(mut fresh)) => {
+                overrides.apply(&mut fresh);
+                fresh
+            }
+            Ok(Err(e)) => {
+                log::error!("❌ No se pudo recargar la configuración, se mantiene la anterior: {}", e);
+                config
+            }
+            Err(e) => {
+                log::error!("❌ La tarea de recarga de configuración falló: {}", e);
+                config
+            }
+        };
+    }
+}
+
+/// Una ejecución del servidor, desde el bind hasta que se pide el cierre o
+/// un reinicio
+async fn serve(config: &Config, control: &BridgeControl, history: &JobHistory) -> Result<Stop, ServerError> {
+    // Rutas de la API, las mismas en cada listener salvo por la autenticación;
+    // el registro de acceso y CORS los aplica `api::routes`
+    let context = api::SecurityContext::start(config.clone(), control.clone(), history.clone());
+    let routes = |auth: ListenerAuth| api::routes(&context, auth).map(Reply::into_response).boxed();
+    let api_routes = routes(ListenerAuth::Token);
+
+    // Validar TLS antes de arrancar: warp entra en pánico con certificados inválidos
+    let tls_files = tls::resolve(config)?;
+    if let Some(files) = &tls_files {
+        log::info!("🔒 Sirviendo por HTTPS con certificado {}", files.cert_path.display());
+    }
+
+    let ip = config.bind_ip()?;
+    if !ip.is_loopback() {
+        // Sin token cualquiera en la red podría imprimir
+        if !config.has_credentials() {
+            return Err(format!("Para escuchar en {} hace falta configurar api_token", ip).into());
+        }
+        log::warn!("⚠️⚠️⚠️ ATENCIÓN: el bridge escucha en {} y es accesible desde otros equipos de la red", ip);
+        log::warn!("⚠️⚠️⚠️ Usa tokens largos, activa TLS y limita allowed_origins; para uso local pon host = \"127.0.0.1\"");
+    }
+    
+    // Antes del bind, para no perder un cierre o reinicio pedido entre medias
+    let stop = stop_signal(control);
+
+    // `try_bind` en lugar de `run`: un puerto ocupado vuelve como error en
+    // vez de un pánico dentro de la tarea, y se prueba el siguiente
+    let mut busy = Vec::new();
+    for port in config.candidate_ports() {
+        let address = SocketAddr::new(ip, port);
+        let (address, server) = match bind(api_routes.clone(), address, tls_files.as_ref(), stop.clone()) {
+            Ok(bound) => bound,
+            Err(e) if is_addr_in_use(&e) => {
+                log::warn!("⚠️ El puerto {} está en uso", port);
+                busy.push(port.to_string());
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if address.port() != config.port {
+            log::warn!("⚠️ Puerto {} ocupado, escuchando en {}", config.port, address.port());
+            control.announce(
+                "Print My Bridge",
+                &format!("El puerto {} está en uso; el bridge escucha en el {}", config.port, address.port()),
+            );
+        }
+        if let Err(e) = RuntimeState::new(address.port(), tls_files.is_some(), config.port).save() {
+            log::warn!("⚠️ No se pudo guardar el estado de ejecución: {}", e);
+        }
+        control.set_server_state(ServerState::Running { address, tls: tls_files.is_some() });
+        let mut servers = vec![server];
+        
+        // Listeners adicionales; uno que no arranca no detiene a los demás
+        for listener in &config.listeners {
+            match bind_listener(listener, routes(listener.auth), config, stop.clone()) {
+                Ok((address, server)) => {
+                    log::info!("🌐 API también disponible en {} ({:?})", address, listener.auth);
+                    servers.push(server);
+                }
+                Err(e) => {
+                    log::warn!("⚠️ No se pudo escuchar en {}:{}: {}", listener.host, listener.port, e);
+                    control.record_error(None, &format!("Listener {}:{}: {}", listener.host, listener.port, e));
+                }
+            }
+        }
+        
+        // Misma API para aplicaciones del equipo, sin TCP; se detiene con la señal de `stop`
+        let ipc_path = config.ipc_path.as_deref();
+        if let Some(path) = ipc_path {
+            match ipc::bind(path) {
+                Ok(incoming) => {
+                    log::info!("🔌 API también disponible en {}", path);
+                    servers.push(warp::serve(api_routes.clone()).serve_incoming_with_graceful_shutdown(incoming, stop.clone()).boxed());
+                }
+                Err(e) => log::warn!("⚠️ No se pudo abrir {}: {}", path, e),
+            }
+        }
+
+        // Los servidores corren hasta que se pide el cierre o un reinicio;
+        // desde ahí no aceptan conexiones nuevas
+        let mut server = tokio::spawn(future::join_all(servers));
+        stop.await;
+        control.set_server_state(ServerState::Draining);
+        let drain = Duration::from_secs(config.shutdown_drain_secs);
+
+        if !control.shutdown_requested() {
+            // El puerto ya quedó libre para el servidor nuevo. Lo que siga en
+            // curso pasado el plazo termina en segundo plano en este.
+            if tokio::time::timeout(drain, &mut server).await.is_err() {
+                log::warn!("⏱️ Quedan solicitudes en curso; terminarán en el servidor anterior");
+            }
+            RuntimeState::clear();
+            return Ok(Stop::Restart);
+        }
+
+        // Al cerrar se espera también a los trabajos fuera de una solicitud
+        // (los recuperados al arrancar)
+        log::info!("⏳ Esperando hasta {}s a {} trabajos en curso", drain.as_secs(), control.jobs().pending());
+        let drained = tokio::time::timeout(drain, async {
+            let _ = (&mut server).await;
+            while control.jobs().pending() > 0 {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        })
+        .await;
+        if drained.is_err() {
+            server.abort();
+            log::warn!(
+                "⏱️ {} trabajos sin terminar al agotarse la espera; se retomarán al volver a iniciar",
+                control.jobs().pending()
+            );
+        }
+
+        if let Err(e) = history.flush() {
+            log::warn!("⚠️ No se pudo volcar el historial: {}", e);
+        }
+        if let Some(path) = ipc_path {
+            ipc::remove(path);
+        }
+        RuntimeState::clear();
+        return Ok(Stop::Shutdown);
+    }
+
+    Err(format!("Todos los puertos candidatos están en uso: {}", busy.join(", ")).into())
+}
+
+/// Escucha en `address`, por HTTPS si hay `tls`
+fn bind(
+    routes: BoxedFilter<(Response,)>,
+    address: SocketAddr,
+    tls: Option<&TlsFiles>,
+    stop: Shared<BoxFuture<'static, ()>>,
+) -> Result<(SocketAddr, BoxFuture<'static, ()>), warp::Error> {
+    match tls {
+        Some(files) => warp::serve(routes)
+            .tls()
+            .cert_path(&files.cert_path)
+            .key_path(&files.key_path)
+            .try_bind_with_graceful_shutdown(address, stop)
+            .map(|(address, server)| (address, server.boxed())),
+        None => warp::serve(routes)
+            .try_bind_with_graceful_shutdown(address, stop)
+            .map(|(address, server)| (address, server.boxed())),
+    }
+}
+
+/// Un listener de `listeners`, con las mismas comprobaciones que `host`
+fn bind_listener(
+    listener: &config::Listener,
+    routes: BoxedFilter<(Response,)>,
+    config: &Config,
+    stop: Shared<BoxFuture<'static, ()>>,
+) -> Result<(SocketAddr, BoxFuture<'static, ()>), ServerError> {
+    let ip = listener.bind_ip()?;
+    if !ip.is_loopback() {
+        if listener.auth == ListenerAuth::None {
+            return Err(format!("auth = \"none\" solo se permite en loopback, no en {}", ip).into());
+        }
+        if !config.has_credentials() {
+            return Err(format!("Para escuchar en {} hace falta configurar api_token", ip).into());
+        }
+        log::warn!("⚠️⚠️⚠️ ATENCIÓN: el bridge escucha en {} y es accesible desde otros equipos de la red", ip);
+    }
+    let tls_files = if listener.tls { Some(tls::certificate(config)?) } else { None };
+    Ok(bind(routes, SocketAddr::new(ip, listener.port), tls_files.as_ref(), stop)?)
+}
+
+/// Se completa al pedirse el cierre o un reinicio. Compartida entre el
+/// apagado ordenado de warp y la espera de `serve`.
+fn stop_signal(control: &BridgeControl) -> Shared<BoxFuture<'static, ()>> {
+    let shutdown = control.shutdown_signal();
+    let restart = control.restart_signal();
+    async move {
+        tokio::select! {
+            _ = shutdown => {}
+            _ = restart => {}
+        }
+    }
+    .boxed()
+    .shared()
+}
+
+/// Si el error de warp viene de un `bind` a un puerto ocupado
+fn is_addr_in_use(error: &warp::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(error) = source {
+        if let Some(io) = error.downcast_ref::<std::io::Error>() {
+            return io.kind() == std::io::ErrorKind::AddrInUse;
+        }
+        source = error.source();
+    }
+    false
+}
@@ -0,0 +1,125 @@
+use super::Overrides;
+use crate::config::{self, Config, RESTART_FIELDS};
+use crate::control::{BridgeControl, ConfigReload};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Espera tras el primer evento: un editor guarda en varios pasos
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Vigila el archivo de configuración y aplica en caliente lo que el servidor
+/// lee en cada solicitud (límites, tipos, impresoras, tokens...). Los campos
+/// que solo cuentan al arrancar se conservan y quedan señalados en la recarga.
+pub fn spawn_watcher(control: BridgeControl, overrides: Overrides) {
+    let path = config::config_path();
+    let Some(file_name) = path.file_name().map(OsStr::to_os_string) else {
+        return;
+    };
+    // Se vigila el directorio: muchos editores reemplazan el archivo en lugar
+    // de reescribirlo, y el vigilante del archivo original se perdería
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let (events, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let _ = events.send(event);
+        }
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            log::warn!("⚠️ No se puede vigilar la configuración, los cambios requerirán reiniciar: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+        log::warn!("⚠️ No se puede vigilar {}: {}", dir.display(), e);
+        return;
+    }
+    log::info!("👀 Vigilando {} para recargar la configuración", path.display());
+
+    tokio::spawn(async move {
+        // El vigilante deja de emitir eventos en cuanto se suelta
+        let _watcher = watcher;
+        while let Some(event) = received.recv().await {
+            if !touches(&event, &file_name) {
+                continue;
+            }
+            tokio::time::sleep(DEBOUNCE).await;
+            while received.try_recv().is_ok() {}
+            reload(&control, &overrides).await;
+        }
+    });
+}
+
+fn touches(event: &notify::Event, file_name: &OsStr) -> bool {
+    !matches!(event.kind, EventKind::Access(_))
+        && event.paths.iter().any(|path| path.file_name() == Some(file_name))
+}
+
+async fn reload(control: &BridgeControl, overrides: &Overrides) {
+    let mut fresh = match tokio::task::spawn_blocking(config::load_config).await {
+        Ok(Ok(config)) => config,
+        Ok(Err(e)) => {
+            log::warn!("⚠️ Configuración inválida, se mantiene la anterior: {}", e);
+            return;
+        }
+        Err(e) => {
+            log::error!("❌ La tarea de recarga de configuración falló: {}", e);
+            return;
+        }
+    };
+    overrides.apply(&mut fresh);
+
+    let current = control.config();
+    let changed = current.changed_fields(&fresh);
+    if changed.is_empty() {
+        return;
+    }
+    let (restart_required, applied): (Vec<String>, Vec<String>) = changed
+        .into_iter()
+        .partition(|field| RESTART_FIELDS.contains(&field.as_str()));
+
+    let Some(effective) = keep_fields(&fresh, &current, &restart_required) else {
+        log::error!("❌ No se pudo combinar la configuración recargada, se mantiene la anterior");
+        return;
+    };
+    // Sin token, cualquiera en la red podría imprimir por el listener abierto
+    if !effective.has_credentials() && effective.listens_beyond_loopback() {
+        log::error!("❌ La configuración recargada no tiene tokens y el bridge escucha en la red, se mantiene la anterior");
+        return;
+    }
+    control.set_config(effective);
+
+    if !applied.is_empty() {
+        log::info!("🔁 Configuración recargada: {}", applied.join(", "));
+    }
+    if !restart_required.is_empty() {
+        log::warn!("⚠️ Cambios que requieren reiniciar el servidor: {}", restart_required.join(", "));
+        control.notify_desktop(
+            "Print My Bridge",
+            &format!("Reinicia el servidor para aplicar: {}", restart_required.join(", ")),
+        );
+    }
+    control.record_reload(ConfigReload {
+        at: chrono::Local::now().to_rfc3339(),
+        applied,
+        restart_required,
+    });
+}
+
+/// `fresh` con los `fields` de `current`: lo que el servidor no puede cambiar
+/// sin reiniciar sigue reflejando lo que de verdad usa
+fn keep_fields(fresh: &Config, current: &Config, fields: &[String]) -> Option<Config> {
+    let mut merged = serde_json::to_value(fresh).ok()?;
+    let current = serde_json::to_value(current).ok()?;
+    for field in fields {
+        merged[field.as_str()] = current[field.as_str()].clone();
+    }
+    serde_json::from_value(merged).ok()
+}
@@ -0,0 +1,383 @@
+use crate::config::Config;
+use crate::error::{BridgeError, BridgeResult};
+use base64::engine::general_purpose;
+use base64::read::DecoderReader;
+use futures_util::{Stream, TryStreamExt};
+use reqwest::Url;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::{NamedTempFile, TempPath};
+use warp::hyper::body::Buf;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Las mismas que sigue reqwest por defecto
+const MAX_REDIRECTS: usize = 10;
+
+/// Documento recibido directamente en disco (subidas multipart, cuerpo binario).
+/// Se borra cuando se suelta la última copia.
+#[derive(Clone)]
+pub struct SpooledFile(Arc<NamedTempFile>);
+
+impl SpooledFile {
+    /// Retoma un archivo del spool que quedó de una ejecución anterior
+    pub fn adopt(path: PathBuf) -> std::io::Result<Self> {
+        let file = std::fs::File::open(&path)?;
+        Ok(Self(Arc::new(NamedTempFile::from_parts(file, TempPath::from_path(path)))))
+    }
+
+    pub fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    pub fn len(&self) -> std::io::Result<u64> {
+        Ok(self.0.as_file().metadata()?.len())
+    }
+}
+
+impl fmt::Debug for SpooledFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SpooledFile").field(&self.path()).finish()
+    }
+}
+
+/// Los documentos se guardan en el directorio de datos y no en el temporal del
+/// sistema para que sobrevivan a un apagón y el trabajo se pueda retomar
+fn spool_dir() -> PathBuf {
+    crate::config::app_data_dir().join("spool")
+}
+
+/// Crea y borra un archivo en el spool para comprobar que admite escritura.
+/// Devuelve el directorio.
+pub fn check_spool_writable() -> std::io::Result<PathBuf> {
+    let dir = spool_dir();
+    std::fs::create_dir_all(&dir)?;
+    NamedTempFile::new_in(&dir)?;
+    Ok(dir)
+}
+
+/// Ocupación del spool y espacio libre en su disco
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SpoolSpace {
+    pub path: PathBuf,
+    pub used_bytes: u64,
+    pub free_bytes: u64,
+}
+
+pub fn spool_space() -> std::io::Result<SpoolSpace> {
+    let dir = spool_dir();
+    std::fs::create_dir_all(&dir)?;
+    Ok(SpoolSpace {
+        used_bytes: spool_usage(&dir),
+        free_bytes: free_space(&dir)?,
+        path: dir,
+    })
+}
+
+/// Bytes disponibles para el usuario en el disco de `path`
+#[cfg(unix)]
+// Los campos de `statvfs` son `u64` en Linux pero no en todos los Unix
+#[allow(clippy::useless_conversion)]
+fn free_space(path: &Path) -> std::io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(std::io::Error::other)?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+#[cfg(windows)]
+fn free_space(path: &Path) -> std::io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::shared::ntdef::ULARGE_INTEGER;
+    use winapi::um::fileapi::GetDiskFreeSpaceExW;
+    
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available: ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+    let ok = unsafe { GetDiskFreeSpaceExW(path.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { *available.QuadPart() })
+}
+
+/// Documento del spool a medio escribir. Corta en cuanto supera el tamaño
+/// máximo por documento o la cuota de `spool_quota_mb` para todo el directorio.
+struct SpoolWriter {
+    file: NamedTempFile,
+    written: u64,
+    max_size: u64,
+    /// Lo que queda de la cuota al empezar; los envíos simultáneos se
+    /// descuentan cuando ya están en disco, así que es aproximada
+    available: u64,
+    quota_mb: u64,
+}
+
+impl SpoolWriter {
+    fn create(max_size: u64, config: &Config) -> BridgeResult<Self> {
+        let dir = spool_dir();
+        std::fs::create_dir_all(&dir)?;
+
+        let available = match config.spool_quota_mb {
+            0 => u64::MAX,
+            quota_mb => (quota_mb * 1024 * 1024).saturating_sub(spool_usage(&dir)),
+        };
+
+        Ok(Self {
+            file: NamedTempFile::new_in(dir)?,
+            written: 0,
+            max_size,
+            available,
+            quota_mb: config.spool_quota_mb,
+        })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> BridgeResult<()> {
+        self.written += bytes.len() as u64;
+        if self.written > self.max_size {
+            log::warn!("🚫 Archivo demasiado grande: más de {} bytes", self.max_size);
+            return Err(BridgeError::FileTooLarge);
+        }
+        if self.written > self.available {
+            log::warn!("🚫 Spool lleno: el documento superaría la cuota de {} MB", self.quota_mb);
+            return Err(BridgeError::SpoolFull { quota_mb: self.quota_mb });
+        }
+        self.file.write_all(bytes)?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> BridgeResult<SpooledFile> {
+        self.file.flush()?;
+        Ok(SpooledFile(Arc::new(self.file)))
+    }
+}
+
+/// Bytes que ocupan ahora los documentos del spool
+fn spool_usage(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Borra los documentos del spool con más de `min_age` que ningún trabajo
+/// pendiente necesita
+pub fn remove_orphans(keep: &[PathBuf], min_age: Duration) {
+    let Ok(entries) = std::fs::read_dir(spool_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let age = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .unwrap_or_default();
+        if age >= min_age && !keep.contains(&path) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::debug!("No se pudo borrar {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Vuelca un cuerpo en streaming al spool sin tenerlo entero en memoria,
+/// cortando en cuanto supera `max_size` o la cuota del spool.
+pub async fn spool_stream<S, B, E>(stream: S, max_size: u64, config: &Config) -> BridgeResult<SpooledFile>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: Buf,
+    E: fmt::Display,
+{
+    let mut file = SpoolWriter::create(max_size, config)?;
+
+    futures_util::pin_mut!(stream);
+    while let Some(mut chunk) = stream
+        .try_next()
+        .await
+        .map_err(|e| BridgeError::InvalidRequest(format!("Error leyendo el cuerpo: {}", e)))?
+    {
+        while chunk.has_remaining() {
+            let bytes = chunk.chunk();
+            file.write(bytes)?;
+            let len = bytes.len();
+            chunk.advance(len);
+        }
+    }
+
+    file.finish()
+}
+
+/// Tipo de contenido del bridge que corresponde a un MIME
+pub fn content_type_for_mime(mime: &str) -> Option<&'static str> {
+    let essence = mime.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    match essence.as_str() {
+        "application/pdf" => Some("pdf"),
+        "text/html" => Some("html"),
+        "text/markdown" => Some("markdown"),
+        "text/plain" => Some("text"),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Some("docx"),
+        "application/vnd.oasis.opendocument.text" => Some("odt"),
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => Some("xlsx"),
+        "image/svg+xml" => Some("svg"),
+        "application/vnd.cups-raw" => Some("raw"),
+        m if m.starts_with("image/") => Some("image"),
+        _ => None,
+    }
+}
+
+/// Tipos cuyo `content` viaja como texto plano en lugar de base64 (la
+/// etiqueta, como JSON)
+pub fn is_text_type(content_type: &str) -> bool {
+    matches!(content_type, "html" | "markdown" | "text" | "label")
+}
+
+/// Descarga el documento de `url` al spool, bloque a bloque
+pub async fn fetch(url: &str, max_size: u64, config: &Config) -> BridgeResult<SpooledFile> {
+    let url = Url::parse(url).map_err(|e| BridgeError::InvalidRequest(format!("URL inválida: {}", e)))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(BridgeError::InvalidRequest(format!("Esquema no soportado: {}", url.scheme())));
+    }
+
+    let host = url.host_str().unwrap_or_default();
+    if !host_allowed(host, &config.allowed_url_hosts) {
+        log::warn!("🚫 Descarga rechazada, host fuera de la lista permitida: {}", host);
+        return Err(BridgeError::InvalidRequest(format!("Host no permitido: {}", host)));
+    }
+
+    // Cada redirección pasa por la misma lista: un host permitido no puede
+    // llevar la descarga a la red interna
+    let allowed_hosts = config.allowed_url_hosts.clone();
+    let redirects = reqwest::redirect::Policy::custom(move |attempt| {
+        let host = attempt.url().host_str().unwrap_or_default().to_string();
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error(format!("Más de {} redirecciones", MAX_REDIRECTS))
+        } else if !matches!(attempt.url().scheme(), "http" | "https") || !host_allowed(&host, &allowed_hosts) {
+            log::warn!("🚫 Redirección rechazada, host fuera de la lista permitida: {}", host);
+            attempt.error(format!("Redirección a un host no permitido: {}", host))
+        } else {
+            attempt.follow()
+        }
+    });
+
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(redirects)
+        .build()
+        .map_err(|e| BridgeError::FetchError(e.to_string()))?;
+
+    let mut response = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| BridgeError::FetchError(e.to_string()))?;
+
+    // Rechazar antes de descargar si el servidor ya anuncia el tamaño
+    if response.content_length().is_some_and(|len| len > max_size) {
+        return Err(BridgeError::FileTooLarge);
+    }
+
+    let mut file = SpoolWriter::create(max_size, config)?;
+    while let Some(chunk) = response.chunk().await.map_err(|e| BridgeError::FetchError(e.to_string()))? {
+        file.write(&chunk)?;
+    }
+
+    log::info!("🌐 Descargados {} bytes de {}", file.written, url);
+    file.finish()
+}
+
+/// Bytes que salen de decodificar `content`, sin decodificarlo: cuenta los
+/// caracteres de datos, sin espacios ni saltos de línea ni el relleno `=`
+fn base64_decoded_len(content: &str) -> u64 {
+    let symbols = content
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace() && *byte != b'=')
+        .count() as u64;
+    // Cada 4 caracteres son 3 bytes; un grupo final de 2 o 3 da 1 o 2
+    symbols / 4 * 3 + (symbols % 4).saturating_sub(1)
+}
+
+/// Base64 sin los espacios y saltos de línea con que lo parten MIME y PEM,
+/// que el decodificador no acepta
+struct WithoutWhitespace<'a>(std::slice::Iter<'a, u8>);
+
+impl Read for WithoutWhitespace<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.0.next() {
+                Some(byte) if byte.is_ascii_whitespace() => continue,
+                Some(byte) => {
+                    buf[read] = *byte;
+                    read += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// Decodifica base64 por bloques directamente al spool. El tamaño se valida
+/// con la longitud decodificada exacta antes de crear el archivo.
+pub fn spool_base64(content: &str, max_size: u64, config: &Config) -> BridgeResult<SpooledFile> {
+    let decoded_len = base64_decoded_len(content);
+    if decoded_len > max_size {
+        log::warn!("🚫 Archivo demasiado grande: {} bytes (máximo {})", decoded_len, max_size);
+        return Err(BridgeError::FileTooLarge);
+    }
+
+    let mut file = SpoolWriter::create(max_size, config)?;
+    let mut decoder = DecoderReader::new(WithoutWhitespace(content.as_bytes().iter()), &general_purpose::STANDARD);
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read = decoder.read(&mut buffer).map_err(|e| {
+            // El lector envuelve los errores de base64 en io::Error
+            match e.get_ref().and_then(|inner| inner.downcast_ref::<base64::DecodeError>()) {
+                Some(decode_error) => BridgeError::Base64Error(decode_error.clone()),
+                None => BridgeError::IoError(e),
+            }
+        })?;
+        if read == 0 {
+            break;
+        }
+        file.write(&buffer[..read])?;
+    }
+
+    file.finish()
+}
+
+/// Guarda en el spool un documento que ya está en memoria, p. ej. uno
+/// generado por el bridge a partir del que envió el cliente
+pub fn spool_bytes(data: &[u8], max_size: u64, config: &Config) -> BridgeResult<SpooledFile> {
+    let mut file = SpoolWriter::create(max_size, config)?;
+    file.write(data)?;
+    file.finish()
+}
+
+/// `example.com` coincide solo con ese host; `*.example.com` también con sus subdominios
+fn host_allowed(host: &str, allowed: &[String]) -> bool {
+    let host = host.to_ascii_lowercase();
+    allowed.iter().any(|pattern| {
+        let pattern = pattern.to_ascii_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+            None => host == pattern,
+        }
+    })
+}
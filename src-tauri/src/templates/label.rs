@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+/// Lado máximo de una etiqueta; algo mayor ya es una hoja
+const MAX_SIDE_MM: f32 = 300.0;
+
+/// Etiqueta de `width_mm` x `height_mm` con sus elementos. Las posiciones
+/// son la esquina superior izquierda de cada elemento, en mm desde la
+/// esquina superior izquierda de la etiqueta.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LabelLayout {
+    pub width_mm: f32,
+    pub height_mm: f32,
+    #[serde(default)]
+    pub elements: Vec<LabelElement>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LabelElement {
+    /// Una línea de texto de `height_mm` de alto
+    Text {
+        x_mm: f32,
+        y_mm: f32,
+        text: String,
+        #[serde(default = "default_text_height")]
+        height_mm: f32,
+    },
+    /// Código de barras; en QR el tamaño lo da `module_mm` y no `height_mm`
+    Barcode {
+        x_mm: f32,
+        y_mm: f32,
+        value: String,
+        #[serde(default)]
+        symbology: Symbology,
+        #[serde(default = "default_barcode_height")]
+        height_mm: f32,
+        /// Ancho de la barra más fina (o del módulo del QR)
+        module_mm: Option<f32>,
+        /// El valor en texto debajo de las barras; no se aplica al QR
+        #[serde(default = "default_human_readable")]
+        human_readable: bool,
+    },
+    /// Recuadro; con el alto igual al grosor es una línea
+    #[serde(rename = "box")]
+    Rect {
+        x_mm: f32,
+        y_mm: f32,
+        width_mm: f32,
+        height_mm: f32,
+        #[serde(default = "default_thickness")]
+        thickness_mm: f32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Symbology {
+    #[default]
+    Code128,
+    Ean13,
+    Qr,
+}
+
+impl Symbology {
+    /// Módulo que leen sin problemas los lectores de mano a 203 ppp
+    pub fn default_module_mm(self) -> f32 {
+        match self {
+            Symbology::Code128 | Symbology::Ean13 => 0.33,
+            Symbology::Qr => 0.5,
+        }
+    }
+}
+
+fn default_text_height() -> f32 {
+    3.0
+}
+
+fn default_barcode_height() -> f32 {
+    10.0
+}
+
+fn default_human_readable() -> bool {
+    true
+}
+
+fn default_thickness() -> f32 {
+    0.3
+}
+
+impl LabelLayout {
+    /// Medidas fuera de rango y valores que el código de barras no admite
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (field, side) in [("width_mm", self.width_mm), ("height_mm", self.height_mm)] {
+            if !(side > 0.0 && side <= MAX_SIDE_MM) {
+                problems.push(format!("{} debe estar entre 0 (sin incluir) y {}", field, MAX_SIDE_MM));
+            }
+        }
+
+        for (index, element) in self.elements.iter().enumerate() {
+            let (x_mm, y_mm) = element.position();
+            if !(0.0..self.width_mm).contains(&x_mm) || !(0.0..self.height_mm).contains(&y_mm) {
+                problems.push(format!("elements[{}] empieza fuera de la etiqueta", index));
+            }
+            let problem = match element {
+                LabelElement::Text { height_mm, .. } if *height_mm <= 0.0 => Some("height_mm debe ser mayor que 0".to_string()),
+                LabelElement::Barcode { value, symbology, height_mm, module_mm, .. } => {
+                    if module_mm.is_some_and(|module| !(module > 0.0 && module <= 5.0)) {
+                        Some("module_mm debe estar entre 0 (sin incluir) y 5".to_string())
+                    } else if *height_mm <= 0.0 {
+                        Some("height_mm debe ser mayor que 0".to_string())
+                    } else {
+                        check_barcode(value, *symbology).err()
+                    }
+                }
+                LabelElement::Rect { width_mm, height_mm, thickness_mm, .. } if *width_mm <= 0.0 || *height_mm <= 0.0 || *thickness_mm <= 0.0 => {
+                    Some("width_mm, height_mm y thickness_mm deben ser mayores que 0".to_string())
+                }
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                problems.push(format!("elements[{}]: {}", index, problem));
+            }
+        }
+        problems
+    }
+}
+
+impl LabelElement {
+    pub fn position(&self) -> (f32, f32) {
+        match self {
+            LabelElement::Text { x_mm, y_mm, .. } | LabelElement::Barcode { x_mm, y_mm, .. } | LabelElement::Rect { x_mm, y_mm, .. } => {
+                (*x_mm, *y_mm)
+            }
+        }
+    }
+}
+
+/// Un valor con `{{campo}}` se comprueba una vez rellenado
+fn check_barcode(value: &str, symbology: Symbology) -> Result<(), String> {
+    match symbology {
+        _ if value.is_empty() => Err("el código de barras no tiene valor".to_string()),
+        _ if value.contains("{{") => Ok(()),
+        Symbology::Code128 if !value.chars().all(|c| c == ' ' || c.is_ascii_graphic()) => {
+            Err(format!("'{}' tiene caracteres que Code 128 no codifica", value))
+        }
+        Symbology::Ean13 => ean13_payload(value).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+/// Los 12 dígitos que se codifican de un EAN-13; el de control lo calcula
+/// quien dibuja las barras. Si llegan 13, el último debe ser el correcto.
+pub fn ean13_payload(value: &str) -> Result<&str, String> {
+    if !value.bytes().all(|byte| byte.is_ascii_digit()) || !matches!(value.len(), 12 | 13) {
+        return Err(format!("'{}' no es un EAN-13: deben ser 12 o 13 dígitos", value));
+    }
+    let payload = &value[..12];
+    let sum: u32 = payload
+        .bytes()
+        .enumerate()
+        .map(|(index, digit)| u32::from(digit - b'0') * if index % 2 == 0 { 1 } else { 3 })
+        .sum();
+    let check = (10 - sum % 10) % 10;
+    match value.as_bytes().get(12) {
+        Some(&digit) if u32::from(digit - b'0') != check => {
+            Err(format!("'{}' no es un EAN-13: el dígito de control debería ser {}", value, check))
+        }
+        _ => Ok(payload),
+    }
+}
@@ -0,0 +1,197 @@
+use crate::api::PrintRequest;
+use crate::config::{Config, PrintTemplate};
+use crate::error::{BridgeError, BridgeResult, FieldProblem};
+use handlebars::{handlebars_helper, Handlebars, Template};
+use serde_json::Value;
+
+mod label;
+
+pub use label::{ean13_payload, LabelElement, LabelLayout, Symbology};
+
+/// Tipos de documento que puede producir una plantilla
+pub const OUTPUT_TYPES: [&str; 3] = ["html", "markdown", "text"];
+
+// Columnas de ancho fijo para los tickets en texto: `{{pad name 24}}` rellena
+// por la derecha y `{{padl total 8}}` por la izquierda; lo que no cabe se corta
+handlebars_helper!(pad: |value: Json, width: u64| column(value, width as usize, false));
+handlebars_helper!(padl: |value: Json, width: u64| column(value, width as usize, true));
+// Importes: `{{fixed price 2}}`
+handlebars_helper!(fixed: |value: f64, decimals: u64| format!("{:.*}", decimals as usize, value));
+
+/// Comprueba que la plantilla produce un tipo admitido y que compila
+pub fn check(template: &PrintTemplate) -> Result<(), String> {
+    if !OUTPUT_TYPES.contains(&template.content_type.as_str()) {
+        return Err(format!(
+            "content_type '{}' no es válido; use uno de: {}",
+            template.content_type,
+            OUTPUT_TYPES.join(", ")
+        ));
+    }
+    Template::compile(&template.body).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Problemas de un diseño de etiqueta guardado: medidas, códigos de barras
+/// sin campos y `{{campo}}` mal escritos
+pub fn check_label(layout: &LabelLayout) -> Vec<String> {
+    let mut problems = layout.problems();
+    for (index, element) in layout.elements.iter().enumerate() {
+        let field = match element {
+            LabelElement::Text { text, .. } => text,
+            LabelElement::Barcode { value, .. } => value,
+            LabelElement::Rect { .. } => continue,
+        };
+        if let Err(e) = Template::compile(field) {
+            problems.push(format!("elements[{}]: {}", index, e));
+        }
+    }
+    problems
+}
+
+/// Convierte una solicitud `content_type = "template"` en el documento que
+/// produce su plantilla. Desde aquí se valida, se guarda en el historial y se
+/// imprime como cualquier otro: reimprimirla no vuelve a aplicar la plantilla.
+pub fn render_request(request: &mut PrintRequest, config: &Config) -> BridgeResult<()> {
+    let mut problems = Vec::new();
+    if !request.content.is_empty() || request.url.is_some() || request.spooled.is_some() {
+        problems.push(FieldProblem::new("content", "una plantilla no lleva 'content' ni 'url'; los datos van en 'data'"));
+    }
+    if request.data.as_ref().is_some_and(|data| !data.is_object()) {
+        problems.push(FieldProblem::new("data", "debe ser un objeto JSON"));
+    }
+    let template = match request.template_name.as_deref() {
+        None => {
+            problems.push(FieldProblem::new("template_name", "es obligatorio con content_type = template"));
+            None
+        }
+        Some(name) => match config.templates.get(name) {
+            Some(template) => Some((name.to_string(), template)),
+            None => {
+                let mut names: Vec<&str> = config.templates.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                problems.push(FieldProblem::new(
+                    "template_name",
+                    format!("no existe la plantilla '{}'; hay: {}", name, names.join(", ")),
+                ));
+                None
+            }
+        },
+    };
+    let Some((name, template)) = template.filter(|_| problems.is_empty()) else {
+        return Err(BridgeError::ValidationFailed(problems));
+    };
+
+    let data = request.data.take().unwrap_or_else(|| Value::Object(Default::default()));
+    request.content = render(template, &data)
+        .map_err(|e| BridgeError::InvalidRequest(format!("la plantilla '{}' no se pudo aplicar: {}", name, e)))?;
+    request.content_type = template.content_type.clone();
+    request.preset = request.preset.take().or_else(|| template.preset.clone());
+    request.template_name = None;
+    // Para buscar en el historial los trabajos de cada plantilla
+    request.metadata.entry("template").or_insert_with(|| Value::from(name.as_str()));
+
+    log::debug!("🧾 Plantilla '{}' aplicada ({} bytes de {})", name, request.content.len(), request.content_type);
+    Ok(())
+}
+
+/// Convierte una solicitud `content_type = "label"` en la etiqueta ya
+/// rellenada: el diseño sale de `labels.layouts` (`template_name`) o de
+/// `content`, y sus `{{campo}}` se sustituyen con `data`. La solicitud sigue
+/// siendo `label`, con el diseño resultante en `content`.
+pub fn render_label(request: &mut PrintRequest, config: &Config) -> BridgeResult<()> {
+    let mut problems = Vec::new();
+    if request.url.is_some() || request.spooled.is_some() {
+        problems.push(FieldProblem::new("content", "una etiqueta va en 'content' o en 'template_name', no en 'url'"));
+    }
+    if request.data.as_ref().is_some_and(|data| !data.is_object()) {
+        problems.push(FieldProblem::new("data", "debe ser un objeto JSON"));
+    }
+    let layout = match (request.template_name.as_deref(), request.content.is_empty()) {
+        (Some(_), false) => {
+            problems.push(FieldProblem::new("template_name", "use 'content' o 'template_name', no los dos"));
+            None
+        }
+        (Some(name), true) => match config.labels.layouts.get(name) {
+            Some(layout) => Some(layout.clone()),
+            None => {
+                let mut names: Vec<&str> = config.labels.layouts.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                problems.push(FieldProblem::new(
+                    "template_name",
+                    format!("no existe la etiqueta '{}'; hay: {}", name, names.join(", ")),
+                ));
+                None
+            }
+        },
+        (None, false) => match serde_json::from_str::<LabelLayout>(&request.content) {
+            Ok(layout) => Some(layout),
+            Err(e) => {
+                problems.push(FieldProblem::new("content", format!("no es una etiqueta válida: {}", e)));
+                None
+            }
+        },
+        (None, true) => {
+            problems.push(FieldProblem::new("content", "falta la etiqueta: envíela en 'content' o indique 'template_name'"));
+            None
+        }
+    };
+    let Some(mut layout) = layout.filter(|_| problems.is_empty()) else {
+        return Err(BridgeError::ValidationFailed(problems));
+    };
+
+    let data = request.data.take().unwrap_or_else(|| Value::Object(Default::default()));
+    let handlebars = registry(false);
+    for element in &mut layout.elements {
+        let field = match element {
+            LabelElement::Text { text, .. } => text,
+            LabelElement::Barcode { value, .. } => value,
+            LabelElement::Rect { .. } => continue,
+        };
+        *field = handlebars
+            .render_template(field, &data)
+            .map_err(|e| BridgeError::InvalidRequest(format!("la etiqueta no se pudo rellenar: {}", e)))?;
+    }
+    // Los valores ya rellenados son los que tienen que caber en el código
+    let problems: Vec<FieldProblem> = layout.problems().into_iter().map(|problem| FieldProblem::new("content", problem)).collect();
+    if !problems.is_empty() {
+        return Err(BridgeError::ValidationFailed(problems));
+    }
+
+    request.content = serde_json::to_string(&layout).map_err(std::io::Error::other)?;
+    if let Some(name) = request.template_name.take() {
+        request.metadata.entry("template").or_insert_with(|| Value::from(name));
+    }
+    log::debug!("🏷️ Etiqueta de {}x{} mm con {} elementos", layout.width_mm, layout.height_mm, layout.elements.len());
+    Ok(())
+}
+
+fn render(template: &PrintTemplate, data: &Value) -> Result<String, handlebars::RenderError> {
+    // Solo el HTML se escapa; en texto y Markdown los datos van tal cual
+    registry(template.content_type == "html").render_template(&template.body, data)
+}
+
+fn registry(escape_html: bool) -> Handlebars<'static> {
+    let mut handlebars = Handlebars::new();
+    // Un campo que falta es un error del cliente, no un hueco en el ticket
+    handlebars.set_strict_mode(true);
+    if !escape_html {
+        handlebars.register_escape_fn(handlebars::no_escape);
+    }
+    handlebars.register_helper("pad", Box::new(pad));
+    handlebars.register_helper("padl", Box::new(padl));
+    handlebars.register_helper("fixed", Box::new(fixed));
+    handlebars
+}
+
+fn column(value: &Value, width: usize, right: bool) -> String {
+    let text = match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    let text: String = text.chars().take(width).collect();
+    if right {
+        format!("{:>1$}", text, width)
+    } else {
+        format!("{:<1$}", text, width)
+    }
+}
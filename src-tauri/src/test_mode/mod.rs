@@ -0,0 +1,64 @@
+use crate::config::Config;
+use crate::error::{BridgeError, BridgeResult};
+use crate::tools::{self, Tool};
+use std::process::Command;
+
+pub const TEST_QUEUE_NAME: &str = "print-my-bridge-test";
+
+/// Cola CUPS desechable que descarta todo lo que recibe.
+/// Se elimina automáticamente al salir del modo de prueba.
+pub struct TestQueue {
+    name: String,
+    lpadmin: std::path::PathBuf,
+}
+
+impl TestQueue {
+    pub fn create(config: &Config) -> BridgeResult<Self> {
+        if cfg!(target_os = "windows") {
+            return Err(BridgeError::ConfigError(
+                "--test-mode requiere CUPS (macOS/Linux)".to_string(),
+            ));
+        }
+
+        let lpadmin = tools::command_path(Tool::Lpadmin, config);
+        let output = Command::new(&lpadmin)
+            .args(["-p", TEST_QUEUE_NAME, "-E", "-v", "file:///dev/null", "-m", "raw"])
+            .output()?;
+
+        if !output.status.success() {
+            // CUPS rechaza file:// salvo con FileDevice Yes en cups-files.conf
+            return Err(BridgeError::PrinterError(format!(
+                "No se pudo crear la cola de prueba: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        log::info!("🧪 Cola de prueba {} creada (salida a /dev/null)", TEST_QUEUE_NAME);
+        Ok(Self {
+            name: TEST_QUEUE_NAME.to_string(),
+            lpadmin,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Drop for TestQueue {
+    fn drop(&mut self) {
+        match Command::new(&self.lpadmin).args(["-x", &self.name]).output() {
+            Ok(output) if output.status.success() => {
+                log::info!("🧹 Cola de prueba {} eliminada", self.name);
+            }
+            Ok(output) => {
+                log::warn!(
+                    "⚠️ No se pudo eliminar la cola de prueba {}: {}",
+                    self.name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            Err(e) => log::warn!("⚠️ No se pudo eliminar la cola de prueba {}: {}", self.name, e),
+        }
+    }
+}
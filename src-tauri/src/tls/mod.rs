@@ -0,0 +1,117 @@
+use crate::config::{self, Config};
+use crate::error::{BridgeError, BridgeResult};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Rutas ya validadas del certificado y la llave privada
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// Huella SHA-256 del certificado, en el formato que muestran los navegadores
+    pub fingerprint: String,
+}
+
+/// Verifica que el certificado y la llave existan y sean PEM válidos.
+/// warp entra en pánico con archivos inválidos, así que se comprueba antes
+/// de arrancar para poder dar un error claro.
+pub fn validate(cert_path: &Path, key_path: &Path) -> BridgeResult<TlsFiles> {
+    let cert_file = File::open(cert_path).map_err(|e| {
+        BridgeError::TlsError(format!("No se puede abrir el certificado {}: {}", cert_path.display(), e))
+    })?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            BridgeError::TlsError(format!("Certificado inválido en {}: {}", cert_path.display(), e))
+        })?;
+    if certs.is_empty() {
+        return Err(BridgeError::TlsError(format!(
+            "{} no contiene ningún certificado PEM",
+            cert_path.display()
+        )));
+    }
+
+    let key_file = File::open(key_path).map_err(|e| {
+        BridgeError::TlsError(format!("No se puede abrir la llave privada {}: {}", key_path.display(), e))
+    })?;
+    match rustls_pemfile::private_key(&mut BufReader::new(key_file)) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err(BridgeError::TlsError(format!(
+                "{} no contiene una llave privada PEM",
+                key_path.display()
+            )));
+        }
+        Err(e) => {
+            return Err(BridgeError::TlsError(format!(
+                "Llave privada inválida en {}: {}",
+                key_path.display(),
+                e
+            )));
+        }
+    }
+
+    Ok(TlsFiles {
+        cert_path: cert_path.to_path_buf(),
+        key_path: key_path.to_path_buf(),
+        fingerprint: fingerprint(certs[0].as_ref()),
+    })
+}
+
+/// Devuelve los archivos TLS a usar, o `None` si TLS está desactivado.
+/// Sin certificado configurado se usa (y si hace falta se genera) uno
+/// autofirmado en el directorio de datos de la aplicación.
+pub fn resolve(config: &Config) -> BridgeResult<Option<TlsFiles>> {
+    if !config.tls_enabled {
+        return Ok(None);
+    }
+    certificate(config).map(Some)
+}
+
+/// El certificado configurado o el autofirmado, aunque `tls_enabled` esté
+/// desactivado; lo usan también los listeners con `tls = true`
+pub fn certificate(config: &Config) -> BridgeResult<TlsFiles> {
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert), Some(key)) => validate(Path::new(cert), Path::new(key)),
+        (None, None) => ensure_self_signed(config),
+        _ => Err(BridgeError::TlsError(
+            "tls_cert_path y tls_key_path deben configurarse juntos".to_string(),
+        )),
+    }
+}
+
+fn ensure_self_signed(config: &Config) -> BridgeResult<TlsFiles> {
+    let dir = config::app_data_dir().join("tls");
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+
+    if !cert_path.exists() || !key_path.exists() {
+        fs::create_dir_all(&dir)?;
+
+        let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        for host in std::iter::once(&config.host).chain(config.listeners.iter().map(|listener| &listener.host)) {
+            if !names.contains(host) {
+                names.push(host.clone());
+            }
+        }
+
+        let generated = rcgen::generate_simple_self_signed(names)
+            .map_err(|e| BridgeError::TlsError(format!("No se pudo generar el certificado: {}", e)))?;
+        fs::write(&cert_path, generated.cert.pem())?;
+        fs::write(&key_path, generated.key_pair.serialize_pem())?;
+
+        log::info!("🔐 Certificado autofirmado generado en {}", dir.display());
+    }
+
+    validate(&cert_path, &key_path)
+}
+
+fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
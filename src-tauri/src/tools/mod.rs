@@ -0,0 +1,329 @@
+use crate::config::{Config, HtmlRenderer};
+use crate::error::{BridgeError, BridgeResult};
+use serde::Serialize;
+use std::env;
+use std::process::{Command, Output};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Programas externos de los que dependen algunos tipos de contenido
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tool {
+    Lp,
+    Lpstat,
+    Lpoptions,
+    Lpadmin,
+    Wkhtmltopdf,
+    Chromium,
+    Soffice,
+    Ghostscript,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 8] = [
+        Tool::Lp,
+        Tool::Lpstat,
+        Tool::Lpoptions,
+        Tool::Lpadmin,
+        Tool::Wkhtmltopdf,
+        Tool::Chromium,
+        Tool::Soffice,
+        Tool::Ghostscript,
+    ];
+
+    /// Clave usada en `tool_paths` dentro de la configuración
+    pub fn key(&self) -> &'static str {
+        match self {
+            Tool::Lp => "lp",
+            Tool::Lpstat => "lpstat",
+            Tool::Lpoptions => "lpoptions",
+            Tool::Lpadmin => "lpadmin",
+            Tool::Wkhtmltopdf => "wkhtmltopdf",
+            Tool::Chromium => "chromium",
+            Tool::Soffice => "soffice",
+            Tool::Ghostscript => "gs",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Tool::Lp => "lp (CUPS)",
+            Tool::Lpstat => "lpstat (CUPS)",
+            Tool::Lpoptions => "lpoptions (CUPS)",
+            Tool::Lpadmin => "lpadmin (CUPS)",
+            Tool::Wkhtmltopdf => "wkhtmltopdf",
+            Tool::Chromium => "Chrome/Chromium",
+            Tool::Soffice => "LibreOffice",
+            Tool::Ghostscript => "Ghostscript",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Tool> {
+        Self::ALL.into_iter().find(|tool| tool.key() == key)
+    }
+
+    fn executable_names(&self) -> &'static [&'static str] {
+        match self {
+            Tool::Lp => &["lp"],
+            Tool::Lpstat => &["lpstat"],
+            Tool::Lpoptions => &["lpoptions"],
+            Tool::Lpadmin => &["lpadmin"],
+            Tool::Wkhtmltopdf => &["wkhtmltopdf"],
+            Tool::Chromium => &["chromium", "chromium-browser", "google-chrome", "google-chrome-stable", "chrome", "msedge"],
+            Tool::Soffice => &["soffice", "libreoffice"],
+            Tool::Ghostscript => &["gs", "gswin64c", "gswin32c"],
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ToolStatus {
+    pub tool: Tool,
+    pub name: &'static str,
+    pub available: bool,
+    pub path: Option<PathBuf>,
+}
+
+/// Resultado de la detección de dependencias hecha al arrancar
+#[derive(Clone, Debug, Default)]
+pub struct ToolReport {
+    statuses: Vec<ToolStatus>,
+}
+
+impl ToolReport {
+    pub fn statuses(&self) -> &[ToolStatus] {
+        &self.statuses
+    }
+
+    pub fn is_available(&self, tool: Tool) -> bool {
+        self.statuses.iter().any(|s| s.tool == tool && s.available)
+    }
+
+    /// Herramientas faltantes para poder procesar un tipo de contenido
+    pub fn missing_for(&self, content_type: &str, config: &Config) -> Vec<Tool> {
+        required_tools(content_type, config)
+            .into_iter()
+            .filter(|tool| !self.is_available(*tool))
+            .collect()
+    }
+
+    pub fn ensure_for(&self, content_type: &str, config: &Config) -> BridgeResult<()> {
+        let missing = self.missing_for(content_type, config);
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let names: Vec<&str> = missing.iter().map(|t| t.display_name()).collect();
+        Err(BridgeError::DependencyMissing(format!(
+            "'{}' requiere {} instalado",
+            content_type,
+            names.join(", ")
+        )))
+    }
+}
+
+/// Tipos de contenido que no pueden imprimirse sin una herramienta externa
+pub fn required_tools(content_type: &str, config: &Config) -> Vec<Tool> {
+    match content_type {
+        "html" | "markdown" => match config.html_renderer {
+            HtmlRenderer::Chromium => vec![Tool::Chromium],
+            HtmlRenderer::Wkhtmltopdf => vec![Tool::Wkhtmltopdf],
+        },
+        "docx" | "odt" | "xlsx" => vec![Tool::Soffice],
+        _ => Vec::new(),
+    }
+}
+
+pub fn detect_all(config: &Config) -> ToolReport {
+    let statuses = Tool::ALL
+        .into_iter()
+        .map(|tool| {
+            let path = locate(tool, config);
+            ToolStatus {
+                tool,
+                name: tool.display_name(),
+                available: path.is_some(),
+                path,
+            }
+        })
+        .collect();
+
+    ToolReport { statuses }
+}
+
+/// Busca el ejecutable: primero la ruta configurada, luego el PATH y por
+/// último las ubicaciones de instalación estándar del sistema operativo.
+/// Una app lanzada desde el escritorio suele heredar un PATH mínimo.
+pub fn locate(tool: Tool, config: &Config) -> Option<PathBuf> {
+    if let Some(configured) = config.tool_paths.get(tool.key()) {
+        let path = PathBuf::from(configured);
+        if path.is_file() {
+            return Some(path);
+        }
+        log::warn!("⚠️ Ruta configurada para {} no existe: {}", tool.display_name(), configured);
+    }
+
+    tool.executable_names()
+        .iter()
+        .find_map(|name| find_in_path(name))
+        .or_else(|| standard_locations(tool).into_iter().find(|path| is_executable(path)))
+}
+
+/// Ruta a usar al invocar la herramienta; si no se detectó se deja
+/// que el sistema la resuelva por nombre.
+pub fn command_path(tool: Tool, config: &Config) -> PathBuf {
+    locate(tool, config).unwrap_or_else(|| PathBuf::from(tool.executable_names()[0]))
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+
+    env::split_paths(&paths).find_map(|dir| {
+        let candidate = dir.join(name);
+        if is_executable(&candidate) {
+            return Some(candidate);
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            let candidate = dir.join(format!("{}.exe", name));
+            if is_executable(&candidate) {
+                return Some(candidate);
+            }
+        }
+
+        None
+    })
+}
+
+/// Ubicaciones habituales de cada herramienta según el sistema operativo
+fn standard_locations(tool: Tool) -> Vec<PathBuf> {
+    let mut locations = Vec::new();
+
+    #[cfg(target_os = "windows")]
+    {
+        let program_dirs: Vec<PathBuf> = ["ProgramFiles", "ProgramFiles(x86)"]
+            .iter()
+            .filter_map(|var| env::var_os(var).map(PathBuf::from))
+            .collect();
+
+        for base in &program_dirs {
+            match tool {
+                Tool::Wkhtmltopdf => locations.push(base.join("wkhtmltopdf").join("bin").join("wkhtmltopdf.exe")),
+                Tool::Chromium => {
+                    locations.push(base.join("Google").join("Chrome").join("Application").join("chrome.exe"));
+                    locations.push(base.join("Microsoft").join("Edge").join("Application").join("msedge.exe"));
+                }
+                Tool::Soffice => locations.push(base.join("LibreOffice").join("program").join("soffice.exe")),
+                Tool::Ghostscript => {
+                    // Ghostscript se instala en un subdirectorio por versión (gs\gs10.02.1)
+                    for version_dir in read_dir_sorted(&base.join("gs")) {
+                        locations.push(version_dir.join("bin").join("gswin64c.exe"));
+                        locations.push(version_dir.join("bin").join("gswin32c.exe"));
+                    }
+                }
+                Tool::Lp | Tool::Lpstat | Tool::Lpoptions | Tool::Lpadmin => {}
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        for dir in ["/usr/bin", "/usr/local/bin", "/opt/homebrew/bin", "/opt/local/bin"] {
+            for name in tool.executable_names() {
+                locations.push(Path::new(dir).join(name));
+            }
+        }
+        if tool == Tool::Soffice {
+            locations.push(PathBuf::from("/Applications/LibreOffice.app/Contents/MacOS/soffice"));
+        }
+        if tool == Tool::Chromium {
+            locations.push(PathBuf::from("/Applications/Google Chrome.app/Contents/MacOS/Google Chrome"));
+            locations.push(PathBuf::from("/Applications/Chromium.app/Contents/MacOS/Chromium"));
+            locations.push(PathBuf::from("/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge"));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        for dir in ["/usr/bin", "/usr/local/bin", "/usr/sbin", "/snap/bin"] {
+            for name in tool.executable_names() {
+                locations.push(Path::new(dir).join(name));
+            }
+        }
+        if tool == Tool::Soffice {
+            // Instalaciones oficiales de LibreOffice: /opt/libreoffice7.6/program/soffice
+            for dir in read_dir_sorted(Path::new("/opt")) {
+                let is_libreoffice = dir
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("libreoffice"));
+                if is_libreoffice {
+                    locations.push(dir.join("program").join("soffice"));
+                }
+            }
+        }
+    }
+
+    locations
+}
+
+/// Subdirectorios ordenados de forma descendente, para preferir la versión más nueva
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn read_dir_sorted(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect())
+        .unwrap_or_default();
+    entries.sort();
+    entries.reverse();
+    entries
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Ejecuta un comando sin bloquear el runtime. Si no termina a tiempo el
+/// proceso se mata (`kill_on_drop`) y se devuelve `BridgeError::Timeout`.
+pub async fn run(command: &mut tokio::process::Command, timeout: Duration) -> BridgeResult<Output> {
+    command.kill_on_drop(true);
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+
+    match tokio::time::timeout(timeout, command.output()).await {
+        Ok(output) => Ok(output?),
+        Err(_) => {
+            log::error!("⏱️ {} no respondió en {}s; proceso terminado", program, timeout.as_secs());
+            Err(BridgeError::Timeout {
+                command: program,
+                seconds: timeout.as_secs(),
+            })
+        }
+    }
+}
+
+/// Abre una URL con el navegador predeterminado del sistema
+pub fn open_url(url: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", "", url]);
+        command
+    };
+
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg(url);
+        command
+    };
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = {
+        let mut command = Command::new("xdg-open");
+        command.arg(url);
+        command
+    };
+
+    command.spawn().map(|_| ())
+}
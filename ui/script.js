@@ -8,6 +8,7 @@ let currentConfig = null;
 let statusDiv, tokenInput, hostInput, portInput, maxFileSizeInput, rateLimitInput;
 let copyButton, generateButton, saveButton;
 let autoStartCheckbox, minimizeToTrayCheckbox;
+let queuePausedCheckbox, queueStatusDiv;
 
 // Check if Tauri API is available
 function isTauriAvailable() {
@@ -58,6 +59,8 @@ document.addEventListener('DOMContentLoaded', async function() {
     saveButton = document.getElementById('save-config');
     autoStartCheckbox = document.getElementById('auto-start');
     minimizeToTrayCheckbox = document.getElementById('minimize-to-tray');
+    queuePausedCheckbox = document.getElementById('queue-paused');
+    queueStatusDiv = document.getElementById('queue-status');
     
     // Set up event listeners
     setupEventListeners();
@@ -70,9 +73,11 @@ document.addEventListener('DOMContentLoaded', async function() {
         // Load configuration and check status
         await loadConfiguration();
         await checkBridgeStatus();
+        await checkQueueStatus();
         
         // Set up periodic status check
         setInterval(checkBridgeStatus, 5000);
+        setInterval(checkQueueStatus, 5000);
     } catch (error) {
         console.error('Failed to initialize Tauri:', error);
         statusDiv.textContent = '❌ Tauri API not available: ' + error.message;
@@ -169,6 +174,25 @@ function setupEventListeners() {
         }
     });
     
+    // Pause/resume queue checkbox
+    queuePausedCheckbox.addEventListener('change', async function() {
+        try {
+            const status = await window.__TAURI__.core.invoke('set_queue_paused', { paused: queuePausedCheckbox.checked });
+            renderQueueStatus(status);
+            showNotification(
+                status.paused ?
+                'Printing paused, new jobs will wait' :
+                'Printing resumed',
+                'success'
+            );
+        } catch (error) {
+            console.error('Error toggling queue:', error);
+            showNotification('Failed to change queue state: ' + error.message, 'error');
+            // Revert checkbox state
+            queuePausedCheckbox.checked = !queuePausedCheckbox.checked;
+        }
+    });
+    
     // Save configuration button
     saveButton.addEventListener('click', async function() {
         try {
@@ -239,10 +263,23 @@ async function checkBridgeStatus() {
         const status = await window.__TAURI__.core.invoke('get_bridge_status');
         
         if (status.active) {
-            statusDiv.textContent = `✅ Bridge is running on port ${status.port} (v${status.version})`;
+            let text = `✅ Bridge is running on port ${status.port} (v${status.version})`;
+            if (status.port !== status.configured_port) {
+                text += ` · port ${status.configured_port} was in use`;
+            }
+            text += ` · up ${formatUptime(status.uptime_seconds)} · ${status.active_jobs} active job(s)`;
+            if (status.last_reload && status.last_reload.restart_required.length > 0) {
+                text += ` · restart needed for: ${status.last_reload.restart_required.join(', ')}`;
+            }
+            if (status.last_error) {
+                text += ` · last error: ${status.last_error.message}`;
+            }
+            statusDiv.textContent = text;
             statusDiv.className = 'status-success';
         } else {
-            statusDiv.textContent = `❌ Bridge is not running (configured port: ${status.port})`;
+            statusDiv.textContent = status.server.state === 'failed' ?
+                `❌ Bridge failed to start on port ${status.port}: ${status.server.error}` :
+                `❌ Bridge is not running (configured port: ${status.port})`;
             statusDiv.className = 'status-error';
         }
         
@@ -254,6 +291,32 @@ async function checkBridgeStatus() {
     }
 }
 
+function formatUptime(seconds) {
+    const days = Math.floor(seconds / 86400);
+    const hours = Math.floor((seconds % 86400) / 3600);
+    const minutes = Math.floor((seconds % 3600) / 60);
+    if (days > 0) return `${days}d ${hours}h`;
+    if (hours > 0) return `${hours}h ${minutes}m`;
+    return `${minutes}m`;
+}
+
+async function checkQueueStatus() {
+    try {
+        const status = await window.__TAURI__.core.invoke('get_queue_status');
+        renderQueueStatus(status);
+    } catch (error) {
+        console.error('Error checking queue status:', error);
+    }
+}
+
+function renderQueueStatus(status) {
+    queuePausedCheckbox.checked = status.paused;
+    queueStatusDiv.textContent = status.paused ?
+        `⏸️ Paused: ${status.pending} job(s) waiting` :
+        `▶️ Printing: ${status.pending} job(s) pending`;
+    queueStatusDiv.className = status.paused ? 'status-warning' : 'status-success';
+}
+
 function showNotification(message, type) {
     // Simple notification system
     const notification = document.createElement('div');